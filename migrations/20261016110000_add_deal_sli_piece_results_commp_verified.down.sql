@@ -0,0 +1,2 @@
+ALTER TABLE deal_sli_piece_results
+    DROP COLUMN IF EXISTS commp_verified;
@@ -0,0 +1,2 @@
+ALTER TABLE deal_sli_piece_results
+    ADD COLUMN commp_verified BOOLEAN;
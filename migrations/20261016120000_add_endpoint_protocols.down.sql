@@ -0,0 +1,2 @@
+ALTER TABLE storage_providers DROP COLUMN IF EXISTS protocols_probed_at;
+ALTER TABLE storage_providers DROP COLUMN IF EXISTS endpoint_protocols;
@@ -0,0 +1,2 @@
+ALTER TABLE storage_providers ADD COLUMN endpoint_protocols JSONB;
+ALTER TABLE storage_providers ADD COLUMN protocols_probed_at TIMESTAMPTZ;
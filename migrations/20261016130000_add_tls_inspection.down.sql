@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_storage_providers_tls_expires_at;
+ALTER TABLE storage_providers DROP COLUMN IF EXISTS tls_inspected_at;
+ALTER TABLE storage_providers DROP COLUMN IF EXISTS tls_expires_at;
+ALTER TABLE storage_providers DROP COLUMN IF EXISTS tls_certificates;
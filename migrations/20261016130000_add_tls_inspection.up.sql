@@ -0,0 +1,6 @@
+ALTER TABLE storage_providers ADD COLUMN tls_certificates JSONB;
+ALTER TABLE storage_providers ADD COLUMN tls_expires_at TIMESTAMPTZ;
+ALTER TABLE storage_providers ADD COLUMN tls_inspected_at TIMESTAMPTZ;
+
+CREATE INDEX idx_storage_providers_tls_expires_at ON storage_providers (tls_expires_at)
+    WHERE tls_expires_at IS NOT NULL;
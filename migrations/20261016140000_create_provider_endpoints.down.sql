@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS provider_endpoints;
+DROP TYPE IF EXISTS endpoint_source;
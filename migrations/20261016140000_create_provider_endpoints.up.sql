@@ -0,0 +1,21 @@
+-- Per-endpoint health tracking, replacing equal weighting of cached_http_endpoints
+
+CREATE TYPE endpoint_source AS ENUM ('Publisher', 'ExtendedProviders', 'CurioContract', 'Manual');
+
+CREATE TABLE provider_endpoints (
+    provider_id             VARCHAR(255) NOT NULL,
+    endpoint                TEXT NOT NULL,
+    source                  endpoint_source NOT NULL,
+    first_seen              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_seen_in_ipni       TIMESTAMPTZ,
+    last_tested_at          TIMESTAMPTZ,
+    last_success            TIMESTAMPTZ,
+    consecutive_failures    INTEGER NOT NULL DEFAULT 0,
+    avg_latency_ms          DOUBLE PRECISION,
+    PRIMARY KEY (provider_id, endpoint)
+);
+
+COMMENT ON TABLE provider_endpoints IS 'HTTP endpoints of each provider with retrieval health from URL discovery';
+COMMENT ON COLUMN provider_endpoints.source IS 'Where the endpoint was found: cid.contact Publisher, ExtendedProviders, Curio contract or added manually';
+COMMENT ON COLUMN provider_endpoints.consecutive_failures IS 'Discovery runs in a row where no piece could be retrieved from the endpoint';
+COMMENT ON COLUMN provider_endpoints.avg_latency_ms IS 'Exponentially weighted average response time of successful piece requests';
@@ -0,0 +1 @@
+-- Postgres cannot remove enum values. No-op.
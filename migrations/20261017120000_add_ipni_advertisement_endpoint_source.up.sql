@@ -0,0 +1 @@
+ALTER TYPE endpoint_source ADD VALUE IF NOT EXISTS 'IpniAdvertisement';
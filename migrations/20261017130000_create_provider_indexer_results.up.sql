@@ -0,0 +1,13 @@
+-- Latest provider record lookup per IPNI indexer, used to spot indexers that disagree
+
+CREATE TABLE provider_indexer_results (
+    provider_id     VARCHAR(255) NOT NULL,
+    indexer         TEXT NOT NULL,
+    result_code     result_code NOT NULL,
+    error_code      error_code,
+    endpoints       TEXT[] NOT NULL DEFAULT '{}',
+    fetched_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (provider_id, indexer)
+);
+
+COMMENT ON COLUMN provider_indexer_results.endpoints IS 'HTTP endpoints the indexer returned from the provider record and content lookups';
@@ -0,0 +1,6 @@
+DROP TABLE IF EXISTS provider_identity_history;
+
+DROP INDEX IF EXISTS idx_storage_providers_peer_id_stale;
+ALTER TABLE storage_providers DROP COLUMN IF EXISTS peer_curio_multiaddrs;
+ALTER TABLE storage_providers DROP COLUMN IF EXISTS peer_multiaddrs;
+ALTER TABLE storage_providers DROP COLUMN IF EXISTS peer_id_fetched_at;
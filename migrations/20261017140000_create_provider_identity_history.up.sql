@@ -0,0 +1,31 @@
+-- Periodic on-chain peer ID refresh with a history of identity changes
+
+ALTER TABLE storage_providers
+ADD COLUMN peer_id_fetched_at TIMESTAMPTZ,
+ADD COLUMN peer_multiaddrs TEXT[],
+ADD COLUMN peer_curio_multiaddrs TEXT[];
+
+CREATE INDEX idx_storage_providers_peer_id_stale
+ON storage_providers (peer_id_fetched_at)
+WHERE peer_id IS NOT NULL;
+
+COMMENT ON COLUMN storage_providers.peer_id_fetched_at IS 'When the peer ID was last re-read from chain by the peer ID refresh scheduler';
+COMMENT ON COLUMN storage_providers.peer_multiaddrs IS 'Multiaddrs from StateMinerInfo at the last peer ID refresh';
+COMMENT ON COLUMN storage_providers.peer_curio_multiaddrs IS 'Multiaddrs from the Curio contract PeerData at the last peer ID refresh; empty when not registered';
+
+CREATE TABLE provider_identity_history (
+    id              UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    provider_id     VARCHAR(255) NOT NULL,
+    old_peer_id     TEXT,
+    new_peer_id     TEXT,
+    old_multiaddrs  TEXT[],
+    new_multiaddrs  TEXT[],
+    old_curio_multiaddrs TEXT[],
+    new_curio_multiaddrs TEXT[],
+    detected_at     TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_provider_identity_history_provider
+ON provider_identity_history (provider_id, detected_at DESC);
+
+COMMENT ON TABLE provider_identity_history IS 'On-chain peer ID or multiaddr changes detected by the peer ID refresh scheduler';
@@ -0,0 +1,2 @@
+-- Postgres cannot remove enum values, MinerInfo stays in endpoint_source.
+ALTER TABLE provider_endpoints DROP COLUMN IF EXISTS last_seen_on_chain;
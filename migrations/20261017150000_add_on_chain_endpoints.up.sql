@@ -0,0 +1,6 @@
+ALTER TYPE endpoint_source ADD VALUE IF NOT EXISTS 'MinerInfo';
+
+ALTER TABLE provider_endpoints ADD COLUMN last_seen_on_chain TIMESTAMPTZ;
+
+COMMENT ON COLUMN provider_endpoints.last_seen_on_chain IS 'Last time the endpoint was read from StateMinerInfo or the Curio contract';
+COMMENT ON COLUMN provider_endpoints.source IS 'Where the endpoint was first found: an IPNI provider record section or content lookup, on chain (StateMinerInfo or the Curio contract) or added manually';
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS pix_payload_samples;
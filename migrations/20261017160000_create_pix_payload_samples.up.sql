@@ -0,0 +1,15 @@
+-- Payload CIDs sampled from pix.filspark (Spark piece indexer) per provider and piece.
+-- Samples follow the provider's IPNI advertisements, so they expire after a TTL.
+
+CREATE TABLE pix_payload_samples (
+    provider_id     VARCHAR(255) NOT NULL,
+    piece_cid       TEXT NOT NULL,
+    payload_cid     TEXT,
+    fetched_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (provider_id, piece_cid)
+);
+
+CREATE INDEX idx_pix_payload_samples_fetched_at ON pix_payload_samples(fetched_at);
+
+COMMENT ON TABLE pix_payload_samples IS 'Cache of payload CID samples from pix.filspark, refreshed after PIX_SAMPLE_TTL_HOURS';
+COMMENT ON COLUMN pix_payload_samples.payload_cid IS 'First sampled payload block CID (CIDv1 base32), NULL when pix.filspark had no sample for the piece';
@@ -0,0 +1 @@
+ALTER TABLE storage_providers DROP COLUMN IF EXISTS url_discovery_reason;
@@ -0,0 +1,3 @@
+ALTER TABLE storage_providers ADD COLUMN url_discovery_reason VARCHAR(50);
+
+COMMENT ON COLUMN storage_providers.url_discovery_reason IS 'Why next_url_discovery_at was chosen: default, stable, state_changed, endpoints_changed or deal_sli_target';
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS provider_test_jobs;
+DROP TYPE IF EXISTS provider_test_status;
@@ -0,0 +1,26 @@
+-- On-demand provider tests requested through the API, claimed ahead of the regular URL discovery batch
+
+CREATE TYPE provider_test_status AS ENUM ('queued', 'running', 'done', 'failed');
+
+CREATE TABLE provider_test_jobs (
+    id              UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    provider_id     VARCHAR(255) NOT NULL,
+    status          provider_test_status NOT NULL DEFAULT 'queued',
+    url_result_id   UUID,
+    error           TEXT,
+    requested_at    TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    started_at      TIMESTAMPTZ,
+    claimed_by      VARCHAR(255),
+    completed_at    TIMESTAMPTZ
+);
+
+CREATE INDEX idx_provider_test_jobs_queued ON provider_test_jobs (requested_at)
+    WHERE status = 'queued';
+
+CREATE UNIQUE INDEX idx_provider_test_jobs_open ON provider_test_jobs (provider_id)
+    WHERE status IN ('queued', 'running');
+
+COMMENT ON TABLE provider_test_jobs IS 'On-demand URL discovery runs requested through POST /providers/{id}/tests';
+COMMENT ON COLUMN provider_test_jobs.url_result_id IS 'Provider-level url_results row written by the run';
+COMMENT ON COLUMN provider_test_jobs.claimed_by IS 'INSTANCE_ID running the job; a running job is reclaimed once its provider_test lease lapses';
+COMMENT ON INDEX idx_provider_test_jobs_open IS 'At most one queued or running job per provider; repeated requests return the open job';
@@ -0,0 +1,17 @@
+-- Expiring, heartbeated claims on scheduler work so several instances can run side by side
+
+CREATE TABLE scheduler_leases (
+    task            VARCHAR(50) NOT NULL,
+    resource_id     VARCHAR(255) NOT NULL,
+    owner           VARCHAR(255) NOT NULL,
+    acquired_at     TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at      TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (task, resource_id)
+);
+
+CREATE INDEX idx_scheduler_leases_owner ON scheduler_leases (owner);
+
+COMMENT ON TABLE scheduler_leases IS 'Work claimed by a scheduler instance; expired leases are free to be claimed again';
+COMMENT ON COLUMN scheduler_leases.task IS 'url_discovery, endpoint_refresh, bms_job, provider_discovery, peer_id_refresh or provider_test';
+COMMENT ON COLUMN scheduler_leases.resource_id IS 'Provider ID, or the task name for singleton tasks';
+COMMENT ON COLUMN scheduler_leases.owner IS 'INSTANCE_ID of the holder, heartbeated while it works';
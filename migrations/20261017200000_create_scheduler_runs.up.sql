@@ -0,0 +1,21 @@
+-- Ledger of background scheduler iterations that had work to do
+
+CREATE TABLE scheduler_runs (
+    id              UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    scheduler       VARCHAR(50) NOT NULL,
+    instance_id     VARCHAR(255) NOT NULL,
+    started_at      TIMESTAMPTZ NOT NULL,
+    finished_at     TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ok_count        INTEGER NOT NULL DEFAULT 0,
+    failed_count    INTEGER NOT NULL DEFAULT 0,
+    skipped_count   INTEGER NOT NULL DEFAULT 0,
+    error           TEXT,
+    details         JSONB
+);
+
+CREATE INDEX idx_scheduler_runs_scheduler_started ON scheduler_runs (scheduler, started_at DESC);
+CREATE INDEX idx_scheduler_runs_started ON scheduler_runs (started_at DESC);
+
+COMMENT ON TABLE scheduler_runs IS 'One row per scheduler batch; idle iterations with nothing due are not recorded';
+COMMENT ON COLUMN scheduler_runs.error IS 'Why the whole iteration failed; per-provider failures only count in failed_count';
+COMMENT ON COLUMN scheduler_runs.details IS 'Scheduler-specific figures, e.g. average retrievability of a URL discovery batch';
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS commp_verifications;
@@ -0,0 +1,17 @@
+-- Weekly CommP budget ledger shared by URL discovery and Deal SLI runs
+
+CREATE TABLE commp_verifications (
+    id              UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    provider_id     VARCHAR(255) NOT NULL,
+    piece_cid       TEXT NOT NULL,
+    source          VARCHAR(20) NOT NULL,
+    charged_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    commp_verified  BOOLEAN,
+    bytes_read      BIGINT
+);
+
+CREATE INDEX idx_commp_verifications_provider_charged ON commp_verifications (provider_id, charged_at DESC);
+
+COMMENT ON TABLE commp_verifications IS 'One row per piece charged against a provider''s weekly CommP budget, written before streaming starts';
+COMMENT ON COLUMN commp_verifications.source IS 'url_discovery or deal_sli';
+COMMENT ON COLUMN commp_verifications.commp_verified IS 'NULL until the verification finishes or when it was inconclusive';
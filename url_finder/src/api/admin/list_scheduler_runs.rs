@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use axum::{
+    debug_handler,
+    extract::{Query, State},
+};
+use serde::Deserialize;
+use tracing::{debug, warn};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    AppState,
+    api_response::{
+        ApiResponse, ErrorCode, ErrorResponse, internal_server_error_with_code, ok_response,
+    },
+    auth::OracleAuth,
+};
+
+use super::types::SchedulerRunsResponse;
+
+#[derive(Deserialize, ToSchema, IntoParams)]
+pub struct ListSchedulerRunsQuery {
+    /// Only runs of this scheduler, e.g. url_discovery or bms_job_creator
+    pub scheduler: Option<String>,
+    /// Maximum number of runs to return (1-500)
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    /// Number of runs to skip
+    #[serde(default)]
+    pub offset: i64,
+}
+
+fn default_limit() -> i64 {
+    100
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/scheduler-runs",
+    description = "List recorded scheduler iterations, newest first. Idle iterations that found nothing due are not recorded.",
+    params(ListSchedulerRunsQuery),
+    responses(
+        (status = 200, description = "Scheduler runs", body = SchedulerRunsResponse),
+        (status = 401, description = "Missing or invalid oracle bearer token", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = [])),
+    tags = ["Admin"],
+)]
+#[debug_handler(state = Arc<AppState>)]
+pub async fn handle_list_scheduler_runs(
+    _auth: OracleAuth,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListSchedulerRunsQuery>,
+) -> Result<ApiResponse<SchedulerRunsResponse>, ApiResponse<()>> {
+    let limit = query.limit.clamp(1, 500);
+    let offset = query.offset.max(0);
+    let scheduler = query.scheduler.as_deref().filter(|s| !s.is_empty());
+
+    debug!("GET /admin/scheduler-runs?scheduler={scheduler:?}&limit={limit}&offset={offset}");
+
+    let query_error = |e| {
+        warn!("Failed to list scheduler runs: {:?}", e);
+        internal_server_error_with_code(ErrorCode::InternalError, "Failed to query scheduler runs")
+    };
+    let runs = state
+        .scheduler_run_repo
+        .list(scheduler, limit, offset)
+        .await
+        .map_err(query_error)?;
+    let total = state
+        .scheduler_run_repo
+        .count(scheduler)
+        .await
+        .map_err(query_error)?;
+
+    Ok(ok_response(SchedulerRunsResponse {
+        runs,
+        total,
+        limit,
+        offset,
+    }))
+}
@@ -0,0 +1,5 @@
+mod types;
+pub use types::*;
+
+mod list_scheduler_runs;
+pub use list_scheduler_runs::*;
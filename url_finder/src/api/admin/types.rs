@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::repository::SchedulerRun;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SchedulerRunsResponse {
+    pub runs: Vec<SchedulerRun>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
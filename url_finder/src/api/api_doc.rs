@@ -2,9 +2,12 @@ use crate::api_response::ErrorResponse;
 use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
 use utoipa::{Modify, OpenApi};
 
+use crate::api::admin::*;
 use crate::api::deals::*;
 use crate::api::providers::*;
 use crate::api::*;
+use crate::repository::{SchedulerRun, UrlResult};
+use crate::types::ProviderTestStatus;
 
 #[allow(dead_code)]
 struct SecurityAddon;
@@ -37,6 +40,11 @@ deal target with a verified manifest, fetch the stored target and derived
 pieces, read the latest measurement state, or trigger a manual run. Deal SLI
 write endpoints require `Authorization: Bearer <AUTH_TOKEN>`.
 
+## Admin API
+
+The `/admin/*` endpoints expose operational state, such as the ledger of
+scheduler runs. They require `Authorization: Bearer <AUTH_TOKEN>`.
+
 ## New Providers API
 
 The `/providers/*` and `/clients/*` endpoints serve pre-computed data from the database with combined URL, retrievability, and performance metrics.
@@ -73,6 +81,8 @@ The `/url/*` endpoints remain fully backward compatible.
         handle_list_providers,
         handle_bulk_providers,
         handle_reset_provider,
+        handle_create_provider_test,
+        handle_get_provider_test,
         handle_history_retrievability,
         handle_history_retrievability_client,
         // Deal SLI API
@@ -80,6 +90,8 @@ The `/url/*` endpoints remain fully backward compatible.
         handle_get_deal,
         handle_get_latest,
         handle_create_run,
+        // Admin API
+        handle_list_scheduler_runs,
     ),
     components(
         schemas(
@@ -114,6 +126,11 @@ The `/url/*` endpoints remain fully backward compatible.
             ResetProviderPath,
             ResetProviderQuery,
             ScheduleType,
+            CreateProviderTestPath,
+            GetProviderTestPath,
+            ProviderTestResponse,
+            ProviderTestStatus,
+            UrlResult,
             HistoryProviderPath,
             HistoryProviderClientPath,
             HistoryQuery,
@@ -125,6 +142,12 @@ The `/url/*` endpoints remain fully backward compatible.
             DiagnosticsResponse,
             ScheduleStateResponse,
             SchedulingResponse,
+            EndpointProtocolsResponse,
+            EndpointTlsResponse,
+            CertificateSummaryResponse,
+            EndpointStatusResponse,
+            IndexerComparisonResponse,
+            IndexerResultResponse,
             // Deal SLI API
             DealPath,
             DealVersion,
@@ -137,6 +160,11 @@ The `/url/*` endpoints remain fully backward compatible.
             DealBmsResultResponse,
             DealLatestMeasurementResponse,
 
+            // Admin API
+            ListSchedulerRunsQuery,
+            SchedulerRunsResponse,
+            SchedulerRun,
+
             // Misc
             HealthcheckResponse,
 
@@ -149,6 +177,7 @@ The `/url/*` endpoints remain fully backward compatible.
         (name = "Providers", description = "New Providers API - pre-computed data with performance metrics"),
         (name = "Clients", description = "Client endpoints - providers for a specific client"),
         (name = "Deals", description = "PoRep V2 deal measurement API contract"),
+        (name = "Admin", description = "Operational endpoints for service operators"),
         (name = "URL", description = "Legacy URL Finder APIs"),
         (name = "Healthcheck", description = "Health check endpoints"),
     ),
@@ -25,6 +25,9 @@ pub struct FindUrlSpResponse {
     pub result: ResultCode,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub url: Option<String>,
+    /// Payload CID that can be fetched from the provider's trustless gateway
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload_cid: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
 }
@@ -32,6 +35,7 @@ pub struct FindUrlSpResponse {
 impl From<ProviderData> for FindUrlSpResponse {
     fn from(data: ProviderData) -> Self {
         Self {
+            payload_cid: data.sample_payload_cid(),
             result: data.result_code.clone(),
             url: data.working_url,
             message: data.result_code.message().map(String::from),
@@ -44,6 +48,7 @@ impl FindUrlSpResponse {
         Self {
             result: ResultCode::Error,
             url: None,
+            payload_cid: None,
             message: Some("Provider has not been indexed yet. Please try again later.".to_string()),
         }
     }
@@ -28,6 +28,9 @@ pub struct FindUrlSpClientResponse {
     pub result: ResultCode,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub url: Option<String>,
+    /// Payload CID that can be fetched from the provider's trustless gateway
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload_cid: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
 }
@@ -35,6 +38,7 @@ pub struct FindUrlSpClientResponse {
 impl From<ProviderData> for FindUrlSpClientResponse {
     fn from(data: ProviderData) -> Self {
         Self {
+            payload_cid: data.sample_payload_cid(),
             result: data.result_code,
             url: data.working_url,
             message: None,
@@ -47,6 +51,7 @@ impl FindUrlSpClientResponse {
         Self {
             result: ResultCode::Error,
             url: None,
+            payload_cid: None,
             message: Some(
                 "Provider/client pair has not been indexed yet. Please try again later."
                     .to_string(),
@@ -22,6 +22,7 @@ pub use find_retri_sp_client::*;
 mod responses;
 pub use responses::*;
 
+pub mod admin;
 pub mod deals;
 pub use deals::*;
 pub mod providers;
@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+};
+use axum_extra::extract::WithRejection;
+use serde::Deserialize;
+use tracing::{debug, error};
+use utoipa::{IntoParams, ToSchema};
+
+use super::types::ProviderTestResponse;
+
+use crate::{
+    AppState,
+    api_response::{
+        ApiResponse, ErrorCode, ErrorResponse, bad_request_with_code,
+        internal_server_error_with_code, not_found_with_code, ok_response,
+    },
+    types::ProviderAddress,
+};
+
+#[derive(Deserialize, ToSchema, IntoParams)]
+pub struct CreateProviderTestPath {
+    pub id: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/providers/{id}/tests",
+    description = "Queue an on-demand URL discovery run for the provider ahead of the regular schedule. Returns the already queued or running test when there is one; poll `GET /tests/{job_id}` for the result.",
+    params(CreateProviderTestPath),
+    responses(
+        (status = 200, description = "Test queued", body = ProviderTestResponse),
+        (status = 400, description = "Invalid provider address", body = ErrorResponse),
+        (status = 404, description = "Provider not found", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse),
+    ),
+    tags = ["Providers"],
+)]
+#[debug_handler]
+pub async fn handle_create_provider_test(
+    State(state): State<Arc<AppState>>,
+    WithRejection(Path(path), _): WithRejection<
+        Path<CreateProviderTestPath>,
+        ApiResponse<ErrorResponse>,
+    >,
+) -> Result<ApiResponse<ProviderTestResponse>, ApiResponse<()>> {
+    debug!("POST /providers/{}/tests", &path.id);
+
+    let provider_address = ProviderAddress::new(&path.id).map_err(|e| {
+        error!("Invalid provider address '{}': {}", &path.id, e);
+        bad_request_with_code(ErrorCode::InvalidAddress, "Invalid provider address")
+    })?;
+
+    let provider_id = provider_address.into();
+
+    state
+        .storage_provider_repo
+        .get_by_provider_id(&provider_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to query provider_id={}: {e:?}", provider_id);
+            internal_server_error_with_code(ErrorCode::InternalError, "Failed to query provider")
+        })?
+        .ok_or_else(|| {
+            not_found_with_code(
+                ErrorCode::NotFound,
+                format!("Provider {} not found", path.id),
+            )
+        })?;
+
+    let job = state
+        .provider_test_repo
+        .enqueue(&provider_id)
+        .await
+        .map_err(|e| {
+            error!(
+                "Failed to queue test for provider_id={}: {e:?}",
+                provider_id
+            );
+            internal_server_error_with_code(ErrorCode::InternalError, "Failed to queue test")
+        })?;
+
+    Ok(ok_response(ProviderTestResponse::new(job, None)))
+}
@@ -102,7 +102,53 @@ pub async fn handle_get_provider_client(
         None
     };
 
+    let endpoint_protocols = if query.extended {
+        state
+            .provider_service
+            .get_endpoint_protocols(&provider_id)
+            .await
+            .ok()
+            .flatten()
+    } else {
+        None
+    };
+
+    let tls_certificates = if query.extended {
+        state
+            .provider_service
+            .get_tls_inspections(&provider_id)
+            .await
+            .ok()
+            .flatten()
+    } else {
+        None
+    };
+
+    let endpoints = if query.extended {
+        state
+            .provider_service
+            .get_endpoint_health(&provider_id)
+            .await
+            .ok()
+    } else {
+        None
+    };
+
+    let indexer_results = if query.extended {
+        state
+            .provider_service
+            .get_indexer_results(&provider_id)
+            .await
+            .ok()
+    } else {
+        None
+    };
+
     Ok(ok_response(
-        ProviderClientResponse::from_data_with_scheduling(data, scheduling, query.extended),
+        ProviderClientResponse::from_data_with_scheduling(data, scheduling, query.extended)
+            .with_endpoint_protocols(endpoint_protocols)
+            .with_tls_certificates(tls_certificates)
+            .with_endpoints(endpoints)
+            .with_indexer_results(indexer_results),
     ))
 }
@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+};
+use axum_extra::extract::WithRejection;
+use serde::Deserialize;
+use tracing::{debug, error};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use super::types::ProviderTestResponse;
+
+use crate::{
+    AppState,
+    api_response::{
+        ApiResponse, ErrorCode, ErrorResponse, bad_request_with_code,
+        internal_server_error_with_code, not_found_with_code, ok_response,
+    },
+};
+
+#[derive(Deserialize, ToSchema, IntoParams)]
+pub struct GetProviderTestPath {
+    pub job_id: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/tests/{job_id}",
+    description = "Status of an on-demand provider test, with the provider-level URL result once done.",
+    params(GetProviderTestPath),
+    responses(
+        (status = 200, description = "Test found", body = ProviderTestResponse),
+        (status = 400, description = "Invalid job ID", body = ErrorResponse),
+        (status = 404, description = "Test not found", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse),
+    ),
+    tags = ["Providers"],
+)]
+#[debug_handler]
+pub async fn handle_get_provider_test(
+    State(state): State<Arc<AppState>>,
+    WithRejection(Path(path), _): WithRejection<
+        Path<GetProviderTestPath>,
+        ApiResponse<ErrorResponse>,
+    >,
+) -> Result<ApiResponse<ProviderTestResponse>, ApiResponse<()>> {
+    debug!("GET /tests/{}", &path.job_id);
+
+    let job_id = Uuid::parse_str(&path.job_id)
+        .map_err(|_| bad_request_with_code(ErrorCode::InvalidRequest, "Invalid job ID"))?;
+
+    let job = state
+        .provider_test_repo
+        .get(job_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to query test job_id={}: {e:?}", job_id);
+            internal_server_error_with_code(ErrorCode::InternalError, "Failed to query test")
+        })?
+        .ok_or_else(|| {
+            not_found_with_code(
+                ErrorCode::NotFound,
+                format!("Test {} not found", path.job_id),
+            )
+        })?;
+
+    let result = match job.url_result_id {
+        Some(result_id) => state.url_repo.get_by_id(result_id).await.map_err(|e| {
+            error!("Failed to query url result id={}: {e:?}", result_id);
+            internal_server_error_with_code(ErrorCode::InternalError, "Failed to query test result")
+        })?,
+        None => None,
+    };
+
+    Ok(ok_response(ProviderTestResponse::new(job, result)))
+}
@@ -65,6 +65,12 @@ pub struct RetrievabilityDataPoint {
     pub is_consistent: Option<bool>,
     pub is_reliable: Option<bool>,
     pub working_url: Option<String>,
+    /// Average time to response headers across tested URLs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttfb_ms: Option<f64>,
+    /// Average throughput of the sampled ranged downloads
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub throughput_mbps: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub result_code: Option<ResultCode>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -92,6 +98,8 @@ impl RetrievabilityDataPoint {
             is_consistent: None,
             is_reliable: None,
             working_url: None,
+            ttfb_ms: None,
+            throughput_mbps: None,
             result_code: None,
             error_code: None,
             tested_at: None,
@@ -100,8 +108,23 @@ impl RetrievabilityDataPoint {
     }
 }
 
+impl RetrievabilityDataPoint {
+    /// Latency and bandwidth come from `url_metadata.timing`, so they are shown without `extended`
+    fn with_timing(mut self, url_metadata: Option<&serde_json::Value>) -> Self {
+        let timing = url_metadata.and_then(|m| m.get("timing"));
+        self.ttfb_ms = timing
+            .and_then(|t| t.get("ttfb_ms_avg"))
+            .and_then(|v| v.as_f64());
+        self.throughput_mbps = timing
+            .and_then(|t| t.get("throughput_mbps_avg"))
+            .and_then(|v| v.as_f64());
+        self
+    }
+}
+
 impl From<HistoryRow> for RetrievabilityDataPoint {
     fn from(row: HistoryRow) -> Self {
+        let url_metadata = row.url_metadata.clone();
         Self {
             date: row.date,
             retrievability_percent: row.retrievability_percent,
@@ -111,11 +134,14 @@ impl From<HistoryRow> for RetrievabilityDataPoint {
             is_consistent: row.is_consistent,
             is_reliable: row.is_reliable,
             working_url: row.working_url,
+            ttfb_ms: None,
+            throughput_mbps: None,
             result_code: Some(row.result_code),
             error_code: row.error_code,
             tested_at: Some(row.tested_at),
             url_metadata: row.url_metadata,
         }
+        .with_timing(url_metadata.as_ref())
     }
 }
 
@@ -204,6 +230,7 @@ pub async fn handle_history_retrievability(
                     row.car_files_percent,
                     row.sector_utilization_percent,
                 )
+                .with_timing(row.url_metadata.as_ref())
             }
         })
         .collect();
@@ -282,6 +309,7 @@ pub async fn handle_history_retrievability_client(
                     row.car_files_percent,
                     row.sector_utilization_percent,
                 )
+                .with_timing(row.url_metadata.as_ref())
             }
         })
         .collect();
@@ -31,6 +31,8 @@ pub struct ListProvidersQuery {
     pub has_working_url: Option<bool>,
     /// Filter by URL consistency: true=consistent, false=inconsistent, omit=all
     pub is_consistent: Option<bool>,
+    /// Only providers whose earliest https certificate expires within this many days (includes expired)
+    pub tls_expiring_within_days: Option<i32>,
     /// Include diagnostic and scheduling details
     #[serde(default)]
     pub extended: bool,
@@ -61,11 +63,15 @@ pub async fn handle_list_providers(
     let filters = ProviderFilters {
         has_working_url: query.has_working_url,
         is_consistent: query.is_consistent,
+        tls_expiring_within_days: query.tls_expiring_within_days,
     };
 
     debug!(
-        "GET /providers?limit={limit}&offset={offset}&has_working_url={:?}&is_consistent={:?}&extended={}",
-        filters.has_working_url, filters.is_consistent, query.extended
+        "GET /providers?limit={limit}&offset={offset}&has_working_url={:?}&is_consistent={:?}&tls_expiring_within_days={:?}&extended={}",
+        filters.has_working_url,
+        filters.is_consistent,
+        filters.tls_expiring_within_days,
+        query.extended
     );
 
     let paginated = state
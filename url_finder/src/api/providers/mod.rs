@@ -21,3 +21,9 @@ pub use reset_provider::*;
 
 mod history_retrievability;
 pub use history_retrievability::*;
+
+mod create_provider_test;
+pub use create_provider_test::*;
+
+mod get_provider_test;
+pub use get_provider_test::*;
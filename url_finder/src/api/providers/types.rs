@@ -1,11 +1,17 @@
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 
+use crate::protocol_probe::EndpointProtocols;
+use crate::repository::{ProviderEndpoint, ProviderIndexerResult, ProviderTestJob, UrlResult};
 use crate::services::provider_service::{
     BandwidthResult, PerformanceData, ProviderData, SchedulingData,
 };
-use crate::types::{ErrorCode, ProviderAddress, ResultCode};
+use crate::services::url_discovery_service::is_dead_endpoint;
+use crate::tls_inspection::{CertificateSummary, TlsFailure, TlsInspection};
+use crate::types::{EndpointSource, ErrorCode, ProviderAddress, ProviderTestStatus, ResultCode};
 
 /// Common query parameters for extended response
 #[derive(Debug, Clone, Deserialize, ToSchema, IntoParams, Default)]
@@ -73,6 +79,23 @@ pub struct InconsistentBreakdown {
     pub both_failed: usize,
     /// (Valid, Valid) but different Content-Length
     pub size_mismatch: usize,
+    /// (Valid, Valid) but a random aligned range was not served as requested
+    pub random_range: usize,
+}
+
+/// Outcome of deep CommP verification of fully downloaded pieces (extended only)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CommpVerificationResponse {
+    /// Number of pieces streamed and hashed
+    pub sample_count: usize,
+    /// Pieces whose computed CommP matched the piece CID
+    pub verified_count: usize,
+    /// Pieces whose computed CommP did not match the piece CID
+    pub mismatch_count: usize,
+    /// Pieces that could not be fully downloaded or hashed
+    pub failed_count: usize,
+    /// false if any piece mismatched, true if at least one matched, null if inconclusive
+    pub commp_verified: Option<bool>,
 }
 
 /// Analysis metrics from URL testing (extended only)
@@ -89,6 +112,35 @@ pub struct AnalysisResponse {
     /// Breakdown of inconsistency causes
     #[serde(skip_serializing_if = "Option::is_none")]
     pub inconsistent_breakdown: Option<InconsistentBreakdown>,
+    /// Failed URLs by cause, e.g. dns_nxdomain, connect_timeout, tls_expired, body_truncated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_breakdown: Option<BTreeMap<String, usize>>,
+    /// Deep CommP verification, present only when pieces were streamed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commp_verification: Option<CommpVerificationResponse>,
+    /// Percent of random aligned range requests served correctly
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub random_range_success_percent: Option<f64>,
+    /// Percent of payload root blocks served and verified via the IPFS trustless gateway
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gateway_retrievability_percent: Option<f64>,
+    /// Percent of sampled CAR root CIDs matching the deal Label payload CID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root_cid_match_percent: Option<f64>,
+    /// Deals whose CAR root CID differs from the deal Label payload CID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root_cid_mismatches: Option<Vec<RootCidMismatchResponse>>,
+}
+
+/// Deal whose served CAR root differs from its on-chain Label (extended only)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RootCidMismatchResponse {
+    pub deal_id: i64,
+    pub piece_cid: String,
+    /// Payload CID from the deal Label
+    pub expected_root_cid: String,
+    /// Root CID parsed from the served CAR header
+    pub actual_root_cid: String,
 }
 
 /// Diagnostic information (extended only)
@@ -116,6 +168,45 @@ pub struct ScheduleStateResponse {
     /// When task entered pending state (URL discovery only)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pending_since: Option<DateTime<Utc>>,
+    /// Why the next run was scheduled when it is: "default", "stable", "state_changed",
+    /// "endpoints_changed" or "deal_sli_target" (URL discovery only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// On-demand URL discovery run of one provider
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ProviderTestResponse {
+    pub job_id: uuid::Uuid,
+    pub provider_id: String,
+    pub status: ProviderTestStatus,
+    pub requested_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+    /// Why the test failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Provider-level URL discovery result, once done
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<UrlResult>,
+}
+
+impl ProviderTestResponse {
+    pub fn new(job: ProviderTestJob, result: Option<UrlResult>) -> Self {
+        let provider_address: ProviderAddress = job.provider_id.into();
+        Self {
+            job_id: job.id,
+            provider_id: provider_address.to_string(),
+            status: job.status,
+            requested_at: job.requested_at,
+            started_at: job.started_at,
+            completed_at: job.completed_at,
+            error: job.error,
+            result,
+        }
+    }
 }
 
 /// Scheduler state for all tasks (extended only)
@@ -127,6 +218,188 @@ pub struct SchedulingResponse {
     pub bms_test: ScheduleStateResponse,
 }
 
+/// HTTP protocol capabilities of one provider endpoint (extended only)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EndpointProtocolsResponse {
+    pub endpoint: String,
+    /// Protocol selected via TLS ALPN when offered h2 and http/1.1 (null for plain http)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpn: Option<String>,
+    /// Endpoint negotiated HTTP/2
+    pub h2: bool,
+    /// Raw Alt-Svc header returned by the endpoint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alt_svc: Option<String>,
+    /// HTTP/3 advertised via Alt-Svc
+    pub h3_advertised: bool,
+    /// QUIC handshake succeeded (null when not attempted)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub h3_reachable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl From<EndpointProtocols> for EndpointProtocolsResponse {
+    fn from(p: EndpointProtocols) -> Self {
+        Self {
+            endpoint: p.endpoint,
+            alpn: p.alpn,
+            h2: p.h2,
+            alt_svc: p.alt_svc,
+            h3_advertised: p.h3_advertised,
+            h3_reachable: p.h3_reachable,
+            error: p.error,
+        }
+    }
+}
+
+/// One certificate of a presented TLS chain
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CertificateSummaryResponse {
+    pub subject: String,
+    pub issuer: String,
+    pub not_after: DateTime<Utc>,
+}
+
+impl From<CertificateSummary> for CertificateSummaryResponse {
+    fn from(c: CertificateSummary) -> Self {
+        Self {
+            subject: c.subject,
+            issuer: c.issuer,
+            not_after: c.not_after,
+        }
+    }
+}
+
+/// TLS certificate inspection of one https provider endpoint (extended only)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EndpointTlsResponse {
+    pub endpoint: String,
+    /// Leaf certificate first
+    pub chain: Vec<CertificateSummaryResponse>,
+    /// Leaf SubjectAltName covers the endpoint host
+    pub san_matches: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Negative once expired
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub days_to_expiry: Option<i64>,
+    /// expired, self_signed, hostname_mismatch, untrusted_issuer or handshake
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub failure: Option<TlsFailure>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl From<TlsInspection> for EndpointTlsResponse {
+    fn from(t: TlsInspection) -> Self {
+        Self {
+            endpoint: t.endpoint,
+            chain: t.chain.into_iter().map(Into::into).collect(),
+            san_matches: t.san_matches,
+            expires_at: t.expires_at,
+            days_to_expiry: t.days_to_expiry,
+            failure: t.failure,
+            error: t.error,
+        }
+    }
+}
+
+/// Retrieval health of one tracked provider endpoint (extended only)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EndpointStatusResponse {
+    pub endpoint: String,
+    pub source: EndpointSource,
+    /// healthy, failing, dead (skipped by discovery) or untested
+    pub status: String,
+    pub first_seen: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_seen_in_ipni: Option<DateTime<Utc>>,
+    /// Last time the endpoint was read from StateMinerInfo or the Curio contract
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_seen_on_chain: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_tested_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_success: Option<DateTime<Utc>>,
+    pub consecutive_failures: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avg_latency_ms: Option<f64>,
+}
+
+impl From<ProviderEndpoint> for EndpointStatusResponse {
+    fn from(e: ProviderEndpoint) -> Self {
+        let status = if e.last_tested_at.is_none() {
+            "untested"
+        } else if is_dead_endpoint(&e, Utc::now()) {
+            "dead"
+        } else if e.consecutive_failures > 0 {
+            "failing"
+        } else {
+            "healthy"
+        };
+
+        Self {
+            endpoint: e.endpoint,
+            source: e.source,
+            status: status.to_string(),
+            first_seen: e.first_seen,
+            last_seen_in_ipni: e.last_seen_in_ipni,
+            last_seen_on_chain: e.last_seen_on_chain,
+            last_tested_at: e.last_tested_at,
+            last_success: e.last_success,
+            consecutive_failures: e.consecutive_failures,
+            avg_latency_ms: e.avg_latency_ms,
+        }
+    }
+}
+
+/// Latest provider record lookup on one IPNI indexer (extended only)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct IndexerResultResponse {
+    pub indexer: String,
+    pub result_code: ResultCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+    pub endpoints: Vec<String>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl From<ProviderIndexerResult> for IndexerResultResponse {
+    fn from(r: ProviderIndexerResult) -> Self {
+        Self {
+            indexer: r.indexer,
+            result_code: r.result_code,
+            error_code: r.error_code,
+            endpoints: r.endpoints,
+            fetched_at: r.fetched_at,
+        }
+    }
+}
+
+/// Endpoints each configured IPNI indexer returned for the provider (extended only)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct IndexerComparisonResponse {
+    /// False when the indexers returned different endpoint sets
+    pub agree: bool,
+    pub results: Vec<IndexerResultResponse>,
+}
+
+impl From<Vec<ProviderIndexerResult>> for IndexerComparisonResponse {
+    fn from(results: Vec<ProviderIndexerResult>) -> Self {
+        // Endpoints are stored sorted, so equal sets compare equal
+        let agree = results
+            .windows(2)
+            .all(|pair| pair[0].endpoints == pair[1].endpoints);
+
+        Self {
+            agree,
+            results: results.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ProviderResponse {
     pub provider_id: String,
@@ -148,6 +421,14 @@ pub struct ProviderResponse {
     pub diagnostics: Option<DiagnosticsResponse>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub scheduling: Option<SchedulingResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint_protocols: Option<Vec<EndpointProtocolsResponse>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_certificates: Option<Vec<EndpointTlsResponse>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoints: Option<Vec<EndpointStatusResponse>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub indexers: Option<IndexerComparisonResponse>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -173,6 +454,14 @@ pub struct ProviderClientResponse {
     pub diagnostics: Option<DiagnosticsResponse>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub scheduling: Option<SchedulingResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint_protocols: Option<Vec<EndpointProtocolsResponse>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_certificates: Option<Vec<EndpointTlsResponse>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoints: Option<Vec<EndpointStatusResponse>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub indexers: Option<IndexerComparisonResponse>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -231,11 +520,13 @@ impl ProviderResponse {
                     next_at: s.url_discovery_next_at,
                     status: s.url_discovery_status,
                     pending_since: s.url_discovery_pending_since,
+                    reason: s.url_discovery_reason,
                 },
                 bms_test: ScheduleStateResponse {
                     next_at: s.bms_test_next_at,
                     status: s.bms_test_status,
                     pending_since: None,
+                    reason: None,
                 },
             })
         } else {
@@ -255,9 +546,33 @@ impl ProviderResponse {
             performance: PerformanceResponse::from_data(data.performance, extended),
             diagnostics,
             scheduling: scheduling_response,
+            endpoint_protocols: None,
+            tls_certificates: None,
+            endpoints: None,
+            indexers: None,
         }
     }
 
+    pub fn with_endpoint_protocols(mut self, protocols: Option<Vec<EndpointProtocols>>) -> Self {
+        self.endpoint_protocols = protocols.map(|p| p.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn with_tls_certificates(mut self, inspections: Option<Vec<TlsInspection>>) -> Self {
+        self.tls_certificates = inspections.map(|t| t.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn with_endpoints(mut self, endpoints: Option<Vec<ProviderEndpoint>>) -> Self {
+        self.endpoints = endpoints.map(|e| e.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn with_indexer_results(mut self, results: Option<Vec<ProviderIndexerResult>>) -> Self {
+        self.indexers = results.filter(|r| !r.is_empty()).map(Into::into);
+        self
+    }
+
     fn parse_analysis(metadata: &Option<serde_json::Value>) -> Option<AnalysisResponse> {
         let meta = metadata.as_ref()?;
 
@@ -294,20 +609,67 @@ impl ProviderResponse {
                 both_failed,
                 size_mismatch: b.get("size_mismatch").and_then(|v| v.as_u64()).unwrap_or(0)
                     as usize,
+                random_range: b.get("random_range").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
             });
 
+        let failure_breakdown = meta
+            .get("failure_breakdown")
+            .and_then(|f| serde_json::from_value(f.clone()).ok());
+
         let inconsistent_count = meta
             .get("inconsistency_breakdown")
             .and_then(|b| b.get("total"))
             .and_then(|v| v.as_u64())
             .unwrap_or(0) as usize;
 
+        let commp_verification =
+            meta.get("commp_verification")
+                .map(|c| CommpVerificationResponse {
+                    sample_count: c.get("sample_count").and_then(|v| v.as_u64()).unwrap_or(0)
+                        as usize,
+                    verified_count: c
+                        .get("verified_count")
+                        .and_then(|v| v.as_u64())
+                        .unwrap_or(0) as usize,
+                    mismatch_count: c
+                        .get("mismatch_count")
+                        .and_then(|v| v.as_u64())
+                        .unwrap_or(0) as usize,
+                    failed_count: c.get("failed_count").and_then(|v| v.as_u64()).unwrap_or(0)
+                        as usize,
+                    commp_verified: c.get("commp_verified").and_then(|v| v.as_bool()),
+                });
+
+        let random_range_success_percent = meta
+            .get("random_range")
+            .and_then(|r| r.get("random_range_success_percent"))
+            .and_then(|v| v.as_f64());
+
+        let gateway_retrievability_percent = meta
+            .get("gateway")
+            .and_then(|g| g.get("gateway_retrievability_percent"))
+            .and_then(|v| v.as_f64());
+
+        let root_cid_verification = meta.get("root_cid_verification");
+        let root_cid_match_percent = root_cid_verification
+            .and_then(|r| r.get("root_cid_match_percent"))
+            .and_then(|v| v.as_f64());
+        let root_cid_mismatches = root_cid_verification
+            .and_then(|r| r.get("mismatches"))
+            .and_then(|m| serde_json::from_value(m.clone()).ok());
+
         Some(AnalysisResponse {
             sample_count,
             success_count,
             timeout_count,
             inconsistent_count,
             inconsistent_breakdown: breakdown,
+            failure_breakdown,
+            commp_verification,
+            random_range_success_percent,
+            gateway_retrievability_percent,
+            root_cid_match_percent,
+            root_cid_mismatches,
         })
     }
 
@@ -327,6 +689,7 @@ impl ProviderResponse {
                 both_failed: b.get("both_failed").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
                 size_mismatch: b.get("size_mismatch").and_then(|v| v.as_u64()).unwrap_or(0)
                     as usize,
+                random_range: b.get("random_range").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
             });
 
         Some(AnalysisResponse {
@@ -338,6 +701,12 @@ impl ProviderResponse {
                 .and_then(|v| v.as_u64())
                 .unwrap_or(0) as usize,
             inconsistent_breakdown: breakdown,
+            failure_breakdown: None,
+            commp_verification: None,
+            random_range_success_percent: None,
+            gateway_retrievability_percent: None,
+            root_cid_match_percent: None,
+            root_cid_mismatches: None,
         })
     }
 }
@@ -379,11 +748,13 @@ impl ProviderClientResponse {
                     next_at: s.url_discovery_next_at,
                     status: s.url_discovery_status,
                     pending_since: s.url_discovery_pending_since,
+                    reason: s.url_discovery_reason,
                 },
                 bms_test: ScheduleStateResponse {
                     next_at: s.bms_test_next_at,
                     status: s.bms_test_status,
                     pending_since: None,
+                    reason: None,
                 },
             })
         } else {
@@ -404,8 +775,32 @@ impl ProviderClientResponse {
             performance: PerformanceResponse::from_data(data.performance, extended),
             diagnostics,
             scheduling: scheduling_response,
+            endpoint_protocols: None,
+            tls_certificates: None,
+            endpoints: None,
+            indexers: None,
         }
     }
+
+    pub fn with_endpoint_protocols(mut self, protocols: Option<Vec<EndpointProtocols>>) -> Self {
+        self.endpoint_protocols = protocols.map(|p| p.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn with_tls_certificates(mut self, inspections: Option<Vec<TlsInspection>>) -> Self {
+        self.tls_certificates = inspections.map(|t| t.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn with_endpoints(mut self, endpoints: Option<Vec<ProviderEndpoint>>) -> Self {
+        self.endpoints = endpoints.map(|e| e.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn with_indexer_results(mut self, results: Option<Vec<ProviderIndexerResult>>) -> Self {
+        self.indexers = results.filter(|r| !r.is_empty()).map(Into::into);
+        self
+    }
 }
 
 impl From<ProviderData> for ProviderClientResponse {
@@ -2,8 +2,14 @@ use crate::{
     bms_client::{BmsClient, BmsJobResponse},
     circuit_breaker::CircuitBreaker,
     config::Config,
-    repository::{BmsBandwidthResult, BmsBandwidthResultRepository, StorageProviderRepository},
+    repository::{
+        BmsBandwidthResult, BmsBandwidthResultRepository, LeaseTask, NewSchedulerRun,
+        SchedulerLeaseRepository, SchedulerRunRepository, StorageProvider,
+        StorageProviderRepository,
+    },
 };
+
+use super::run_ledger::record_run;
 use chrono::Utc;
 use color_eyre::Result;
 use std::sync::Arc;
@@ -17,11 +23,30 @@ const JOB_CREATOR_SLEEP_INTERVAL: Duration = Duration::from_secs(3600);
 const RESULT_POLLER_INTERVAL: Duration = Duration::from_secs(30);
 const BATCH_SIZE: i64 = 50;
 const BMS_JOB_TIMEOUT_HOURS: i64 = 48;
+const BMS_JOB_CREATOR_RUN: &str = "bms_job_creator";
 
 // Circuit breaker configuration
 const BMS_CIRCUIT_BREAKER_THRESHOLD: usize = 5;
 const BMS_CIRCUIT_BREAKER_COOLDOWN: Duration = Duration::from_secs(300); // 5 minutes
 
+/// Outcome of one claimed batch; skipped providers were never sent to BMS
+#[derive(Debug, Default)]
+struct BmsJobBatchStats {
+    claimed: usize,
+    created: usize,
+    failed: usize,
+    skipped_circuit_open: usize,
+    skipped_no_url: usize,
+}
+
+/// What happened to a single claimed provider
+enum BmsJobOutcome {
+    Created,
+    Failed,
+    SkippedCircuitOpen,
+    SkippedNoUrl,
+}
+
 /// Create a circuit breaker for BMS API calls.
 pub fn create_bms_circuit_breaker() -> CircuitBreaker {
     CircuitBreaker::new(
@@ -31,59 +56,87 @@ pub fn create_bms_circuit_breaker() -> CircuitBreaker {
     )
 }
 
+/// BMS API access and repositories shared by the job creator and the result poller
+#[derive(Clone)]
+pub struct BmsDeps {
+    pub bms_client: Arc<BmsClient>,
+    pub circuit_breaker: Arc<CircuitBreaker>,
+    pub sp_repo: Arc<StorageProviderRepository>,
+    pub result_repo: Arc<BmsBandwidthResultRepository>,
+    pub lease_repo: Arc<SchedulerLeaseRepository>,
+}
+
 pub async fn run_bms_scheduler(
     config: Arc<Config>,
-    bms_client: Arc<BmsClient>,
-    circuit_breaker: Arc<CircuitBreaker>,
-    sp_repo: Arc<StorageProviderRepository>,
-    result_repo: Arc<BmsBandwidthResultRepository>,
+    deps: BmsDeps,
+    run_repo: Arc<SchedulerRunRepository>,
     shutdown: CancellationToken,
 ) {
     info!("Starting BMS scheduler");
     tokio::join!(
-        run_job_creator(
-            config.clone(),
-            bms_client.clone(),
-            circuit_breaker.clone(),
-            sp_repo.clone(),
-            result_repo.clone(),
-            shutdown.clone()
+        run_job_creator(config.clone(), deps.clone(), run_repo, shutdown.clone()),
+        run_result_poller(
+            config,
+            deps.bms_client,
+            deps.circuit_breaker,
+            deps.result_repo,
+            shutdown
         ),
-        run_result_poller(config, bms_client, circuit_breaker, result_repo, shutdown),
     );
     info!("BMS scheduler stopped");
 }
 
 async fn run_job_creator(
     config: Arc<Config>,
-    bms_client: Arc<BmsClient>,
-    circuit_breaker: Arc<CircuitBreaker>,
-    sp_repo: Arc<StorageProviderRepository>,
-    result_repo: Arc<BmsBandwidthResultRepository>,
+    deps: BmsDeps,
+    run_repo: Arc<SchedulerRunRepository>,
     shutdown: CancellationToken,
 ) {
     info!("Starting BMS job creator loop");
 
     loop {
+        let mut run = NewSchedulerRun::new(BMS_JOB_CREATOR_RUN, &config.instance_id, Utc::now());
         let interval = match create_bms_jobs(
             &config,
-            &bms_client,
-            &circuit_breaker,
-            &sp_repo,
-            &result_repo,
+            &deps.bms_client,
+            &deps.circuit_breaker,
+            &deps.sp_repo,
+            &deps.result_repo,
+            &deps.lease_repo,
         )
         .await
         {
-            Ok(0) => {
+            Ok(stats) if stats.claimed == 0 => {
                 debug!("No providers due for BMS test, sleeping...");
                 JOB_CREATOR_SLEEP_INTERVAL
             }
-            Ok(count) => {
-                info!("BMS job creation cycle completed: {count} jobs created");
-                JOB_CREATOR_INTERVAL
+            Ok(stats) => {
+                run.ok_count = stats.created;
+                run.failed_count = stats.failed;
+                run.skipped_count = stats.skipped_circuit_open + stats.skipped_no_url;
+                run.details = Some(serde_json::json!({
+                    "skipped_circuit_open": stats.skipped_circuit_open,
+                    "skipped_no_url": stats.skipped_no_url,
+                }));
+                record_run(&run_repo, run).await;
+                if stats.created == 0 {
+                    debug!(
+                        "No BMS jobs created for {} due providers, sleeping...",
+                        stats.claimed
+                    );
+                    JOB_CREATOR_SLEEP_INTERVAL
+                } else {
+                    info!(
+                        "BMS job creation cycle completed: {} jobs created",
+                        stats.created
+                    );
+                    JOB_CREATOR_INTERVAL
+                }
             }
             Err(e) => {
                 error!("BMS job creator failed: {:?}", e);
+                run.error = Some(e.to_string());
+                record_run(&run_repo, run).await;
                 JOB_CREATOR_SLEEP_INTERVAL
             }
         };
@@ -106,92 +159,151 @@ async fn create_bms_jobs(
     circuit_breaker: &CircuitBreaker,
     sp_repo: &StorageProviderRepository,
     result_repo: &BmsBandwidthResultRepository,
-) -> Result<usize> {
-    let providers = sp_repo.get_due_for_bms_test(BATCH_SIZE).await?;
+    lease_repo: &SchedulerLeaseRepository,
+) -> Result<BmsJobBatchStats> {
+    let providers = sp_repo
+        .claim_due_for_bms_test(
+            BATCH_SIZE,
+            &config.instance_id,
+            config.scheduler_lease_ttl_secs as f64,
+        )
+        .await?;
 
     debug!("Found {} providers due for BMS test", providers.len());
 
-    let mut jobs_created = 0;
+    let mut stats = BmsJobBatchStats {
+        claimed: providers.len(),
+        ..Default::default()
+    };
+    let providers: Vec<_> = providers
+        .into_iter()
+        .map(|provider| {
+            let lease = lease_repo.track(LeaseTask::BmsJob, provider.provider_id.as_str());
+            (provider, lease)
+        })
+        .collect();
+
+    for (provider, lease) in providers {
+        match create_bms_job(
+            config,
+            bms_client,
+            circuit_breaker,
+            sp_repo,
+            result_repo,
+            &provider,
+        )
+        .await
+        {
+            BmsJobOutcome::Created => stats.created += 1,
+            BmsJobOutcome::Failed => stats.failed += 1,
+            BmsJobOutcome::SkippedCircuitOpen => stats.skipped_circuit_open += 1,
+            BmsJobOutcome::SkippedNoUrl => stats.skipped_no_url += 1,
+        }
 
-    for provider in providers {
-        // Check circuit breaker before each BMS API call
-        if let Err(e) = circuit_breaker.check_allowed() {
+        if let Err(e) = lease_repo
+            .release(
+                LeaseTask::BmsJob,
+                provider.provider_id.as_str(),
+                &config.instance_id,
+            )
+            .await
+        {
             warn!(
-                "BMS circuit breaker open, skipping provider {}: {}",
+                "Failed to release BMS lease of provider {}: {:?}",
                 provider.provider_id, e
             );
-            continue;
         }
+        drop(lease);
+    }
 
-        let url = match &provider.last_working_url {
-            Some(url) => url,
-            None => {
-                warn!(
-                    "Provider {} has no last_working_url, skipping BMS test",
-                    provider.provider_id
-                );
-                continue;
-            }
-        };
+    Ok(stats)
+}
 
-        // Schedule next test FIRST to prevent duplicate jobs if later steps fail.
-        // This marks the provider as not-due before creating external resources.
-        if let Err(e) = sp_repo
-            .schedule_next_bms_test(&provider.provider_id, config.bms_test_interval_days)
-            .await
-        {
-            error!(
-                "Failed to schedule next BMS test for provider {}: {:?}",
-                provider.provider_id, e
+/// Creates and tracks a BMS job for the provider
+async fn create_bms_job(
+    config: &Config,
+    bms_client: &BmsClient,
+    circuit_breaker: &CircuitBreaker,
+    sp_repo: &StorageProviderRepository,
+    result_repo: &BmsBandwidthResultRepository,
+    provider: &StorageProvider,
+) -> BmsJobOutcome {
+    // Check circuit breaker before each BMS API call
+    if let Err(e) = circuit_breaker.check_allowed() {
+        warn!(
+            "BMS circuit breaker open, skipping provider {}: {}",
+            provider.provider_id, e
+        );
+        return BmsJobOutcome::SkippedCircuitOpen;
+    }
+
+    let url = match &provider.last_working_url {
+        Some(url) => url,
+        None => {
+            warn!(
+                "Provider {} has no last_working_url, skipping BMS test",
+                provider.provider_id
             );
-            continue;
+            return BmsJobOutcome::SkippedNoUrl;
         }
+    };
 
-        match bms_client
-            .create_job(
-                url.clone(),
-                config.bms_default_worker_count,
-                Some(format!("f0{}", provider.provider_id)),
-            )
-            .await
-        {
-            Ok(job) => {
-                circuit_breaker.record_success();
-
-                if let Err(e) = result_repo
-                    .insert_pending(
-                        &provider.provider_id,
-                        job.id,
-                        &job.url,
-                        &job.routing_key,
-                        config.bms_default_worker_count as i32,
-                    )
-                    .await
-                {
-                    error!(
-                        "Failed to insert pending result for provider {} (BMS job {} created but untracked): {:?}",
-                        provider.provider_id, job.id, e
-                    );
-                    continue;
-                }
+    // Schedule next test FIRST to prevent duplicate jobs if later steps fail.
+    // This marks the provider as not-due before creating external resources.
+    if let Err(e) = sp_repo
+        .schedule_next_bms_test(&provider.provider_id, config.bms_test_interval_days)
+        .await
+    {
+        error!(
+            "Failed to schedule next BMS test for provider {}: {:?}",
+            provider.provider_id, e
+        );
+        return BmsJobOutcome::Failed;
+    }
 
-                debug!(
-                    "Created BMS job {} for provider {} (routing_key: {})",
-                    job.id, provider.provider_id, job.routing_key
-                );
-                jobs_created += 1;
-            }
-            Err(e) => {
-                circuit_breaker.record_failure();
+    match bms_client
+        .create_job(
+            url.clone(),
+            config.bms_default_worker_count,
+            Some(format!("f0{}", provider.provider_id)),
+        )
+        .await
+    {
+        Ok(job) => {
+            circuit_breaker.record_success();
+
+            if let Err(e) = result_repo
+                .insert_pending(
+                    &provider.provider_id,
+                    job.id,
+                    &job.url,
+                    &job.routing_key,
+                    config.bms_default_worker_count as i32,
+                )
+                .await
+            {
                 error!(
-                    "Failed to create BMS job for provider {}: {} {:?}",
-                    provider.provider_id, url, e
+                    "Failed to insert pending result for provider {} (BMS job {} created but untracked): {:?}",
+                    provider.provider_id, job.id, e
                 );
+                return BmsJobOutcome::Failed;
             }
+
+            debug!(
+                "Created BMS job {} for provider {} (routing_key: {})",
+                job.id, provider.provider_id, job.routing_key
+            );
+            BmsJobOutcome::Created
+        }
+        Err(e) => {
+            circuit_breaker.record_failure();
+            error!(
+                "Failed to create BMS job for provider {}: {} {:?}",
+                provider.provider_id, url, e
+            );
+            BmsJobOutcome::Failed
         }
     }
-
-    Ok(jobs_created)
 }
 
 async fn run_result_poller(
@@ -8,11 +8,15 @@ use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 
 use super::bms_scheduler::extract_results_from_job;
+use super::run_ledger::record_run;
 use crate::{
     bms_client::BmsClient,
     circuit_breaker::CircuitBreaker,
     config::Config,
-    repository::{DealSliBmsJobCompletion, DealSliRepository, NewDealSliBmsJob},
+    repository::{
+        DealSliBmsJobCompletion, DealSliRepository, NewDealSliBmsJob, NewSchedulerRun,
+        SchedulerRunRepository,
+    },
     services::deal_sli_service::{DealSliService, DealSliServiceError},
 };
 
@@ -21,6 +25,7 @@ const DEAL_SLI_SCHEDULER_CATCHUP_INTERVAL: Duration = Duration::from_secs(30);
 const DEAL_SLI_BMS_RESULT_POLLER_INTERVAL: Duration = Duration::from_secs(30);
 const DEAL_SLI_SCHEDULER_BATCH_SIZE: i64 = 25;
 const DEAL_SLI_BMS_JOB_TIMEOUT_HOURS: i64 = 48;
+const DEAL_SLI_RUN: &str = "deal_sli";
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct DealSliSchedulerStats {
@@ -34,11 +39,13 @@ pub async fn run_deal_sli_scheduler(
     deal_sli_repo: Arc<DealSliRepository>,
     bms_client: Arc<BmsClient>,
     circuit_breaker: Arc<CircuitBreaker>,
+    run_repo: Arc<SchedulerRunRepository>,
     shutdown: CancellationToken,
 ) {
     info!("Starting Deal SLI scheduler");
 
     loop {
+        let mut run = NewSchedulerRun::new(DEAL_SLI_RUN, &config.instance_id, Utc::now());
         let interval = match run_deal_sli_scheduler_once(
             &config,
             &deal_sli_service,
@@ -53,6 +60,11 @@ pub async fn run_deal_sli_scheduler(
                     "Deal SLI scheduler processed {} targets and created {} BMS jobs",
                     stats.targets_processed, stats.bms_jobs_created
                 );
+                run.ok_count = stats.targets_processed;
+                run.details = Some(serde_json::json!({
+                    "bms_jobs_created": stats.bms_jobs_created,
+                }));
+                record_run(&run_repo, run).await;
                 DEAL_SLI_SCHEDULER_CATCHUP_INTERVAL
             }
             Ok(_) => {
@@ -61,6 +73,8 @@ pub async fn run_deal_sli_scheduler(
             }
             Err(error) => {
                 error!("Deal SLI scheduler failed: {:?}", error);
+                run.error = Some(error.to_string());
+                record_run(&run_repo, run).await;
                 DEAL_SLI_SCHEDULER_INTERVAL
             }
         };
@@ -2,37 +2,76 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use chrono::Utc;
+use futures::future::join_all;
+use tokio::sync::Notify;
 use tokio::time::sleep;
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+use crate::chain::{CurioPeer, MinerInfo};
 use crate::config::Config;
-use crate::lotus_rpc;
-use crate::provider_endpoints::{get_provider_endpoints, valid_curio_provider};
+use crate::http_client::build_client;
+use crate::libp2p_probe::test_pieces_double_tap;
+use crate::multiaddr_parser;
+use crate::protocol_probe::probe_endpoint;
+use crate::provider_endpoints::{
+    IndexerAdvertisement, IndexerLookup, get_content_advertised_endpoints, get_provider_endpoints,
+    merge_advertised_endpoints, merge_indexer_endpoints,
+};
 use crate::repository::{
-    StorageProvider, StorageProviderRepository, UrlResult, UrlResultRepository,
+    DealLabelRepository, DealRepository, LeaseTask, NewProviderEndpoint, NewProviderIndexerResult,
+    NewSchedulerRun, ProviderEndpointRepository, SchedulerRunRepository, StorageProvider,
+    StorageProviderRepository, UrlResult, UrlResultRepository,
+};
+use crate::services::{
+    consistency_analyzer::analyze_results, deal_service, url_discovery_service::DiscoveryDeps,
 };
-use crate::types::{DiscoveryType, ProviderAddress, ResultCode};
+use crate::tls_inspection::inspect_tls;
+use crate::types::{DiscoveryType, EndpointSource, ProviderAddress, ResultCode, Transport};
+
+use super::run_ledger::record_run;
 
 const SCHEDULER_INTERVAL: Duration = Duration::from_secs(300);
 const CATCHUP_INTERVAL: Duration = Duration::from_secs(5);
 const BATCH_SIZE: i64 = 100;
 const RATE_LIMIT_DELAY: Duration = Duration::from_millis(100);
+const MAX_PROBED_ENDPOINTS: usize = 5;
+/// Deals sampled for IPNI content lookups
+const IPNI_LOOKUP_DEALS: i64 = 3;
+/// Pieces double-tapped when probing a libp2p-only provider
+const LIBP2P_PROBE_PIECES: usize = 5;
+const ENDPOINT_REFRESH_RUN: &str = "endpoint_refresh";
+
+/// Outcome of one claimed batch; providers neither cached nor failed were cut off by shutdown
+#[derive(Debug, Default)]
+struct EndpointBatchStats {
+    total: usize,
+    cached: usize,
+    failed: usize,
+}
 
 pub async fn run_endpoint_scheduler(
     config: Arc<Config>,
-    sp_repo: Arc<StorageProviderRepository>,
-    url_repo: Arc<UrlResultRepository>,
+    deps: DiscoveryDeps,
+    run_repo: Arc<SchedulerRunRepository>,
+    refresh_requested: Arc<Notify>,
     shutdown: CancellationToken,
 ) {
     info!("Starting endpoint scheduler");
 
     loop {
-        match refresh_endpoints(&config, &sp_repo, &url_repo, &shutdown).await {
-            Ok((count, more_pending)) => {
-                if count > 0 {
-                    info!("Endpoint refresh: {} providers updated", count);
+        let mut run = NewSchedulerRun::new(ENDPOINT_REFRESH_RUN, &config.instance_id, Utc::now());
+        match refresh_endpoints(&config, &deps, &shutdown).await {
+            Ok((stats, more_pending)) => {
+                if stats.cached > 0 {
+                    info!("Endpoint refresh: {} providers updated", stats.cached);
+                }
+                if stats.total > 0 {
+                    run.ok_count = stats.cached;
+                    run.failed_count = stats.failed;
+                    run.skipped_count = stats.total - stats.cached - stats.failed;
+                    record_run(&run_repo, run).await;
                 }
 
                 if more_pending {
@@ -42,11 +81,19 @@ pub async fn run_endpoint_scheduler(
                     }
                 }
             }
-            Err(e) => error!("Endpoint refresh failed: {:?}", e),
+            Err(e) => {
+                error!("Endpoint refresh failed: {:?}", e);
+                run.error = Some(e.to_string());
+                record_run(&run_repo, run).await;
+            }
         }
 
         tokio::select! {
             _ = sleep(SCHEDULER_INTERVAL) => {}
+            // Providers whose on-chain identity changed had their endpoints invalidated
+            _ = refresh_requested.notified() => {
+                debug!("Endpoint refresh requested");
+            }
             _ = shutdown.cancelled() => {
                 info!("Endpoint scheduler received shutdown signal");
                 break;
@@ -59,11 +106,17 @@ pub async fn run_endpoint_scheduler(
 
 async fn refresh_endpoints(
     config: &Config,
-    sp_repo: &StorageProviderRepository,
-    url_repo: &UrlResultRepository,
+    deps: &DiscoveryDeps,
     shutdown: &CancellationToken,
-) -> color_eyre::Result<(usize, bool)> {
-    let providers = sp_repo.get_providers_needing_endpoints(BATCH_SIZE).await?;
+) -> color_eyre::Result<(EndpointBatchStats, bool)> {
+    let providers = deps
+        .sp_repo
+        .claim_providers_needing_endpoints(
+            BATCH_SIZE,
+            &config.instance_id,
+            config.scheduler_lease_ttl_secs as f64,
+        )
+        .await?;
     let batch_was_full = providers.len() as i64 == BATCH_SIZE;
 
     if !providers.is_empty() {
@@ -78,44 +131,83 @@ async fn refresh_endpoints(
         );
     }
 
-    let count = process_provider_batch(config, sp_repo, url_repo, providers, shutdown).await;
+    let stats = process_provider_batch(config, deps, providers, shutdown).await;
 
-    Ok((count, batch_was_full))
+    Ok((stats, batch_was_full))
 }
 
 async fn process_provider_batch(
     config: &Config,
-    sp_repo: &StorageProviderRepository,
-    url_repo: &UrlResultRepository,
+    deps: &DiscoveryDeps,
     providers: Vec<StorageProvider>,
     shutdown: &CancellationToken,
-) -> usize {
-    let mut count = 0;
+) -> EndpointBatchStats {
+    let lease_repo = &deps.lease_repo;
+    let mut stats = EndpointBatchStats {
+        total: providers.len(),
+        ..Default::default()
+    };
+    let providers: Vec<_> = providers
+        .into_iter()
+        .map(|provider| {
+            let lease = lease_repo.track(LeaseTask::EndpointRefresh, provider.provider_id.as_str());
+            (provider, lease)
+        })
+        .collect();
 
-    for provider in providers {
+    for (provider, lease) in providers {
         if shutdown.is_cancelled() {
             debug!("Endpoint batch processing interrupted by shutdown");
             break;
         }
 
-        match fetch_and_cache_endpoints(config, sp_repo, url_repo, &provider).await {
+        match fetch_and_cache_endpoints(
+            config,
+            &deps.sp_repo,
+            &deps.url_repo,
+            &deps.endpoint_repo,
+            &deps.deal_repo,
+            &deps.deal_label_repo,
+            &provider,
+        )
+        .await
+        {
             Ok(None) => {
                 debug!("Cached endpoints for {}", provider.provider_id);
-                count += 1;
+                stats.cached += 1;
             }
             Ok(Some(result_code)) => {
-                debug!("No endpoints for {}: {}", provider.provider_id, result_code)
+                debug!("No endpoints for {}: {}", provider.provider_id, result_code);
+                stats.failed += 1;
+            }
+            Err(e) => {
+                debug!(
+                    "Failed to fetch endpoints for {}: {:?}",
+                    provider.provider_id, e
+                );
+                stats.failed += 1;
             }
-            Err(e) => debug!(
-                "Failed to fetch endpoints for {}: {:?}",
+        }
+
+        if let Err(e) = lease_repo
+            .release(
+                LeaseTask::EndpointRefresh,
+                provider.provider_id.as_str(),
+                &config.instance_id,
+            )
+            .await
+        {
+            warn!(
+                "Failed to release endpoint lease of {}: {:?}",
                 provider.provider_id, e
-            ),
+            );
         }
+        drop(lease);
 
         sleep(RATE_LIMIT_DELAY).await;
     }
 
-    count
+    stats
 }
 
 /// Phase 1 of URL discovery: resolve peer_id, fetch endpoints, cache or record failure.
@@ -124,63 +216,338 @@ async fn fetch_and_cache_endpoints(
     config: &Config,
     sp_repo: &StorageProviderRepository,
     url_repo: &UrlResultRepository,
+    endpoint_repo: &ProviderEndpointRepository,
+    deal_repo: &DealRepository,
+    deal_label_repo: &DealLabelRepository,
     provider: &StorageProvider,
 ) -> color_eyre::Result<Option<ResultCode>> {
     let provider_id = &provider.provider_id;
     let address: ProviderAddress = provider_id.clone().into();
 
-    let peer_id = match valid_curio_provider(config, &address).await {
-        Ok(Some(pid)) => pid,
-        _ => {
-            debug!(
-                "Curio lookup failed for {}, falling back to Lotus",
-                provider_id
-            );
-            match lotus_rpc::get_peer_id(config, &address).await {
-                Ok(pid) => pid,
-                Err(e) => {
-                    debug!("Lotus lookup failed for {}: {:?}", provider_id, e);
-                    return record_failure(
-                        sp_repo,
-                        url_repo,
-                        provider_id,
-                        ResultCode::NoPeerId,
-                        None,
-                    )
-                    .await;
-                }
-            }
+    let curio_peer = match config.chain.get_curio_peer(&address).await {
+        Ok(peer) => peer,
+        Err(e) => {
+            debug!("Curio lookup failed for {}: {:?}", provider_id, e);
+            None
+        }
+    };
+    let miner_info = match config.chain.get_miner_info(&address).await {
+        Ok(info) => Some(info),
+        Err(e) => {
+            debug!("StateMinerInfo failed for {}: {:?}", provider_id, e);
+            None
+        }
+    };
+
+    let Some(peer_id) = curio_peer
+        .as_ref()
+        .map(|peer| peer.peer_id.clone())
+        .or_else(|| miner_info.as_ref().and_then(|info| info.peer_id.clone()))
+    else {
+        return record_failure(sp_repo, url_repo, provider_id, ResultCode::NoPeerId, None).await;
+    };
+
+    let chain_endpoints = on_chain_endpoints(curio_peer.as_ref(), miner_info.as_ref()).await;
+    endpoint_repo
+        .upsert_seen_on_chain(provider_id, &chain_endpoints)
+        .await?;
+
+    let lookups = match get_provider_endpoints(config, &address, Some(peer_id.clone())).await {
+        Ok(lookups) => lookups,
+        Err(error_code) => {
+            debug!("get_provider_endpoints failed for {provider_id}: {error_code}");
+            return record_failure(
+                sp_repo,
+                url_repo,
+                provider_id,
+                ResultCode::Error,
+                Some(error_code),
+            )
+            .await;
         }
     };
+    let advertised =
+        lookup_advertised_endpoints(config, deal_repo, deal_label_repo, provider_id, &peer_id)
+            .await;
+    endpoint_repo
+        .replace_indexer_results(provider_id, &indexer_results(&lookups, &advertised))
+        .await?;
 
-    match get_provider_endpoints(config, &address, Some(peer_id.clone())).await {
-        Ok((ResultCode::Success, Some(endpoints))) => {
-            sp_repo
-                .update_cached_endpoints(provider_id, &peer_id, &endpoints)
-                .await?;
-            debug!(
-                "Cached {} endpoints for {} (peer_id: {})",
-                endpoints.len(),
+    let mut merged = match merge_indexer_endpoints(&lookups) {
+        Ok((ResultCode::Success, Some(merged))) => merged,
+        _ if !advertised.is_empty() => {
+            debug!("No http endpoints in provider records of {provider_id}, using content lookups");
+            vec![]
+        }
+        _ if !chain_endpoints.is_empty() => {
+            debug!("No http endpoints in IPNI for {provider_id}, using on-chain multiaddrs");
+            vec![]
+        }
+        Ok((ResultCode::MissingHttpAddrFromCidContact, _)) if config.libp2p_probe_enabled => {
+            return probe_libp2p_provider(
+                sp_repo,
+                url_repo,
+                deal_repo,
                 provider_id,
-                peer_id
-            );
-            Ok(None)
+                &peer_id,
+                &lookups,
+            )
+            .await;
         }
         Ok((result_code, _)) => {
-            record_failure(sp_repo, url_repo, provider_id, result_code, None).await
+            return record_failure(sp_repo, url_repo, provider_id, result_code, None).await;
         }
         Err(error_code) => {
-            debug!("get_provider_endpoints failed for {provider_id}: {error_code}");
-            record_failure(
+            debug!("No indexer returned endpoints for {provider_id}: {error_code}");
+            return record_failure(
                 sp_repo,
                 url_repo,
                 provider_id,
                 ResultCode::Error,
                 Some(error_code),
             )
-            .await
+            .await;
         }
+    };
+    merge_advertised_endpoints(&mut merged, &advertised);
+
+    let new_endpoints: Vec<NewProviderEndpoint> = merged
+        .iter()
+        .map(|m| NewProviderEndpoint {
+            endpoint: m.endpoint.clone(),
+            source: m.source,
+        })
+        .collect();
+    endpoint_repo
+        .upsert_seen_in_ipni(provider_id, &new_endpoints)
+        .await?;
+    let mut endpoints: Vec<String> = merged.into_iter().map(|m| m.endpoint).collect();
+    if endpoints.is_empty() {
+        endpoints = chain_endpoints.into_iter().map(|e| e.endpoint).collect();
+    }
+
+    sp_repo
+        .update_cached_endpoints(provider_id, &peer_id, &endpoints)
+        .await?;
+    debug!(
+        "Cached {} endpoints for {} (peer_id: {})",
+        endpoints.len(),
+        provider_id,
+        peer_id
+    );
+    if let Err(e) = probe_protocols(config, sp_repo, provider_id, &endpoints).await {
+        debug!("Protocol probe failed for {provider_id}: {e:?}");
+    }
+    if let Err(e) = inspect_certificates(sp_repo, provider_id, &endpoints).await {
+        debug!("TLS inspection failed for {provider_id}: {e:?}");
     }
+    Ok(None)
+}
+
+/// HTTP endpoints the provider set on chain, in the Curio contract first and then in
+/// StateMinerInfo. Only explicit /http and /https multiaddrs count, since the rest are
+/// libp2p listen addresses.
+async fn on_chain_endpoints(
+    curio_peer: Option<&CurioPeer>,
+    miner_info: Option<&MinerInfo>,
+) -> Vec<NewProviderEndpoint> {
+    let mut endpoints: Vec<NewProviderEndpoint> = vec![];
+    let sources = [
+        (
+            EndpointSource::CurioContract,
+            curio_peer.map(|peer| peer.multiaddrs.clone()),
+        ),
+        (
+            EndpointSource::MinerInfo,
+            miner_info.map(|info| info.multiaddrs.clone()),
+        ),
+    ];
+
+    for (source, addrs) in sources {
+        let Some(addrs) = addrs.filter(|addrs| !addrs.is_empty()) else {
+            continue;
+        };
+        for endpoint in multiaddr_parser::parse_explicit_http(addrs).await {
+            if !endpoints.iter().any(|e| e.endpoint == endpoint) {
+                endpoints.push(NewProviderEndpoint { endpoint, source });
+            }
+        }
+    }
+
+    endpoints
+}
+
+/// What each indexer returned, so disagreements between indexers can be reported
+fn indexer_results(
+    lookups: &[IndexerLookup],
+    advertised: &[IndexerAdvertisement],
+) -> Vec<NewProviderIndexerResult> {
+    lookups
+        .iter()
+        .map(|lookup| {
+            let (result_code, error_code, mut endpoints) = match &lookup.outcome {
+                Ok((result_code, found)) => (
+                    result_code.clone(),
+                    None,
+                    found.clone().map(|(_, e)| e).unwrap_or_default(),
+                ),
+                Err(error_code) => (ResultCode::Error, Some(error_code.clone()), vec![]),
+            };
+            for ad in advertised.iter().filter(|ad| ad.indexer == lookup.indexer) {
+                for endpoint in &ad.endpoints {
+                    if !endpoints.contains(endpoint) {
+                        endpoints.push(endpoint.clone());
+                    }
+                }
+            }
+            endpoints.sort();
+
+            NewProviderIndexerResult {
+                indexer: lookup.indexer.clone(),
+                result_code,
+                error_code,
+                endpoints,
+            }
+        })
+        .collect()
+}
+
+/// HTTP endpoints IPNI advertises for a few of the provider's deals, empty when none resolve
+async fn lookup_advertised_endpoints(
+    config: &Config,
+    deal_repo: &DealRepository,
+    deal_label_repo: &DealLabelRepository,
+    provider_id: &crate::types::ProviderId,
+    peer_id: &str,
+) -> Vec<IndexerAdvertisement> {
+    let cids = match deal_service::get_ipni_lookup_cids(
+        deal_repo,
+        deal_label_repo,
+        provider_id,
+        IPNI_LOOKUP_DEALS,
+    )
+    .await
+    {
+        Ok(cids) => cids,
+        Err(e) => {
+            debug!("Failed to sample IPNI lookup CIDs for {provider_id}: {e:?}");
+            return vec![];
+        }
+    };
+
+    get_content_advertised_endpoints(config, peer_id, &cids).await
+}
+
+/// Records ALPN, Alt-Svc and HTTP/3 reachability for the first few cached endpoints
+async fn probe_protocols(
+    config: &Config,
+    sp_repo: &StorageProviderRepository,
+    provider_id: &crate::types::ProviderId,
+    endpoints: &[String],
+) -> color_eyre::Result<()> {
+    let client = build_client(config)?;
+    let results = join_all(
+        endpoints
+            .iter()
+            .take(MAX_PROBED_ENDPOINTS)
+            .map(|endpoint| probe_endpoint(&client, endpoint)),
+    )
+    .await;
+
+    sp_repo
+        .update_endpoint_protocols(provider_id, serde_json::to_value(&results)?)
+        .await
+}
+
+/// Records the certificate chain and expiry of the first few cached https endpoints
+async fn inspect_certificates(
+    sp_repo: &StorageProviderRepository,
+    provider_id: &crate::types::ProviderId,
+    endpoints: &[String],
+) -> color_eyre::Result<()> {
+    let results = join_all(
+        endpoints
+            .iter()
+            .filter(|endpoint| endpoint.starts_with("https://"))
+            .take(MAX_PROBED_ENDPOINTS)
+            .map(|endpoint| inspect_tls(endpoint)),
+    )
+    .await;
+    let expires_at = results.iter().filter_map(|r| r.expires_at).min();
+
+    sp_repo
+        .update_tls_inspection(provider_id, serde_json::to_value(&results)?, expires_at)
+        .await
+}
+
+/// Measures retrievability of a provider without HTTP endpoints over HTTP on libp2p streams.
+/// The result code stays MissingHttpAddrFromCidContact and the measurement goes to the metadata
+/// only, so libp2p never counts toward the HTTP retrievability reported for providers.
+async fn probe_libp2p_provider(
+    sp_repo: &StorageProviderRepository,
+    url_repo: &UrlResultRepository,
+    deal_repo: &DealRepository,
+    provider_id: &crate::types::ProviderId,
+    peer_id: &str,
+    lookups: &[IndexerLookup],
+) -> color_eyre::Result<Option<ResultCode>> {
+    let result_code = ResultCode::MissingHttpAddrFromCidContact;
+
+    let mut addrs: Vec<String> = vec![];
+    for addr in lookups
+        .iter()
+        .flat_map(|l| multiaddr_parser::libp2p_addrs(&l.addrs))
+    {
+        if !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+    let pieces = match deal_service::get_random_piece_ids_by_provider(deal_repo, provider_id).await
+    {
+        Ok(pieces) => pieces,
+        Err(e) => {
+            debug!("Failed to sample pieces for libp2p probe of {provider_id}: {e:?}");
+            vec![]
+        }
+    };
+    if addrs.is_empty() || pieces.is_empty() {
+        return record_failure(sp_repo, url_repo, provider_id, result_code, None).await;
+    }
+
+    let pieces: Vec<String> = pieces.into_iter().take(LIBP2P_PROBE_PIECES).collect();
+    let results = test_pieces_double_tap(peer_id, &addrs, &pieces).await;
+    let analysis = analyze_results(&results);
+    debug!(
+        "libp2p probe of {provider_id}: retrievability {:.1}%",
+        analysis.retrievability_percent
+    );
+
+    sp_repo.mark_endpoint_fetch_failed(provider_id).await?;
+
+    let url_result = UrlResult {
+        url_metadata: Some(serde_json::json!({
+            "transport": Transport::Libp2p,
+            "peer_id": peer_id,
+            "addrs": addrs,
+            "libp2p": {
+                "retrievability_percent": analysis.retrievability_percent,
+                "is_consistent": analysis.is_consistent,
+                "is_reliable": analysis.is_reliable,
+                "car_files_percent": analysis.car_files_percent,
+                "large_files_percent": analysis.large_files_percent,
+                "counts": {
+                    "sample_count": analysis.sample_count,
+                    "success_count": analysis.success_count,
+                    "failed_count": analysis.failed_count,
+                },
+                "failure_breakdown": analysis.failure_causes,
+            },
+        })),
+        ..failure_result(provider_id, result_code.clone(), None)
+    };
+    url_repo.insert_batch(&[url_result]).await?;
+
+    Ok(Some(result_code))
 }
 
 async fn record_failure(
@@ -192,14 +559,25 @@ async fn record_failure(
 ) -> color_eyre::Result<Option<ResultCode>> {
     sp_repo.mark_endpoint_fetch_failed(provider_id).await?;
 
-    let url_result = UrlResult {
+    let url_result = failure_result(provider_id, result_code.clone(), error_code);
+    url_repo.insert_batch(&[url_result]).await?;
+
+    Ok(Some(result_code))
+}
+
+fn failure_result(
+    provider_id: &crate::types::ProviderId,
+    result_code: ResultCode,
+    error_code: Option<crate::types::ErrorCode>,
+) -> UrlResult {
+    UrlResult {
         id: Uuid::new_v4(),
         provider_id: provider_id.clone(),
         client_id: None,
         result_type: DiscoveryType::Provider,
         working_url: None,
         retrievability_percent: None,
-        result_code: result_code.clone(),
+        result_code,
         error_code,
         tested_at: Utc::now(),
         is_consistent: None,
@@ -208,9 +586,5 @@ async fn record_failure(
         sector_utilization_percent: None,
         car_files_percent: None,
         large_files_percent: None,
-    };
-
-    url_repo.insert_batch(&[url_result]).await?;
-
-    Ok(Some(result_code))
+    }
 }
@@ -0,0 +1,43 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::config::Config;
+use crate::repository::SchedulerLeaseRepository;
+
+/// Renews the in-flight leases of this instance a few times per TTL, so leases only lapse once
+/// the instance stops heartbeating or the task holding one is gone, and other instances can
+/// then reclaim the work.
+pub async fn run_lease_heartbeat(
+    config: Arc<Config>,
+    lease_repo: Arc<SchedulerLeaseRepository>,
+    shutdown: CancellationToken,
+) {
+    info!(
+        "Starting lease heartbeat for instance {}",
+        config.instance_id
+    );
+
+    let ttl_secs = config.scheduler_lease_ttl_secs as f64;
+    let interval = Duration::from_secs((config.scheduler_lease_ttl_secs as u64 / 3).max(1));
+
+    loop {
+        match lease_repo.heartbeat(&config.instance_id, ttl_secs).await {
+            Ok(held) => debug!("Lease heartbeat: {held} leases renewed"),
+            Err(e) => warn!("Lease heartbeat failed, leases may lapse: {:?}", e),
+        }
+
+        tokio::select! {
+            _ = sleep(interval) => {}
+            _ = shutdown.cancelled() => {
+                info!("Lease heartbeat received shutdown signal");
+                break;
+            }
+        }
+    }
+
+    info!("Lease heartbeat stopped");
+}
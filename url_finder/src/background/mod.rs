@@ -1,11 +1,17 @@
 mod bms_scheduler;
 mod deal_sli_scheduler;
 mod endpoint_scheduler;
+mod lease_heartbeat;
+mod peer_id_scheduler;
 mod provider_discovery;
+mod run_ledger;
 mod url_discovery_scheduler;
 
 pub use bms_scheduler::*;
 pub use deal_sli_scheduler::*;
 pub use endpoint_scheduler::*;
+pub use lease_heartbeat::*;
+pub use peer_id_scheduler::*;
 pub use provider_discovery::*;
+pub use run_ledger::run_scheduler_run_pruner;
 pub use url_discovery_scheduler::*;
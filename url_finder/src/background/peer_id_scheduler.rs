@@ -0,0 +1,358 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use tokio::sync::Notify;
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+use crate::config::Config;
+use crate::repository::{
+    LeaseTask, NewSchedulerRun, ProviderIdentity, ProviderIdentityRepository,
+    SchedulerLeaseRepository, SchedulerRunRepository,
+};
+use crate::types::ProviderAddress;
+
+use super::run_ledger::record_run;
+
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(600);
+const CATCHUP_INTERVAL: Duration = Duration::from_secs(5);
+const BATCH_SIZE: i64 = 100;
+const RATE_LIMIT_DELAY: Duration = Duration::from_millis(100);
+const PEER_ID_REFRESH_RUN: &str = "peer_id_refresh";
+
+/// Outcome of one stale batch; providers not checked were cut off by shutdown
+#[derive(Debug, Default)]
+struct PeerIdBatchStats {
+    total: usize,
+    checked: usize,
+    changed: usize,
+    failed: usize,
+}
+
+/// Re-reads peer IDs and multiaddrs from chain once they are older than
+/// `peer_id_max_age_hours`. Changes are recorded and wake the endpoint scheduler.
+pub async fn run_peer_id_scheduler(
+    config: Arc<Config>,
+    identity_repo: Arc<ProviderIdentityRepository>,
+    lease_repo: Arc<SchedulerLeaseRepository>,
+    run_repo: Arc<SchedulerRunRepository>,
+    endpoint_refresh: Arc<Notify>,
+    shutdown: CancellationToken,
+) {
+    info!("Starting peer ID scheduler");
+
+    loop {
+        let mut run = NewSchedulerRun::new(PEER_ID_REFRESH_RUN, &config.instance_id, Utc::now());
+        match refresh_peer_ids(&config, &identity_repo, &lease_repo, &shutdown).await {
+            Ok((stats, more_pending)) => {
+                if stats.checked > 0 {
+                    info!(
+                        "Peer ID refresh: {} providers checked, {} changed",
+                        stats.checked, stats.changed
+                    );
+                }
+                if stats.changed > 0 {
+                    endpoint_refresh.notify_one();
+                }
+                if stats.total > 0 {
+                    run.ok_count = stats.checked - stats.failed;
+                    run.failed_count = stats.failed;
+                    run.skipped_count = stats.total - stats.checked;
+                    run.details = Some(serde_json::json!({ "changed": stats.changed }));
+                    record_run(&run_repo, run).await;
+                }
+
+                if more_pending {
+                    tokio::select! {
+                        _ = sleep(CATCHUP_INTERVAL) => continue,
+                        _ = shutdown.cancelled() => break,
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Peer ID refresh failed: {:?}", e);
+                run.error = Some(e.to_string());
+                record_run(&run_repo, run).await;
+            }
+        }
+
+        tokio::select! {
+            _ = sleep(SCHEDULER_INTERVAL) => {}
+            _ = shutdown.cancelled() => {
+                info!("Peer ID scheduler received shutdown signal");
+                break;
+            }
+        }
+    }
+
+    info!("Peer ID scheduler stopped");
+}
+
+async fn refresh_peer_ids(
+    config: &Config,
+    identity_repo: &ProviderIdentityRepository,
+    lease_repo: &SchedulerLeaseRepository,
+    shutdown: &CancellationToken,
+) -> color_eyre::Result<(PeerIdBatchStats, bool)> {
+    let fetched_before = Utc::now() - chrono::Duration::hours(config.peer_id_max_age_hours);
+    let providers = identity_repo
+        .claim_stale(
+            fetched_before,
+            BATCH_SIZE,
+            &config.instance_id,
+            config.scheduler_lease_ttl_secs as f64,
+        )
+        .await?;
+    let batch_was_full = providers.len() as i64 == BATCH_SIZE;
+
+    let mut stats = PeerIdBatchStats {
+        total: providers.len(),
+        ..Default::default()
+    };
+    let providers: Vec<_> = providers
+        .into_iter()
+        .map(|provider| {
+            let lease = lease_repo.track(LeaseTask::PeerIdRefresh, provider.provider_id.as_str());
+            (provider, lease)
+        })
+        .collect();
+
+    for (provider, lease) in providers {
+        if shutdown.is_cancelled() {
+            info!("Peer ID refresh interrupted by shutdown");
+            break;
+        }
+
+        match refresh_identity(config, identity_repo, &provider).await {
+            Ok(true) => stats.changed += 1,
+            Ok(false) => {}
+            Err(e) => {
+                error!(
+                    "Failed to refresh peer ID of {}: {:?}",
+                    provider.provider_id, e
+                );
+                stats.failed += 1;
+            }
+        }
+        stats.checked += 1;
+
+        if let Err(e) = lease_repo
+            .release(
+                LeaseTask::PeerIdRefresh,
+                provider.provider_id.as_str(),
+                &config.instance_id,
+            )
+            .await
+        {
+            warn!(
+                "Failed to release peer ID lease of {}: {:?}",
+                provider.provider_id, e
+            );
+        }
+        drop(lease);
+
+        sleep(RATE_LIMIT_DELAY).await;
+    }
+
+    Ok((stats, batch_was_full))
+}
+
+/// Reads the provider's identity from chain, returning whether it changed.
+/// The Curio contract takes precedence over StateMinerInfo for the peer ID, as in the
+/// endpoint scheduler. The multiaddrs of both sources are compared, each against its own
+/// recorded set.
+async fn refresh_identity(
+    config: &Config,
+    identity_repo: &ProviderIdentityRepository,
+    provider: &ProviderIdentity,
+) -> color_eyre::Result<bool> {
+    let address: ProviderAddress = provider.provider_id.clone().into();
+
+    let (lotus_peer_id, multiaddrs) = match config.chain.get_miner_info(&address).await {
+        Ok(info) => (info.peer_id, Some(info.multiaddrs)),
+        Err(e) => {
+            debug!(
+                "StateMinerInfo failed for {}: {:?}",
+                provider.provider_id, e
+            );
+            (None, None)
+        }
+    };
+    // Not registered in the contract reads as an empty set; a failed lookup as unknown
+    let (curio_peer_id, curio_multiaddrs) = match config.chain.get_curio_peer(&address).await {
+        Ok(Some(peer)) => (Some(peer.peer_id), Some(peer.multiaddrs)),
+        Ok(None) => (None, Some(vec![])),
+        Err(e) => {
+            debug!(
+                "Curio peer lookup failed for {}: {:?}",
+                provider.provider_id, e
+            );
+            (None, None)
+        }
+    };
+
+    // Chain unreachable or peer ID unset: keep the recorded identity until the next refresh
+    let Some(peer_id) = curio_peer_id.or(lotus_peer_id) else {
+        identity_repo
+            .mark_refreshed(&provider.provider_id, None, None)
+            .await?;
+        return Ok(false);
+    };
+
+    if !identity_changed(
+        provider,
+        &peer_id,
+        multiaddrs.as_deref(),
+        curio_multiaddrs.as_deref(),
+    ) {
+        identity_repo
+            .mark_refreshed(
+                &provider.provider_id,
+                multiaddrs.as_deref(),
+                curio_multiaddrs.as_deref(),
+            )
+            .await?;
+        return Ok(false);
+    }
+
+    info!(
+        "Identity of {} changed: peer_id {:?} -> {}, multiaddrs {:?} -> {:?}, Curio multiaddrs {:?} -> {:?}",
+        provider.provider_id,
+        provider.peer_id,
+        peer_id,
+        provider.peer_multiaddrs,
+        multiaddrs,
+        provider.peer_curio_multiaddrs,
+        curio_multiaddrs
+    );
+    let multiaddrs = multiaddrs
+        .or_else(|| provider.peer_multiaddrs.clone())
+        .unwrap_or_default();
+    let curio_multiaddrs = curio_multiaddrs
+        .or_else(|| provider.peer_curio_multiaddrs.clone())
+        .unwrap_or_default();
+    identity_repo
+        .record_change(provider, &peer_id, &multiaddrs, &curio_multiaddrs)
+        .await?;
+    Ok(true)
+}
+
+/// Multiaddrs only count as changed once a previous set was recorded, and order is ignored
+fn identity_changed(
+    previous: &ProviderIdentity,
+    peer_id: &str,
+    multiaddrs: Option<&[String]>,
+    curio_multiaddrs: Option<&[String]>,
+) -> bool {
+    previous.peer_id.as_deref() != Some(peer_id)
+        || multiaddrs_changed(previous.peer_multiaddrs.as_deref(), multiaddrs)
+        || multiaddrs_changed(previous.peer_curio_multiaddrs.as_deref(), curio_multiaddrs)
+}
+
+fn multiaddrs_changed(previous: Option<&[String]>, current: Option<&[String]>) -> bool {
+    match (previous, current) {
+        (Some(old), Some(new)) => {
+            let mut old = old.to_vec();
+            let mut new = new.to_vec();
+            old.sort();
+            new.sort();
+            old != new
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ProviderId;
+
+    const PEER_ID: &str = "12D3KooWRf7tJR2NfJYE3PQJKXGt1EFqmFBBfQCgPRBLwwR9XL15";
+
+    fn identity(peer_id: &str, multiaddrs: Option<&[&str]>) -> ProviderIdentity {
+        ProviderIdentity {
+            provider_id: ProviderId::new("1000").unwrap(),
+            peer_id: Some(peer_id.to_string()),
+            peer_multiaddrs: multiaddrs.map(|m| m.iter().map(|a| a.to_string()).collect()),
+            peer_curio_multiaddrs: None,
+            peer_id_fetched_at: None,
+        }
+    }
+
+    fn addrs(addrs: &[&str]) -> Vec<String> {
+        addrs.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn new_peer_id_is_a_change() {
+        let previous = identity(PEER_ID, None);
+
+        assert!(identity_changed(&previous, "12D3KooWOther", None, None));
+    }
+
+    #[test]
+    fn first_multiaddrs_are_not_a_change() {
+        let previous = identity(PEER_ID, None);
+
+        assert!(!identity_changed(
+            &previous,
+            PEER_ID,
+            Some(&addrs(&["/ip4/1.2.3.4/tcp/24001"])),
+            None
+        ));
+    }
+
+    #[test]
+    fn multiaddr_order_is_ignored() {
+        let previous = identity(
+            PEER_ID,
+            Some(&["/ip4/1.2.3.4/tcp/24001", "/ip4/1.2.3.4/udp/24001/quic-v1"]),
+        );
+
+        assert!(!identity_changed(
+            &previous,
+            PEER_ID,
+            Some(&addrs(&[
+                "/ip4/1.2.3.4/udp/24001/quic-v1",
+                "/ip4/1.2.3.4/tcp/24001"
+            ])),
+            None
+        ));
+        assert!(identity_changed(
+            &previous,
+            PEER_ID,
+            Some(&addrs(&["/ip4/5.6.7.8/tcp/24001"])),
+            None
+        ));
+    }
+
+    #[test]
+    fn unknown_multiaddrs_keep_identity() {
+        let previous = identity(PEER_ID, Some(&["/ip4/1.2.3.4/tcp/24001"]));
+
+        assert!(!identity_changed(&previous, PEER_ID, None, None));
+    }
+
+    #[test]
+    fn curio_multiaddr_change_is_a_change() {
+        let mut previous = identity(PEER_ID, Some(&["/ip4/1.2.3.4/tcp/24001"]));
+        previous.peer_curio_multiaddrs = Some(addrs(&["/dns/sp.example.com/tcp/443/https"]));
+        let lotus = addrs(&["/ip4/1.2.3.4/tcp/24001"]);
+
+        assert!(!identity_changed(
+            &previous,
+            PEER_ID,
+            Some(&lotus),
+            Some(&addrs(&["/dns/sp.example.com/tcp/443/https"]))
+        ));
+        assert!(identity_changed(
+            &previous,
+            PEER_ID,
+            Some(&lotus),
+            Some(&addrs(&["/dns/sp2.example.com/tcp/443/https"]))
+        ));
+        assert!(!identity_changed(&previous, PEER_ID, Some(&lotus), None));
+    }
+}
@@ -1,29 +1,75 @@
+use chrono::Utc;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::sleep;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info};
 
-use crate::repository::{DealRepository, StorageProviderRepository};
+use crate::config::Config;
+use crate::repository::{
+    DealRepository, LeaseTask, NewSchedulerRun, SchedulerLeaseRepository, SchedulerRunRepository,
+    StorageProviderRepository,
+};
+
+use super::run_ledger::record_run;
 
 const DISCOVERY_INTERVAL: Duration = Duration::from_secs(3600 * 12); // 12 hours
+const LEASE_RETRY_INTERVAL: Duration = Duration::from_secs(600); // 10 minutes
 const DMOB_QUERY_TIMEOUT: Duration = Duration::from_secs(1200); // 20 minutes
 
+/// Only the instance holding the provider discovery lease syncs; the heartbeat keeps it held,
+/// and the others take over once it lapses.
 pub async fn run_provider_discovery(
+    config: Arc<Config>,
     sp_repo: Arc<StorageProviderRepository>,
     deal_repo: Arc<DealRepository>,
+    lease_repo: Arc<SchedulerLeaseRepository>,
+    run_repo: Arc<SchedulerRunRepository>,
     shutdown: CancellationToken,
 ) {
     info!("Starting provider discovery loop");
 
+    let task = LeaseTask::ProviderDiscovery;
+    // Heartbeated whenever this instance holds it, and lapses once this loop is gone
+    let _lease = lease_repo.track(task, task.as_str());
+
     loop {
-        match discover_and_sync_providers(&sp_repo, &deal_repo).await {
-            Ok(count) => info!("Provider discovery completed: {} providers synced", count),
-            Err(e) => error!("Provider discovery failed: {:?}", e),
-        }
+        let interval = match lease_repo
+            .acquire(
+                task,
+                task.as_str(),
+                &config.instance_id,
+                config.scheduler_lease_ttl_secs as f64,
+            )
+            .await
+        {
+            Ok(true) => {
+                let mut run = NewSchedulerRun::new(task.as_str(), &config.instance_id, Utc::now());
+                match discover_and_sync_providers(&sp_repo, &deal_repo).await {
+                    Ok(count) => {
+                        info!("Provider discovery completed: {} providers synced", count);
+                        run.ok_count = count;
+                    }
+                    Err(e) => {
+                        error!("Provider discovery failed: {:?}", e);
+                        run.error = Some(e.to_string());
+                    }
+                }
+                record_run(&run_repo, run).await;
+                DISCOVERY_INTERVAL
+            }
+            Ok(false) => {
+                debug!("Provider discovery is leased to another instance");
+                LEASE_RETRY_INTERVAL
+            }
+            Err(e) => {
+                error!("Failed to acquire provider discovery lease: {:?}", e);
+                LEASE_RETRY_INTERVAL
+            }
+        };
 
         tokio::select! {
-            _ = sleep(DISCOVERY_INTERVAL) => {}
+            _ = sleep(interval) => {}
             _ = shutdown.cancelled() => {
                 info!("Provider discovery received shutdown signal");
                 break;
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::repository::{NewSchedulerRun, SchedulerRunRepository};
+
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Records a scheduler iteration in `scheduler_runs`. The ledger is best effort: a failed
+/// insert is logged and never fails the scheduler.
+pub(super) async fn record_run(run_repo: &SchedulerRunRepository, run: NewSchedulerRun) {
+    if let Err(e) = run_repo.insert(&run).await {
+        warn!("Failed to record {} run: {:?}", run.scheduler, e);
+    }
+}
+
+/// Keeps `scheduler_runs` bounded by deleting runs older than `scheduler_run_retention_days`
+pub async fn run_scheduler_run_pruner(
+    config: Arc<Config>,
+    run_repo: Arc<SchedulerRunRepository>,
+    shutdown: CancellationToken,
+) {
+    info!("Starting scheduler run pruner");
+
+    loop {
+        let started_before =
+            Utc::now() - chrono::Duration::days(config.scheduler_run_retention_days);
+        match run_repo.delete_started_before(started_before).await {
+            Ok(0) => {}
+            Ok(deleted) => info!("Pruned {deleted} scheduler runs before {started_before}"),
+            Err(e) => warn!("Failed to prune scheduler runs: {:?}", e),
+        }
+
+        tokio::select! {
+            _ = sleep(PRUNE_INTERVAL) => {}
+            _ = shutdown.cancelled() => {
+                info!("Scheduler run pruner received shutdown signal");
+                break;
+            }
+        }
+    }
+
+    info!("Scheduler run pruner stopped");
+}
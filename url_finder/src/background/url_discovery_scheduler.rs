@@ -1,10 +1,15 @@
 use crate::{
     config::Config,
     repository::{
-        DealRepository, StorageProvider, StorageProviderRepository, UrlResult, UrlResultRepository,
+        DealSliRepository, LeaseTask, NewSchedulerRun, ProviderEndpoint, ProviderTestJob,
+        ProviderTestJobRepository, SchedulerLeaseRepository, SchedulerRunRepository,
+        StorageProvider, UrlDiscoveryUpdate, UrlResult, UrlResultRepository,
     },
-    services::url_discovery_service,
-    types::{ClientAddress, ClientId, ProviderAddress, ProviderId, ResultCode},
+    services::{
+        discovery_cadence::{self, Cadence, MAX_BACKOFF_STEPS},
+        url_discovery_service::{self, CommpCandidate, DiscoveryDeps},
+    },
+    types::{ClientAddress, ClientId, ProviderAddress, ProviderId, ProviderTestStatus, ResultCode},
 };
 use chrono::Utc;
 use color_eyre::Result;
@@ -14,11 +19,18 @@ use std::time::{Duration, Instant};
 use tokio::{sync::Semaphore, time::sleep};
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use super::run_ledger::record_run;
 
 const SCHEDULER_SLEEP_INTERVAL: Duration = Duration::from_secs(300);
 const SCHEDULER_NEXT_INTERVAL: Duration = Duration::from_secs(60);
 const BATCH_SIZE: i64 = 100;
 const MAX_CONCURRENT_CLIENT_TESTS: usize = 5;
+const PRIORITY_POLL_INTERVAL: Duration = Duration::from_secs(10);
+const PRIORITY_BATCH_SIZE: i64 = 5;
+const URL_DISCOVERY_RUN: &str = "url_discovery";
+const PROVIDER_TEST_RUN: &str = "provider_test";
 
 // --- Helper Structs ---
 
@@ -54,6 +66,7 @@ impl DiscoveryBatchStats {
                 success,
                 retrievability,
                 consistent,
+                ..
             } => {
                 if *success {
                     self.ok += 1;
@@ -98,6 +111,8 @@ enum ProviderOutcome {
         success: bool,
         retrievability: Option<f64>,
         consistent: Option<bool>,
+        /// Provider-level result of this run, when it was stored
+        url_result_id: Option<Uuid>,
     },
     Skipped,
 }
@@ -106,19 +121,15 @@ enum ProviderOutcome {
 
 pub async fn run_url_discovery_scheduler(
     config: Arc<Config>,
-    sp_repo: Arc<StorageProviderRepository>,
-    url_repo: Arc<UrlResultRepository>,
-    deal_repo: Arc<DealRepository>,
+    deps: DiscoveryDeps,
+    run_repo: Arc<SchedulerRunRepository>,
     shutdown: CancellationToken,
 ) {
     info!("Starting URL discovery scheduler loop");
 
     loop {
-        let interval = match schedule_url_discoveries(
-            &config, &sp_repo, &url_repo, &deal_repo, &shutdown,
-        )
-        .await
-        {
+        let mut run = NewSchedulerRun::new(URL_DISCOVERY_RUN, &config.instance_id, Utc::now());
+        let interval = match schedule_url_discoveries(&config, &deps, &shutdown).await {
             Ok(stats) if stats.is_empty() => {
                 info!("URL discovery: idle, sleeping 5m");
                 SCHEDULER_SLEEP_INTERVAL
@@ -135,10 +146,20 @@ pub async fn run_url_discovery_scheduler(
                     stats.total,
                     stats.skipped
                 );
+                run.ok_count = stats.ok;
+                run.failed_count = stats.failed;
+                run.skipped_count = stats.skipped;
+                run.details = Some(serde_json::json!({
+                    "avg_retrievability": stats.avg_retrievability(),
+                    "consistent": stats.consistent,
+                }));
+                record_run(&run_repo, run).await;
                 SCHEDULER_NEXT_INTERVAL
             }
             Err(e) => {
                 error!("URL discovery scheduler failed: {:?}", e);
+                run.error = Some(e.to_string());
+                record_run(&run_repo, run).await;
                 SCHEDULER_SLEEP_INTERVAL
             }
         };
@@ -155,14 +176,216 @@ pub async fn run_url_discovery_scheduler(
     info!("URL discovery scheduler stopped");
 }
 
+/// Priority lane for tests requested through the API. Claimed jobs run right away, alongside
+/// and independent of the regular batch.
+pub async fn run_provider_test_scheduler(
+    config: Arc<Config>,
+    deps: DiscoveryDeps,
+    test_job_repo: Arc<ProviderTestJobRepository>,
+    run_repo: Arc<SchedulerRunRepository>,
+    shutdown: CancellationToken,
+) {
+    info!("Starting provider test scheduler loop");
+
+    loop {
+        let mut run = NewSchedulerRun::new(PROVIDER_TEST_RUN, &config.instance_id, Utc::now());
+        let claimed = test_job_repo
+            .claim_queued(
+                PRIORITY_BATCH_SIZE,
+                &config.instance_id,
+                config.scheduler_lease_ttl_secs as f64,
+            )
+            .await;
+        match claimed {
+            Ok(jobs) if jobs.is_empty() => {}
+            Ok(jobs) => {
+                info!("Provider tests: starting {} requested tests", jobs.len());
+                let statuses = join_all(jobs.iter().map(|job| async {
+                    // The claim leased the job; the heartbeat keeps it while the test runs
+                    let lease = deps
+                        .lease_repo
+                        .track(LeaseTask::ProviderTest, job.provider_id.as_str());
+                    let status =
+                        run_provider_test(&config, &deps, &test_job_repo, job, &shutdown).await;
+                    release_lease(
+                        &config,
+                        &deps.lease_repo,
+                        LeaseTask::ProviderTest,
+                        &job.provider_id,
+                    )
+                    .await;
+                    drop(lease);
+                    status
+                }))
+                .await;
+                for status in statuses {
+                    match status {
+                        ProviderTestStatus::Done => run.ok_count += 1,
+                        ProviderTestStatus::Failed => run.failed_count += 1,
+                        ProviderTestStatus::Queued | ProviderTestStatus::Running => {
+                            run.skipped_count += 1
+                        }
+                    }
+                }
+                record_run(&run_repo, run).await;
+                // More may be waiting, poll again right away
+                if !shutdown.is_cancelled() {
+                    continue;
+                }
+            }
+            Err(e) => {
+                error!("Failed to claim provider tests: {:?}", e);
+                run.error = Some(e.to_string());
+                record_run(&run_repo, run).await;
+            }
+        }
+
+        tokio::select! {
+            _ = sleep(PRIORITY_POLL_INTERVAL) => {}
+            _ = shutdown.cancelled() => {
+                info!("Provider test scheduler received shutdown signal");
+                break;
+            }
+        }
+    }
+
+    info!("Provider test scheduler stopped");
+}
+
+/// Runs one claimed test and returns the status it was left in; queued means it was put back
+async fn run_provider_test(
+    config: &Arc<Config>,
+    deps: &DiscoveryDeps,
+    test_job_repo: &ProviderTestJobRepository,
+    job: &ProviderTestJob,
+    shutdown: &CancellationToken,
+) -> ProviderTestStatus {
+    let provider = match deps.sp_repo.get_by_provider_id(&job.provider_id).await {
+        Ok(Some(provider)) if provider.cached_http_endpoints.is_some() => provider,
+        Ok(Some(_)) => {
+            return fail_provider_test(test_job_repo, job, "Provider has no HTTP endpoints yet")
+                .await;
+        }
+        Ok(None) => {
+            return fail_provider_test(test_job_repo, job, "Provider not found").await;
+        }
+        Err(e) => {
+            return fail_provider_test(test_job_repo, job, &e.to_string()).await;
+        }
+    };
+
+    // Shares the regular batch's lease, so the provider is never tested twice at once
+    let provider_id = provider.provider_id.as_str();
+    match deps
+        .lease_repo
+        .acquire(
+            LeaseTask::UrlDiscovery,
+            provider_id,
+            &config.instance_id,
+            config.scheduler_lease_ttl_secs as f64,
+        )
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            debug!("Provider {provider_id} is being tested by another run, requeueing");
+            if let Err(e) = test_job_repo.requeue(job.id).await {
+                error!("Failed to requeue provider test {}: {:?}", job.id, e);
+            }
+            return ProviderTestStatus::Queued;
+        }
+        Err(e) => {
+            return fail_provider_test(test_job_repo, job, &e.to_string()).await;
+        }
+    }
+    let lease = deps.lease_repo.track(LeaseTask::UrlDiscovery, provider_id);
+
+    let outcome = process_single_provider(config, deps, &provider, shutdown).await;
+    release_lease(
+        config,
+        &deps.lease_repo,
+        LeaseTask::UrlDiscovery,
+        &provider.provider_id,
+    )
+    .await;
+    drop(lease);
+
+    let (updated, status) = match outcome {
+        Ok(ProviderOutcome::Processed { url_result_id, .. }) => (
+            test_job_repo.complete(job.id, url_result_id).await,
+            ProviderTestStatus::Done,
+        ),
+        Ok(ProviderOutcome::Skipped) if shutdown.is_cancelled() => (
+            test_job_repo.requeue(job.id).await,
+            ProviderTestStatus::Queued,
+        ),
+        Ok(ProviderOutcome::Skipped) => (
+            test_job_repo
+                .fail(
+                    job.id,
+                    "URL discovery was skipped after a system error, retry later",
+                )
+                .await,
+            ProviderTestStatus::Failed,
+        ),
+        Err(e) => (
+            test_job_repo.fail(job.id, &e.to_string()).await,
+            ProviderTestStatus::Failed,
+        ),
+    };
+    if let Err(e) = updated {
+        error!("Failed to update provider test {}: {:?}", job.id, e);
+    }
+    status
+}
+
+async fn release_lease(
+    config: &Config,
+    lease_repo: &SchedulerLeaseRepository,
+    task: LeaseTask,
+    provider_id: &ProviderId,
+) {
+    if let Err(e) = lease_repo
+        .release(task, provider_id.as_str(), &config.instance_id)
+        .await
+    {
+        warn!(
+            "Failed to release {} lease of {}: {:?}",
+            task.as_str(),
+            provider_id,
+            e
+        );
+    }
+}
+
+async fn fail_provider_test(
+    test_job_repo: &ProviderTestJobRepository,
+    job: &ProviderTestJob,
+    error: &str,
+) -> ProviderTestStatus {
+    warn!(
+        "Provider test {} for f0{} failed: {}",
+        job.id, job.provider_id, error
+    );
+    if let Err(e) = test_job_repo.fail(job.id, error).await {
+        error!("Failed to update provider test {}: {:?}", job.id, e);
+    }
+    ProviderTestStatus::Failed
+}
+
 async fn schedule_url_discoveries(
     config: &Arc<Config>,
-    sp_repo: &Arc<StorageProviderRepository>,
-    url_repo: &Arc<UrlResultRepository>,
-    deal_repo: &Arc<DealRepository>,
+    deps: &DiscoveryDeps,
     shutdown: &CancellationToken,
 ) -> Result<DiscoveryBatchStats> {
-    let providers = sp_repo.get_due_for_url_discovery(BATCH_SIZE).await?;
+    let providers = deps
+        .sp_repo
+        .claim_due_for_url_discovery(
+            BATCH_SIZE,
+            &config.instance_id,
+            config.scheduler_lease_ttl_secs as f64,
+        )
+        .await?;
 
     if !providers.is_empty() {
         let ready = providers
@@ -182,12 +405,23 @@ async fn schedule_url_discoveries(
         }
     }
 
+    // Heartbeated until each provider is done, however long it waits for a permit
+    let providers: Vec<_> = providers
+        .into_iter()
+        .map(|provider| {
+            let lease = deps
+                .lease_repo
+                .track(LeaseTask::UrlDiscovery, provider.provider_id.as_str());
+            (provider, lease)
+        })
+        .collect();
+
     let stats = Arc::new(tokio::sync::Mutex::new(DiscoveryBatchStats::new()));
 
     let semaphore = Arc::new(Semaphore::new(config.max_concurrent_providers));
     let mut tasks = vec![];
 
-    for provider in providers {
+    for (provider, lease) in providers {
         if shutdown.is_cancelled() {
             info!("URL discovery batch interrupted by shutdown before spawning");
             break;
@@ -195,17 +429,20 @@ async fn schedule_url_discoveries(
 
         let permit = semaphore.clone().acquire_owned().await?;
         let config = config.clone();
-        let sp_repo = sp_repo.clone();
-        let url_repo = url_repo.clone();
-        let deal_repo = deal_repo.clone();
+        let deps = deps.clone();
         let shutdown = shutdown.clone();
         let stats = stats.clone();
 
         tasks.push(tokio::spawn(async move {
-            let outcome = process_single_provider(
-                &config, &sp_repo, &url_repo, &deal_repo, &provider, &shutdown,
+            let outcome = process_single_provider(&config, &deps, &provider, &shutdown).await;
+            release_lease(
+                &config,
+                &deps.lease_repo,
+                LeaseTask::UrlDiscovery,
+                &provider.provider_id,
             )
             .await;
+            drop(lease);
 
             if let Ok(ref o) = outcome {
                 let mut s = stats.lock().await;
@@ -241,14 +478,13 @@ async fn schedule_url_discoveries(
 }
 
 async fn process_single_provider(
-    config: &Config,
-    sp_repo: &StorageProviderRepository,
-    url_repo: &UrlResultRepository,
-    deal_repo: &DealRepository,
+    config: &Arc<Config>,
+    deps: &DiscoveryDeps,
     provider: &StorageProvider,
     shutdown: &CancellationToken,
 ) -> Result<ProviderOutcome> {
     let provider_id = &provider.provider_id;
+    let sp_repo = &deps.sp_repo;
 
     if provider.cached_http_endpoints.is_none() {
         warn!(
@@ -270,7 +506,7 @@ async fn process_single_provider(
 
     sp_repo.set_url_discovery_pending(provider_id).await?;
 
-    let clients = deal_repo.get_clients_for_provider(provider_id).await?;
+    let clients = deps.deal_repo.get_clients_for_provider(provider_id).await?;
 
     let client_ids_for_log: Vec<String> = clients
         .iter()
@@ -279,16 +515,21 @@ async fn process_single_provider(
 
     debug!("Provider {} has {} clients", provider_id, clients.len());
 
-    let cached_endpoints = provider.cached_http_endpoints.clone().unwrap_or_default();
-    let results = test_provider_with_clients(
-        config,
-        provider_id,
-        clients,
-        deal_repo,
-        cached_endpoints,
-        shutdown,
-    )
-    .await;
+    let endpoint_health = deps
+        .endpoint_repo
+        .get_by_provider(provider_id)
+        .await
+        .unwrap_or_else(|e| {
+            warn!(
+                "Failed to load endpoint health for {}: {:?}",
+                provider_id, e
+            );
+            vec![]
+        });
+    let endpoints_changed = recently_added_endpoint(config, &endpoint_health);
+    let results =
+        test_provider_with_clients(config, deps, provider, clients, endpoint_health, shutdown)
+            .await;
 
     // Shutdown interrupted mid-batch
     if results.is_empty() && shutdown.is_cancelled() {
@@ -319,7 +560,24 @@ async fn process_single_provider(
         return Ok(ProviderOutcome::Skipped);
     }
 
-    let (last_working_url, is_consistent, is_reliable, url_metadata, outcome) =
+    // Client discoveries test the same endpoints, so health follows the provider-level run only
+    if let Some(r) = provider_discovery
+        && let Err(e) = deps
+            .endpoint_repo
+            .record_test_outcomes(provider_id, &r.endpoint_outcomes)
+            .await
+    {
+        error!(
+            "Failed to record endpoint health for {}: {:?}",
+            provider_id, e
+        );
+    }
+
+    let commp_candidates = provider_discovery
+        .map(|r| r.commp_candidates.clone())
+        .unwrap_or_default();
+
+    let (last_working_url, is_consistent, is_reliable, url_metadata, mut outcome) =
         match provider_discovery {
             Some(r) => (
                 r.working_url.clone(),
@@ -330,6 +588,7 @@ async fn process_single_provider(
                     success: r.working_url.is_some(),
                     retrievability: r.retrievability_percent,
                     consistent: r.is_consistent,
+                    url_result_id: None,
                 },
             ),
             None => (
@@ -341,27 +600,72 @@ async fn process_single_provider(
                     success: false,
                     retrievability: None,
                     consistent: None,
+                    url_result_id: None,
                 },
             ),
         };
 
     let url_results: Vec<UrlResult> = results.into_iter().map(|r| r.into()).collect();
 
-    match url_repo.insert_batch(&url_results).await {
-        Ok(count) => debug!(
-            "Inserted {} URL results for provider {}",
-            count, provider_id
-        ),
-        Err(e) => error!("Failed to insert URL results: {:?}", e),
+    let stored_result_id = match deps.url_repo.insert_batch(&url_results).await {
+        Ok(count) => {
+            debug!(
+                "Inserted {} URL results for provider {}",
+                count, provider_id
+            );
+            url_results
+                .iter()
+                .find(|r| r.client_id.is_none())
+                .map(|r| r.id)
+        }
+        Err(e) => {
+            error!("Failed to insert URL results: {:?}", e);
+            None
+        }
+    };
+    if let ProviderOutcome::Processed { url_result_id, .. } = &mut outcome {
+        *url_result_id = stored_result_id;
+    }
+
+    if let Some(result_id) = stored_result_id
+        && !commp_candidates.is_empty()
+    {
+        spawn_commp_verification(
+            config,
+            deps,
+            provider_id,
+            result_id,
+            commp_candidates,
+            shutdown,
+        );
     }
 
+    let cadence = next_discovery_cadence(
+        config,
+        &deps.url_repo,
+        &deps.deal_sli_repo,
+        provider_id,
+        endpoints_changed,
+    )
+    .await;
+    debug!(
+        "Next URL discovery for {} in {}h ({})",
+        provider_id,
+        cadence.interval.num_hours(),
+        cadence.reason.as_str()
+    );
+
     sp_repo
         .update_after_url_discovery(
             provider_id,
-            last_working_url,
-            is_consistent,
-            is_reliable,
-            url_metadata,
+            UrlDiscoveryUpdate {
+                last_working_url,
+                is_consistent,
+                is_reliable,
+                url_metadata,
+                next_interval_hours: cadence.interval.num_hours() as i32,
+                reason: cadence.reason.as_str(),
+            },
         )
         .await?;
 
@@ -369,6 +673,7 @@ async fn process_single_provider(
         success,
         retrievability,
         consistent,
+        ..
     } = &outcome
     {
         let clients_count = client_ids_for_log.len();
@@ -393,36 +698,123 @@ async fn process_single_provider(
     Ok(outcome)
 }
 
-async fn test_provider_with_clients(
+/// Streams full pieces, so it runs in the background instead of holding the provider's discovery
+/// permit and lease; the weekly reservation keeps overlapping runs from streaming a piece twice.
+fn spawn_commp_verification(
+    config: &Arc<Config>,
+    deps: &DiscoveryDeps,
+    provider_id: &ProviderId,
+    url_result_id: Uuid,
+    candidates: Vec<CommpCandidate>,
+    shutdown: &CancellationToken,
+) {
+    let config = config.clone();
+    let deps = deps.clone();
+    let provider_id = provider_id.clone();
+    let shutdown = shutdown.clone();
+
+    tokio::spawn(async move {
+        let verification = async {
+            let _permit = deps
+                .commp_permits
+                .acquire()
+                .await
+                .expect("Semaphore should never be closed");
+            url_discovery_service::verify_commp_samples(
+                &config,
+                &deps,
+                &provider_id,
+                url_result_id,
+                &candidates,
+            )
+            .await;
+        };
+        tokio::select! {
+            _ = verification => {}
+            _ = shutdown.cancelled() => {
+                debug!("Shutdown interrupted CommP verification of f0{}", provider_id);
+            }
+        }
+    });
+}
+
+/// Any endpoint first seen within the regular discovery interval
+fn recently_added_endpoint(config: &Config, endpoint_health: &[ProviderEndpoint]) -> bool {
+    let since = Utc::now() - chrono::Duration::hours(config.url_discovery_interval_hours);
+    endpoint_health.iter().any(|e| e.first_seen > since)
+}
+
+/// Cadence after the run just stored. Lookup failures leave out that signal rather than
+/// failing the provider.
+async fn next_discovery_cadence(
     config: &Config,
+    url_repo: &UrlResultRepository,
+    deal_sli_repo: &DealSliRepository,
     provider_id: &ProviderId,
+    endpoints_changed: bool,
+) -> Cadence {
+    let recent_runs = url_repo
+        .get_recent_provider_runs(
+            provider_id,
+            config.url_discovery_stable_runs + MAX_BACKOFF_STEPS,
+        )
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Failed to load recent runs for {}: {:?}", provider_id, e);
+            vec![]
+        });
+    let has_deal_sli_target = deal_sli_repo
+        .has_target_for_provider(provider_id.as_str())
+        .await
+        .unwrap_or_else(|e| {
+            warn!(
+                "Failed to check Deal SLI targets for {}: {:?}",
+                provider_id, e
+            );
+            false
+        });
+
+    discovery_cadence::next_cadence(config, &recent_runs, endpoints_changed, has_deal_sli_target)
+}
+
+async fn test_provider_with_clients(
+    config: &Config,
+    deps: &DiscoveryDeps,
+    provider: &StorageProvider,
     client_ids: Vec<ClientId>,
-    deal_repo: &DealRepository,
-    cached_http_endpoints: Vec<String>,
+    endpoint_health: Vec<ProviderEndpoint>,
     shutdown: &CancellationToken,
 ) -> Vec<url_discovery_service::UrlDiscoveryResult> {
     let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_CLIENT_TESTS));
     let mut tasks = vec![];
+    let provider_id = &provider.provider_id;
     let provider_address: ProviderAddress = provider_id.clone().into();
+    let peer_id = provider.peer_id.clone();
+    let cached_http_endpoints = provider.cached_http_endpoints.clone().unwrap_or_default();
 
     // Provider result always has an earlier tested_at than ProviderClient
     let provider_tested_at = Utc::now();
 
     let provider_task = {
         let cfg = config.clone();
+        let deps = deps.clone();
         let addr = provider_address.clone();
-        let repo = deal_repo.clone();
+        let peer_id = peer_id.clone();
         let endpoints = cached_http_endpoints.clone();
+        let health = endpoint_health.clone();
         tokio::spawn(async move {
-            url_discovery_service::discover_url(
+            let mut result = url_discovery_service::discover_url(
                 &cfg,
+                &deps,
                 &addr,
+                peer_id.as_deref(),
                 None,
-                &repo,
                 endpoints,
-                Some(provider_tested_at),
+                &health,
             )
-            .await
+            .await;
+            result.tested_at = provider_tested_at;
+            result
         })
     };
     tasks.push(provider_task);
@@ -434,18 +826,21 @@ async fn test_provider_with_clients(
             .await
             .expect("Semaphore should never be closed");
         let cfg = config.clone();
+        let deps = deps.clone();
         let provider_addr = provider_address.clone();
         let client_address: ClientAddress = client_id.into();
-        let repo = deal_repo.clone();
+        let peer_id = peer_id.clone();
         let endpoints = cached_http_endpoints.clone();
+        let health = endpoint_health.clone();
         tasks.push(tokio::spawn(async move {
             let result = url_discovery_service::discover_url(
                 &cfg,
+                &deps,
                 &provider_addr,
+                peer_id.as_deref(),
                 Some(client_address),
-                &repo,
                 endpoints,
-                None,
+                &health,
             )
             .await;
             drop(permit);
@@ -1,10 +1,26 @@
 //! CAR (Content Addressable aRchive) header parsing.
 //!
-//! Parses CAR v1/v2 headers to extract root CID for verification against deal Labels.
+//! Parses CAR v1/v2 headers to extract root CID for verification against deal Labels,
+//! then walks the block sections that follow to verify each block's multihash.
 
+use blake2::{
+    Blake2bVar,
+    digest::{Update, VariableOutput},
+};
 use ciborium::Value;
+use sha2::{Digest, Sha256};
 use tracing::trace;
 
+const MULTIHASH_IDENTITY: usize = 0x00;
+const MULTIHASH_SHA2_256: usize = 0x12;
+/// blake2b-8 .. blake2b-512 occupy 0xb201..=0xb240, offset from the base is the digest length
+const MULTIHASH_BLAKE2B_BASE: usize = 0xb200;
+const MULTIHASH_BLAKE2B_MIN: usize = MULTIHASH_BLAKE2B_BASE + 1;
+const MULTIHASH_BLAKE2B_MAX: usize = MULTIHASH_BLAKE2B_BASE + 64;
+
+/// Blocks walked after the header; a range sample rarely holds more than a few
+const MAX_SCANNED_BLOCKS: usize = 16;
+
 /// CARv2 pragma: fixed 11 bytes identifying CARv2 format
 const CAR_V2_PRAGMA: &[u8] = &[
     0x0a, 0xa1, 0x67, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x02,
@@ -17,6 +33,47 @@ pub struct CarHeaderParseResult {
     pub version: Option<u8>,
     pub root_cid: Option<String>,
     pub header_size: Option<usize>,
+    /// Block sections following a valid header
+    pub blocks: Option<CarBlockScan>,
+    /// Fixed CARv2 header fields, present for valid CARv2 only
+    pub v2_header: Option<CarV2Header>,
+}
+
+/// Fixed 40-byte CARv2 header following the pragma (characteristics omitted)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CarV2Header {
+    /// Offset of the inner CARv1 payload from the start of the file
+    pub data_offset: u64,
+    pub data_size: u64,
+    /// Offset of the index from the start of the file, 0 when there is no index
+    pub index_offset: u64,
+}
+
+/// Result of walking the block sections after the CAR header
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CarBlockScan {
+    /// Blocks whose data hashed to their CID's multihash
+    pub blocks_verified: usize,
+    /// Offset of the first section that was malformed or failed hash verification
+    pub first_bad_block_offset: Option<usize>,
+    /// Multihash algorithms seen, in order of first appearance
+    pub hash_algorithms: Vec<String>,
+}
+
+/// One `[varint length][CID][data]` section of a CARv1 payload
+struct CarSection<'a> {
+    hash_code: usize,
+    digest: &'a [u8],
+    data: &'a [u8],
+    cid_bytes: &'a [u8],
+    end: usize,
+}
+
+enum SectionRead<'a> {
+    Section(CarSection<'a>),
+    /// The sample ends mid-section (or at a zero-length padding section)
+    End,
+    Malformed,
 }
 
 impl CarHeaderParseResult {
@@ -32,11 +89,54 @@ pub fn parse_car_header(bytes: &[u8]) -> CarHeaderParseResult {
     }
 
     // Check for CARv2 pragma
-    if bytes.len() >= CAR_V2_PRAGMA.len() && bytes.starts_with(CAR_V2_PRAGMA) {
-        return parse_car_v2_header(bytes);
+    let mut result = if bytes.len() >= CAR_V2_PRAGMA.len() && bytes.starts_with(CAR_V2_PRAGMA) {
+        parse_car_v2_header(bytes)
+    } else {
+        parse_car_v1_header(bytes)
+    };
+
+    if let Some(header_size) = result.header_size.filter(|_| result.is_valid) {
+        result.blocks = Some(scan_car_blocks(bytes, header_size, MAX_SCANNED_BLOCKS));
+    }
+    result
+}
+
+/// Walk up to `max_blocks` sections starting at `offset`, verifying each block's multihash.
+/// Stops at the first bad section; a section cut off by the end of the sample is not bad.
+pub fn scan_car_blocks(bytes: &[u8], offset: usize, max_blocks: usize) -> CarBlockScan {
+    let mut scan = CarBlockScan::default();
+    let mut offset = offset;
+
+    for _ in 0..max_blocks {
+        let section = match read_section(bytes, offset) {
+            SectionRead::Section(section) => section,
+            SectionRead::End => break,
+            SectionRead::Malformed => {
+                scan.first_bad_block_offset = Some(offset);
+                break;
+            }
+        };
+
+        let algorithm = hash_algorithm_name(section.hash_code);
+        if !scan.hash_algorithms.contains(&algorithm) {
+            scan.hash_algorithms.push(algorithm);
+        }
+
+        match verify_block_hash(section.hash_code, section.digest, section.data) {
+            Some(true) => scan.blocks_verified += 1,
+            Some(false) => {
+                trace!("CAR block at {offset} failed hash verification");
+                scan.first_bad_block_offset = Some(offset);
+                break;
+            }
+            // Unsupported hash: cannot judge the block, keep walking
+            None => {}
+        }
+
+        offset = section.end;
     }
 
-    parse_car_v1_header(bytes)
+    scan
 }
 
 fn parse_car_v1_header(bytes: &[u8]) -> CarHeaderParseResult {
@@ -94,6 +194,8 @@ fn parse_car_v1_header(bytes: &[u8]) -> CarHeaderParseResult {
         version: Some(1),
         root_cid,
         header_size: Some(header_end),
+        blocks: None,
+        v2_header: None,
     }
 }
 
@@ -110,17 +212,22 @@ fn parse_car_v2_header(bytes: &[u8]) -> CarHeaderParseResult {
     const HEADER_START: usize = 11;
     const HEADER_SIZE: usize = 40;
     const DATA_OFFSET_POS: usize = HEADER_START + 16;
+    const DATA_SIZE_POS: usize = DATA_OFFSET_POS + 8;
+    const INDEX_OFFSET_POS: usize = DATA_SIZE_POS + 8;
 
     if bytes.len() < HEADER_START + HEADER_SIZE {
         trace!("CARv2 header truncated");
         return CarHeaderParseResult::invalid();
     }
 
-    // Read data_offset (little-endian u64)
-    let data_offset_bytes: [u8; 8] = bytes[DATA_OFFSET_POS..DATA_OFFSET_POS + 8]
-        .try_into()
-        .unwrap();
-    let data_offset_u64 = u64::from_le_bytes(data_offset_bytes);
+    let read_u64_le = |pos: usize| u64::from_le_bytes(bytes[pos..pos + 8].try_into().unwrap());
+    let v2_header = CarV2Header {
+        data_offset: read_u64_le(DATA_OFFSET_POS),
+        data_size: read_u64_le(DATA_SIZE_POS),
+        index_offset: read_u64_le(INDEX_OFFSET_POS),
+    };
+
+    let data_offset_u64 = v2_header.data_offset;
     let data_offset = match usize::try_from(data_offset_u64) {
         Ok(offset) => offset,
         Err(_) => {
@@ -138,6 +245,7 @@ fn parse_car_v2_header(bytes: &[u8]) -> CarHeaderParseResult {
     let mut inner_result = parse_car_v1_header(&bytes[data_offset..]);
     if inner_result.is_valid {
         inner_result.version = Some(2);
+        inner_result.v2_header = Some(v2_header);
         if let Some(size) = inner_result.header_size {
             inner_result.header_size = Some(data_offset + size);
         }
@@ -145,6 +253,129 @@ fn parse_car_v2_header(bytes: &[u8]) -> CarHeaderParseResult {
     inner_result
 }
 
+fn read_section(bytes: &[u8], offset: usize) -> SectionRead<'_> {
+    let Some(rest) = bytes.get(offset..) else {
+        return SectionRead::End;
+    };
+    if rest.is_empty() {
+        return SectionRead::End;
+    }
+
+    let Some((section_len, varint_size)) = read_varint(rest) else {
+        // A varint cut off by the end of the sample is indistinguishable from truncation
+        return if rest.len() < 10 {
+            SectionRead::End
+        } else {
+            SectionRead::Malformed
+        };
+    };
+    if section_len == 0 {
+        return SectionRead::End;
+    }
+    let Some(section) = rest.get(varint_size..varint_size + section_len) else {
+        return SectionRead::End;
+    };
+
+    match read_block_cid(section) {
+        Some((cid_len, hash_code, digest)) => SectionRead::Section(CarSection {
+            hash_code,
+            digest,
+            data: &section[cid_len..],
+            cid_bytes: &section[..cid_len],
+            end: offset + varint_size + section_len,
+        }),
+        None => SectionRead::Malformed,
+    }
+}
+
+/// Multihash (code, digest) of the CID at the start of a `[varint length][CID][data]` section.
+/// Only the section prefix is needed, so this works on a short range read.
+pub fn section_multihash(bytes: &[u8]) -> Option<(usize, Vec<u8>)> {
+    let (_section_len, varint_size) = read_varint(bytes)?;
+    let (_cid_len, hash_code, digest) = read_block_cid(bytes.get(varint_size..)?)?;
+    Some((hash_code, digest.to_vec()))
+}
+
+/// Some(matches) for supported hash functions, None when the hash cannot be checked
+fn verify_block_hash(hash_code: usize, digest: &[u8], data: &[u8]) -> Option<bool> {
+    match hash_code {
+        MULTIHASH_SHA2_256 => Some(Sha256::digest(data)[..] == *digest),
+        MULTIHASH_IDENTITY => Some(data == digest),
+        MULTIHASH_BLAKE2B_MIN..=MULTIHASH_BLAKE2B_MAX => {
+            let output_len = hash_code - MULTIHASH_BLAKE2B_BASE;
+            if digest.len() != output_len {
+                return Some(false);
+            }
+            let mut hasher = Blake2bVar::new(output_len).ok()?;
+            hasher.update(data);
+            let mut out = vec![0u8; output_len];
+            hasher.finalize_variable(&mut out).ok()?;
+            Some(out == digest)
+        }
+        _ => None,
+    }
+}
+
+fn hash_algorithm_name(hash_code: usize) -> String {
+    match hash_code {
+        MULTIHASH_SHA2_256 => "sha2-256".to_string(),
+        MULTIHASH_IDENTITY => "identity".to_string(),
+        MULTIHASH_BLAKE2B_MIN..=MULTIHASH_BLAKE2B_MAX => {
+            format!("blake2b-{}", (hash_code - MULTIHASH_BLAKE2B_BASE) * 8)
+        }
+        other => format!("{other:#x}"),
+    }
+}
+
+/// Check that a CARv1 response starts with the block for `expected_cid` and that the
+/// block data hashes to the CID's multihash. Used for trustless gateway responses.
+pub fn verify_first_block(bytes: &[u8], expected_cid: &str) -> bool {
+    let Some(expected) = normalize_cid(expected_cid) else {
+        return false;
+    };
+
+    let header = parse_car_v1_header(bytes);
+    let Some(header_size) = header.header_size.filter(|_| header.is_valid) else {
+        return false;
+    };
+
+    let SectionRead::Section(section) = read_section(bytes, header_size) else {
+        trace!("CAR response has no complete first block");
+        return false;
+    };
+
+    if encode_cid_base32(&cid_v0_to_v1(section.cid_bytes)) != expected {
+        trace!("CAR first block CID does not match {expected}");
+        return false;
+    }
+
+    verify_block_hash(section.hash_code, section.digest, section.data).unwrap_or(false)
+}
+
+/// Parse the CID at the start of a CAR section, return (cid_len, multihash code, digest)
+fn read_block_cid(section: &[u8]) -> Option<(usize, usize, &[u8])> {
+    // CIDv0: bare sha2-256 multihash
+    if section.len() >= 34 && section[0] == 0x12 && section[1] == 0x20 {
+        return Some((34, MULTIHASH_SHA2_256, &section[2..34]));
+    }
+
+    let mut pos = 0;
+    let (version, n) = read_varint(section)?;
+    if version != 1 {
+        return None;
+    }
+    pos += n;
+    let (_codec, n) = read_varint(&section[pos..])?;
+    pos += n;
+    let (hash_code, n) = read_varint(&section[pos..])?;
+    pos += n;
+    let (digest_len, n) = read_varint(&section[pos..])?;
+    pos += n;
+    let digest = section.get(pos..pos + digest_len)?;
+
+    Some((pos + digest_len, hash_code, digest))
+}
+
 /// Read unsigned LEB128 varint, return (value, bytes_consumed)
 fn read_varint(bytes: &[u8]) -> Option<(usize, usize)> {
     let mut result: usize = 0;
@@ -183,11 +414,14 @@ fn extract_first_root_cid(map: &[(Value, Value)]) -> Option<String> {
         .find(|(k, _)| matches!(k, Value::Text(s) if s == "roots"))
         .and_then(|(_, v)| v.as_array())?;
 
-    let first_root = roots.first()?;
+    cid_from_link(roots.first()?)
+}
 
+/// Decode a dag-cbor link to a CIDv1 base32 string
+pub fn cid_from_link(link: &Value) -> Option<String> {
     // CID is stored as CBOR tag 42 with byte string
     // Tag 42 value contains: [0x00 multibase prefix][CID bytes]
-    let cid_bytes = match first_root {
+    let cid_bytes = match link {
         Value::Tag(42, inner) => inner.as_bytes()?,
         _ => return None,
     };
@@ -198,7 +432,65 @@ fn extract_first_root_cid(map: &[(Value, Value)]) -> Option<String> {
     }
 
     let raw_cid = &cid_bytes[1..];
-    Some(encode_cid_base32(raw_cid))
+    Some(encode_cid_base32(&cid_v0_to_v1(raw_cid)))
+}
+
+/// CIDv0 is a bare sha2-256 multihash; upgrade it to CIDv1 dag-pb so it has a base32 form
+fn cid_v0_to_v1(raw_cid: &[u8]) -> Vec<u8> {
+    if raw_cid.len() == 34 && raw_cid[0] == 0x12 && raw_cid[1] == 0x20 {
+        let mut upgraded = Vec::with_capacity(raw_cid.len() + 2);
+        upgraded.extend_from_slice(&[0x01, 0x70]);
+        upgraded.extend_from_slice(raw_cid);
+        return upgraded;
+    }
+    raw_cid.to_vec()
+}
+
+/// Normalize a CID string to CIDv1 base32lower so root CIDs and deal Labels compare equal.
+/// Accepts base32 CIDv1 (`b...`) and base58btc CIDv0 (`Qm...`). Returns None for anything else.
+pub fn normalize_cid(cid: &str) -> Option<String> {
+    let cid = cid.trim();
+
+    if cid.len() == 46 && cid.starts_with("Qm") {
+        let multihash = decode_base58btc(cid)?;
+        if multihash.len() != 34 {
+            return None;
+        }
+        return Some(encode_cid_base32(&cid_v0_to_v1(&multihash)));
+    }
+
+    let body = cid.strip_prefix('b')?;
+    if body.is_empty()
+        || !body
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || ('2'..='7').contains(&c))
+    {
+        return None;
+    }
+    Some(cid.to_string())
+}
+
+fn decode_base58btc(input: &str) -> Option<Vec<u8>> {
+    const ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in input.bytes() {
+        let mut carry = ALPHABET.iter().position(|&a| a == c)? as u32;
+        for byte in bytes.iter_mut().rev() {
+            carry += u32::from(*byte) * 58;
+            *byte = (carry & 0xff) as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.insert(0, (carry & 0xff) as u8);
+            carry >>= 8;
+        }
+    }
+
+    let leading_zeros = input.bytes().take_while(|&c| c == b'1').count();
+    let mut out = vec![0u8; leading_zeros];
+    out.extend(bytes);
+    Some(out)
 }
 
 /// Encode raw CID bytes to base32lower (multibase 'b' prefix)
@@ -274,6 +566,166 @@ mod tests {
         assert!(!result.is_valid);
     }
 
+    #[test]
+    fn test_normalize_cid_v0_to_v1() {
+        // Well-known empty directory CID in both encodings
+        assert_eq!(
+            normalize_cid("QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn").as_deref(),
+            Some("bafybeiczsscdsbs7ffqz55asqdf3smv6klcw3gofszvwlyarci47bgf354")
+        );
+    }
+
+    #[test]
+    fn test_normalize_cid_rejects_non_cid_labels() {
+        assert_eq!(normalize_cid("my dataset label"), None);
+        assert_eq!(normalize_cid(""), None);
+        assert_eq!(
+            normalize_cid(" bafkqaaa ").as_deref(),
+            Some("bafkqaaa"),
+            "surrounding whitespace is ignored"
+        );
+    }
+
+    /// CARv1 with a single raw block whose CID is `cid` and data is `data`
+    fn build_single_block_car(cid: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut root = vec![0x00];
+        root.extend_from_slice(cid);
+        let header = Value::Map(vec![
+            (
+                Value::Text("roots".into()),
+                Value::Array(vec![Value::Tag(42, Box::new(Value::Bytes(root)))]),
+            ),
+            (Value::Text("version".into()), Value::Integer(1.into())),
+        ]);
+        let mut header_bytes = Vec::new();
+        ciborium::into_writer(&header, &mut header_bytes).unwrap();
+
+        let mut car = vec![header_bytes.len() as u8];
+        car.extend(header_bytes);
+        car.push((cid.len() + data.len()) as u8);
+        car.extend_from_slice(cid);
+        car.extend_from_slice(data);
+        car
+    }
+
+    fn raw_sha256_cid(data: &[u8]) -> Vec<u8> {
+        let mut cid = vec![0x01, 0x55, 0x12, 0x20];
+        cid.extend_from_slice(&Sha256::digest(data));
+        cid
+    }
+
+    #[test]
+    fn test_verify_first_block_matches_cid() {
+        let data = b"hello trustless gateway";
+        let cid = raw_sha256_cid(data);
+        let car = build_single_block_car(&cid, data);
+
+        assert!(verify_first_block(&car, &encode_cid_base32(&cid)));
+    }
+
+    #[test]
+    fn test_verify_first_block_rejects_tampered_data() {
+        let cid = raw_sha256_cid(b"hello trustless gateway");
+        let car = build_single_block_car(&cid, b"HELLO trustless gateway");
+
+        assert!(!verify_first_block(&car, &encode_cid_base32(&cid)));
+    }
+
+    #[test]
+    fn test_verify_first_block_rejects_other_cid() {
+        let data = b"hello trustless gateway";
+        let car = build_single_block_car(&raw_sha256_cid(data), data);
+        let other = encode_cid_base32(&raw_sha256_cid(b"something else"));
+
+        assert!(!verify_first_block(&car, &other));
+    }
+
+    #[test]
+    fn test_verify_first_block_identity_hash() {
+        let data = b"tiny";
+        let mut cid = vec![0x01, 0x55, 0x00, data.len() as u8];
+        cid.extend_from_slice(data);
+        let car = build_single_block_car(&cid, data);
+
+        assert!(verify_first_block(&car, &encode_cid_base32(&cid)));
+    }
+
+    /// `[varint len][cid][data]` section; lengths in these tests stay below 128
+    fn section(cid: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut out = vec![(cid.len() + data.len()) as u8];
+        out.extend_from_slice(cid);
+        out.extend_from_slice(data);
+        out
+    }
+
+    #[test]
+    fn test_scan_blocks_verifies_supported_hashes() {
+        let sha_data = b"first block";
+        let mut car = build_single_block_car(&raw_sha256_cid(sha_data), sha_data);
+
+        // blake2b-256 of the empty string
+        let mut blake_cid = vec![0x01, 0x55, 0xa0, 0xe4, 0x02, 0x20];
+        blake_cid.extend_from_slice(
+            &hex::decode("0e5751c026e543b2e8ab2eb06099daa1d1e5df47778f7787faab45cdf12fe3a8")
+                .unwrap(),
+        );
+        car.extend(section(&blake_cid, b""));
+
+        let mut identity_cid = vec![0x01, 0x55, 0x00, 0x02];
+        identity_cid.extend_from_slice(b"hi");
+        car.extend(section(&identity_cid, b"hi"));
+
+        let result = parse_car_header(&car);
+        let blocks = result
+            .blocks
+            .expect("valid header should be followed by a scan");
+
+        assert_eq!(blocks.blocks_verified, 3);
+        assert_eq!(blocks.first_bad_block_offset, None);
+        assert_eq!(
+            blocks.hash_algorithms,
+            vec!["sha2-256", "blake2b-256", "identity"]
+        );
+    }
+
+    #[test]
+    fn test_scan_blocks_reports_first_bad_block() {
+        let data = b"first block";
+        let mut car = build_single_block_car(&raw_sha256_cid(data), data);
+        let bad_offset = car.len();
+        car.extend(section(&raw_sha256_cid(b"expected"), b"garbage"));
+
+        let blocks = parse_car_header(&car).blocks.unwrap();
+
+        assert_eq!(blocks.blocks_verified, 1);
+        assert_eq!(blocks.first_bad_block_offset, Some(bad_offset));
+    }
+
+    #[test]
+    fn test_scan_blocks_garbage_after_header() {
+        let data = b"first block";
+        let car = build_single_block_car(&raw_sha256_cid(data), data);
+        let header_size = parse_car_header(&car).header_size.unwrap();
+        let mut garbage = car[..header_size].to_vec();
+        garbage.extend_from_slice(&[0x40; 200]);
+
+        let blocks = parse_car_header(&garbage).blocks.unwrap();
+
+        assert_eq!(blocks.blocks_verified, 0);
+        assert_eq!(blocks.first_bad_block_offset, Some(header_size));
+    }
+
+    #[test]
+    fn test_scan_blocks_truncated_section_is_not_bad() {
+        let data = b"first block";
+        let car = build_single_block_car(&raw_sha256_cid(data), data);
+
+        let blocks = parse_car_header(&car[..car.len() - 3]).blocks.unwrap();
+
+        assert_eq!(blocks.blocks_verified, 0);
+        assert_eq!(blocks.first_bad_block_offset, None);
+    }
+
     #[test]
     fn test_encode_cid_base32() {
         // CIDv1 raw bytes (simplified test)
@@ -0,0 +1,165 @@
+//! CARv2 index parsing.
+//!
+//! Reads the IndexSorted (0x0400) and MultihashIndexSorted (0x0401) formats written by
+//! go-car so indexed block offsets can be spot-checked against the inner CARv1 payload.
+
+use tracing::trace;
+
+const INDEX_SORTED: u64 = 0x0400;
+const MULTIHASH_INDEX_SORTED: u64 = 0x0401;
+
+/// Every bucket entry is `[digest][u64 offset]`
+const OFFSET_SIZE: usize = 8;
+
+/// One index record: a block's multihash digest and its section offset in the CARv1 payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// Multihash code of the bucket, known only for MultihashIndexSorted
+    pub hash_code: Option<u64>,
+    pub digest: Vec<u8>,
+    /// Offset of the block section relative to the CARv2 `data_offset`
+    pub offset: u64,
+}
+
+/// Parse up to `max_entries` entries from the first bucket of an index.
+///
+/// `bytes` may be a prefix of the index section; entries cut off by the end are dropped.
+/// Returns None if the codec is unknown or the bucket headers are inconsistent.
+pub fn parse_index_prefix(bytes: &[u8], max_entries: usize) -> Option<Vec<IndexEntry>> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    let codec = reader.uvarint()?;
+    let hash_code = match codec {
+        INDEX_SORTED => None,
+        MULTIHASH_INDEX_SORTED => {
+            let code_count = reader.i32_le()?;
+            if code_count <= 0 {
+                return Some(vec![]);
+            }
+            Some(reader.u64_le()?)
+        }
+        other => {
+            trace!("Unsupported CARv2 index codec {other:#x}");
+            return None;
+        }
+    };
+
+    let width_count = reader.i32_le()?;
+    if width_count <= 0 {
+        return Some(vec![]);
+    }
+
+    let width = reader.u32_le()? as usize;
+    let data_len = reader.u64_le()?;
+    if width <= OFFSET_SIZE || data_len % width as u64 != 0 {
+        trace!("Malformed CARv2 index bucket: width {width}, data_len {data_len}");
+        return None;
+    }
+
+    let total_entries = usize::try_from(data_len / width as u64).ok()?;
+    let available = (bytes.len() - reader.pos) / width;
+    let entries = reader.bytes[reader.pos..]
+        .chunks_exact(width)
+        .take(total_entries.min(available).min(max_entries))
+        .map(|entry| {
+            let (digest, offset) = entry.split_at(width - OFFSET_SIZE);
+            IndexEntry {
+                hash_code,
+                digest: digest.to_vec(),
+                offset: u64::from_le_bytes(offset.try_into().unwrap()),
+            }
+        })
+        .collect();
+
+    Some(entries)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let out = self.bytes.get(self.pos..self.pos + N)?.try_into().ok()?;
+        self.pos += N;
+        Some(out)
+    }
+
+    fn i32_le(&mut self) -> Option<i32> {
+        self.take().map(i32::from_le_bytes)
+    }
+
+    fn u32_le(&mut self) -> Option<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    fn u64_le(&mut self) -> Option<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    fn uvarint(&mut self) -> Option<u64> {
+        let (value, rest) = unsigned_varint::decode::u64(self.bytes.get(self.pos..)?).ok()?;
+        self.pos = self.bytes.len() - rest.len();
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// IndexSorted with one bucket of 4-byte digests
+    fn index_sorted(entries: &[([u8; 4], u64)]) -> Vec<u8> {
+        let mut out = vec![0x80, 0x08]; // varint 0x0400
+        out.extend(1i32.to_le_bytes());
+        out.extend(12u32.to_le_bytes());
+        out.extend((entries.len() as u64 * 12).to_le_bytes());
+        for (digest, offset) in entries {
+            out.extend_from_slice(digest);
+            out.extend(offset.to_le_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn test_parse_index_sorted() {
+        let bytes = index_sorted(&[([1, 2, 3, 4], 59), ([5, 6, 7, 8], 200)]);
+
+        let entries = parse_index_prefix(&bytes, 10).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].digest, vec![1, 2, 3, 4]);
+        assert_eq!(entries[0].offset, 59);
+        assert_eq!(entries[1].offset, 200);
+        assert_eq!(entries[1].hash_code, None);
+    }
+
+    #[test]
+    fn test_parse_multihash_index_sorted() {
+        let mut bytes = vec![0x81, 0x08]; // varint 0x0401
+        bytes.extend(1i32.to_le_bytes());
+        bytes.extend(0x12u64.to_le_bytes());
+        bytes.extend_from_slice(&index_sorted(&[([9, 9, 9, 9], 7)])[2..]);
+
+        let entries = parse_index_prefix(&bytes, 10).unwrap();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].hash_code, Some(0x12));
+        assert_eq!(entries[0].offset, 7);
+    }
+
+    #[test]
+    fn test_parse_index_prefix_drops_truncated_entries() {
+        let bytes = index_sorted(&[([1, 2, 3, 4], 59), ([5, 6, 7, 8], 200)]);
+
+        let entries = parse_index_prefix(&bytes[..bytes.len() - 1], 10).unwrap();
+
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_index_rejects_unknown_codec() {
+        assert!(parse_index_prefix(&[0x01, 0, 0, 0, 0], 10).is_none());
+    }
+}
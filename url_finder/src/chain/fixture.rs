@@ -0,0 +1,178 @@
+//! Chain state read from a local JSON file, for tests and runs without a Filecoin node.
+//!
+//! ```json
+//! {
+//!   "miners": {
+//!     "f01234": {
+//!       "peer_id": "12D3KooW...",
+//!       "multiaddrs": ["/ip4/1.2.3.4/tcp/24001"],
+//!       "curio": { "peer_id": "12D3KooW...", "multiaddrs": ["/dns/sp.example.com/https"] }
+//!     }
+//!   },
+//!   "deals": { "123": "bafy..." }
+//! }
+//! ```
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use color_eyre::{Result, eyre::eyre};
+use serde::Deserialize;
+
+use super::{ChainBackend, CurioPeer, MinerInfo, Network};
+use crate::types::ProviderAddress;
+
+#[derive(Debug, Default, Deserialize)]
+struct FixtureMiner {
+    peer_id: Option<String>,
+    #[serde(default)]
+    multiaddrs: Vec<String>,
+    curio: Option<FixtureCurioPeer>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FixtureCurioPeer {
+    peer_id: String,
+    #[serde(default)]
+    multiaddrs: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Fixture {
+    #[serde(default)]
+    miners: HashMap<String, FixtureMiner>,
+    /// Deal ID to label; deals missing here are treated as no longer on chain
+    #[serde(default)]
+    deals: HashMap<String, Option<String>>,
+}
+
+#[derive(Debug)]
+pub struct FixtureBackend {
+    network: Network,
+    fixture: Fixture,
+}
+
+impl FixtureBackend {
+    pub fn from_file(network: Network, path: &str) -> Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| eyre!("Failed to read chain fixture {}: {}", path, e))?;
+        Self::from_json(network, &json)
+    }
+
+    pub fn from_json(network: Network, json: &str) -> Result<Self> {
+        let fixture =
+            serde_json::from_str(json).map_err(|e| eyre!("Invalid chain fixture: {}", e))?;
+        Ok(Self { network, fixture })
+    }
+
+    fn miner(&self, address: &ProviderAddress) -> Result<&FixtureMiner> {
+        self.fixture
+            .miners
+            .get(address.as_str())
+            .or_else(|| self.fixture.miners.get(&self.network.address(address)))
+            .ok_or_else(|| eyre!("actor not found: {}", address))
+    }
+}
+
+#[async_trait]
+impl ChainBackend for FixtureBackend {
+    fn name(&self) -> &'static str {
+        "fixture"
+    }
+
+    fn network(&self) -> Network {
+        self.network
+    }
+
+    async fn get_miner_info(&self, address: &ProviderAddress) -> Result<MinerInfo> {
+        let miner = self.miner(address)?;
+        Ok(MinerInfo {
+            peer_id: miner.peer_id.clone().filter(|p| !p.is_empty()),
+            multiaddrs: miner.multiaddrs.clone(),
+        })
+    }
+
+    async fn get_deal_label(&self, deal_id: i32) -> Result<Option<String>> {
+        Ok(self
+            .fixture
+            .deals
+            .get(&deal_id.to_string())
+            .cloned()
+            .flatten()
+            .filter(|l| !l.is_empty()))
+    }
+
+    async fn get_curio_peer(&self, address: &ProviderAddress) -> Result<Option<CurioPeer>> {
+        Ok(self.miner(address)?.curio.as_ref().map(|peer| CurioPeer {
+            peer_id: peer.peer_id.clone(),
+            multiaddrs: peer.multiaddrs.clone(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = r#"{
+        "miners": {
+            "f01234": {
+                "peer_id": "12D3KooWRf7tJR2NfJYE3PQJKXGt1EFqmFBBfQCgPRBLwwR9XL15",
+                "multiaddrs": ["/ip4/1.2.3.4/tcp/24001"],
+                "curio": { "peer_id": "12D3KooWCurio", "multiaddrs": ["/dns/sp.example.com/https"] }
+            },
+            "t05678": { "peer_id": "" }
+        },
+        "deals": { "42": "bafybeiczsscdsbs7ffqz55asqdf3smv6klcw3gofszvwlyarci47bgf354", "43": null }
+    }"#;
+
+    fn address(addr: &str) -> ProviderAddress {
+        ProviderAddress::new(addr).unwrap()
+    }
+
+    #[tokio::test]
+    async fn reads_miner_info_and_curio_peer() {
+        let backend = FixtureBackend::from_json(Network::Mainnet, FIXTURE).unwrap();
+
+        let info = backend.get_miner_info(&address("f01234")).await.unwrap();
+        assert_eq!(
+            info.peer_id.as_deref(),
+            Some("12D3KooWRf7tJR2NfJYE3PQJKXGt1EFqmFBBfQCgPRBLwwR9XL15")
+        );
+        assert_eq!(info.multiaddrs, vec!["/ip4/1.2.3.4/tcp/24001"]);
+
+        let curio = backend.get_curio_peer(&address("f01234")).await.unwrap();
+        assert_eq!(curio.unwrap().peer_id, "12D3KooWCurio");
+    }
+
+    #[tokio::test]
+    async fn unknown_miner_is_an_error() {
+        let backend = FixtureBackend::from_json(Network::Mainnet, FIXTURE).unwrap();
+
+        assert!(backend.get_miner_info(&address("f09999")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn calibration_miners_may_use_testnet_prefix() {
+        let backend = FixtureBackend::from_json(Network::Calibration, FIXTURE).unwrap();
+
+        let info = backend.get_miner_info(&address("f05678")).await.unwrap();
+        assert_eq!(info.peer_id, None);
+        assert_eq!(
+            backend.get_curio_peer(&address("f05678")).await.unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_and_empty_deals_have_no_label() {
+        let backend = FixtureBackend::from_json(Network::Mainnet, FIXTURE).unwrap();
+
+        assert_eq!(
+            backend.get_deal_label(42).await.unwrap().as_deref(),
+            Some("bafybeiczsscdsbs7ffqz55asqdf3smv6klcw3gofszvwlyarci47bgf354")
+        );
+        assert_eq!(backend.get_deal_label(43).await.unwrap(), None);
+        assert_eq!(backend.get_deal_label(44).await.unwrap(), None);
+    }
+}
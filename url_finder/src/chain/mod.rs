@@ -0,0 +1,122 @@
+//! Read access to Filecoin chain state: miner info, market deals and the Curio peer
+//! registry. The backend is picked by `CHAIN_BACKEND` and shared through `Config::chain`.
+
+mod fixture;
+mod rpc;
+
+use std::{fmt, str::FromStr};
+
+use async_trait::async_trait;
+use color_eyre::{Result, eyre::eyre};
+
+use crate::types::ProviderAddress;
+
+pub use fixture::FixtureBackend;
+pub use rpc::{RpcBackend, RpcNode};
+
+/// Curio peer registry deployed on mainnet
+pub const MAINNET_CURIO_CONTRACT: &str = "0x14183aD016Ddc83D638425D6328009aa390339Ce";
+
+/// Filecoin network the backend reads from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Network {
+    #[default]
+    Mainnet,
+    Calibration,
+}
+
+impl Network {
+    /// Addresses are stored with the mainnet `f` prefix; calibration nodes expect `t`
+    pub fn address(&self, address: &ProviderAddress) -> String {
+        match self {
+            Self::Mainnet => address.as_str().to_string(),
+            Self::Calibration => address.as_str().replacen('f', "t", 1),
+        }
+    }
+
+    /// The calibration deployment has no well-known address, so it must be configured
+    pub fn default_curio_contract(&self) -> Option<&'static str> {
+        match self {
+            Self::Mainnet => Some(MAINNET_CURIO_CONTRACT),
+            Self::Calibration => None,
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mainnet => write!(f, "mainnet"),
+            Self::Calibration => write!(f, "calibration"),
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = color_eyre::eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "mainnet" => Ok(Self::Mainnet),
+            "calibration" | "calibnet" => Ok(Self::Calibration),
+            _ => Err(eyre!("Invalid network: {}", s)),
+        }
+    }
+}
+
+/// Peer ID and multiaddrs a miner has set on chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinerInfo {
+    pub peer_id: Option<String>,
+    pub multiaddrs: Vec<String>,
+}
+
+/// Peer registered for a miner in the Curio contract
+#[derive(Debug, Clone, PartialEq)]
+pub struct CurioPeer {
+    pub peer_id: String,
+    /// Multiaddrs decoded from the contract's opaque bytes
+    pub multiaddrs: Vec<String>,
+}
+
+#[async_trait]
+pub trait ChainBackend: fmt::Debug + Send + Sync {
+    /// Short backend name for logs
+    fn name(&self) -> &'static str;
+
+    fn network(&self) -> Network;
+
+    /// Reads the peer ID and multiaddrs from `StateMinerInfo`
+    async fn get_miner_info(&self, address: &ProviderAddress) -> Result<MinerInfo>;
+
+    async fn get_peer_id(&self, address: &ProviderAddress) -> Result<Option<String>> {
+        Ok(self.get_miner_info(address).await?.peer_id)
+    }
+
+    /// Fetches `DealProposal.Label` for a market deal.
+    /// Returns Ok(None) when the deal is no longer on chain or has an empty Label.
+    async fn get_deal_label(&self, deal_id: i32) -> Result<Option<String>>;
+
+    /// Peer data the miner registered in the Curio contract, None when it has none
+    async fn get_curio_peer(&self, address: &ProviderAddress) -> Result<Option<CurioPeer>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calibration_uses_testnet_prefix() {
+        let address = ProviderAddress::new("f01234").unwrap();
+
+        assert_eq!(Network::Mainnet.address(&address), "f01234");
+        assert_eq!(Network::Calibration.address(&address), "t01234");
+    }
+
+    #[test]
+    fn parses_network_names() {
+        assert_eq!("mainnet".parse::<Network>().unwrap(), Network::Mainnet);
+        assert_eq!("Calibnet".parse::<Network>().unwrap(), Network::Calibration);
+        assert!("devnet".parse::<Network>().is_err());
+    }
+}
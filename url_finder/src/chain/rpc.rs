@@ -0,0 +1,339 @@
+//! Filecoin JSON-RPC backend for Lotus (e.g. Glif) and Forest nodes. Both serve the same
+//! `Filecoin.*` v1 methods and `eth_call`, so one client covers them; the node kind only
+//! picks the default URL and names the backend in logs.
+
+use std::time::Duration;
+
+use alloy::{
+    network::TransactionBuilder,
+    primitives::{Address, Bytes},
+    providers::{Provider, ProviderBuilder},
+    rpc::types::eth::TransactionRequest,
+    sol,
+    sol_types::{SolCall, SolType},
+};
+use async_trait::async_trait;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use color_eyre::{Result, eyre::eyre};
+use serde_json::json;
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+
+use super::{ChainBackend, CurioPeer, MinerInfo, Network};
+use crate::{multiaddr_parser, types::ProviderAddress, utils::build_reqwest_retry_client};
+
+const LOTUS_RPC_MIN_RETRY_INTERVAL_MS: u64 = 10_000;
+const LOTUS_RPC_MAX_RETRY_INTERVAL_MS: u64 = 180_000;
+const LOTUS_RPC_TOTAL_TIMEOUT_MS: u64 = 250_000;
+const CURIO_CALL_ATTEMPTS: u32 = 3;
+
+sol! {
+    struct PeerData {
+        string peerID;
+        bytes multiaddrs;
+    }
+
+    function getPeerData(uint64 minerID) view returns (PeerData);
+}
+
+/// Node implementation behind a JSON-RPC endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcNode {
+    Lotus,
+    Forest,
+}
+
+impl RpcNode {
+    pub fn default_url(&self, network: Network) -> &'static str {
+        match (self, network) {
+            (Self::Lotus, Network::Mainnet) => "https://api.node.glif.io/rpc/v1",
+            (Self::Lotus, Network::Calibration) => "https://api.calibration.node.glif.io/rpc/v1",
+            (Self::Forest, _) => "http://127.0.0.1:2345/rpc/v1",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RpcBackend {
+    node: RpcNode,
+    network: Network,
+    url: String,
+    /// Curio peer registry; Curio lookups are skipped without one
+    curio_contract: Option<Address>,
+}
+
+impl RpcBackend {
+    pub fn new(
+        node: RpcNode,
+        network: Network,
+        url: String,
+        curio_contract: Option<Address>,
+    ) -> Self {
+        Self {
+            node,
+            network,
+            url,
+            curio_contract,
+        }
+    }
+
+    async fn call(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value> {
+        let client = build_reqwest_retry_client(
+            LOTUS_RPC_MIN_RETRY_INTERVAL_MS,
+            LOTUS_RPC_MAX_RETRY_INTERVAL_MS,
+        );
+        let res = client
+            .post(&self.url)
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": method,
+                "params": params
+            }))
+            .timeout(Duration::from_millis(LOTUS_RPC_TOTAL_TIMEOUT_MS))
+            .send()
+            .await?;
+
+        Ok(res.json::<serde_json::Value>().await?)
+    }
+}
+
+#[async_trait]
+impl ChainBackend for RpcBackend {
+    fn name(&self) -> &'static str {
+        match self.node {
+            RpcNode::Lotus => "lotus",
+            RpcNode::Forest => "forest",
+        }
+    }
+
+    fn network(&self) -> Network {
+        self.network
+    }
+
+    async fn get_miner_info(&self, address: &ProviderAddress) -> Result<MinerInfo> {
+        debug!("get_miner_info address: {}", address);
+
+        let json = self
+            .call(
+                "Filecoin.StateMinerInfo",
+                json!([self.network.address(address), null]),
+            )
+            .await?;
+
+        parse_miner_info_response(&json)
+    }
+
+    async fn get_deal_label(&self, deal_id: i32) -> Result<Option<String>> {
+        debug!("get_deal_label deal_id: {}", deal_id);
+
+        let json = self
+            .call("Filecoin.StateMarketStorageDeal", json!([deal_id, null]))
+            .await?;
+
+        parse_deal_label_response(&json)
+    }
+
+    async fn get_curio_peer(&self, address: &ProviderAddress) -> Result<Option<CurioPeer>> {
+        let Some(contract) = self.curio_contract else {
+            return Ok(None);
+        };
+
+        let rpc_provider = ProviderBuilder::new()
+            .connect(&self.url)
+            .await
+            .map_err(|err| eyre!("Building provider failed: {}", err))?;
+
+        let miner_id = address.miner_id()?;
+        let call: Vec<u8> = getPeerDataCall { minerID: miner_id }.abi_encode();
+        let tx = TransactionRequest::default()
+            .with_to(contract)
+            .with_input(Bytes::from(call));
+
+        let mut response = None;
+
+        for attempt in 1..=CURIO_CALL_ATTEMPTS {
+            match rpc_provider.call(tx.clone()).await {
+                Ok(res) => {
+                    response = Some(res);
+                    break;
+                }
+                Err(e) => {
+                    debug!(
+                        "Attempt {attempt}/{CURIO_CALL_ATTEMPTS} failed: {e} for address: {address}"
+                    );
+                    sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+
+        let Some(response) = response else {
+            warn!("Curio lookup failed after {CURIO_CALL_ATTEMPTS} attempts for {address}");
+            return Err(eyre!(
+                "All {CURIO_CALL_ATTEMPTS} attempts failed for address {address}"
+            ));
+        };
+
+        let peer_data: PeerData = PeerData::abi_decode(response.as_ref())?;
+
+        if peer_data.peerID.is_empty() {
+            return Ok(None);
+        }
+
+        info!("Curio provider found: {}: {}", &peer_data.peerID, &address);
+        Ok(Some(CurioPeer {
+            peer_id: peer_data.peerID.to_string(),
+            multiaddrs: multiaddr_parser::decode_contract_bytes(&peer_data.multiaddrs),
+        }))
+    }
+}
+
+/// Multiaddrs are base64 encoded binary multiaddrs; ones that fail to decode are skipped
+fn parse_miner_info_response(json: &serde_json::Value) -> Result<MinerInfo> {
+    if let Some(message) = json
+        .get("error")
+        .and_then(|e| e.get("message"))
+        .and_then(|m| m.as_str())
+    {
+        return Err(eyre!("{}", message));
+    }
+
+    let info = json
+        .get("result")
+        .ok_or(eyre!("Missing lotus rpc result"))?;
+
+    let peer_id = info
+        .get("PeerId")
+        .and_then(|p| p.as_str())
+        .filter(|p| !p.is_empty())
+        .map(|p| p.to_string());
+
+    let multiaddrs = info
+        .get("Multiaddrs")
+        .and_then(|m| m.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|addr| addr.as_str())
+        .filter_map(|addr| {
+            let bytes = BASE64_STANDARD.decode(addr).ok()?;
+            multiaddr_parser::decode_binary(&bytes)
+        })
+        .collect();
+
+    Ok(MinerInfo {
+        peer_id,
+        multiaddrs,
+    })
+}
+
+fn parse_deal_label_response(json: &serde_json::Value) -> Result<Option<String>> {
+    if let Some(message) = json
+        .get("error")
+        .and_then(|e| e.get("message"))
+        .and_then(|m| m.as_str())
+    {
+        // Expired or slashed deals are removed from market state
+        if message.contains("not found") {
+            return Ok(None);
+        }
+        return Err(eyre!("{}", message));
+    }
+
+    let proposal = json
+        .get("result")
+        .ok_or(eyre!("Missing lotus rpc result"))?
+        .get("Proposal")
+        .ok_or(eyre!("Missing lotus rpc Proposal"))?;
+
+    Ok(proposal
+        .get("Label")
+        .and_then(|l| l.as_str())
+        .filter(|l| !l.is_empty())
+        .map(|l| l.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_deal_label_response() {
+        let json = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": {
+                "Proposal": {
+                    "PieceCID": { "/": "baga6ea4seaqao7s73y24kcutaosvacpdjgfe5pw76ooefnyqw4ynr3d2y6x2mpq" },
+                    "Label": "bafybeiczsscdsbs7ffqz55asqdf3smv6klcw3gofszvwlyarci47bgf354"
+                },
+                "State": {}
+            }
+        });
+
+        assert_eq!(
+            parse_deal_label_response(&json).unwrap().as_deref(),
+            Some("bafybeiczsscdsbs7ffqz55asqdf3smv6klcw3gofszvwlyarci47bgf354")
+        );
+    }
+
+    #[test]
+    fn test_parse_deal_label_response_deal_not_found() {
+        let json = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "error": { "code": 1, "message": "deal 123 not found" }
+        });
+
+        assert_eq!(parse_deal_label_response(&json).unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_deal_label_response_rpc_error() {
+        let json = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "error": { "code": -32603, "message": "internal error" }
+        });
+
+        assert!(parse_deal_label_response(&json).is_err());
+    }
+
+    #[test]
+    fn test_parse_miner_info_response() {
+        // /ip4/1.2.3.4/tcp/24001
+        let addr = BASE64_STANDARD.encode([0x04, 1, 2, 3, 4, 0x06, 0x5d, 0xc1]);
+        let json = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": {
+                "PeerId": "12D3KooWRf7tJR2NfJYE3PQJKXGt1EFqmFBBfQCgPRBLwwR9XL15",
+                "Multiaddrs": [addr, "not base64!"]
+            }
+        });
+
+        assert_eq!(
+            parse_miner_info_response(&json).unwrap(),
+            MinerInfo {
+                peer_id: Some("12D3KooWRf7tJR2NfJYE3PQJKXGt1EFqmFBBfQCgPRBLwwR9XL15".to_string()),
+                multiaddrs: vec!["/ip4/1.2.3.4/tcp/24001".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_miner_info_response_without_peer() {
+        let json = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": { "PeerId": null, "Multiaddrs": null }
+        });
+
+        assert_eq!(
+            parse_miner_info_response(&json).unwrap(),
+            MinerInfo {
+                peer_id: None,
+                multiaddrs: vec![],
+            }
+        );
+    }
+}
@@ -1,14 +1,17 @@
 use std::{fmt, time::Duration};
 
+use base64::{Engine, prelude::BASE64_STANDARD};
 use color_eyre::Result;
 use tracing::debug;
 use urlencoding::decode;
 
-use crate::{config::Config, utils::build_reqwest_retry_client};
+use crate::{
+    car_header::cid_from_link, config::IpniIndexer, types::EndpointSource,
+    utils::build_reqwest_retry_client,
+};
 
 const CID_CONTACT_MIN_RETRY_INTERVAL_MS: u64 = 2_000;
 const CID_CONTACT_MAX_RETRY_INTERVAL_MS: u64 = 30_000;
-const CID_CONTACT_TOTAL_TIMEOUT_MS: u64 = 60_000;
 
 pub enum CidContactError {
     InvalidResponse,
@@ -25,23 +28,38 @@ impl fmt::Display for CidContactError {
 }
 
 pub async fn get_contact(
-    config: &Config,
+    indexer: &IpniIndexer,
     peer_id: &str,
+) -> Result<serde_json::Value, CidContactError> {
+    fetch_json(indexer, &format!("providers/{peer_id}")).await
+}
+
+/// IPNI content lookup: provider results the indexer holds for the multihash of `cid`
+pub async fn get_cid_providers(
+    indexer: &IpniIndexer,
+    cid: &str,
+) -> Result<serde_json::Value, CidContactError> {
+    fetch_json(indexer, &format!("cid/{cid}")).await
+}
+
+async fn fetch_json(
+    indexer: &IpniIndexer,
+    path: &str,
 ) -> Result<serde_json::Value, CidContactError> {
     let client = build_reqwest_retry_client(
         CID_CONTACT_MIN_RETRY_INTERVAL_MS,
         CID_CONTACT_MAX_RETRY_INTERVAL_MS,
     );
-    let base_url = config.cid_contact_url.trim_end_matches('/');
-    let url = format!("{base_url}/providers/{peer_id}");
+    let base_url = indexer.url.trim_end_matches('/');
+    let url = format!("{base_url}/{path}");
 
-    debug!("cid contact url: {:?}", url);
+    debug!("{} url: {:?}", indexer.name, url);
 
     let res = client
         .get(&url)
         .header("Accept", "application/json")
         .header("User-Agent", "url-finder/0.1.0")
-        .timeout(Duration::from_millis(CID_CONTACT_TOTAL_TIMEOUT_MS))
+        .timeout(Duration::from_millis(indexer.timeout_ms))
         .send()
         .await
         .map_err(|_| CidContactError::InvalidResponse)?;
@@ -66,10 +84,14 @@ pub async fn get_contact(
     Ok(json)
 }
 
-pub fn get_all_addresses_from_response(json: serde_json::Value) -> Vec<String> {
+/// Addresses advertised in the response and the section they came from.
+/// ExtendedProviders take precedence over Publisher; None when neither is present.
+pub fn get_all_addresses_from_response(
+    json: serde_json::Value,
+) -> Option<(EndpointSource, Vec<String>)> {
     let mut addresses = vec![];
 
-    if let Some(e_providers) = json
+    let source = if let Some(e_providers) = json
         .get("ExtendedProviders")
         .and_then(|ep| ep.get("Providers"))
         .and_then(|p| p.as_array())
@@ -83,6 +105,7 @@ pub fn get_all_addresses_from_response(json: serde_json::Value) -> Vec<String> {
             .for_each(|addr| {
                 addresses.push(addr.to_string());
             });
+        EndpointSource::ExtendedProviders
     } else if let Some(e_providers) = json
         .get("Publisher")
         .and_then(|p| p.get("Addrs"))
@@ -92,30 +115,131 @@ pub fn get_all_addresses_from_response(json: serde_json::Value) -> Vec<String> {
             .iter()
             .filter_map(|addr| addr.as_str())
             .for_each(|addr: &str| {
-                let decoded_addr = decode(addr)
-                    .map(|s| s.into_owned())
-                    .unwrap_or_else(|_| addr.to_string());
-
-                let cleaned: String = decoded_addr.replace("//", "/");
+                addresses.push(strip_publisher_path(addr));
+            });
+        EndpointSource::Publisher
+    } else {
+        return None;
+    };
 
-                let trimmed = match cleaned.find("/http-path") {
-                    Some(index) => &cleaned[..index],
-                    None => &cleaned,
-                };
+    Some((source, addresses))
+}
 
-                let has_tcp = trimmed.contains("/tcp/");
-                let final_addr = if !has_tcp && trimmed.ends_with("/https") {
-                    trimmed.replace("/https", "/tcp/443/https")
-                } else if !has_tcp && trimmed.ends_with("/http") {
-                    trimmed.replace("/http", "/tcp/80/http")
-                } else {
-                    trimmed.to_string()
-                };
+/// Publisher addrs are where the provider publishes IPNI advertisements. An http-path under
+/// /ipni-provider is that announce path rather than a retrieval base path, so it is dropped;
+/// everything else is left to the multiaddr parser.
+fn strip_publisher_path(addr: &str) -> String {
+    let mut components = addr.split('/').peekable();
+    let mut kept = vec![];
 
-                addresses.push(final_addr);
+    while let Some(component) = components.next() {
+        let is_announce_path = component == "http-path"
+            && components.peek().is_some_and(|value| {
+                decode(value)
+                    .map(|v| v.trim_start_matches('/').starts_with("ipni-provider"))
+                    .unwrap_or(false)
             });
+        if is_announce_path {
+            components.next();
+        } else {
+            kept.push(component);
+        }
     }
 
+    kept.join("/")
+}
+
+/// Retrieval transport advertised in IPNI provider result metadata (multicodec table)
+#[derive(Debug, Clone, PartialEq)]
+pub enum IpniTransport {
+    Bitswap,
+    GraphsyncFilecoinV1 { piece_cid: Option<String> },
+    IpfsGatewayHttp,
+}
+
+const TRANSPORT_BITSWAP: u64 = 0x0900;
+const TRANSPORT_GRAPHSYNC_FILECOINV1: u64 = 0x0910;
+const TRANSPORT_IPFS_GATEWAY_HTTP: u64 = 0x0920;
+
+/// Decodes `[varint protocol][payload]...` metadata. Bitswap and HTTP carry no payload,
+/// graphsync is followed by a dag-cbor map. Decoding stops at the first unknown protocol
+/// since its payload length cannot be known.
+pub fn decode_metadata(bytes: &[u8]) -> Vec<IpniTransport> {
+    let mut transports = vec![];
+    let mut rest = bytes;
+
+    while !rest.is_empty() {
+        let Ok((code, remaining)) = unsigned_varint::decode::u64(rest) else {
+            debug!("Malformed IPNI metadata protocol varint");
+            break;
+        };
+        rest = remaining;
+
+        match code {
+            TRANSPORT_BITSWAP => transports.push(IpniTransport::Bitswap),
+            TRANSPORT_IPFS_GATEWAY_HTTP => transports.push(IpniTransport::IpfsGatewayHttp),
+            TRANSPORT_GRAPHSYNC_FILECOINV1 => {
+                // Reading from `&mut rest` advances it past the cbor map
+                let Ok(value) = ciborium::from_reader::<ciborium::Value, _>(&mut rest) else {
+                    debug!("Malformed graphsync metadata payload");
+                    break;
+                };
+                let piece_cid = value.as_map().and_then(|map| {
+                    map.iter()
+                        .find(|(k, _)| k.as_text() == Some("PieceCID"))
+                        .and_then(|(_, v)| cid_from_link(v))
+                });
+                transports.push(IpniTransport::GraphsyncFilecoinV1 { piece_cid });
+            }
+            other => {
+                debug!("Unknown IPNI metadata protocol 0x{other:x}");
+                break;
+            }
+        }
+    }
+
+    transports
+}
+
+/// Addresses the indexer advertises for `peer_id` on content served over HTTP.
+/// Results from other providers, or without the ipfs-gateway-http transport, are ignored.
+pub fn get_http_addresses_from_cid_response(
+    json: &serde_json::Value,
+    peer_id: &str,
+) -> Vec<String> {
+    let mut addresses: Vec<String> = vec![];
+
+    json.get("MultihashResults")
+        .and_then(|r| r.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|mh| mh.get("ProviderResults").and_then(|p| p.as_array()))
+        .flatten()
+        .filter(|result| {
+            result
+                .get("Provider")
+                .and_then(|p| p.get("ID"))
+                .and_then(|id| id.as_str())
+                == Some(peer_id)
+        })
+        .filter(|result| {
+            result
+                .get("Metadata")
+                .and_then(|m| m.as_str())
+                .and_then(|m| BASE64_STANDARD.decode(m).ok())
+                .is_some_and(|m| decode_metadata(&m).contains(&IpniTransport::IpfsGatewayHttp))
+        })
+        .filter_map(|result| result.get("Provider").and_then(|p| p.get("Addrs")))
+        .filter_map(|addrs| addrs.as_array())
+        .flatten()
+        .filter_map(|addr| addr.as_str())
+        .map(str::to_string)
+        .for_each(|addr| {
+            if !addresses.contains(&addr) {
+                addresses.push(addr);
+            }
+        });
+
     addresses
 }
 
@@ -126,7 +250,7 @@ mod tests {
 
     // Real-world example: https://cid.contact/providers/12D3KooWRf7tJR2NfJYE3PQJKXGt1EFqmFBBfQCgPRBLwwR9XL15
     #[test]
-    fn transforms_publisher_https_with_http_path() {
+    fn strips_publisher_announce_path() {
         let response = json!({
             "Publisher": {
                 "ID": "12D3KooWRf7tJR2NfJYE3PQJKXGt1EFqmFBBfQCgPRBLwwR9XL15",
@@ -136,10 +260,28 @@ mod tests {
             }
         });
 
-        let addrs = get_all_addresses_from_response(response);
+        let (source, addrs) = get_all_addresses_from_response(response).unwrap();
 
+        assert_eq!(source, EndpointSource::Publisher);
         assert_eq!(addrs.len(), 1);
-        assert_eq!(addrs[0], "/dns/adela.myfil.net/tcp/443/https");
+        assert_eq!(addrs[0], "/dns/adela.myfil.net/https");
+    }
+
+    #[test]
+    fn keeps_publisher_retrieval_path() {
+        let response = json!({
+            "Publisher": {
+                "ID": "test-peer-id",
+                "Addrs": ["/dns/sp.example.com/tcp/443/https/http-path/%2Fretrieval"]
+            }
+        });
+
+        let (_, addrs) = get_all_addresses_from_response(response).unwrap();
+
+        assert_eq!(
+            addrs,
+            vec!["/dns/sp.example.com/tcp/443/https/http-path/%2Fretrieval"]
+        );
     }
 
     #[test]
@@ -151,7 +293,7 @@ mod tests {
             }
         });
 
-        let addrs = get_all_addresses_from_response(response);
+        let (_, addrs) = get_all_addresses_from_response(response).unwrap();
 
         assert_eq!(addrs.len(), 1);
         assert_eq!(addrs[0], "/ip4/1.2.3.4/tcp/8080/http");
@@ -169,9 +311,111 @@ mod tests {
             }
         });
 
-        let addrs = get_all_addresses_from_response(response);
+        let (source, addrs) = get_all_addresses_from_response(response).unwrap();
 
+        assert_eq!(source, EndpointSource::ExtendedProviders);
         assert_eq!(addrs.len(), 1);
         assert_eq!(addrs[0], "/dns/example.com/https");
     }
+
+    #[test]
+    fn missing_addresses_returns_none() {
+        let response = json!({ "AddrInfo": { "ID": "test-peer-id" } });
+
+        assert!(get_all_addresses_from_response(response).is_none());
+    }
+
+    const PEER_ID: &str = "12D3KooWRf7tJR2NfJYE3PQJKXGt1EFqmFBBfQCgPRBLwwR9XL15";
+
+    fn graphsync_metadata() -> Vec<u8> {
+        // baga6ea4seaq... style piece CID bytes: v1, fil-commitment-unsealed, sha2-256-trunc254
+        let mut cid = vec![0x00, 0x01, 0x81, 0xe2, 0x03, 0x92, 0x20, 0x20];
+        cid.extend([0xab; 32]);
+        let map = ciborium::Value::Map(vec![
+            (
+                ciborium::Value::Text("PieceCID".into()),
+                ciborium::Value::Tag(42, Box::new(ciborium::Value::Bytes(cid))),
+            ),
+            (
+                ciborium::Value::Text("VerifiedDeal".into()),
+                ciborium::Value::Bool(true),
+            ),
+            (
+                ciborium::Value::Text("FastRetrieval".into()),
+                ciborium::Value::Bool(true),
+            ),
+        ]);
+
+        let mut bytes = vec![0x90, 0x12];
+        ciborium::into_writer(&map, &mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn decode_metadata_reads_all_transports() {
+        let mut metadata = vec![0x80, 0x12];
+        metadata.extend(graphsync_metadata());
+        metadata.extend([0xa0, 0x12]);
+
+        let transports = decode_metadata(&metadata);
+
+        assert_eq!(transports.len(), 3);
+        assert_eq!(transports[0], IpniTransport::Bitswap);
+        assert!(matches!(
+            &transports[1],
+            IpniTransport::GraphsyncFilecoinV1 { piece_cid: Some(cid) } if cid.starts_with("baga6ea4sea")
+        ));
+        assert_eq!(transports[2], IpniTransport::IpfsGatewayHttp);
+    }
+
+    #[test]
+    fn decode_metadata_stops_at_unknown_protocol() {
+        let transports = decode_metadata(&[0xa0, 0x12, 0x81, 0x20, 0x01, 0x80, 0x12]);
+
+        assert_eq!(transports, vec![IpniTransport::IpfsGatewayHttp]);
+    }
+
+    #[test]
+    fn cid_response_keeps_http_results_of_peer() {
+        let http = BASE64_STANDARD.encode([0xa0, 0x12]);
+        let bitswap = BASE64_STANDARD.encode([0x80, 0x12]);
+        let response = json!({
+            "MultihashResults": [{
+                "Multihash": "EiCx",
+                "ProviderResults": [
+                    {
+                        "ContextID": "AQ==",
+                        "Metadata": http,
+                        "Provider": { "ID": PEER_ID, "Addrs": ["/dns/sp.example.com/https"] }
+                    },
+                    {
+                        "ContextID": "Ag==",
+                        "Metadata": http,
+                        "Provider": { "ID": PEER_ID, "Addrs": ["/dns/sp.example.com/https"] }
+                    },
+                    {
+                        "ContextID": "Aw==",
+                        "Metadata": bitswap,
+                        "Provider": { "ID": PEER_ID, "Addrs": ["/ip4/1.2.3.4/tcp/4001"] }
+                    },
+                    {
+                        "ContextID": "BA==",
+                        "Metadata": http,
+                        "Provider": { "ID": "12D3KooWOther", "Addrs": ["/ip4/5.6.7.8/tcp/80/http"] }
+                    }
+                ]
+            }]
+        });
+
+        let addrs = get_http_addresses_from_cid_response(&response, PEER_ID);
+
+        assert_eq!(addrs, vec!["/dns/sp.example.com/https"]);
+    }
+
+    #[test]
+    fn cid_response_without_results_is_empty() {
+        let addrs = get_http_addresses_from_cid_response(&json!({}), PEER_ID);
+
+        assert!(addrs.is_empty());
+    }
 }
@@ -0,0 +1,381 @@
+//! Filecoin piece commitment (CommP) computation.
+//!
+//! Streams raw piece bytes through fr32 padding and a SHA256-trunc254 binary merkle tree,
+//! so the resulting root can be compared against the commitment embedded in a piece CID.
+
+use sha2::{Digest, Sha256};
+
+/// Multicodec for `fil-commitment-unsealed` (CommP in piece CID v1)
+const FIL_COMMITMENT_UNSEALED: u64 = 0xf101;
+/// Multihash for `sha2-256-trunc254-padded` (piece CID v1)
+const SHA2_256_TRUNC254_PADDED: u64 = 0x1012;
+/// Multicodec for `raw` (piece CID v2, FRC-0069)
+const RAW_CODEC: u64 = 0x55;
+/// Multihash for `fr32-sha256-trunc254-padbintree` (piece CID v2, FRC-0069)
+const FR32_SHA256_TRUNC254_PADBINTREE: u64 = 0x1011;
+
+const NODE_SIZE: usize = 32;
+const FR32_UNPADDED_CHUNK: usize = 127;
+const FR32_PADDED_CHUNK: usize = 128;
+
+/// Smallest valid padded piece size (one fr32 chunk)
+const MIN_PADDED_PIECE_SIZE: u64 = FR32_PADDED_CHUNK as u64;
+
+/// Commitment and (when encoded in the CID) padded piece size extracted from a piece CID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PieceCommitment {
+    pub root: [u8; NODE_SIZE],
+    pub padded_size: Option<u64>,
+}
+
+/// Parse a base32 piece CID (v1 `baga...` or v2 `bafkzcib...`) into its commitment.
+pub fn parse_piece_cid(cid: &str) -> Option<PieceCommitment> {
+    let bytes = decode_base32_lower(cid.strip_prefix('b')?)?;
+
+    let (version, rest) = read_uvarint(&bytes)?;
+    if version != 1 {
+        return None;
+    }
+    let (codec, rest) = read_uvarint(rest)?;
+    let (hash_code, rest) = read_uvarint(rest)?;
+    let (digest_len, digest) = read_uvarint(rest)?;
+    if digest.len() as u64 != digest_len {
+        return None;
+    }
+
+    match (codec, hash_code) {
+        (FIL_COMMITMENT_UNSEALED, SHA2_256_TRUNC254_PADDED) => Some(PieceCommitment {
+            root: digest.try_into().ok()?,
+            padded_size: None,
+        }),
+        (RAW_CODEC, FR32_SHA256_TRUNC254_PADBINTREE) => {
+            // digest = uvarint(padding) || height (u8) || root
+            let (_padding, rest) = read_uvarint(digest)?;
+            let (&height, root) = rest.split_first()?;
+            if height > 58 {
+                return None;
+            }
+            Some(PieceCommitment {
+                root: root.try_into().ok()?,
+                padded_size: Some((NODE_SIZE as u64) << height),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Incremental CommP hasher. Feed raw (unpadded) piece bytes with [`CommpHasher::update`].
+#[derive(Debug, Default)]
+pub struct CommpHasher {
+    pending: Vec<u8>,
+    stack: Vec<(u32, [u8; NODE_SIZE])>,
+    bytes_written: u64,
+}
+
+impl CommpHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.bytes_written += data.len() as u64;
+
+        if !self.pending.is_empty() {
+            let take = (FR32_UNPADDED_CHUNK - self.pending.len()).min(data.len());
+            self.pending.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.pending.len() < FR32_UNPADDED_CHUNK {
+                return;
+            }
+            let chunk = std::mem::take(&mut self.pending);
+            self.push_chunk(&chunk);
+        }
+
+        let mut chunks = data.chunks_exact(FR32_UNPADDED_CHUNK);
+        for chunk in &mut chunks {
+            self.push_chunk(chunk);
+        }
+        self.pending.extend_from_slice(chunks.remainder());
+    }
+
+    /// Zero-fill up to `padded_piece_size` and return the merkle root.
+    /// Returns None if the size is not a valid piece size or the data does not fit.
+    pub fn finalize(mut self, padded_piece_size: u64) -> Option<[u8; NODE_SIZE]> {
+        if padded_piece_size < MIN_PADDED_PIECE_SIZE || !padded_piece_size.is_power_of_two() {
+            return None;
+        }
+        if self.bytes_written > unpadded_size(padded_piece_size) {
+            return None;
+        }
+
+        let target_level = (padded_piece_size / NODE_SIZE as u64).trailing_zeros();
+        let zero_comms = zero_commitments(target_level);
+
+        if !self.pending.is_empty() {
+            let mut chunk = std::mem::take(&mut self.pending);
+            chunk.resize(FR32_UNPADDED_CHUNK, 0);
+            self.push_chunk(&chunk);
+        }
+
+        loop {
+            let Some(&(level, node)) = self.stack.last() else {
+                return Some(zero_comms[target_level as usize]);
+            };
+            if self.stack.len() == 1 && level == target_level {
+                return Some(node);
+            }
+            if level >= target_level {
+                return None;
+            }
+            self.stack.pop();
+            self.push_node(level + 1, hash_pair(&node, &zero_comms[level as usize]));
+        }
+    }
+
+    fn push_chunk(&mut self, chunk: &[u8]) {
+        let mut padded = [0u8; FR32_PADDED_CHUNK];
+        fr32_pad(chunk, &mut padded);
+        for leaf in padded.chunks_exact(NODE_SIZE) {
+            let mut node = [0u8; NODE_SIZE];
+            node.copy_from_slice(leaf);
+            self.push_node(0, node);
+        }
+    }
+
+    fn push_node(&mut self, mut level: u32, mut node: [u8; NODE_SIZE]) {
+        while let Some(&(top_level, top)) = self.stack.last() {
+            if top_level != level {
+                break;
+            }
+            self.stack.pop();
+            node = hash_pair(&top, &node);
+            level += 1;
+        }
+        self.stack.push((level, node));
+    }
+}
+
+/// Number of raw bytes that fit into a padded piece of the given size
+pub fn unpadded_size(padded_piece_size: u64) -> u64 {
+    padded_piece_size - padded_piece_size / FR32_PADDED_CHUNK as u64
+}
+
+/// Expands 127 input bytes into 128 output bytes, inserting two zero bits after every 254 bits.
+fn fr32_pad(input: &[u8], out: &mut [u8; FR32_PADDED_CHUNK]) {
+    debug_assert_eq!(input.len(), FR32_UNPADDED_CHUNK);
+
+    out[..31].copy_from_slice(&input[..31]);
+
+    let mut t = input[31] >> 6;
+    out[31] = input[31] & 0x3f;
+    let mut v = 0u8;
+
+    for i in 32..64 {
+        v = input[i];
+        out[i] = (v << 2) | t;
+        t = v >> 6;
+    }
+
+    t = v >> 4;
+    out[63] &= 0x3f;
+
+    for i in 64..96 {
+        v = input[i];
+        out[i] = (v << 4) | t;
+        t = v >> 4;
+    }
+
+    t = v >> 2;
+    out[95] &= 0x3f;
+
+    for i in 96..127 {
+        v = input[i];
+        out[i] = (v << 6) | t;
+        t = v >> 2;
+    }
+
+    out[127] = t & 0x3f;
+}
+
+fn hash_pair(left: &[u8; NODE_SIZE], right: &[u8; NODE_SIZE]) -> [u8; NODE_SIZE] {
+    let mut hasher = Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+    let mut node: [u8; NODE_SIZE] = hasher.finalize().into();
+    node[NODE_SIZE - 1] &= 0x3f;
+    node
+}
+
+/// Roots of all-zero subtrees for levels 0..=max_level
+fn zero_commitments(max_level: u32) -> Vec<[u8; NODE_SIZE]> {
+    let mut comms = Vec::with_capacity(max_level as usize + 1);
+    comms.push([0u8; NODE_SIZE]);
+    for level in 0..max_level as usize {
+        let prev = comms[level];
+        comms.push(hash_pair(&prev, &prev));
+    }
+    comms
+}
+
+fn read_uvarint(bytes: &[u8]) -> Option<(u64, &[u8])> {
+    let mut value: u64 = 0;
+    for (i, &byte) in bytes.iter().enumerate().take(9) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, &bytes[i + 1..]));
+        }
+    }
+    None
+}
+
+/// Decode RFC 4648 base32 lowercase without padding (multibase prefix already stripped)
+fn decode_base32_lower(input: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len() * 5 / 8);
+    let mut buffer: u64 = 0;
+    let mut bits = 0;
+
+    for c in input.bytes() {
+        let value = match c {
+            b'a'..=b'z' => c - b'a',
+            b'2'..=b'7' => c - b'2' + 26,
+            _ => return None,
+        };
+        buffer = (buffer << 5) | u64::from(value);
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_base32_lower(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+        let mut out = String::from("b");
+        let mut buffer: u64 = 0;
+        let mut bits = 0;
+        for &byte in bytes {
+            buffer = (buffer << 8) | u64::from(byte);
+            bits += 8;
+            while bits >= 5 {
+                bits -= 5;
+                out.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+            }
+        }
+        if bits > 0 {
+            out.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+        }
+        out
+    }
+
+    fn piece_cid_v1(root: &[u8; NODE_SIZE]) -> String {
+        let mut bytes = vec![0x01, 0x81, 0xe2, 0x03, 0x92, 0x20, 0x20];
+        bytes.extend_from_slice(root);
+        encode_base32_lower(&bytes)
+    }
+
+    #[test]
+    fn test_fr32_pad_all_ones() {
+        let input = [0xffu8; FR32_UNPADDED_CHUNK];
+        let mut out = [0u8; FR32_PADDED_CHUNK];
+        fr32_pad(&input, &mut out);
+
+        for (i, byte) in out.iter().enumerate() {
+            let expected = if i % 32 == 31 { 0x3f } else { 0xff };
+            assert_eq!(*byte, expected, "byte {i}");
+        }
+    }
+
+    #[test]
+    fn test_zero_piece_commitment_128_bytes() {
+        let mut hasher = CommpHasher::new();
+        hasher.update(&[0u8; 127]);
+        let root = hasher.finalize(128).unwrap();
+        assert_eq!(
+            hex::encode(root),
+            "3731bb99ac689f66eef5973e4a94da188f4ddcae580724fc6f3fd60dfd488333"
+        );
+    }
+
+    #[test]
+    fn test_short_data_is_zero_filled() {
+        let mut partial = CommpHasher::new();
+        partial.update(&[0u8; 10]);
+
+        let mut full = CommpHasher::new();
+        full.update(&vec![0u8; unpadded_size(2048) as usize]);
+
+        assert_eq!(partial.finalize(2048), full.finalize(2048));
+    }
+
+    #[test]
+    fn test_chunked_updates_match_single_update() {
+        let data: Vec<u8> = (0..5000u32).map(|i| (i * 7 % 251) as u8).collect();
+
+        let mut single = CommpHasher::new();
+        single.update(&data);
+
+        let mut chunked = CommpHasher::new();
+        for chunk in data.chunks(13) {
+            chunked.update(chunk);
+        }
+
+        assert_eq!(chunked.bytes_written(), data.len() as u64);
+        assert_eq!(single.finalize(8192), chunked.finalize(8192));
+    }
+
+    #[test]
+    fn test_finalize_rejects_oversized_data() {
+        let mut hasher = CommpHasher::new();
+        hasher.update(&[1u8; 128]);
+        assert_eq!(hasher.finalize(128), None);
+    }
+
+    #[test]
+    fn test_finalize_rejects_invalid_piece_size() {
+        assert_eq!(CommpHasher::new().finalize(100), None);
+        assert_eq!(CommpHasher::new().finalize(64), None);
+    }
+
+    #[test]
+    fn test_parse_piece_cid_v1_roundtrip() {
+        let root = [0x11u8; NODE_SIZE];
+        let cid = piece_cid_v1(&root);
+        assert!(cid.starts_with("baga6ea4seaq"));
+
+        let parsed = parse_piece_cid(&cid).unwrap();
+        assert_eq!(parsed.root, root);
+        assert_eq!(parsed.padded_size, None);
+    }
+
+    #[test]
+    fn test_parse_piece_cid_v2_extracts_size() {
+        let root = [0x22u8; NODE_SIZE];
+        // version, raw codec, fr32-sha256-trunc254-padbintree, digest len, padding=0, height=6
+        let mut bytes = vec![0x01, 0x55, 0x91, 0x20, 0x22, 0x00, 0x06];
+        bytes.extend_from_slice(&root);
+
+        let parsed = parse_piece_cid(&encode_base32_lower(&bytes)).unwrap();
+        assert_eq!(parsed.root, root);
+        assert_eq!(parsed.padded_size, Some(2048));
+    }
+
+    #[test]
+    fn test_parse_piece_cid_rejects_non_piece_cid() {
+        // CIDv1 raw sha2-256
+        let mut bytes = vec![0x01, 0x55, 0x12, 0x20];
+        bytes.extend_from_slice(&[0u8; NODE_SIZE]);
+        assert_eq!(parse_piece_cid(&encode_base32_lower(&bytes)), None);
+        assert_eq!(parse_piece_cid("QmNotBase32"), None);
+    }
+}
@@ -1,14 +1,20 @@
-use std::env;
+use std::{env, fmt, str::FromStr, sync::Arc};
 
-use color_eyre::Result;
+use alloy::primitives::Address;
+use color_eyre::{Result, eyre::eyre};
+use serde::Deserialize;
 use tracing::warn;
 
+use crate::chain::{
+    ChainBackend, FixtureBackend, MAINNET_CURIO_CONTRACT, Network, RpcBackend, RpcNode,
+};
 use crate::types::DbConnectParams;
 
 // Double-tap consistency testing settings
 pub const DOUBLE_TAP_DELAY_MS: u64 = 500;
 pub const RANGE_REQUEST_BYTES: u64 = 4096;
 pub const MAX_CONCURRENT_URL_TESTS: usize = 20;
+pub const MAX_RANDOM_RANGE_SAMPLES: usize = 16;
 
 // Thresholds
 pub const RELIABILITY_TIMEOUT_THRESHOLD: f64 = 0.30;
@@ -18,6 +24,237 @@ pub const MIN_VALID_CONTENT_LENGTH: u64 = 8 * 1024 * 1024 * 1024; // 8GB
 pub const MAX_HISTORY_DAYS: i64 = 30;
 
 const DEFAULT_AUTH_TOKEN: &str = "mysecrettokenthatdefinatelyisnotongithubpublicrepo";
+const DEFAULT_INDEXER_NAME: &str = "cid.contact";
+const DEFAULT_INDEXER_TIMEOUT_MS: u64 = 60_000;
+
+/// What a process runs: the HTTP API, the background schedulers, or both
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Role {
+    Api,
+    Worker,
+    #[default]
+    All,
+}
+
+impl Role {
+    pub fn serves_api(&self) -> bool {
+        matches!(self, Role::Api | Role::All)
+    }
+
+    pub fn runs_workers(&self) -> bool {
+        matches!(self, Role::Worker | Role::All)
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Api => write!(f, "api"),
+            Role::Worker => write!(f, "worker"),
+            Role::All => write!(f, "all"),
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "api" => Ok(Role::Api),
+            "worker" => Ok(Role::Worker),
+            "all" => Ok(Role::All),
+            other => Err(eyre!("Unknown role '{other}', expected api, worker or all")),
+        }
+    }
+}
+
+/// `--role <role>` or `--role=<role>` from the command line, then ROLE, defaulting to all
+fn parse_role(args: &[String], env_role: Option<String>) -> Result<Role> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if let Some(role) = arg.strip_prefix("--role=") {
+            return role.parse();
+        }
+        if arg == "--role" {
+            return args.next().ok_or(eyre!("--role requires a value"))?.parse();
+        }
+    }
+
+    env_role.map_or(Ok(Role::default()), |role| role.parse())
+}
+
+/// Background schedulers this process runs; all off for the api role
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnabledSchedulers {
+    pub provider_discovery: bool,
+    pub peer_id: bool,
+    pub endpoint: bool,
+    pub url_discovery: bool,
+    pub provider_test: bool,
+    pub bms: bool,
+    /// Deal SLI scheduler and its BMS result poller
+    pub deal_sli: bool,
+}
+
+impl EnabledSchedulers {
+    fn from_env(role: Role) -> Self {
+        let enabled = |env_var| role.runs_workers() && parse_bool_or_default(env_var, true);
+        Self {
+            provider_discovery: enabled("PROVIDER_DISCOVERY_SCHEDULER_ENABLED"),
+            peer_id: enabled("PEER_ID_SCHEDULER_ENABLED"),
+            endpoint: enabled("ENDPOINT_SCHEDULER_ENABLED"),
+            url_discovery: enabled("URL_DISCOVERY_SCHEDULER_ENABLED"),
+            provider_test: enabled("PROVIDER_TEST_SCHEDULER_ENABLED"),
+            bms: enabled("BMS_SCHEDULER_ENABLED"),
+            deal_sli: enabled("DEAL_SLI_SCHEDULER_ENABLED"),
+        }
+    }
+
+    pub fn all() -> Self {
+        Self {
+            provider_discovery: true,
+            peer_id: true,
+            endpoint: true,
+            url_discovery: true,
+            provider_test: true,
+            bms: true,
+            deal_sli: true,
+        }
+    }
+
+    /// Whether anything claims leased work, so the lease heartbeat is needed
+    pub fn any_leased(&self) -> bool {
+        self.provider_discovery
+            || self.peer_id
+            || self.endpoint
+            || self.url_discovery
+            || self.provider_test
+            || self.bms
+    }
+}
+
+/// IPNI indexer queried for provider records and content lookups
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct IpniIndexer {
+    pub name: String,
+    pub url: String,
+    #[serde(default = "default_indexer_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Lower values win when indexers attribute an endpoint to different sources
+    #[serde(default)]
+    pub priority: i32,
+}
+
+fn default_indexer_timeout_ms() -> u64 {
+    DEFAULT_INDEXER_TIMEOUT_MS
+}
+
+fn default_indexer(url: String) -> IpniIndexer {
+    IpniIndexer {
+        name: DEFAULT_INDEXER_NAME.to_string(),
+        url,
+        timeout_ms: DEFAULT_INDEXER_TIMEOUT_MS,
+        priority: 0,
+    }
+}
+
+/// Indexers from IPNI_INDEXERS_JSON sorted by priority, or cid.contact at `fallback_url`
+fn parse_ipni_indexers(json: Option<String>, fallback_url: String) -> Vec<IpniIndexer> {
+    let Some(json) = json else {
+        return vec![default_indexer(fallback_url)];
+    };
+
+    let mut indexers: Vec<IpniIndexer> =
+        serde_json::from_str(&json).expect("Invalid JSON in IPNI_INDEXERS_JSON");
+    assert!(!indexers.is_empty(), "IPNI_INDEXERS_JSON must not be empty");
+    for (i, indexer) in indexers.iter().enumerate() {
+        assert!(
+            !indexer.name.trim().is_empty() && !indexer.url.trim().is_empty(),
+            "IPNI_INDEXERS_JSON entries need a name and url"
+        );
+        assert!(
+            indexers[..i].iter().all(|other| other.name != indexer.name),
+            "Duplicate indexer name in IPNI_INDEXERS_JSON: {}",
+            indexer.name
+        );
+    }
+
+    indexers.sort_by_key(|indexer| indexer.priority);
+    indexers
+}
+
+/// Chain backend settings as read from the environment
+#[derive(Debug, Default)]
+struct ChainSettings {
+    network: Option<String>,
+    backend: Option<String>,
+    rpc_url: Option<String>,
+    fixture_path: Option<String>,
+    curio_contract: Option<String>,
+}
+
+impl ChainSettings {
+    fn from_env() -> Self {
+        Self {
+            network: env::var("CHAIN_NETWORK").ok(),
+            backend: env::var("CHAIN_BACKEND").ok(),
+            // GLIF_URL predates the other backends and is still honoured
+            rpc_url: env::var("CHAIN_RPC_URL")
+                .or_else(|_| env::var("GLIF_URL"))
+                .ok(),
+            fixture_path: env::var("CHAIN_FIXTURE_PATH").ok(),
+            curio_contract: env::var("CURIO_CONTRACT_ADDRESS").ok(),
+        }
+    }
+}
+
+/// Lotus on the configured network unless CHAIN_BACKEND picks forest or fixture. The RPC URL
+/// and Curio contract default per network; calibration has no default contract.
+fn build_chain_backend(settings: ChainSettings) -> Result<Arc<dyn ChainBackend>> {
+    let network: Network = match settings.network {
+        Some(network) => network.parse()?,
+        None => Network::default(),
+    };
+
+    let curio_contract = match settings
+        .curio_contract
+        .as_deref()
+        .or(network.default_curio_contract())
+    {
+        Some(address) => Some(
+            address
+                .parse::<Address>()
+                .map_err(|e| eyre!("Invalid CURIO_CONTRACT_ADDRESS '{address}': {e}"))?,
+        ),
+        None => {
+            warn!("No Curio contract configured for {network}, Curio peer lookups are disabled");
+            None
+        }
+    };
+
+    let node = match settings.backend.as_deref().map(str::trim) {
+        None | Some("lotus") => RpcNode::Lotus,
+        Some("forest") => RpcNode::Forest,
+        Some("fixture") => {
+            let path = settings
+                .fixture_path
+                .ok_or_else(|| eyre!("CHAIN_FIXTURE_PATH must be set for the fixture backend"))?;
+            return Ok(Arc::new(FixtureBackend::from_file(network, &path)?));
+        }
+        Some(other) => return Err(eyre!("Invalid CHAIN_BACKEND: {other}")),
+    };
+
+    let url = settings
+        .rpc_url
+        .unwrap_or_else(|| node.default_url(network).to_string());
+    Ok(Arc::new(RpcBackend::new(
+        node,
+        network,
+        url,
+        curio_contract,
+    )))
+}
 
 fn parse_positive_i64_or_default(env_var: &str, default: i64) -> i64 {
     assert!(default > 0, "default must be positive");
@@ -37,6 +274,20 @@ fn parse_positive_i64_or_default(env_var: &str, default: i64) -> i64 {
     }
 }
 
+fn parse_bool_or_default(env_var: &str, default: bool) -> bool {
+    match env::var(env_var) {
+        Ok(s) => match s.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" => true,
+            "false" | "0" => false,
+            _ => {
+                warn!("{env_var}='{s}' is not a valid boolean, defaulting to {default}");
+                default
+            }
+        },
+        Err(_) => default,
+    }
+}
+
 fn require_non_empty_env_value(env_var: &str, value: String) -> String {
     assert!(!value.trim().is_empty(), "{env_var} must not be empty");
     value
@@ -53,13 +304,27 @@ fn read_auth_token() -> String {
     auth_token_or_default(env::var("AUTH_TOKEN").ok())
 }
 
+/// INSTANCE_ID, or the host name with a random suffix so restarts never inherit old leases
+fn read_instance_id() -> String {
+    match env::var("INSTANCE_ID") {
+        Ok(id) => require_non_empty_env_value("INSTANCE_ID", id),
+        Err(_) => {
+            let host = env::var("HOSTNAME").unwrap_or("url-finder".to_string());
+            let suffix = uuid::Uuid::new_v4().simple().to_string();
+            format!("{host}-{}", &suffix[..8])
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub db_url: String,
     pub dmob_db_url: String,
     pub log_level: String,
-    pub glif_url: String,
-    pub cid_contact_url: String,
+    /// Chain state backend: Lotus or Forest JSON-RPC, or a fixture file
+    pub chain: Arc<dyn ChainBackend>,
+    /// Sorted by priority, lowest value first
+    pub ipni_indexers: Vec<IpniIndexer>,
     pub proxy_url: Option<String>,
     pub proxy_user: Option<String>,
     pub proxy_password: Option<String>,
@@ -70,6 +335,37 @@ pub struct Config {
     pub bms_default_worker_count: i64,
     pub bms_test_interval_days: i64,
     pub max_concurrent_providers: usize,
+    pub commp_verification_enabled: bool,
+    pub commp_verification_pieces_per_week: i64,
+    /// Deadline for streaming and hashing one piece
+    pub commp_verification_timeout_secs: i64,
+    /// CommP verifications streaming at once, apart from MAX_CONCURRENT_PROVIDERS
+    pub commp_verification_max_concurrent: usize,
+    pub random_range_samples: usize,
+    pub throughput_sample_bytes: u64,
+    pub libp2p_probe_enabled: bool,
+    pub peer_id_max_age_hours: i64,
+    /// DNS, TCP and HEAD check of each endpoint before piece testing
+    pub endpoint_preflight_enabled: bool,
+    /// Payload CID samples from pix.filspark for pieces without a Label payload CID
+    pub pix_filspark_enabled: bool,
+    pub pix_filspark_url: String,
+    pub pix_sample_ttl_hours: i64,
+    /// URL discovery interval, backed off up to the maximum for stable providers and
+    /// shortened to the minimum for changing or Deal SLI providers
+    pub url_discovery_interval_hours: i64,
+    pub url_discovery_min_interval_hours: i64,
+    pub url_discovery_max_interval_hours: i64,
+    /// Consecutive runs with a steady outcome before a provider backs off
+    pub url_discovery_stable_runs: i64,
+    /// Owner recorded on scheduler leases, unique per running instance
+    pub instance_id: String,
+    /// Leases not heartbeated for this long are free to be claimed by another instance
+    pub scheduler_lease_ttl_secs: i64,
+    /// Scheduler runs older than this are pruned from the ledger
+    pub scheduler_run_retention_days: i64,
+    pub role: Role,
+    pub schedulers: EnabledSchedulers,
 }
 
 impl Config {
@@ -84,13 +380,20 @@ impl Config {
             params.to_url()
         });
 
+        let role = parse_role(
+            &env::args().skip(1).collect::<Vec<_>>(),
+            env::var("ROLE").ok(),
+        )?;
+
         Ok(Self {
             db_url,
             dmob_db_url: env::var("DMOB_DATABASE_URL").expect("DMOB_DATABASE_URL must be set"),
             log_level: env::var("LOG_LEVEL").unwrap_or("info".to_string()),
-            glif_url: env::var("GLIF_URL").unwrap_or("https://api.node.glif.io/rpc/v1".to_string()),
-            cid_contact_url: env::var("CID_CONTACT_URL")
-                .unwrap_or("https://cid.contact".to_string()),
+            chain: build_chain_backend(ChainSettings::from_env())?,
+            ipni_indexers: parse_ipni_indexers(
+                env::var("IPNI_INDEXERS_JSON").ok(),
+                env::var("CID_CONTACT_URL").unwrap_or("https://cid.contact".to_string()),
+            ),
             proxy_url: env::var("PROXY_URL").unwrap_or("US".to_string()).into(),
             proxy_user: env::var("PROXY_USER").ok(),
             proxy_password: env::var("PROXY_PASSWORD").ok(),
@@ -107,17 +410,79 @@ impl Config {
                 .and_then(|v| v.parse::<usize>().ok())
                 .filter(|&v| v > 0 && v <= 100)
                 .unwrap_or(10),
+            commp_verification_enabled: parse_bool_or_default("COMMP_VERIFICATION_ENABLED", false),
+            commp_verification_pieces_per_week: parse_positive_i64_or_default(
+                "COMMP_VERIFICATION_PIECES_PER_WEEK",
+                1,
+            ),
+            commp_verification_timeout_secs: parse_positive_i64_or_default(
+                "COMMP_VERIFICATION_TIMEOUT_SECS",
+                3600,
+            ),
+            commp_verification_max_concurrent: env::var("COMMP_VERIFICATION_MAX_CONCURRENT")
+                .ok()
+                .and_then(|v| v.parse::<usize>().ok())
+                .filter(|&v| v > 0 && v <= 100)
+                .unwrap_or(2),
+            random_range_samples: env::var("RANDOM_RANGE_SAMPLES")
+                .ok()
+                .and_then(|v| v.parse::<usize>().ok())
+                .filter(|&v| v <= MAX_RANDOM_RANGE_SAMPLES)
+                .unwrap_or(2),
+            throughput_sample_bytes: env::var("THROUGHPUT_SAMPLE_BYTES")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(16 * 1024 * 1024),
+            libp2p_probe_enabled: parse_bool_or_default("LIBP2P_PROBE_ENABLED", false),
+            peer_id_max_age_hours: parse_positive_i64_or_default("PEER_ID_MAX_AGE_HOURS", 24),
+            endpoint_preflight_enabled: parse_bool_or_default("ENDPOINT_PREFLIGHT_ENABLED", true),
+            pix_filspark_enabled: parse_bool_or_default("PIX_FILSPARK_ENABLED", false),
+            pix_filspark_url: env::var("PIX_FILSPARK_URL")
+                .unwrap_or("https://pix.filspark.com".to_string()),
+            pix_sample_ttl_hours: parse_positive_i64_or_default("PIX_SAMPLE_TTL_HOURS", 168),
+            url_discovery_interval_hours: parse_positive_i64_or_default(
+                "URL_DISCOVERY_INTERVAL_HOURS",
+                24,
+            ),
+            url_discovery_min_interval_hours: parse_positive_i64_or_default(
+                "URL_DISCOVERY_MIN_INTERVAL_HOURS",
+                6,
+            ),
+            url_discovery_max_interval_hours: parse_positive_i64_or_default(
+                "URL_DISCOVERY_MAX_INTERVAL_HOURS",
+                168,
+            ),
+            url_discovery_stable_runs: parse_positive_i64_or_default(
+                "URL_DISCOVERY_STABLE_RUNS",
+                3,
+            ),
+            instance_id: read_instance_id(),
+            scheduler_lease_ttl_secs: parse_positive_i64_or_default(
+                "SCHEDULER_LEASE_TTL_SECS",
+                300,
+            ),
+            scheduler_run_retention_days: parse_positive_i64_or_default(
+                "SCHEDULER_RUN_RETENTION_DAYS",
+                30,
+            ),
+            role,
+            schedulers: EnabledSchedulers::from_env(role),
         })
     }
 
     // Test helper
-    pub fn new_for_test(glif_url: String, cid_contact_url: String) -> Self {
+    pub fn new_for_test(lotus_url: String, cid_contact_url: String) -> Self {
         Self {
             db_url: "dummy".to_string(),
             dmob_db_url: "dummy".to_string(),
             log_level: "info".to_string(),
-            glif_url,
-            cid_contact_url,
+            chain: Arc::new(RpcBackend::new(
+                RpcNode::Lotus,
+                Network::Mainnet,
+                lotus_url,
+                MAINNET_CURIO_CONTRACT.parse().ok(),
+            )),
+            ipni_indexers: vec![default_indexer(cid_contact_url)],
             proxy_password: None,
             proxy_url: None,
             proxy_user: None,
@@ -128,13 +493,38 @@ impl Config {
             bms_default_worker_count: 10,
             bms_test_interval_days: 7,
             max_concurrent_providers: 10,
+            commp_verification_enabled: false,
+            commp_verification_pieces_per_week: 1,
+            commp_verification_timeout_secs: 3600,
+            commp_verification_max_concurrent: 2,
+            random_range_samples: 2,
+            throughput_sample_bytes: 0,
+            libp2p_probe_enabled: false,
+            peer_id_max_age_hours: 24,
+            endpoint_preflight_enabled: true,
+            pix_filspark_enabled: false,
+            pix_filspark_url: "http://localhost:8080".to_string(),
+            pix_sample_ttl_hours: 168,
+            url_discovery_interval_hours: 24,
+            url_discovery_min_interval_hours: 6,
+            url_discovery_max_interval_hours: 168,
+            url_discovery_stable_runs: 3,
+            instance_id: "test-instance".to_string(),
+            scheduler_lease_ttl_secs: 300,
+            scheduler_run_retention_days: 30,
+            role: Role::All,
+            schedulers: EnabledSchedulers::all(),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{DEFAULT_AUTH_TOKEN, auth_token_or_default, require_non_empty_env_value};
+    use super::{
+        ChainSettings, DEFAULT_AUTH_TOKEN, DEFAULT_INDEXER_TIMEOUT_MS, Role, auth_token_or_default,
+        build_chain_backend, parse_ipni_indexers, parse_role, require_non_empty_env_value,
+    };
+    use crate::chain::Network;
 
     #[test]
     fn accepts_non_empty_env_value() {
@@ -168,4 +558,107 @@ mod tests {
     fn rejects_whitespace_env_value() {
         auth_token_or_default(Some("   ".to_string()));
     }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn role_defaults_to_all() {
+        assert_eq!(parse_role(&[], None).unwrap(), Role::All);
+    }
+
+    #[test]
+    fn role_flag_overrides_env() {
+        assert_eq!(
+            parse_role(&args(&["--role", "worker"]), Some("api".to_string())).unwrap(),
+            Role::Worker
+        );
+        assert_eq!(parse_role(&args(&["--role=API"]), None).unwrap(), Role::Api);
+        assert_eq!(
+            parse_role(&[], Some("worker".to_string())).unwrap(),
+            Role::Worker
+        );
+    }
+
+    #[test]
+    fn role_rejects_unknown_or_missing_value() {
+        assert!(parse_role(&args(&["--role", "scheduler"]), None).is_err());
+        assert!(parse_role(&args(&["--role"]), None).is_err());
+    }
+
+    #[test]
+    fn ipni_indexers_default_to_cid_contact_url() {
+        let indexers = parse_ipni_indexers(None, "https://cid.contact".to_string());
+
+        assert_eq!(indexers.len(), 1);
+        assert_eq!(indexers[0].name, "cid.contact");
+        assert_eq!(indexers[0].url, "https://cid.contact");
+        assert_eq!(indexers[0].timeout_ms, DEFAULT_INDEXER_TIMEOUT_MS);
+    }
+
+    #[test]
+    fn ipni_indexers_sorted_by_priority() {
+        let json = r#"[
+            {"name": "cid.contact", "url": "https://cid.contact", "priority": 10},
+            {"name": "private", "url": "http://indexer.internal:3000", "timeout_ms": 5000}
+        ]"#;
+
+        let indexers = parse_ipni_indexers(Some(json.to_string()), "unused".to_string());
+
+        assert_eq!(indexers[0].name, "private");
+        assert_eq!(indexers[0].timeout_ms, 5000);
+        assert_eq!(indexers[1].name, "cid.contact");
+        assert_eq!(indexers[1].timeout_ms, DEFAULT_INDEXER_TIMEOUT_MS);
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate indexer name")]
+    fn ipni_indexers_reject_duplicate_names() {
+        let json = r#"[
+            {"name": "a", "url": "http://one"},
+            {"name": "a", "url": "http://two"}
+        ]"#;
+
+        parse_ipni_indexers(Some(json.to_string()), "unused".to_string());
+    }
+
+    #[test]
+    fn chain_backend_defaults_to_lotus_on_mainnet() {
+        let backend = build_chain_backend(ChainSettings::default()).unwrap();
+
+        assert_eq!(backend.name(), "lotus");
+        assert_eq!(backend.network(), Network::Mainnet);
+    }
+
+    #[test]
+    fn chain_backend_selects_forest_on_calibration() {
+        let backend = build_chain_backend(ChainSettings {
+            network: Some("calibration".to_string()),
+            backend: Some("forest".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(backend.name(), "forest");
+        assert_eq!(backend.network(), Network::Calibration);
+    }
+
+    #[test]
+    fn chain_backend_rejects_invalid_settings() {
+        assert!(
+            build_chain_backend(ChainSettings {
+                backend: Some("fixture".to_string()),
+                ..Default::default()
+            })
+            .is_err()
+        );
+        assert!(
+            build_chain_backend(ChainSettings {
+                curio_contract: Some("0x1234".to_string()),
+                ..Default::default()
+            })
+            .is_err()
+        );
+    }
 }
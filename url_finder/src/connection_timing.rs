@@ -0,0 +1,166 @@
+//! Cold-connection phase timing for provider URLs.
+//!
+//! reqwest pools connections and does not expose DNS, connect or TLS timings, so each
+//! phase is measured here on a fresh connection made outside the HTTP client.
+//! The probe connects directly, bypassing any configured proxy, so callers skip it when a
+//! proxy is configured rather than expose the worker's own address.
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use reqwest::Url;
+use tokio::net::{TcpStream, lookup_host};
+use tokio::time::timeout;
+use tokio_rustls::{
+    TlsConnector,
+    rustls::{ClientConfig, RootCertStore, crypto::aws_lc_rs, pki_types::ServerName},
+};
+use tracing::debug;
+
+const PHASE_TIMEOUT: Duration = Duration::from_secs(10);
+
+static TLS_CONNECTOR: Lazy<Option<TlsConnector>> = Lazy::new(|| {
+    let config = tls_client_config(&[b"http/1.1"])?;
+    Some(TlsConnector::from(Arc::new(config)))
+});
+
+/// rustls client config verifying against the webpki roots and offering the given ALPN protocols
+pub(crate) fn tls_client_config(alpn_protocols: &[&[u8]]) -> Option<ClientConfig> {
+    let roots = RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let mut config = ClientConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+        .with_safe_default_protocol_versions()
+        .ok()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    config.alpn_protocols = alpn_protocols.iter().map(|p| p.to_vec()).collect();
+    Some(config)
+}
+
+/// Duration of each connection phase, None when the phase was skipped or failed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectionTiming {
+    /// None for IP literal hosts
+    pub dns_ms: Option<u64>,
+    pub connect_ms: Option<u64>,
+    /// None for plain http
+    pub tls_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Resolves, connects and (for https) completes a TLS handshake with the URL's host
+pub async fn measure_connection(url: &str) -> ConnectionTiming {
+    let mut timing = ConnectionTiming::default();
+
+    let Some((host, port, is_https)) = Url::parse(url).ok().and_then(|u| {
+        let host = u.host_str()?.trim_matches(['[', ']']).to_string();
+        Some((host, u.port_or_known_default()?, u.scheme() == "https"))
+    }) else {
+        timing.error = Some("invalid url".to_string());
+        return timing;
+    };
+
+    let addr = match host.parse::<IpAddr>() {
+        Ok(ip) => SocketAddr::new(ip, port),
+        Err(_) => {
+            let start = Instant::now();
+            let resolved = timeout(PHASE_TIMEOUT, lookup_host((host.as_str(), port))).await;
+            match resolved {
+                Ok(Ok(mut addrs)) => match addrs.next() {
+                    Some(addr) => {
+                        timing.dns_ms = Some(start.elapsed().as_millis() as u64);
+                        addr
+                    }
+                    None => {
+                        timing.error = Some("dns: no addresses".to_string());
+                        return timing;
+                    }
+                },
+                Ok(Err(e)) => {
+                    timing.error = Some(format!("dns: {e}"));
+                    return timing;
+                }
+                Err(_) => {
+                    timing.error = Some("dns: timeout".to_string());
+                    return timing;
+                }
+            }
+        }
+    };
+
+    let start = Instant::now();
+    let stream = match timeout(PHASE_TIMEOUT, TcpStream::connect(addr)).await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => {
+            timing.error = Some(format!("connect: {e}"));
+            return timing;
+        }
+        Err(_) => {
+            timing.error = Some("connect: timeout".to_string());
+            return timing;
+        }
+    };
+    timing.connect_ms = Some(start.elapsed().as_millis() as u64);
+
+    if !is_https {
+        return timing;
+    }
+
+    let (Some(connector), Ok(server_name)) =
+        (TLS_CONNECTOR.as_ref(), ServerName::try_from(host.clone()))
+    else {
+        timing.error = Some("tls: unsupported host".to_string());
+        return timing;
+    };
+
+    let start = Instant::now();
+    match timeout(PHASE_TIMEOUT, connector.connect(server_name, stream)).await {
+        Ok(Ok(_)) => timing.tls_ms = Some(start.elapsed().as_millis() as u64),
+        Ok(Err(e)) => {
+            debug!("TLS handshake with {host} failed: {e}");
+            timing.error = Some(format!("tls: {e}"));
+        }
+        Err(_) => timing.error = Some("tls: timeout".to_string()),
+    }
+
+    timing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_measure_connection_plain_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let timing = measure_connection(&format!("http://127.0.0.1:{port}/piece/x")).await;
+
+        assert!(timing.error.is_none(), "{:?}", timing.error);
+        assert!(timing.dns_ms.is_none(), "IP literal needs no lookup");
+        assert!(timing.connect_ms.is_some());
+        assert!(timing.tls_ms.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_measure_connection_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let timing = measure_connection(&format!("http://127.0.0.1:{port}/")).await;
+
+        assert!(timing.connect_ms.is_none());
+        assert!(timing.error.unwrap().starts_with("connect"));
+    }
+
+    #[tokio::test]
+    async fn test_measure_connection_invalid_url() {
+        let timing = measure_connection("not a url").await;
+
+        assert_eq!(timing.error.as_deref(), Some("invalid url"));
+    }
+}
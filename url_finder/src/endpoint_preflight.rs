@@ -0,0 +1,212 @@
+//! Reachability pre-check of provider endpoints before the piece fanout.
+//!
+//! A dead endpoint otherwise costs two timeouts for every sampled piece. DNS resolution, a
+//! TCP connect and one HEAD to the endpoint are checked first, and any HTTP status counts as
+//! reachable. The HEAD uses the tester's client. When a proxy is configured, DNS and TCP are
+//! skipped: the tester never reaches the endpoint directly, so only the proxied HEAD tells
+//! whether the pieces will be reachable.
+
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use reqwest::{Client, Url};
+use serde::Serialize;
+use tokio::net::{TcpStream, lookup_host};
+use tokio::time::timeout;
+use tracing::debug;
+
+const PREFLIGHT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Stage at which an endpoint was found unreachable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreflightFailure {
+    InvalidUrl,
+    DnsError,
+    DnsNoAddresses,
+    DnsTimeout,
+    ConnectRefused,
+    ConnectTimeout,
+    ConnectFailed,
+    HeadTimeout,
+    HeadFailed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EndpointPreflight {
+    pub endpoint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<PreflightFailure>,
+    /// Error message behind the failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub head_status: Option<u16>,
+}
+
+impl EndpointPreflight {
+    pub fn reachable(&self) -> bool {
+        self.failure.is_none()
+    }
+
+    fn failed(mut self, failure: PreflightFailure, detail: Option<String>) -> Self {
+        self.failure = Some(failure);
+        self.detail = detail;
+        self
+    }
+}
+
+/// Resolves the endpoint's host, connects to it and sends one HEAD request. With `proxied`
+/// set, only the HEAD is sent.
+pub async fn preflight_endpoint(
+    client: &Client,
+    endpoint: &str,
+    proxied: bool,
+) -> EndpointPreflight {
+    let result = EndpointPreflight {
+        endpoint: endpoint.to_string(),
+        failure: None,
+        detail: None,
+        head_status: None,
+    };
+
+    let Some((host, port)) = Url::parse(endpoint).ok().and_then(|u| {
+        let host = u.host_str()?.trim_matches(['[', ']']).to_string();
+        Some((host, u.port_or_known_default()?))
+    }) else {
+        return result.failed(PreflightFailure::InvalidUrl, None);
+    };
+
+    if !proxied && let Err((failure, detail)) = check_direct(&host, port).await {
+        return result.failed(failure, detail);
+    }
+
+    match client
+        .head(endpoint)
+        .timeout(PREFLIGHT_TIMEOUT)
+        .send()
+        .await
+    {
+        Ok(resp) => EndpointPreflight {
+            head_status: Some(resp.status().as_u16()),
+            ..result
+        },
+        Err(e) if e.is_timeout() => result.failed(PreflightFailure::HeadTimeout, None),
+        Err(e) => {
+            debug!("Preflight HEAD to {endpoint} failed: {e:?}");
+            result.failed(PreflightFailure::HeadFailed, Some(e.to_string()))
+        }
+    }
+}
+
+/// Resolves the host and connects to it without going through any proxy
+async fn check_direct(host: &str, port: u16) -> Result<(), (PreflightFailure, Option<String>)> {
+    let addrs: Vec<SocketAddr> = match host.parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => match timeout(PREFLIGHT_TIMEOUT, lookup_host((host, port))).await {
+            Ok(Ok(addrs)) => addrs.collect(),
+            Ok(Err(e)) => return Err((PreflightFailure::DnsError, Some(e.to_string()))),
+            Err(_) => return Err((PreflightFailure::DnsTimeout, None)),
+        },
+    };
+    if addrs.is_empty() {
+        return Err((PreflightFailure::DnsNoAddresses, None));
+    }
+
+    match timeout(PREFLIGHT_TIMEOUT, connect_any(&addrs)).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) if e.kind() == ErrorKind::ConnectionRefused => {
+            Err((PreflightFailure::ConnectRefused, Some(e.to_string())))
+        }
+        Ok(Err(e)) => Err((PreflightFailure::ConnectFailed, Some(e.to_string()))),
+        Err(_) => Err((PreflightFailure::ConnectTimeout, None)),
+    }
+}
+
+/// Tries the resolved addresses in order, returning the last error when none accepts
+async fn connect_any(addrs: &[SocketAddr]) -> std::io::Result<TcpStream> {
+    let mut last_error = None;
+    for addr in addrs {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| ErrorKind::AddrNotAvailable.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn invalid_url_fails_before_dns() {
+        let result = preflight_endpoint(&Client::new(), "not a url", false).await;
+
+        assert_eq!(result.failure, Some(PreflightFailure::InvalidUrl));
+        assert!(!result.reachable());
+    }
+
+    #[tokio::test]
+    async fn closed_port_is_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let result =
+            preflight_endpoint(&Client::new(), &format!("http://127.0.0.1:{port}"), false).await;
+
+        assert_eq!(result.failure, Some(PreflightFailure::ConnectRefused));
+        assert!(result.detail.is_some());
+    }
+
+    #[tokio::test]
+    async fn any_http_status_is_reachable() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+            // The preflight's own TCP connect, then the HEAD request
+            for _ in 0..2 {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 1024];
+                if socket.read(&mut buf).await.unwrap_or(0) > 0 {
+                    let _ = socket
+                        .write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n")
+                        .await;
+                }
+            }
+        });
+
+        let result =
+            preflight_endpoint(&Client::new(), &format!("http://127.0.0.1:{port}"), false).await;
+
+        assert!(result.reachable());
+        assert_eq!(result.head_status, Some(404));
+    }
+
+    #[tokio::test]
+    async fn proxied_preflight_only_sends_head() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+            // A single connection: the HEAD request, with no direct TCP connect before it
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            if socket.read(&mut buf).await.unwrap_or(0) > 0 {
+                let _ = socket
+                    .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                    .await;
+            }
+        });
+
+        let result =
+            preflight_endpoint(&Client::new(), &format!("http://127.0.0.1:{port}"), true).await;
+
+        assert!(result.reachable());
+        assert_eq!(result.head_status, Some(200));
+    }
+}
@@ -4,10 +4,11 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::config::Config;
 use rand::Rng;
-use reqwest::{Client, Proxy};
+use reqwest::{Client, ClientBuilder, Proxy};
 use tracing::info;
 
 const RETRI_TIMEOUT_SEC: u64 = 15;
+const STREAMING_READ_TIMEOUT_SEC: u64 = 60;
 static ATOMIC_PROXY_PORT: AtomicU32 = AtomicU32::new(8001);
 static ATOMIC_PROXY_LAST_CHANGE: AtomicU64 = AtomicU64::new(0);
 static PROXY_LOG_ONCE: Once = Once::new();
@@ -47,8 +48,39 @@ fn get_sticky_port_atomic(config: &Config) -> u32 {
 }
 
 pub fn build_client(config: &Config) -> Result<Client, reqwest::Error> {
-    let mut builder = Client::builder().timeout(std::time::Duration::from_secs(RETRI_TIMEOUT_SEC));
+    // rustls and hickory so TLS and DNS failures surface as typed errors for classification
+    let builder = Client::builder()
+        .use_rustls_tls()
+        .hickory_dns(true)
+        .timeout(std::time::Duration::from_secs(RETRI_TIMEOUT_SEC));
 
+    with_proxy(builder, config)?.build()
+}
+
+/// Client for full piece downloads: no total timeout, only connect and per-read timeouts
+pub fn build_streaming_client(config: &Config) -> Result<Client, reqwest::Error> {
+    let builder = Client::builder()
+        .use_rustls_tls()
+        .hickory_dns(true)
+        .connect_timeout(Duration::from_secs(RETRI_TIMEOUT_SEC))
+        .read_timeout(Duration::from_secs(STREAMING_READ_TIMEOUT_SEC));
+
+    with_proxy(builder, config)?.build()
+}
+
+/// Whether clients built from `config` send their requests through the proxy
+pub fn proxy_configured(config: &Config) -> bool {
+    config.proxy_url.is_some()
+        && config.proxy_user.is_some()
+        && config.proxy_password.is_some()
+        && config.proxy_ip_count.is_some()
+        && config.proxy_default_port.is_some()
+}
+
+fn with_proxy(
+    mut builder: ClientBuilder,
+    config: &Config,
+) -> Result<ClientBuilder, reqwest::Error> {
     if let (
         Some(proxy_url),
         Some(proxy_user),
@@ -78,5 +110,5 @@ pub fn build_client(config: &Config) -> Result<Client, reqwest::Error> {
             .pool_idle_timeout(Duration::from_secs(60 * 60 * 24));
     }
 
-    builder.build()
+    Ok(builder)
 }
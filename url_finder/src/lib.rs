@@ -8,17 +8,25 @@ pub mod auth;
 pub mod background;
 pub mod bms_client;
 pub mod car_header;
+pub mod car_index;
+pub mod chain;
 mod cid_contact;
 pub mod circuit_breaker;
+pub mod commp;
 pub mod config;
+mod connection_timing;
+mod endpoint_preflight;
 mod http_client;
-mod lotus_rpc;
+pub mod libp2p_probe;
 mod multiaddr_parser;
 mod pix_filspark;
+pub mod protocol_probe;
 pub mod provider_endpoints;
 pub mod repository;
+pub mod request_error;
 pub mod routes;
 pub mod services;
+pub mod tls_inspection;
 pub mod types;
 pub mod url_tester;
 pub mod utils;
@@ -30,7 +38,9 @@ pub struct AppState {
     pub deal_sli_repo: Arc<repository::DealSliRepository>,
     pub storage_provider_repo: Arc<repository::StorageProviderRepository>,
     pub url_repo: Arc<repository::UrlResultRepository>,
+    pub provider_test_repo: Arc<repository::ProviderTestJobRepository>,
     pub bms_repo: Arc<repository::BmsBandwidthResultRepository>,
+    pub scheduler_run_repo: Arc<repository::SchedulerRunRepository>,
     pub deal_sli_service: Arc<services::deal_sli_service::DealSliService>,
     pub provider_service: Arc<services::provider_service::ProviderService>,
     pub config: Arc<config::Config>,
@@ -0,0 +1,278 @@
+//! HTTP-over-libp2p retrieval probe for providers that only advertise libp2p transports.
+//!
+//! The provider's peer is dialed at its advertised multiaddrs, a stream is negotiated with
+//! protocol `/http/1.1`, and the same `/piece/{cid}` range request the HTTP tester makes is
+//! sent as plain HTTP/1.1 on it. Results share the `UrlTestResult` shape with
+//! `Transport::Libp2p`, so they feed the same consistency analysis.
+
+use std::time::{Duration, Instant};
+
+use color_eyre::Result;
+use futures::StreamExt;
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Bytes;
+use hyper::header::{CONTENT_LENGTH, HOST, RANGE};
+use hyper_util::rt::TokioIo;
+use libp2p::{Multiaddr, PeerId, StreamProtocol, Swarm, SwarmBuilder, noise, tcp, yamux};
+use libp2p_stream::{Behaviour, Control, OpenStreamError};
+use tokio::time::timeout;
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+use tracing::debug;
+
+use crate::config::{DOUBLE_TAP_DELAY_MS, RANGE_REQUEST_BYTES};
+use crate::types::{Transport, UrlTestError, UrlTestResult, UrlTiming};
+use crate::url_tester::{RangeResponse, content_range_total, double_tap_result};
+
+const HTTP_PROTOCOL: StreamProtocol = StreamProtocol::new("/http/1.1");
+/// Covers dialing, security and muxer negotiation, and the stream protocol handshake
+const OPEN_STREAM_TIMEOUT: Duration = Duration::from_secs(20);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const IDLE_CONNECTION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Identifies a piece served over libp2p, in place of an http(s) URL
+pub fn libp2p_piece_url(peer_id: &str, piece_cid: &str) -> String {
+    format!("libp2p://{peer_id}/piece/{piece_cid}")
+}
+
+/// Double-taps `/piece/{cid}` for each piece over HTTP on libp2p streams to `peer_id`.
+/// One connection is shared by all requests; every tap opens its own stream.
+pub async fn test_pieces_double_tap(
+    peer_id: &str,
+    addrs: &[String],
+    piece_cids: &[String],
+) -> Vec<UrlTestResult> {
+    let failed = |error: UrlTestError| -> Vec<UrlTestResult> {
+        piece_cids
+            .iter()
+            .map(|cid| {
+                double_tap_result(
+                    &libp2p_piece_url(peer_id, cid),
+                    Transport::Libp2p,
+                    Err(error.clone()),
+                    Err(error.clone()),
+                    UrlTiming::default(),
+                )
+            })
+            .collect()
+    };
+
+    let Ok(peer) = peer_id.parse::<PeerId>() else {
+        return failed(UrlTestError::Other(format!("invalid peer id {peer_id}")));
+    };
+    let addrs: Vec<Multiaddr> = addrs.iter().filter_map(|a| a.parse().ok()).collect();
+    if addrs.is_empty() {
+        return failed(UrlTestError::Other("no libp2p addresses".to_string()));
+    }
+
+    let mut swarm = match build_swarm() {
+        Ok(swarm) => swarm,
+        Err(e) => return failed(UrlTestError::Other(format!("libp2p swarm: {e}"))),
+    };
+    for addr in addrs {
+        swarm.add_peer_address(peer, addr);
+    }
+    let mut control = swarm.behaviour().new_control();
+    let driver = tokio::spawn(async move {
+        loop {
+            swarm.select_next_some().await;
+        }
+    });
+
+    let mut results = Vec::with_capacity(piece_cids.len());
+    for piece_cid in piece_cids {
+        let r1 = range_request(&mut control, peer, peer_id, piece_cid).await;
+        tokio::time::sleep(Duration::from_millis(DOUBLE_TAP_DELAY_MS)).await;
+        let r2 = range_request(&mut control, peer, peer_id, piece_cid).await;
+
+        let timing = UrlTiming {
+            tap_ttfb_ms: [&r1, &r2]
+                .iter()
+                .map(|r| r.as_ref().ok().map(|r| r.response_time_ms))
+                .collect(),
+            ..Default::default()
+        };
+        results.push(double_tap_result(
+            &libp2p_piece_url(peer_id, piece_cid),
+            Transport::Libp2p,
+            r1,
+            r2,
+            timing,
+        ));
+    }
+
+    driver.abort();
+    debug!(
+        "libp2p probe of {peer_id}: {}/{} pieces retrievable",
+        results.iter().filter(|r| r.success).count(),
+        results.len()
+    );
+    results
+}
+
+fn build_swarm() -> Result<Swarm<Behaviour>> {
+    Ok(SwarmBuilder::with_new_identity()
+        .with_tokio()
+        .with_tcp(
+            tcp::Config::default(),
+            noise::Config::new,
+            yamux::Config::default,
+        )?
+        .with_quic()
+        .with_dns()?
+        .with_behaviour(|_| Behaviour::new())?
+        .with_swarm_config(|c| c.with_idle_connection_timeout(IDLE_CONNECTION_TIMEOUT))
+        .build())
+}
+
+/// Range request (bytes=0-4095) on a fresh `/http/1.1` stream, mirroring the HTTP tester
+async fn range_request(
+    control: &mut Control,
+    peer: PeerId,
+    peer_id: &str,
+    piece_cid: &str,
+) -> Result<RangeResponse, UrlTestError> {
+    let start = Instant::now();
+
+    let stream = match timeout(
+        OPEN_STREAM_TIMEOUT,
+        control.open_stream(peer, HTTP_PROTOCOL),
+    )
+    .await
+    {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(OpenStreamError::UnsupportedProtocol(_))) => {
+            return Err(UrlTestError::StreamProtocolUnsupported);
+        }
+        Ok(Err(e)) => {
+            debug!("Opening libp2p stream to {peer_id} failed: {e}");
+            return Err(UrlTestError::ConnectionFailed);
+        }
+        Err(_) => return Err(UrlTestError::ConnectTimeout),
+    };
+
+    match timeout(
+        REQUEST_TIMEOUT,
+        send_range_request(stream, peer_id, piece_cid, start),
+    )
+    .await
+    {
+        Ok(result) => result,
+        Err(_) => Err(UrlTestError::Timeout),
+    }
+}
+
+async fn send_range_request(
+    stream: libp2p::Stream,
+    peer_id: &str,
+    piece_cid: &str,
+    start: Instant,
+) -> Result<RangeResponse, UrlTestError> {
+    let io = TokioIo::new(stream.compat());
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(io)
+        .await
+        .map_err(|e| classify_hyper_error(&e, false))?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            debug!("HTTP over libp2p connection closed: {e}");
+        }
+    });
+
+    let request = hyper::Request::get(format!("/piece/{piece_cid}"))
+        .header(HOST, peer_id)
+        .header(RANGE, format!("bytes=0-{}", RANGE_REQUEST_BYTES - 1))
+        .body(Empty::<Bytes>::new())
+        .map_err(|e| UrlTestError::Other(e.to_string()))?;
+    let resp = sender
+        .send_request(request)
+        .await
+        .map_err(|e| classify_hyper_error(&e, false))?;
+
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(UrlTestError::HttpError(status.as_u16()));
+    }
+
+    let content_length = content_range_total(resp.headers()).or_else(|| {
+        resp.headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+    });
+    let response_time_ms = start.elapsed().as_millis() as u64;
+
+    // Capture body sample for CAR header parsing, stopping once the range is read
+    let mut body = resp.into_body();
+    let mut sample = Vec::with_capacity(RANGE_REQUEST_BYTES as usize);
+    while (sample.len() as u64) < RANGE_REQUEST_BYTES {
+        match body.frame().await {
+            Some(Ok(frame)) => {
+                if let Some(data) = frame.data_ref() {
+                    sample.extend_from_slice(data);
+                }
+            }
+            Some(Err(e)) => return Err(classify_hyper_error(&e, true)),
+            None => break,
+        }
+    }
+    sample.truncate(RANGE_REQUEST_BYTES as usize);
+
+    Ok(RangeResponse {
+        content_length,
+        response_time_ms,
+        body_sample: (!sample.is_empty()).then_some(sample),
+    })
+}
+
+/// Closed mid-body is truncation, closed before any response is a reset
+fn classify_hyper_error(e: &hyper::Error, in_body: bool) -> UrlTestError {
+    if e.is_incomplete_message() {
+        if in_body {
+            UrlTestError::BodyTruncated
+        } else {
+            UrlTestError::ConnectionReset
+        }
+    } else if e.is_parse() || e.is_parse_status() {
+        UrlTestError::HttpProtocolError
+    } else {
+        UrlTestError::Other(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PEER_ID: &str = "12D3KooWRf7tJR2NfJYE3PQJKXGt1EFqmFBBfQCgPRBLwwR9XL15";
+
+    #[test]
+    fn piece_url_names_peer_and_piece() {
+        assert_eq!(
+            libp2p_piece_url(PEER_ID, "baga6ea4seaq"),
+            format!("libp2p://{PEER_ID}/piece/baga6ea4seaq")
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_peer_id_fails_every_piece() {
+        let pieces = vec!["baga1".to_string(), "baga2".to_string()];
+        let results =
+            test_pieces_double_tap("not-a-peer", &["/ip4/127.0.0.1/tcp/1".to_string()], &pieces)
+                .await;
+
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| !r.success));
+        assert!(results.iter().all(|r| r.transport == Transport::Libp2p));
+        assert!(matches!(results[0].error, Some(UrlTestError::Other(_))));
+    }
+
+    #[tokio::test]
+    async fn missing_addresses_fail_without_dialing() {
+        let results = test_pieces_double_tap(PEER_ID, &[], &["baga1".to_string()]).await;
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].error,
+            Some(UrlTestError::Other("no libp2p addresses".to_string()))
+        );
+    }
+}
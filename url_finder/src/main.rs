@@ -4,6 +4,7 @@ use color_eyre::Result;
 use tokio::{
     net::TcpListener,
     signal::unix::{SignalKind, signal},
+    sync::{Notify, Semaphore},
     task::JoinHandle,
 };
 use tokio_util::sync::CancellationToken;
@@ -11,7 +12,10 @@ use tower_http::cors::{Any, CorsLayer};
 use tracing::{error, info, warn};
 use tracing_subscriber::EnvFilter;
 
-use url_finder::{AppState, background, config::Config, repository::*, routes::create_routes};
+use url_finder::{
+    AppState, background, config::Config, repository::*, routes::create_routes,
+    services::url_discovery_service::DiscoveryDeps,
+};
 
 const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
 
@@ -31,6 +35,11 @@ async fn main() -> Result<()> {
         )
         .init();
 
+    info!(
+        "Running as '{}' role (instance {})",
+        config.role, config.instance_id
+    );
+
     let pool = sqlx::PgPool::connect(&config.db_url).await?;
     let dmob_pool = sqlx::PgPool::connect(&config.dmob_db_url).await?;
 
@@ -45,23 +54,29 @@ async fn main() -> Result<()> {
 
     let sp_repo = Arc::new(StorageProviderRepository::new(pool.clone()));
     let deal_repo = Arc::new(DealRepository::new(dmob_pool.clone()));
+    let deal_label_repo = Arc::new(DealLabelRepository::new(pool.clone()));
     let deal_sli_repo = Arc::new(DealSliRepository::new(pool.clone()));
+    let pix_sample_repo = Arc::new(PixSampleRepository::new(pool.clone()));
     let url_repo = Arc::new(UrlResultRepository::new(pool.clone()));
+    let endpoint_repo = Arc::new(ProviderEndpointRepository::new(pool.clone()));
+    let identity_repo = Arc::new(ProviderIdentityRepository::new(pool.clone()));
+    let provider_test_repo = Arc::new(ProviderTestJobRepository::new(pool.clone()));
+    let lease_repo = Arc::new(SchedulerLeaseRepository::new(pool.clone()));
+    let scheduler_run_repo = Arc::new(SchedulerRunRepository::new(pool.clone()));
     let bms_result_repo = Arc::new(BmsBandwidthResultRepository::new(pool.clone()));
-    let bms_client = Arc::new(url_finder::bms_client::BmsClient::new(
-        config.bms_url.clone(),
-    ));
-
+    let commp_repo = Arc::new(CommpVerificationRepository::new(pool.clone()));
     let provider_service = Arc::new(
         url_finder::services::provider_service::ProviderService::new(
             url_repo.clone(),
             bms_result_repo.clone(),
             sp_repo.clone(),
+            endpoint_repo.clone(),
         ),
     );
     let deal_sli_service = Arc::new(url_finder::services::deal_sli_service::DealSliService::new(
         deal_sli_repo.clone(),
         sp_repo.clone(),
+        commp_repo.clone(),
         config.clone(),
     ));
 
@@ -70,138 +85,287 @@ async fn main() -> Result<()> {
         deal_sli_repo: deal_sli_repo.clone(),
         storage_provider_repo: sp_repo.clone(),
         url_repo: url_repo.clone(),
+        provider_test_repo: provider_test_repo.clone(),
         bms_repo: bms_result_repo.clone(),
+        scheduler_run_repo: scheduler_run_repo.clone(),
         deal_sli_service,
         provider_service,
         config: config.clone(),
     });
 
-    // Start the provider discovery in the background
-    let provider_discovery_handle: JoinHandle<()> = tokio::spawn({
-        let sp_repo = sp_repo.clone();
-        let deal_repo = deal_repo.clone();
-        let shutdown = shutdown_token.clone();
-        async move {
-            background::run_provider_discovery(sp_repo, deal_repo, shutdown).await;
-        }
+    let discovery_deps = DiscoveryDeps {
+        sp_repo: sp_repo.clone(),
+        url_repo: url_repo.clone(),
+        deal_repo: deal_repo.clone(),
+        deal_label_repo: deal_label_repo.clone(),
+        pix_sample_repo: pix_sample_repo.clone(),
+        endpoint_repo: endpoint_repo.clone(),
+        deal_sli_repo: deal_sli_repo.clone(),
+        lease_repo: lease_repo.clone(),
+        commp_repo,
+        commp_permits: Arc::new(Semaphore::new(config.commp_verification_max_concurrent)),
+    };
+
+    let schedulers = config.schedulers;
+    let mut background_handles: Vec<(&str, JoinHandle<()>)> = vec![];
+
+    // Keep this instance's scheduler leases alive
+    let lease_heartbeat_handle: Option<JoinHandle<()>> = schedulers.any_leased().then(|| {
+        tokio::spawn({
+            let config = config.clone();
+            let lease_repo = lease_repo.clone();
+            let shutdown = shutdown_token.clone();
+            async move {
+                background::run_lease_heartbeat(config, lease_repo, shutdown).await;
+            }
+        })
     });
 
+    // Bound the scheduler run ledger wherever schedulers write to it
+    if config.role.runs_workers() {
+        background_handles.push((
+            "scheduler_run_pruner",
+            tokio::spawn({
+                let config = config.clone();
+                let run_repo = scheduler_run_repo.clone();
+                let shutdown = shutdown_token.clone();
+                async move {
+                    background::run_scheduler_run_pruner(config, run_repo, shutdown).await;
+                }
+            }),
+        ));
+    }
+
+    // Start the provider discovery in the background
+    if schedulers.provider_discovery {
+        background_handles.push((
+            "provider_discovery",
+            tokio::spawn({
+                let config = config.clone();
+                let sp_repo = sp_repo.clone();
+                let deal_repo = deal_repo.clone();
+                let lease_repo = lease_repo.clone();
+                let run_repo = scheduler_run_repo.clone();
+                let shutdown = shutdown_token.clone();
+                async move {
+                    background::run_provider_discovery(
+                        config, sp_repo, deal_repo, lease_repo, run_repo, shutdown,
+                    )
+                    .await;
+                }
+            }),
+        ));
+    }
+
+    // Wakes the endpoint scheduler when a provider's on-chain identity changed
+    let endpoint_refresh = Arc::new(Notify::new());
+
+    // Start the peer ID scheduler in the background
+    if schedulers.peer_id {
+        background_handles.push((
+            "peer_id_scheduler",
+            tokio::spawn({
+                let config = config.clone();
+                let identity_repo = identity_repo.clone();
+                let lease_repo = lease_repo.clone();
+                let run_repo = scheduler_run_repo.clone();
+                let endpoint_refresh = endpoint_refresh.clone();
+                let shutdown = shutdown_token.clone();
+                async move {
+                    background::run_peer_id_scheduler(
+                        config,
+                        identity_repo,
+                        lease_repo,
+                        run_repo,
+                        endpoint_refresh,
+                        shutdown,
+                    )
+                    .await;
+                }
+            }),
+        ));
+    }
+
     // Start the endpoint scheduler in the background
-    let endpoint_scheduler_handle: JoinHandle<()> = tokio::spawn({
-        let config = config.clone();
-        let sp_repo = sp_repo.clone();
-        let url_repo = url_repo.clone();
-        let shutdown = shutdown_token.clone();
-        async move {
-            background::run_endpoint_scheduler(config, sp_repo, url_repo, shutdown).await;
-        }
-    });
+    if schedulers.endpoint {
+        background_handles.push((
+            "endpoint_scheduler",
+            tokio::spawn({
+                let config = config.clone();
+                let deps = discovery_deps.clone();
+                let run_repo = scheduler_run_repo.clone();
+                let endpoint_refresh = endpoint_refresh.clone();
+                let shutdown = shutdown_token.clone();
+                async move {
+                    background::run_endpoint_scheduler(
+                        config,
+                        deps,
+                        run_repo,
+                        endpoint_refresh,
+                        shutdown,
+                    )
+                    .await;
+                }
+            }),
+        ));
+    }
 
     // Start the URL discovery scheduler in the background
-    let url_discovery_handle: JoinHandle<()> = tokio::spawn({
-        let sp_repo = sp_repo.clone();
-        let url_repo = url_repo.clone();
-        let deal_repo = deal_repo.clone();
-        let config = config.clone();
-        let shutdown = shutdown_token.clone();
-        async move {
-            background::run_url_discovery_scheduler(config, sp_repo, url_repo, deal_repo, shutdown)
-                .await;
-        }
-    });
+    if schedulers.url_discovery {
+        background_handles.push((
+            "url_discovery",
+            tokio::spawn({
+                let deps = discovery_deps.clone();
+                let run_repo = scheduler_run_repo.clone();
+                let config = config.clone();
+                let shutdown = shutdown_token.clone();
+                async move {
+                    background::run_url_discovery_scheduler(config, deps, run_repo, shutdown).await;
+                }
+            }),
+        ));
+    }
 
-    // Start the BMS scheduler in the background
-    let bms_circuit_breaker = Arc::new(background::create_bms_circuit_breaker());
-    let bms_scheduler_handle: JoinHandle<()> = tokio::spawn({
-        let config = config.clone();
-        let sp_repo = sp_repo.clone();
-        let bms_result_repo = bms_result_repo.clone();
-        let bms_client = bms_client.clone();
-        let bms_circuit_breaker = bms_circuit_breaker.clone();
-        let shutdown = shutdown_token.clone();
-        async move {
-            background::run_bms_scheduler(
-                config,
-                bms_client,
-                bms_circuit_breaker,
-                sp_repo,
-                bms_result_repo,
-                shutdown,
-            )
-            .await;
-        }
-    });
+    // Start the priority lane for API-requested provider tests
+    if schedulers.provider_test {
+        background_handles.push((
+            "provider_test",
+            tokio::spawn({
+                let deps = discovery_deps.clone();
+                let provider_test_repo = provider_test_repo.clone();
+                let run_repo = scheduler_run_repo.clone();
+                let config = config.clone();
+                let shutdown = shutdown_token.clone();
+                async move {
+                    background::run_provider_test_scheduler(
+                        config,
+                        deps,
+                        provider_test_repo,
+                        run_repo,
+                        shutdown,
+                    )
+                    .await;
+                }
+            }),
+        ));
+    }
+
+    // The BMS client is only created when a scheduler talks to BMS
+    if schedulers.bms || schedulers.deal_sli {
+        let bms_client = Arc::new(url_finder::bms_client::BmsClient::new(
+            config.bms_url.clone(),
+        ));
+        let bms_circuit_breaker = Arc::new(background::create_bms_circuit_breaker());
 
-    // Start the Deal SLI scheduler in the background
-    let deal_sli_scheduler_handle: JoinHandle<()> = tokio::spawn({
-        let config = config.clone();
-        let deal_sli_service = app_state.deal_sli_service.clone();
-        let deal_sli_repo = deal_sli_repo.clone();
-        let bms_client = bms_client.clone();
-        let bms_circuit_breaker = bms_circuit_breaker.clone();
-        let shutdown = shutdown_token.clone();
-        async move {
-            background::run_deal_sli_scheduler(
-                config,
-                deal_sli_service,
-                deal_sli_repo,
-                bms_client,
-                bms_circuit_breaker,
-                shutdown,
-            )
-            .await;
+        // Start the BMS scheduler in the background
+        if schedulers.bms {
+            background_handles.push((
+                "bms_scheduler",
+                tokio::spawn({
+                    let config = config.clone();
+                    let deps = background::BmsDeps {
+                        bms_client: bms_client.clone(),
+                        circuit_breaker: bms_circuit_breaker.clone(),
+                        sp_repo: sp_repo.clone(),
+                        result_repo: bms_result_repo.clone(),
+                        lease_repo: lease_repo.clone(),
+                    };
+                    let run_repo = scheduler_run_repo.clone();
+                    let shutdown = shutdown_token.clone();
+                    async move {
+                        background::run_bms_scheduler(config, deps, run_repo, shutdown).await;
+                    }
+                }),
+            ));
         }
-    });
 
-    // Start the Deal SLI BMS result poller in the background
-    let deal_sli_bms_result_poller_handle: JoinHandle<()> = tokio::spawn({
-        let deal_sli_repo = deal_sli_repo.clone();
-        let bms_client = bms_client.clone();
-        let bms_circuit_breaker = bms_circuit_breaker.clone();
-        let shutdown = shutdown_token.clone();
-        async move {
-            background::run_deal_sli_bms_result_poller(
-                deal_sli_repo,
-                bms_client,
-                bms_circuit_breaker,
-                shutdown,
-            )
-            .await;
+        if schedulers.deal_sli {
+            // Start the Deal SLI scheduler in the background
+            background_handles.push((
+                "deal_sli_scheduler",
+                tokio::spawn({
+                    let config = config.clone();
+                    let deal_sli_service = app_state.deal_sli_service.clone();
+                    let deal_sli_repo = deal_sli_repo.clone();
+                    let bms_client = bms_client.clone();
+                    let bms_circuit_breaker = bms_circuit_breaker.clone();
+                    let run_repo = scheduler_run_repo.clone();
+                    let shutdown = shutdown_token.clone();
+                    async move {
+                        background::run_deal_sli_scheduler(
+                            config,
+                            deal_sli_service,
+                            deal_sli_repo,
+                            bms_client,
+                            bms_circuit_breaker,
+                            run_repo,
+                            shutdown,
+                        )
+                        .await;
+                    }
+                }),
+            ));
+
+            // Start the Deal SLI BMS result poller in the background
+            background_handles.push((
+                "deal_sli_bms_result_poller",
+                tokio::spawn({
+                    let deal_sli_repo = deal_sli_repo.clone();
+                    let bms_client = bms_client.clone();
+                    let bms_circuit_breaker = bms_circuit_breaker.clone();
+                    let shutdown = shutdown_token.clone();
+                    async move {
+                        background::run_deal_sli_bms_result_poller(
+                            deal_sli_repo,
+                            bms_client,
+                            bms_circuit_breaker,
+                            shutdown,
+                        )
+                        .await;
+                    }
+                }),
+            ));
         }
-    });
+    }
+
+    info!(
+        "Started {} background tasks: {:?}",
+        background_handles.len(),
+        background_handles
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+    );
 
-    let allowed_origins = ["https://sp-tool.allocator.tech".parse().unwrap()];
-    let cors = CorsLayer::new()
-        .allow_origin(allowed_origins)
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+    if config.role.serves_api() {
+        let allowed_origins = ["https://sp-tool.allocator.tech".parse().unwrap()];
+        let cors = CorsLayer::new()
+            .allow_origin(allowed_origins)
+            .allow_origin(Any)
+            .allow_methods(Any)
+            .allow_headers(Any);
 
-    let app = create_routes().layer(cors).with_state(app_state.clone());
+        let app = create_routes().layer(cors).with_state(app_state.clone());
 
-    let server_addr = SocketAddr::from(([0, 0, 0, 0], 3010));
-    let listener = TcpListener::bind(&server_addr).await?;
+        let server_addr = SocketAddr::from(([0, 0, 0, 0], 3010));
+        let listener = TcpListener::bind(&server_addr).await?;
 
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .with_graceful_shutdown(shutdown_signal(shutdown_token.clone()))
-    .await?;
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown_signal(shutdown_token.clone()))
+        .await?;
+    } else {
+        // Worker-only instances don't bind the API port and just wait for a signal
+        shutdown_signal(shutdown_token.clone()).await;
+    }
 
     // Await background task completion with timeout
     info!("Waiting for background tasks to complete...");
-    let background_handles = vec![
-        ("provider_discovery", provider_discovery_handle),
-        ("endpoint_scheduler", endpoint_scheduler_handle),
-        ("url_discovery", url_discovery_handle),
-        ("bms_scheduler", bms_scheduler_handle),
-        ("deal_sli_scheduler", deal_sli_scheduler_handle),
-        (
-            "deal_sli_bms_result_poller",
-            deal_sli_bms_result_poller_handle,
-        ),
-    ];
+    if let Some(handle) = lease_heartbeat_handle {
+        background_handles.push(("lease_heartbeat", handle));
+    }
 
     for (name, handle) in background_handles {
         match tokio::time::timeout(SHUTDOWN_TIMEOUT, handle).await {
@@ -211,6 +375,14 @@ async fn main() -> Result<()> {
         }
     }
 
+    // Hand unfinished work to other instances without waiting for the leases to expire
+    if schedulers.any_leased() {
+        match lease_repo.release_all(&config.instance_id).await {
+            Ok(released) => info!("Released {released} scheduler leases"),
+            Err(e) => warn!("Failed to release scheduler leases: {:?}", e),
+        }
+    }
+
     info!("UrlFinder shut down gracefully");
 
     Ok(())
@@ -1,12 +1,24 @@
+use std::collections::HashSet;
+
+use ciborium::Value;
 use color_eyre::{Result, eyre::eyre};
+use hickory_resolver::TokioResolver;
 use multiaddr::{Multiaddr, Protocol};
 use tracing::{debug, info, warn};
+use urlencoding::decode;
+
+/// Nested /dnsaddr lookups followed before giving up, as in go-multiaddr-dns
+const MAX_DNSADDR_DEPTH: usize = 4;
+const DNSADDR_PREFIX: &str = "dnsaddr=";
 
 pub struct UrlParts {
     protocol: Option<String>,
     host: Option<String>,
     port: Option<String>,
     is_tcp: bool,
+    tls: bool,
+    sni: Option<String>,
+    path: Option<String>,
 }
 impl UrlParts {
     fn new() -> Self {
@@ -15,14 +27,28 @@ impl UrlParts {
             host: None,
             port: None,
             is_tcp: false,
+            tls: false,
+            sni: None,
+            path: None,
         }
     }
     fn to_url(&self) -> Result<String> {
+        let protocol = self.protocol.clone().ok_or(eyre!("Missing protocol"))?;
+        // The SNI name is what the certificate is issued for, so prefer it over a bare IP
+        let host = self
+            .sni
+            .clone()
+            .or_else(|| self.host.clone())
+            .ok_or(eyre!("Missing host"))?;
+        let port = self
+            .port
+            .clone()
+            .or_else(|| default_port(&protocol).map(str::to_string))
+            .ok_or(eyre!("Missing port"))?;
+
         Ok(format!(
-            "{}://{}:{}",
-            self.protocol.clone().ok_or(eyre!("Missing protocol"))?,
-            self.host.clone().ok_or(eyre!("Missing host"))?,
-            self.port.clone().ok_or(eyre!("Missing port"))?
+            "{protocol}://{host}:{port}{}",
+            self.path.as_deref().unwrap_or_default()
         ))
     }
 }
@@ -30,22 +56,218 @@ impl std::fmt::Display for UrlParts {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "protocol: {:?}, host: {:?}, port: {:?}, is_tcp: {}",
-            self.protocol, self.host, self.port, self.is_tcp
+            "protocol: {:?}, host: {:?}, port: {:?}, is_tcp: {}, tls: {}, sni: {:?}, path: {:?}",
+            self.protocol, self.host, self.port, self.is_tcp, self.tls, self.sni, self.path
         )
     }
 }
 
-pub fn parse(addrs: Vec<String>) -> Vec<String> {
+fn default_port(protocol: &str) -> Option<&'static str> {
+    match protocol {
+        "http" => Some("80"),
+        "https" => Some("443"),
+        _ => None,
+    }
+}
+
+/// Parses multiaddrs to http(s) endpoints, resolving /dnsaddr entries through DNS TXT records
+pub async fn parse(addrs: Vec<String>) -> Vec<String> {
+    parse_resolved(expand_dnsaddrs(addrs).await)
+}
+
+/// Like `parse`, but keeps only addresses with an explicit /http or /https component.
+/// Multiaddrs set on chain are mostly libp2p listen addresses, so a bare /tcp port there
+/// must not be taken for an HTTP server.
+pub async fn parse_explicit_http(addrs: Vec<String>) -> Vec<String> {
+    expand_dnsaddrs(addrs)
+        .await
+        .into_iter()
+        .filter(|addr| has_http_transport(addr))
+        .filter_map(parse_addr)
+        .collect()
+}
+
+async fn expand_dnsaddrs(addrs: Vec<String>) -> Vec<String> {
+    let mut expanded = vec![];
+    for addr in addrs {
+        if addr.contains("/dnsaddr/") {
+            expanded.extend(resolve_dnsaddr(&addr).await);
+        } else {
+            expanded.push(addr);
+        }
+    }
+    expanded
+}
+
+fn has_http_transport(addr: &str) -> bool {
+    let (base, _) = split_http_path(addr);
+    base.parse::<Multiaddr>().is_ok_and(|multiaddr| {
+        multiaddr
+            .iter()
+            .any(|p| matches!(p, Protocol::Http | Protocol::Https))
+    })
+}
+
+/// Decodes a binary multiaddr, as stored on chain, to its string form
+pub fn decode_binary(bytes: &[u8]) -> Option<String> {
+    Multiaddr::try_from(bytes.to_vec())
+        .ok()
+        .map(|multiaddr| multiaddr.to_string())
+}
+
+/// Decodes the opaque `multiaddrs` bytes of the Curio contract's PeerData. Curio writes a
+/// CBOR array of binary multiaddrs; a single binary multiaddr and UTF-8 text (separated by
+/// whitespace or commas) are accepted too, as the contract does not enforce an encoding.
+pub fn decode_contract_bytes(bytes: &[u8]) -> Vec<String> {
+    if bytes.is_empty() {
+        return vec![];
+    }
+
+    if let Ok(Value::Array(items)) = ciborium::from_reader::<Value, _>(bytes) {
+        return items
+            .iter()
+            .filter_map(|item| match item {
+                Value::Bytes(b) => decode_binary(b),
+                Value::Text(t) => t.parse::<Multiaddr>().ok().map(|m| m.to_string()),
+                _ => None,
+            })
+            .collect();
+    }
+
+    if let Some(addr) = decode_binary(bytes) {
+        return vec![addr];
+    }
+
+    std::str::from_utf8(bytes)
+        .map(|text| {
+            text.split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|addr| addr.starts_with('/'))
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parses multiaddrs that need no DNS resolution, dropping /dnsaddr entries
+pub fn parse_resolved(addrs: Vec<String>) -> Vec<String> {
     addrs.into_iter().filter_map(parse_addr).collect()
 }
 
+/// Multiaddrs the provider can only be reached at over libp2p: every address that parses and
+/// does not end in an HTTP transport. /dnsaddr is left for the libp2p DNS transport to resolve.
+pub fn libp2p_addrs(addrs: &[String]) -> Vec<String> {
+    addrs
+        .iter()
+        .filter(|addr| {
+            addr.parse::<Multiaddr>().is_ok_and(|multiaddr| {
+                !multiaddr
+                    .iter()
+                    .any(|p| matches!(p, Protocol::Http | Protocol::Https))
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+/// Expands `/dnsaddr/{host}` using the `dnsaddr=` TXT records of `_dnsaddr.{host}`.
+/// When the address ends in /p2p/{id}, only records for that peer are kept.
+async fn resolve_dnsaddr(addr: &str) -> Vec<String> {
+    let resolver = match TokioResolver::builder_tokio() {
+        Ok(builder) => builder.build(),
+        Err(e) => {
+            warn!("Failed to build DNS resolver for {addr}: {e}");
+            return vec![];
+        }
+    };
+
+    let mut pending = vec![(addr.to_string(), 0)];
+    let mut seen = HashSet::new();
+    let mut resolved = vec![];
+
+    while let Some((addr, depth)) = pending.pop() {
+        if !seen.insert(addr.clone()) {
+            continue;
+        }
+        let Some((host, peer_id)) = dnsaddr_target(&addr) else {
+            resolved.push(addr);
+            continue;
+        };
+        if depth >= MAX_DNSADDR_DEPTH {
+            warn!("Giving up on {addr}: dnsaddr nested deeper than {MAX_DNSADDR_DEPTH}");
+            continue;
+        }
+
+        let records = match resolver.txt_lookup(format!("_dnsaddr.{host}")).await {
+            Ok(lookup) => lookup.iter().map(|txt| txt.to_string()).collect(),
+            Err(e) => {
+                info!("TXT lookup for _dnsaddr.{host} failed: {e}");
+                vec![]
+            }
+        };
+        for record in dnsaddr_records(&records, peer_id.as_deref()) {
+            pending.push((record, depth + 1));
+        }
+    }
+
+    debug!("Resolved {addr} to {resolved:?}");
+    resolved
+}
+
+/// Host of the first /dnsaddr component and the trailing /p2p peer id, if any
+fn dnsaddr_target(addr: &str) -> Option<(String, Option<String>)> {
+    let multiaddr = addr.parse::<Multiaddr>().ok()?;
+    let host = multiaddr.iter().find_map(|p| match p {
+        Protocol::Dnsaddr(host) => Some(host.to_string()),
+        _ => None,
+    })?;
+    let peer_id = multiaddr.iter().find_map(|p| match p {
+        Protocol::P2p(peer_id) => Some(peer_id.to_string()),
+        _ => None,
+    });
+    Some((host, peer_id))
+}
+
+/// Multiaddrs from `dnsaddr=` TXT records, restricted to `peer_id` when given
+fn dnsaddr_records(records: &[String], peer_id: Option<&str>) -> Vec<String> {
+    records
+        .iter()
+        .filter_map(|record| record.strip_prefix(DNSADDR_PREFIX))
+        .filter(|addr| peer_id.is_none_or(|id| addr.ends_with(&format!("/p2p/{id}"))))
+        .map(str::to_string)
+        .collect()
+}
+
+/// The multiaddr crate does not know /http-path, so it is cut out of the string and its
+/// url-encoded value returned as a path without a trailing slash
+fn split_http_path(addr: &str) -> (String, Option<String>) {
+    let mut components = addr.split('/');
+    let mut rest = vec![];
+    let mut path = None;
+
+    while let Some(component) = components.next() {
+        if component == "http-path" {
+            let value = components.next().unwrap_or_default();
+            let decoded = decode(value)
+                .map(|s| s.into_owned())
+                .unwrap_or_else(|_| value.to_string());
+            let trimmed = decoded.trim_matches('/');
+            path = (!trimmed.is_empty()).then(|| format!("/{trimmed}"));
+        } else {
+            rest.push(component);
+        }
+    }
+
+    (rest.join("/"), path)
+}
+
 fn parse_addr(addr: String) -> Option<String> {
     debug!("Parsing multiaddr: {}", addr);
 
-    match addr.parse::<Multiaddr>() {
+    let (base, path) = split_http_path(&addr);
+    match base.parse::<Multiaddr>() {
         Ok(multiaddr) => {
-            let url_parts = parse_multiaddr(multiaddr);
+            let mut url_parts = parse_multiaddr(multiaddr);
+            url_parts.path = path;
             debug!("Multiaddr parsed to url_parts: {}", url_parts);
 
             match url_parts.to_url() {
@@ -79,7 +301,7 @@ fn parse_multiaddr(multiaddr: Multiaddr) -> UrlParts {
                     url_parts.host = Some(ip.to_string());
                 }
                 Protocol::Ip6(ip) => {
-                    url_parts.host = Some(ip.to_string());
+                    url_parts.host = Some(format!("[{ip}]"));
                 }
                 Protocol::Tcp(port) => {
                     if url_parts.port.is_none() {
@@ -87,10 +309,18 @@ fn parse_multiaddr(multiaddr: Multiaddr) -> UrlParts {
                     }
                     url_parts.is_tcp = true;
                 }
-                Protocol::Udp(port) => {
-                    if url_parts.port.is_none() {
-                        url_parts.port = Some(port.to_string());
-                    }
+                Protocol::Udp(port) if url_parts.port.is_none() => {
+                    url_parts.port = Some(port.to_string());
+                }
+                Protocol::Tls => {
+                    url_parts.tls = true;
+                }
+                Protocol::Sni(host) => {
+                    url_parts.sni = Some(host.to_string());
+                }
+                // /tls/http is the multiaddr spelling of https
+                Protocol::Http if url_parts.tls => {
+                    url_parts.protocol = Some("https".to_string());
                 }
                 Protocol::Http => {
                     url_parts.protocol = Some("http".to_string());
@@ -98,6 +328,7 @@ fn parse_multiaddr(multiaddr: Multiaddr) -> UrlParts {
                 Protocol::Https => {
                     url_parts.protocol = Some("https".to_string());
                 }
+                // /p2p/{peer_id} identifies the peer and does not change the URL
                 _ => {}
             }
             url_parts
@@ -137,8 +368,20 @@ mod tests {
     }
 
     #[test]
-    fn fails_without_tcp_port() {
-        assert_eq!(parse_addr("/dns/example.com/https".to_string()), None);
+    fn defaults_port_from_scheme() {
+        assert_eq!(
+            parse_addr("/dns/example.com/https".to_string()),
+            Some("https://example.com:443".to_string())
+        );
+        assert_eq!(
+            parse_addr("/dns4/example.com/http".to_string()),
+            Some("http://example.com:80".to_string())
+        );
+    }
+
+    #[test]
+    fn fails_without_protocol_or_port() {
+        assert_eq!(parse_addr("/dns/example.com".to_string()), None);
     }
 
     #[test]
@@ -151,14 +394,159 @@ mod tests {
     fn parse_filters_invalid_multiaddrs() {
         let addrs = vec![
             "/ip4/1.2.3.4/tcp/8080/http".to_string(),
-            "/dns/host/https".to_string(),
+            "/dns/host".to_string(),
             "/dns/example.com/tcp/443/https".to_string(),
         ];
 
-        let result = parse(addrs);
+        let result = parse_resolved(addrs);
 
         assert_eq!(result.len(), 2);
         assert_eq!(result[0], "http://1.2.3.4:8080");
         assert_eq!(result[1], "https://example.com:443");
     }
+
+    #[test]
+    fn tls_http_is_https() {
+        assert_eq!(
+            parse_addr("/ip4/1.2.3.4/tcp/443/tls/http".to_string()),
+            Some("https://1.2.3.4:443".to_string())
+        );
+    }
+
+    #[test]
+    fn sni_replaces_ip_host() {
+        assert_eq!(
+            parse_addr("/ip4/1.2.3.4/tcp/8443/tls/sni/sp.example.com/http".to_string()),
+            Some("https://sp.example.com:8443".to_string())
+        );
+    }
+
+    #[test]
+    fn ip6_host_is_bracketed() {
+        assert_eq!(
+            parse_addr("/ip6/2001:db8::1/tcp/8080/http".to_string()),
+            Some("http://[2001:db8::1]:8080".to_string())
+        );
+    }
+
+    #[test]
+    fn http_path_becomes_base_path() {
+        assert_eq!(
+            parse_addr("/dns/sp.example.com/tcp/443/https/http-path/%2Fretrieval%2F".to_string()),
+            Some("https://sp.example.com:443/retrieval".to_string())
+        );
+        assert_eq!(
+            parse_addr("/dns/sp.example.com/https/http-path/%2F".to_string()),
+            Some("https://sp.example.com:443".to_string())
+        );
+    }
+
+    #[test]
+    fn p2p_suffix_is_ignored() {
+        assert_eq!(
+            parse_addr(
+                "/ip4/1.2.3.4/tcp/80/http/p2p/12D3KooWRf7tJR2NfJYE3PQJKXGt1EFqmFBBfQCgPRBLwwR9XL15"
+                    .to_string()
+            ),
+            Some("http://1.2.3.4:80".to_string())
+        );
+    }
+
+    #[test]
+    fn dnsaddr_target_reads_host_and_peer() {
+        let peer = "12D3KooWRf7tJR2NfJYE3PQJKXGt1EFqmFBBfQCgPRBLwwR9XL15";
+
+        assert_eq!(
+            dnsaddr_target(&format!("/dnsaddr/sp.example.com/p2p/{peer}")),
+            Some(("sp.example.com".to_string(), Some(peer.to_string())))
+        );
+        assert_eq!(dnsaddr_target("/ip4/1.2.3.4/tcp/80/http"), None);
+    }
+
+    #[test]
+    fn dnsaddr_records_filtered_by_peer() {
+        let records = vec![
+            "dnsaddr=/ip4/1.2.3.4/tcp/80/http/p2p/12D3KooWA".to_string(),
+            "dnsaddr=/ip4/5.6.7.8/tcp/80/http/p2p/12D3KooWB".to_string(),
+            "v=spf1 -all".to_string(),
+        ];
+
+        assert_eq!(
+            dnsaddr_records(&records, Some("12D3KooWA")),
+            vec!["/ip4/1.2.3.4/tcp/80/http/p2p/12D3KooWA"]
+        );
+        assert_eq!(dnsaddr_records(&records, None).len(), 2);
+    }
+
+    #[test]
+    fn libp2p_addrs_exclude_http_transports() {
+        let addrs = vec![
+            "/ip4/1.2.3.4/udp/24001/quic-v1".to_string(),
+            "/ip4/1.2.3.4/tcp/24001".to_string(),
+            "/ip4/1.2.3.4/tcp/443/tls/http".to_string(),
+            "/dns/sp.example.com/https".to_string(),
+            "not a multiaddr".to_string(),
+        ];
+
+        assert_eq!(
+            libp2p_addrs(&addrs),
+            vec!["/ip4/1.2.3.4/udp/24001/quic-v1", "/ip4/1.2.3.4/tcp/24001"]
+        );
+    }
+
+    // /ip4/1.2.3.4/tcp/24001
+    const BINARY_TCP: [u8; 8] = [0x04, 1, 2, 3, 4, 0x06, 0x5d, 0xc1];
+
+    #[tokio::test]
+    async fn explicit_http_skips_bare_tcp() {
+        let addrs = vec![
+            "/ip4/1.2.3.4/tcp/24001".to_string(),
+            "/ip4/1.2.3.4/tcp/443/tls/http".to_string(),
+            "/dns/sp.example.com/https/http-path/%2Fipfs".to_string(),
+        ];
+
+        assert_eq!(
+            parse_explicit_http(addrs).await,
+            vec!["https://1.2.3.4:443", "https://sp.example.com:443/ipfs"]
+        );
+    }
+
+    #[test]
+    fn decodes_contract_cbor_array() {
+        let mut bytes = vec![];
+        ciborium::into_writer(
+            &Value::Array(vec![
+                Value::Bytes(BINARY_TCP.to_vec()),
+                Value::Text("/dns/sp.example.com/tcp/443/https".to_string()),
+                Value::Bytes(vec![0xff]),
+            ]),
+            &mut bytes,
+        )
+        .unwrap();
+
+        assert_eq!(
+            decode_contract_bytes(&bytes),
+            vec![
+                "/ip4/1.2.3.4/tcp/24001",
+                "/dns/sp.example.com/tcp/443/https"
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_contract_single_binary_multiaddr() {
+        assert_eq!(
+            decode_contract_bytes(&BINARY_TCP),
+            vec!["/ip4/1.2.3.4/tcp/24001"]
+        );
+    }
+
+    #[test]
+    fn decodes_contract_text_multiaddrs() {
+        assert_eq!(
+            decode_contract_bytes(b"/dns/sp.example.com/https, /ip4/1.2.3.4/tcp/24001\n"),
+            vec!["/dns/sp.example.com/https", "/ip4/1.2.3.4/tcp/24001"]
+        );
+        assert!(decode_contract_bytes(&[]).is_empty());
+    }
 }
@@ -1,33 +1,74 @@
+//! Payload CID samples from pix.filspark, the Spark piece indexer.
+//!
+//! pix.filspark walks the provider's IPNI advertisements and returns payload block CIDs
+//! for a piece. Samples are arbitrary blocks of the piece, not necessarily its root.
+
+use std::time::Duration;
+
 use color_eyre::{Result, eyre::eyre};
-use reqwest::Client;
+use reqwest::StatusCode;
 use tracing::debug;
 
-/// Get the cid from pix.filspark.com
-#[allow(dead_code)]
-pub async fn get_cid(peer_id: &str, piece_id: &str) -> Result<String> {
-    let client = Client::new();
-    let url = format!("https://pix.filspark.com/sample/{peer_id}/{piece_id}");
+use crate::{car_header::normalize_cid, utils::build_reqwest_retry_client};
+
+const PIX_MIN_RETRY_INTERVAL_MS: u64 = 1_000;
+const PIX_MAX_RETRY_INTERVAL_MS: u64 = 10_000;
+const PIX_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Sampled payload CIDs of `piece_cid`, normalized to CIDv1 base32.
+/// Empty when pix.filspark has no samples for the piece.
+pub async fn get_samples(base_url: &str, peer_id: &str, piece_cid: &str) -> Result<Vec<String>> {
+    let client = build_reqwest_retry_client(PIX_MIN_RETRY_INTERVAL_MS, PIX_MAX_RETRY_INTERVAL_MS);
+    let url = format!(
+        "{}/sample/{peer_id}/{piece_cid}",
+        base_url.trim_end_matches('/')
+    );
 
     debug!("pix filspark url: {:?}", url);
 
-    let res = client
-        .get(&url)
-        .send()
-        .await?
-        .json::<serde_json::Value>()
-        .await?;
+    let res = client.get(&url).timeout(PIX_REQUEST_TIMEOUT).send().await?;
+    if res.status() == StatusCode::NOT_FOUND {
+        return Ok(vec![]);
+    }
+    if !res.status().is_success() {
+        return Err(eyre!("pix filspark returned {} for {}", res.status(), url));
+    }
+
+    let json = res.json::<serde_json::Value>().await?;
+    debug!("pix filspark res: {:?}", json);
 
-    debug!("pix filspark res: {:?}", res);
+    parse_samples(&json)
+}
 
-    let cid = res
+fn parse_samples(json: &serde_json::Value) -> Result<Vec<String>> {
+    let samples = json
         .get("samples")
-        .ok_or(eyre!("Missing samples"))?
-        .get(0)
-        .ok_or(eyre!("Missing samples"))?
-        .as_str()
+        .and_then(|s| s.as_array())
         .ok_or(eyre!("Missing samples"))?;
 
-    debug!("cid: {:?}", cid);
+    Ok(samples
+        .iter()
+        .filter_map(|s| s.as_str())
+        .filter_map(normalize_cid)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const PAYLOAD_CID: &str = "bafybeiczsscdsbs7ffqz55asqdf3smv6klcw3gofszvwlyarci47bgf354";
+
+    #[test]
+    fn parses_samples_and_skips_invalid_cids() {
+        let json = json!({ "samples": [PAYLOAD_CID, "not-a-cid", 42] });
+
+        assert_eq!(parse_samples(&json).unwrap(), vec![PAYLOAD_CID]);
+    }
 
-    Ok(cid.to_string())
+    #[test]
+    fn missing_samples_is_an_error() {
+        assert!(parse_samples(&json!({ "error": "unknown piece" })).is_err());
+    }
 }
@@ -0,0 +1,223 @@
+//! HTTP protocol capability probing for provider endpoints.
+//!
+//! reqwest negotiates whatever the server offers, so the protocols an endpoint supports
+//! are probed separately: ALPN over a direct TLS connection, the `Alt-Svc` header of a
+//! plain request, and a QUIC handshake with ALPN `h3` when HTTP/3 is advertised.
+
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use quinn::crypto::rustls::QuicClientConfig;
+use reqwest::{Client, Url, header::ALT_SVC};
+use serde::{Deserialize, Serialize};
+use tokio::net::{TcpStream, lookup_host};
+use tokio::time::timeout;
+use tokio_rustls::{TlsConnector, rustls::pki_types::ServerName};
+use tracing::debug;
+
+use crate::connection_timing::tls_client_config;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Protocol capabilities of a single endpoint
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EndpointProtocols {
+    pub endpoint: String,
+    /// Protocol selected via ALPN when offered h2 and http/1.1, None for plain http
+    pub alpn: Option<String>,
+    pub h2: bool,
+    /// Raw `Alt-Svc` header returned by the endpoint
+    pub alt_svc: Option<String>,
+    pub h3_advertised: bool,
+    /// QUIC handshake with ALPN h3 succeeded, None when not attempted
+    pub h3_reachable: Option<bool>,
+    pub error: Option<String>,
+}
+
+/// Probes ALPN, `Alt-Svc` and (for advertised https origins) HTTP/3 reachability
+pub async fn probe_endpoint(client: &Client, endpoint: &str) -> EndpointProtocols {
+    let mut result = EndpointProtocols {
+        endpoint: endpoint.to_string(),
+        ..Default::default()
+    };
+
+    let Some((host, port, is_https)) = Url::parse(endpoint).ok().and_then(|u| {
+        let host = u.host_str()?.trim_matches(['[', ']']).to_string();
+        Some((host, u.port_or_known_default()?, u.scheme() == "https"))
+    }) else {
+        result.error = Some("invalid url".to_string());
+        return result;
+    };
+
+    if is_https {
+        match negotiate_alpn(&host, port).await {
+            Ok(alpn) => {
+                result.h2 = alpn.as_deref() == Some("h2");
+                result.alpn = alpn;
+            }
+            Err(e) => result.error = Some(format!("alpn: {e}")),
+        }
+    }
+
+    match timeout(PROBE_TIMEOUT, client.head(endpoint).send()).await {
+        Ok(Ok(resp)) => {
+            result.alt_svc = resp
+                .headers()
+                .get(ALT_SVC)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+        }
+        Ok(Err(e)) => {
+            result.error.get_or_insert(format!("http: {e}"));
+        }
+        Err(_) => {
+            result.error.get_or_insert("http: timeout".to_string());
+        }
+    }
+
+    let h3_authority = result.alt_svc.as_deref().and_then(h3_alt_authority);
+    result.h3_advertised = h3_authority.is_some();
+
+    if let (true, Some((alt_host, alt_port))) = (is_https, h3_authority) {
+        let quic_host = alt_host.unwrap_or_else(|| host.clone());
+        let reachable = match resolve(&quic_host, alt_port).await {
+            Ok(addr) => quic_handshake(&host, addr).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = &reachable {
+            debug!("HTTP/3 handshake with {quic_host}:{alt_port} failed: {e}");
+        }
+        result.h3_reachable = Some(reachable.is_ok());
+    }
+
+    result
+}
+
+/// Host (None = same as origin) and port of the first h3 entry in an `Alt-Svc` header
+fn h3_alt_authority(alt_svc: &str) -> Option<(Option<String>, u16)> {
+    alt_svc.split(',').find_map(|entry| {
+        let alternative = entry.split(';').next()?.trim();
+        let (protocol, authority) = alternative.split_once('=')?;
+        if protocol != "h3" && !protocol.starts_with("h3-") {
+            return None;
+        }
+        let (host, port) = authority.trim_matches('"').rsplit_once(':')?;
+        let host = host.trim_matches(['[', ']']);
+        Some((
+            (!host.is_empty()).then(|| host.to_string()),
+            port.parse().ok()?,
+        ))
+    })
+}
+
+async fn resolve(host: &str, port: u16) -> Result<SocketAddr, String> {
+    match timeout(PROBE_TIMEOUT, lookup_host((host, port))).await {
+        Ok(Ok(mut addrs)) => addrs.next().ok_or_else(|| "dns: no addresses".to_string()),
+        Ok(Err(e)) => Err(format!("dns: {e}")),
+        Err(_) => Err("dns: timeout".to_string()),
+    }
+}
+
+async fn negotiate_alpn(host: &str, port: u16) -> Result<Option<String>, String> {
+    let addr = resolve(host, port).await?;
+    let config = tls_client_config(&[b"h2", b"http/1.1"]).ok_or("tls config unavailable")?;
+    let server_name = ServerName::try_from(host.to_string()).map_err(|e| e.to_string())?;
+
+    let stream = timeout(PROBE_TIMEOUT, TcpStream::connect(addr))
+        .await
+        .map_err(|_| "connect: timeout".to_string())?
+        .map_err(|e| format!("connect: {e}"))?;
+    let tls = timeout(
+        PROBE_TIMEOUT,
+        TlsConnector::from(Arc::new(config)).connect(server_name, stream),
+    )
+    .await
+    .map_err(|_| "tls: timeout".to_string())?
+    .map_err(|e| format!("tls: {e}"))?;
+
+    let (_, connection) = tls.get_ref();
+    Ok(connection
+        .alpn_protocol()
+        .map(|p| String::from_utf8_lossy(p).into_owned()))
+}
+
+async fn quic_handshake(server_name: &str, addr: SocketAddr) -> Result<(), String> {
+    let tls = tls_client_config(&[b"h3"]).ok_or("tls config unavailable")?;
+    let quic = QuicClientConfig::try_from(tls).map_err(|e| e.to_string())?;
+
+    let bind: SocketAddr = if addr.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let mut endpoint = quinn::Endpoint::client(bind).map_err(|e| e.to_string())?;
+    endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(quic)));
+
+    let connecting = endpoint
+        .connect(addr, server_name)
+        .map_err(|e| e.to_string())?;
+    let result = match timeout(PROBE_TIMEOUT, connecting).await {
+        Ok(Ok(connection)) => {
+            connection.close(0u32.into(), b"");
+            Ok(())
+        }
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("timeout".to_string()),
+    };
+
+    endpoint.close(0u32.into(), b"");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{Mock, MockServer, ResponseTemplate, matchers::method};
+
+    #[test]
+    fn test_h3_alt_authority_same_host() {
+        assert_eq!(
+            h3_alt_authority(r#"h3=":443"; ma=86400, h2=":443""#),
+            Some((None, 443))
+        );
+    }
+
+    #[test]
+    fn test_h3_alt_authority_draft_and_alternate_host() {
+        assert_eq!(
+            h3_alt_authority(r#"h2=":443", h3-29="alt.example.com:8443"; ma=3600"#),
+            Some((Some("alt.example.com".to_string()), 8443))
+        );
+    }
+
+    #[test]
+    fn test_h3_alt_authority_not_advertised() {
+        assert_eq!(h3_alt_authority(r#"h2=":443""#), None);
+        assert_eq!(h3_alt_authority("clear"), None);
+    }
+
+    #[tokio::test]
+    async fn test_probe_plain_http_endpoint_reads_alt_svc() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .respond_with(ResponseTemplate::new(404).insert_header("Alt-Svc", r#"h3=":443""#))
+            .mount(&mock_server)
+            .await;
+
+        let result = probe_endpoint(&Client::new(), &mock_server.uri()).await;
+
+        assert_eq!(result.alpn, None);
+        assert!(!result.h2);
+        assert!(result.h3_advertised);
+        assert_eq!(result.h3_reachable, None, "h3 is only attempted for https");
+        assert!(result.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_probe_invalid_endpoint() {
+        let result = probe_endpoint(&Client::new(), "not a url").await;
+
+        assert_eq!(result.error.as_deref(), Some("invalid url"));
+    }
+}
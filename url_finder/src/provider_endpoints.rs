@@ -1,124 +1,112 @@
-use std::time::Duration;
-
-use alloy::sol_types::SolType;
-use alloy::{
-    network::TransactionBuilder,
-    primitives::{Address, Bytes, address},
-    providers::{Provider, ProviderBuilder},
-    rpc::types::eth::TransactionRequest,
-    sol,
-    sol_types::SolCall,
-};
-use color_eyre::{Result, eyre::eyre};
-use tokio::time::sleep;
-use tracing::{debug, error, info, warn};
+use futures::future::join_all;
+use tracing::{debug, error};
 
 use crate::{
     ErrorCode, ResultCode,
     cid_contact::{self, CidContactError},
-    config::Config,
+    config::{Config, IpniIndexer},
     multiaddr_parser,
-    types::ProviderAddress,
+    types::{EndpointSource, ProviderAddress},
 };
 
-sol! {
-    struct PeerData {
-        string peerID;
-        bytes multiaddrs;
-    }
-
-    function getPeerData(uint64 minerID) view returns (PeerData);
-}
-
-pub async fn valid_curio_provider(
-    config: &Config,
-    address: &ProviderAddress,
-) -> Result<Option<String>> {
-    let rpc_url = &config.glif_url;
-
-    let rpc_provider = ProviderBuilder::new()
-        .connect(rpc_url)
-        .await
-        .map_err(|err| eyre!("Building provider failed: {}", err))?;
-
-    let miner_peer_id_contract: Address = address!("0x14183aD016Ddc83D638425D6328009aa390339Ce");
-
-    let miner_id = address
-        .as_str()
-        .strip_prefix("f")
-        .ok_or_else(|| eyre!("Address does not start with 'f': {}", address))?
-        .parse::<u64>()
-        .map_err(|e| eyre!("Failed to parse miner ID from '{}': {}", address, e))?;
-
-    let call: Vec<u8> = getPeerDataCall { minerID: miner_id }.abi_encode();
-    let input = Bytes::from(call);
-    let tx = TransactionRequest::default()
-        .with_to(miner_peer_id_contract)
-        .with_input(input);
-
-    let mut response = None;
-
-    for attempt in 1..=3 {
-        match rpc_provider.call(tx.clone()).await {
-            Ok(res) => {
-                response = Some(res);
-                break;
-            }
-            Err(e) => {
-                debug!("Attempt {attempt}/3 failed: {e} for address: {address}");
-                sleep(Duration::from_secs(1)).await;
-            }
-        }
-    }
+/// IPNI content lookups per provider refresh
+const MAX_CONTENT_LOOKUPS: usize = 3;
 
-    let Some(response) = response else {
-        warn!("Curio lookup failed after 3 attempts for {address}");
-        return Err(eyre!("All 3 attempts failed for address {address}"));
-    };
+/// Result code of a provider record lookup and the HTTP endpoints found, with their section
+pub type IndexerOutcome = Result<(ResultCode, Option<(EndpointSource, Vec<String>)>), ErrorCode>;
 
-    let peer_data: PeerData = PeerData::abi_decode(response.as_ref())?;
+/// Provider record lookup against one indexer
+#[derive(Debug, Clone)]
+pub struct IndexerLookup {
+    pub indexer: String,
+    pub outcome: IndexerOutcome,
+    /// Raw multiaddrs of the record, kept for transports other than HTTP
+    pub addrs: Vec<String>,
+}
 
-    if peer_data.peerID.is_empty() {
-        return Ok(None);
-    }
+/// Endpoints one indexer advertises for a sampled piece or payload CID
+#[derive(Debug, Clone)]
+pub struct IndexerAdvertisement {
+    pub indexer: String,
+    pub endpoints: Vec<String>,
+}
 
-    info!("Curio provider found: {}: {}", &peer_data.peerID, &address);
-    Ok(Some(peer_data.peerID.to_string()))
+/// HTTP endpoint merged across indexers
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergedEndpoint {
+    pub endpoint: String,
+    /// Source reported by the highest priority indexer that returned the endpoint
+    pub source: EndpointSource,
+    /// Indexers that returned the endpoint, highest priority first
+    pub indexers: Vec<String>,
 }
 
+/// Looks the provider record up on every configured indexer concurrently, in priority order
 pub async fn get_provider_endpoints(
     config: &Config,
     _address: &ProviderAddress,
     cached_peer_id: Option<String>,
-) -> Result<(ResultCode, Option<Vec<String>>), ErrorCode> {
+) -> Result<Vec<IndexerLookup>, ErrorCode> {
     let peer_id = match cached_peer_id {
         Some(pid) => pid,
         None => return Err(ErrorCode::PeerIdNotCached),
     };
 
+    let peer_id = peer_id.as_str();
+    Ok(join_all(
+        config
+            .ipni_indexers
+            .iter()
+            .map(|indexer| get_indexer_endpoints(indexer, peer_id)),
+    )
+    .await)
+}
+
+/// Resolves the provider's HTTP endpoints from one indexer, along with the section they came from
+async fn get_indexer_endpoints(indexer: &IpniIndexer, peer_id: &str) -> IndexerLookup {
+    let mut addrs = vec![];
+    let outcome = get_indexer_outcome(indexer, peer_id, &mut addrs).await;
+    IndexerLookup {
+        indexer: indexer.name.clone(),
+        outcome,
+        addrs,
+    }
+}
+
+async fn get_indexer_outcome(
+    indexer: &IpniIndexer,
+    peer_id: &str,
+    raw_addrs: &mut Vec<String>,
+) -> IndexerOutcome {
     // get cid contact response
-    let cid_contact_res = match cid_contact::get_contact(config, &peer_id).await {
+    let cid_contact_res = match cid_contact::get_contact(indexer, peer_id).await {
         Ok(res) => res,
         Err(CidContactError::NoData) => {
             return Ok((ResultCode::NoCidContactData, None));
         }
         Err(e) => {
-            error!("Failed to get cid contact: {:?}", e.to_string());
+            error!(
+                "Failed to get {} provider record: {:?}",
+                indexer.name,
+                e.to_string()
+            );
 
             return Err(ErrorCode::FailedToRetrieveCidContactData);
         }
     };
 
     // Get all addresses (containing IP and Port) from cid contact response
-    let addrs = cid_contact::get_all_addresses_from_response(cid_contact_res);
-    if addrs.is_empty() {
+    let Some((source, addrs)) = cid_contact::get_all_addresses_from_response(cid_contact_res)
+        .filter(|(_, addrs)| !addrs.is_empty())
+    else {
         debug!("Missing addr from cid contact, No addresses found");
 
         return Ok((ResultCode::MissingAddrFromCidContact, None));
-    }
+    };
+    raw_addrs.clone_from(&addrs);
 
     // parse addresses to http endpoints
-    let mut endpoints = multiaddr_parser::parse(addrs);
+    let mut endpoints = multiaddr_parser::parse(addrs).await;
     if endpoints.is_empty() {
         debug!("Missing http addr from cid contact, No endpoints found");
 
@@ -137,5 +125,215 @@ pub async fn get_provider_endpoints(
         );
     }
 
-    Ok((ResultCode::Success, Some(endpoints)))
+    Ok((ResultCode::Success, Some((source, endpoints))))
+}
+
+/// Merges the indexers' provider records, deduplicating endpoints. When no indexer returned
+/// endpoints, the outcome of the highest priority indexer is returned.
+pub fn merge_indexer_endpoints(
+    lookups: &[IndexerLookup],
+) -> Result<(ResultCode, Option<Vec<MergedEndpoint>>), ErrorCode> {
+    let mut merged = vec![];
+    for lookup in lookups {
+        if let Ok((ResultCode::Success, Some((source, endpoints)))) = &lookup.outcome {
+            add_endpoints(&mut merged, &lookup.indexer, *source, endpoints);
+        }
+    }
+
+    if !merged.is_empty() {
+        return Ok((ResultCode::Success, Some(merged)));
+    }
+
+    match lookups.first().map(|l| l.outcome.clone()) {
+        Some(Err(error_code)) => Err(error_code),
+        Some(Ok((result_code, _))) => Ok((result_code, None)),
+        None => Ok((ResultCode::NoCidContactData, None)),
+    }
+}
+
+/// Adds content-advertised endpoints that no provider record listed
+pub fn merge_advertised_endpoints(
+    merged: &mut Vec<MergedEndpoint>,
+    advertisements: &[IndexerAdvertisement],
+) {
+    for ad in advertisements {
+        add_endpoints(
+            merged,
+            &ad.indexer,
+            EndpointSource::IpniAdvertisement,
+            &ad.endpoints,
+        );
+    }
+}
+
+fn add_endpoints(
+    merged: &mut Vec<MergedEndpoint>,
+    indexer: &str,
+    source: EndpointSource,
+    endpoints: &[String],
+) {
+    for endpoint in endpoints {
+        match merged.iter_mut().find(|m| &m.endpoint == endpoint) {
+            Some(existing) => {
+                if !existing.indexers.iter().any(|i| i == indexer) {
+                    existing.indexers.push(indexer.to_string());
+                }
+            }
+            None => merged.push(MergedEndpoint {
+                endpoint: endpoint.clone(),
+                source,
+                indexers: vec![indexer.to_string()],
+            }),
+        }
+    }
+}
+
+/// Resolves HTTP endpoints from IPNI content lookups of the sampled CIDs on every indexer.
+/// Catches providers whose provider record is stale while per-content advertisements are
+/// correct. Stops at the first CID any indexer advertises HTTP addresses for.
+pub async fn get_content_advertised_endpoints(
+    config: &Config,
+    peer_id: &str,
+    cids: &[String],
+) -> Vec<IndexerAdvertisement> {
+    for cid in cids.iter().take(MAX_CONTENT_LOOKUPS) {
+        let advertisements: Vec<IndexerAdvertisement> =
+            join_all(config.ipni_indexers.iter().map(|indexer| async move {
+                let response = match cid_contact::get_cid_providers(indexer, cid).await {
+                    Ok(res) => res,
+                    Err(e) => {
+                        debug!(
+                            "IPNI content lookup on {} failed for {cid}: {e}",
+                            indexer.name
+                        );
+                        return None;
+                    }
+                };
+
+                let addrs = cid_contact::get_http_addresses_from_cid_response(&response, peer_id);
+                let endpoints = multiaddr_parser::parse(addrs).await;
+                (!endpoints.is_empty()).then(|| IndexerAdvertisement {
+                    indexer: indexer.name.clone(),
+                    endpoints,
+                })
+            }))
+            .await
+            .into_iter()
+            .flatten()
+            .collect();
+
+        if !advertisements.is_empty() {
+            debug!(
+                "{} indexers advertise http endpoints for {cid} (peer_id: {peer_id})",
+                advertisements.len()
+            );
+            return advertisements;
+        }
+    }
+
+    vec![]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(indexer: &str, outcome: IndexerOutcome) -> IndexerLookup {
+        IndexerLookup {
+            indexer: indexer.to_string(),
+            outcome,
+            addrs: vec![],
+        }
+    }
+
+    fn found(source: EndpointSource, endpoints: &[&str]) -> IndexerOutcome {
+        Ok((
+            ResultCode::Success,
+            Some((source, endpoints.iter().map(|e| e.to_string()).collect())),
+        ))
+    }
+
+    #[test]
+    fn merge_dedupes_and_keeps_priority_source() {
+        let lookups = vec![
+            lookup(
+                "private",
+                found(EndpointSource::Publisher, &["http://a:80", "http://b:80"]),
+            ),
+            lookup(
+                "cid.contact",
+                found(
+                    EndpointSource::ExtendedProviders,
+                    &["http://b:80", "http://c:80"],
+                ),
+            ),
+        ];
+
+        let (code, merged) = merge_indexer_endpoints(&lookups).unwrap();
+
+        assert_eq!(code, ResultCode::Success);
+        let merged = merged.unwrap();
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[1].endpoint, "http://b:80");
+        assert_eq!(merged[1].source, EndpointSource::Publisher);
+        assert_eq!(merged[1].indexers, vec!["private", "cid.contact"]);
+        assert_eq!(merged[2].indexers, vec!["cid.contact"]);
+    }
+
+    #[test]
+    fn merge_uses_any_indexer_with_endpoints() {
+        let lookups = vec![
+            lookup("private", Err(ErrorCode::FailedToRetrieveCidContactData)),
+            lookup(
+                "cid.contact",
+                found(EndpointSource::Publisher, &["http://a:80"]),
+            ),
+        ];
+
+        let (code, merged) = merge_indexer_endpoints(&lookups).unwrap();
+
+        assert_eq!(code, ResultCode::Success);
+        assert_eq!(merged.unwrap()[0].indexers, vec!["cid.contact"]);
+    }
+
+    #[test]
+    fn merge_without_endpoints_returns_highest_priority_outcome() {
+        let lookups = vec![
+            lookup(
+                "private",
+                Ok((ResultCode::MissingHttpAddrFromCidContact, None)),
+            ),
+            lookup(
+                "cid.contact",
+                Err(ErrorCode::FailedToRetrieveCidContactData),
+            ),
+        ];
+
+        let (code, merged) = merge_indexer_endpoints(&lookups).unwrap();
+
+        assert_eq!(code, ResultCode::MissingHttpAddrFromCidContact);
+        assert!(merged.is_none());
+    }
+
+    #[test]
+    fn advertised_endpoints_extend_record() {
+        let (_, merged) = merge_indexer_endpoints(&[lookup(
+            "cid.contact",
+            found(EndpointSource::Publisher, &["http://a:80"]),
+        )])
+        .unwrap();
+        let mut merged = merged.unwrap();
+
+        merge_advertised_endpoints(
+            &mut merged,
+            &[IndexerAdvertisement {
+                indexer: "private".to_string(),
+                endpoints: vec!["http://a:80".to_string(), "http://d:80".to_string()],
+            }],
+        );
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].indexers, vec!["cid.contact", "private"]);
+        assert_eq!(merged[1].source, EndpointSource::IpniAdvertisement);
+    }
 }
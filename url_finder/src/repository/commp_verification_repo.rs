@@ -0,0 +1,139 @@
+use color_eyre::Result;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::types::ProviderId;
+
+/// Which measurement spent a piece of the weekly CommP budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommpSource {
+    UrlDiscovery,
+    DealSli,
+}
+
+impl CommpSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CommpSource::UrlDiscovery => "url_discovery",
+            CommpSource::DealSli => "deal_sli",
+        }
+    }
+}
+
+/// A piece charged against the budget, to be streamed by the caller
+#[derive(Debug, Clone)]
+pub struct CommpReservation {
+    pub id: Uuid,
+    pub piece_cid: String,
+}
+
+/// Ledger behind the per-provider weekly CommP budget. URL discovery and Deal SLI runs
+/// both charge it, so a provider streams at most `pieces_per_week` distinct pieces in total.
+#[derive(Clone)]
+pub struct CommpVerificationRepository {
+    pool: PgPool,
+}
+
+impl CommpVerificationRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Charges candidate pieces, in order, until the provider's budget for the past 7 days is spent.
+    /// Pieces already charged in that window are skipped rather than charged twice.
+    pub async fn reserve(
+        &self,
+        provider_id: &ProviderId,
+        piece_cids: &[String],
+        pieces_per_week: i64,
+        source: CommpSource,
+    ) -> Result<Vec<CommpReservation>> {
+        if piece_cids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        // Serialise concurrent reservations for the provider so the remaining budget is not read twice
+        sqlx::query_scalar!(
+            r#"SELECT
+                    1 AS "locked!"
+               FROM
+                    pg_advisory_xact_lock(hashtext('commp_verifications:' || $1))
+            "#,
+            provider_id.as_str()
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let reservations = sqlx::query_as!(
+            CommpReservation,
+            r#"WITH charged AS (
+                    SELECT
+                         piece_cid
+                    FROM
+                         commp_verifications
+                    WHERE
+                         provider_id = $1
+                         AND charged_at >= NOW() - INTERVAL '7 days'
+               ),
+               candidates AS (
+                    SELECT
+                         c.piece_cid
+                    FROM
+                         UNNEST($2::text[]) WITH ORDINALITY AS c(piece_cid, position)
+                    WHERE
+                         c.piece_cid NOT IN (SELECT piece_cid FROM charged)
+                    ORDER BY
+                         c.position
+                    LIMIT GREATEST($3 - (SELECT COUNT(DISTINCT piece_cid) FROM charged), 0)
+               )
+               INSERT INTO
+                    commp_verifications (provider_id, piece_cid, source)
+               SELECT
+                    $1, piece_cid, $4
+               FROM
+                    candidates
+               RETURNING
+                    id,
+                    piece_cid
+            "#,
+            provider_id.as_str(),
+            piece_cids,
+            pieces_per_week,
+            source.as_str()
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(reservations)
+    }
+
+    /// Stores the outcome of a reserved verification; the charge stands either way
+    pub async fn record_outcome(
+        &self,
+        id: Uuid,
+        commp_verified: Option<bool>,
+        bytes_read: u64,
+    ) -> Result<()> {
+        sqlx::query!(
+            r#"UPDATE
+                    commp_verifications
+               SET
+                    commp_verified = $2,
+                    bytes_read = $3
+               WHERE
+                    id = $1
+            "#,
+            id,
+            commp_verified,
+            i64::try_from(bytes_read)?
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
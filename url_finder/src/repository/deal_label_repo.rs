@@ -0,0 +1,98 @@
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use sqlx::PgPool;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DealLabel {
+    pub deal_id: i32,
+    pub piece_cid: String,
+    pub label_raw: Option<String>,
+    pub payload_cid: Option<String>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewDealLabel {
+    pub deal_id: i32,
+    pub piece_cid: String,
+    pub label_raw: Option<String>,
+    pub payload_cid: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct DealLabelRepository {
+    pool: PgPool,
+}
+
+impl DealLabelRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get_by_deal_ids(&self, deal_ids: &[i32]) -> Result<Vec<DealLabel>> {
+        if deal_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        Ok(sqlx::query_as!(
+            DealLabel,
+            r#"SELECT
+                    deal_id,
+                    piece_cid,
+                    label_raw,
+                    payload_cid,
+                    fetched_at
+               FROM
+                    deal_labels
+               WHERE
+                    deal_id = ANY($1)
+            "#,
+            deal_ids
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Labels are immutable once a deal is made, so existing rows are never overwritten
+    pub async fn insert_batch(&self, labels: &[NewDealLabel]) -> Result<usize> {
+        if labels.is_empty() {
+            return Ok(0);
+        }
+
+        let len = labels.len();
+        let mut deal_ids: Vec<i32> = Vec::with_capacity(len);
+        let mut piece_cids: Vec<String> = Vec::with_capacity(len);
+        let mut label_raws: Vec<Option<String>> = Vec::with_capacity(len);
+        let mut payload_cids: Vec<Option<String>> = Vec::with_capacity(len);
+
+        for label in labels {
+            deal_ids.push(label.deal_id);
+            piece_cids.push(label.piece_cid.clone());
+            label_raws.push(label.label_raw.clone());
+            payload_cids.push(label.payload_cid.clone());
+        }
+
+        let result = sqlx::query!(
+            r#"INSERT INTO
+                    deal_labels (deal_id, piece_cid, label_raw, payload_cid)
+               SELECT
+                    a1, a2, a3, a4
+               FROM UNNEST(
+                    $1::integer[],
+                    $2::text[],
+                    $3::text[],
+                    $4::text[]
+               ) AS t(a1, a2, a3, a4)
+               ON CONFLICT (deal_id) DO NOTHING
+            "#,
+            &deal_ids as &[i32],
+            &piece_cids as &[String],
+            &label_raws as &[Option<String>],
+            &payload_cids as &[Option<String>]
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected().try_into()?)
+    }
+}
@@ -157,6 +157,7 @@ pub struct NewDealSliPieceResult {
     pub size_matched: Option<bool>,
     pub manifest_response_time_ms: Option<i64>,
     pub is_valid_car: bool,
+    pub commp_verified: Option<bool>,
     pub result_code: ResultCode,
 }
 
@@ -753,6 +754,24 @@ impl DealSliRepository {
         .await?)
     }
 
+    /// Every target is scheduled for SLI runs, so any target counts as active
+    pub async fn has_target_for_provider(&self, provider_id: &str) -> Result<bool> {
+        Ok(sqlx::query_scalar!(
+            r#"SELECT EXISTS (
+                    SELECT
+                        1
+                    FROM
+                        deal_sli_targets
+                    WHERE
+                        provider_id = $1
+               ) AS "exists!"
+            "#,
+            provider_id
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
     pub async fn sample_manifest_pieces(
         &self,
         deal_id: &str,
@@ -919,11 +938,12 @@ impl DealSliRepository {
                             size_matched,
                             manifest_response_time_ms,
                             is_valid_car,
+                            commp_verified,
                             result_code,
                             tested_at
                         )
                    VALUES
-                        ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, NOW())
+                        ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, NOW())
                 "#,
                 inserted.id,
                 piece_result.deal_id,
@@ -938,6 +958,7 @@ impl DealSliRepository {
                 piece_result.size_matched,
                 piece_result.manifest_response_time_ms,
                 piece_result.is_valid_car,
+                piece_result.commp_verified,
                 piece_result.result_code.clone() as ResultCode,
             )
             .execute(&mut *tx)
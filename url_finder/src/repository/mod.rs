@@ -1,11 +1,27 @@
 mod bms_result_repo;
+mod commp_verification_repo;
+mod deal_label_repo;
 mod deal_repo;
 mod deal_sli_repo;
+mod pix_sample_repo;
+mod provider_endpoint_repo;
+mod provider_identity_repo;
+mod provider_test_job_repo;
+mod scheduler_lease_repo;
+mod scheduler_run_repo;
 mod storage_provider_repo;
 mod url_result_repo;
 
 pub use bms_result_repo::*;
+pub use commp_verification_repo::*;
+pub use deal_label_repo::*;
 pub use deal_repo::*;
 pub use deal_sli_repo::*;
+pub use pix_sample_repo::*;
+pub use provider_endpoint_repo::*;
+pub use provider_identity_repo::*;
+pub use provider_test_job_repo::*;
+pub use scheduler_lease_repo::*;
+pub use scheduler_run_repo::*;
 pub use storage_provider_repo::*;
 pub use url_result_repo::*;
@@ -0,0 +1,100 @@
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use sqlx::PgPool;
+
+use crate::types::ProviderId;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PixSample {
+    pub provider_id: ProviderId,
+    pub piece_cid: String,
+    /// None when pix.filspark had no sample for the piece
+    pub payload_cid: Option<String>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewPixSample {
+    pub piece_cid: String,
+    pub payload_cid: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct PixSampleRepository {
+    pool: PgPool,
+}
+
+impl PixSampleRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Samples of the given pieces fetched after `fetched_after`, including empty ones
+    pub async fn get_fresh(
+        &self,
+        provider_id: &ProviderId,
+        piece_cids: &[String],
+        fetched_after: DateTime<Utc>,
+    ) -> Result<Vec<PixSample>> {
+        if piece_cids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        Ok(sqlx::query_as!(
+            PixSample,
+            r#"SELECT
+                    provider_id AS "provider_id: ProviderId",
+                    piece_cid,
+                    payload_cid,
+                    fetched_at
+               FROM
+                    pix_payload_samples
+               WHERE
+                    provider_id = $1
+                    AND piece_cid = ANY($2)
+                    AND fetched_at > $3
+            "#,
+            provider_id.as_str(),
+            piece_cids,
+            fetched_after
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Inserts new samples and replaces expired ones
+    pub async fn upsert_batch(
+        &self,
+        provider_id: &ProviderId,
+        samples: &[NewPixSample],
+    ) -> Result<usize> {
+        if samples.is_empty() {
+            return Ok(0);
+        }
+
+        let (piece_cids, payload_cids): (Vec<String>, Vec<Option<String>>) = samples
+            .iter()
+            .map(|s| (s.piece_cid.clone(), s.payload_cid.clone()))
+            .unzip();
+
+        let result = sqlx::query!(
+            r#"INSERT INTO
+                    pix_payload_samples (provider_id, piece_cid, payload_cid)
+               SELECT
+                    $1, t.piece_cid, t.payload_cid
+               FROM UNNEST($2::text[], $3::text[]) AS t(piece_cid, payload_cid)
+               ON CONFLICT (provider_id, piece_cid) DO UPDATE
+               SET
+                    payload_cid = EXCLUDED.payload_cid,
+                    fetched_at = NOW()
+            "#,
+            provider_id.as_str(),
+            &piece_cids as &[String],
+            &payload_cids as &[Option<String>]
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected().try_into()?)
+    }
+}
@@ -0,0 +1,282 @@
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use sqlx::PgPool;
+
+use crate::types::{EndpointSource, ErrorCode, ProviderId, ResultCode};
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ProviderEndpoint {
+    pub provider_id: ProviderId,
+    pub endpoint: String,
+    pub source: EndpointSource,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen_in_ipni: Option<DateTime<Utc>>,
+    pub last_seen_on_chain: Option<DateTime<Utc>>,
+    pub last_tested_at: Option<DateTime<Utc>>,
+    pub last_success: Option<DateTime<Utc>>,
+    pub consecutive_failures: i32,
+    pub avg_latency_ms: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewProviderEndpoint {
+    pub endpoint: String,
+    pub source: EndpointSource,
+}
+
+/// Latest provider record lookup on one IPNI indexer
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ProviderIndexerResult {
+    pub provider_id: ProviderId,
+    pub indexer: String,
+    pub result_code: ResultCode,
+    pub error_code: Option<ErrorCode>,
+    /// HTTP endpoints the indexer returned, from the provider record and content lookups
+    pub endpoints: Vec<String>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewProviderIndexerResult {
+    pub indexer: String,
+    pub result_code: ResultCode,
+    pub error_code: Option<ErrorCode>,
+    pub endpoints: Vec<String>,
+}
+
+/// Outcome of one discovery run against a single endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointTestOutcome {
+    pub endpoint: String,
+    /// At least one piece was retrieved
+    pub success: bool,
+    /// Mean response time of the successful requests
+    pub avg_latency_ms: Option<f64>,
+}
+
+#[derive(Clone)]
+pub struct ProviderEndpointRepository {
+    pool: PgPool,
+}
+
+impl ProviderEndpointRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get_by_provider(&self, provider_id: &ProviderId) -> Result<Vec<ProviderEndpoint>> {
+        Ok(sqlx::query_as!(
+            ProviderEndpoint,
+            r#"SELECT
+                    provider_id AS "provider_id: ProviderId",
+                    endpoint,
+                    source AS "source: EndpointSource",
+                    first_seen,
+                    last_seen_in_ipni,
+                    last_seen_on_chain,
+                    last_tested_at,
+                    last_success,
+                    consecutive_failures,
+                    avg_latency_ms
+               FROM
+                    provider_endpoints
+               WHERE
+                    provider_id = $1
+               ORDER BY
+                    endpoint
+            "#,
+            provider_id.as_str()
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Inserts new endpoints and refreshes `last_seen_in_ipni` of the ones already known
+    pub async fn upsert_seen_in_ipni(
+        &self,
+        provider_id: &ProviderId,
+        endpoints: &[NewProviderEndpoint],
+    ) -> Result<usize> {
+        if endpoints.is_empty() {
+            return Ok(0);
+        }
+
+        let (endpoint_urls, sources): (Vec<String>, Vec<String>) = endpoints
+            .iter()
+            .map(|e| (e.endpoint.clone(), e.source.to_string()))
+            .unzip();
+
+        let result = sqlx::query!(
+            r#"INSERT INTO
+                    provider_endpoints (provider_id, endpoint, source, last_seen_in_ipni)
+               SELECT
+                    $1, t.endpoint, t.source::endpoint_source, NOW()
+               FROM UNNEST($2::text[], $3::text[]) AS t(endpoint, source)
+               ON CONFLICT (provider_id, endpoint) DO UPDATE
+               SET
+                    source = EXCLUDED.source,
+                    last_seen_in_ipni = EXCLUDED.last_seen_in_ipni
+            "#,
+            provider_id.as_str(),
+            &endpoint_urls as &[String],
+            &sources as &[String]
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected().try_into()?)
+    }
+
+    /// Inserts endpoints read from chain and refreshes `last_seen_on_chain` of the ones already
+    /// known. The source of a known endpoint is kept, so an endpoint IPNI also advertises stays
+    /// attributed to IPNI.
+    pub async fn upsert_seen_on_chain(
+        &self,
+        provider_id: &ProviderId,
+        endpoints: &[NewProviderEndpoint],
+    ) -> Result<usize> {
+        if endpoints.is_empty() {
+            return Ok(0);
+        }
+
+        let (endpoint_urls, sources): (Vec<String>, Vec<String>) = endpoints
+            .iter()
+            .map(|e| (e.endpoint.clone(), e.source.to_string()))
+            .unzip();
+
+        let result = sqlx::query!(
+            r#"INSERT INTO
+                    provider_endpoints (provider_id, endpoint, source, last_seen_on_chain)
+               SELECT
+                    $1, t.endpoint, t.source::endpoint_source, NOW()
+               FROM UNNEST($2::text[], $3::text[]) AS t(endpoint, source)
+               ON CONFLICT (provider_id, endpoint) DO UPDATE
+               SET
+                    last_seen_on_chain = EXCLUDED.last_seen_on_chain
+            "#,
+            provider_id.as_str(),
+            &endpoint_urls as &[String],
+            &sources as &[String]
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected().try_into()?)
+    }
+
+    /// Applies one discovery run: resets or increments the failure streak and folds the
+    /// measured latency into the running average (weight 0.3 for the newest sample)
+    pub async fn record_test_outcomes(
+        &self,
+        provider_id: &ProviderId,
+        outcomes: &[EndpointTestOutcome],
+    ) -> Result<usize> {
+        if outcomes.is_empty() {
+            return Ok(0);
+        }
+
+        let len = outcomes.len();
+        let mut endpoints: Vec<String> = Vec::with_capacity(len);
+        let mut successes: Vec<bool> = Vec::with_capacity(len);
+        let mut latencies: Vec<Option<f64>> = Vec::with_capacity(len);
+
+        for outcome in outcomes {
+            endpoints.push(outcome.endpoint.clone());
+            successes.push(outcome.success);
+            latencies.push(outcome.avg_latency_ms);
+        }
+
+        let result = sqlx::query!(
+            r#"UPDATE
+                    provider_endpoints pe
+               SET
+                    last_tested_at = NOW(),
+                    last_success = CASE WHEN t.success THEN NOW() ELSE pe.last_success END,
+                    consecutive_failures = CASE WHEN t.success THEN 0 ELSE pe.consecutive_failures + 1 END,
+                    avg_latency_ms = COALESCE(pe.avg_latency_ms * 0.7 + t.latency_ms * 0.3, t.latency_ms, pe.avg_latency_ms)
+               FROM UNNEST(
+                    $2::text[],
+                    $3::bool[],
+                    $4::double precision[]
+               ) AS t(endpoint, success, latency_ms)
+               WHERE
+                    pe.provider_id = $1
+                    AND pe.endpoint = t.endpoint
+            "#,
+            provider_id.as_str(),
+            &endpoints as &[String],
+            &successes as &[bool],
+            &latencies as &[Option<f64>]
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected().try_into()?)
+    }
+
+    pub async fn get_indexer_results(
+        &self,
+        provider_id: &ProviderId,
+    ) -> Result<Vec<ProviderIndexerResult>> {
+        Ok(sqlx::query_as!(
+            ProviderIndexerResult,
+            r#"SELECT
+                    provider_id AS "provider_id: ProviderId",
+                    indexer,
+                    result_code AS "result_code: ResultCode",
+                    error_code AS "error_code: ErrorCode",
+                    endpoints,
+                    fetched_at
+               FROM
+                    provider_indexer_results
+               WHERE
+                    provider_id = $1
+               ORDER BY
+                    indexer
+            "#,
+            provider_id.as_str()
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Replaces the provider's indexer results with the ones from the latest endpoint refresh
+    pub async fn replace_indexer_results(
+        &self,
+        provider_id: &ProviderId,
+        results: &[NewProviderIndexerResult],
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"DELETE FROM
+                    provider_indexer_results
+               WHERE
+                    provider_id = $1
+            "#,
+            provider_id.as_str()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for result in results {
+            sqlx::query!(
+                r#"INSERT INTO
+                        provider_indexer_results (provider_id, indexer, result_code, error_code, endpoints)
+                   VALUES
+                        ($1, $2, $3, $4, $5)
+                "#,
+                provider_id.as_str(),
+                result.indexer,
+                result.result_code.clone() as ResultCode,
+                result.error_code.clone() as Option<ErrorCode>,
+                &result.endpoints
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
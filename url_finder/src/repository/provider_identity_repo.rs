@@ -0,0 +1,238 @@
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::types::ProviderId;
+
+/// On-chain identity of a provider as last recorded by the peer ID refresh
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ProviderIdentity {
+    pub provider_id: ProviderId,
+    pub peer_id: Option<String>,
+    /// None until the first refresh read them from chain
+    pub peer_multiaddrs: Option<Vec<String>>,
+    /// Multiaddrs of the Curio contract's PeerData, empty when not registered there;
+    /// None until the first refresh read them
+    pub peer_curio_multiaddrs: Option<Vec<String>>,
+    pub peer_id_fetched_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ProviderIdentityChange {
+    pub id: Uuid,
+    pub provider_id: ProviderId,
+    pub old_peer_id: Option<String>,
+    pub new_peer_id: Option<String>,
+    pub old_multiaddrs: Option<Vec<String>>,
+    pub new_multiaddrs: Option<Vec<String>>,
+    pub old_curio_multiaddrs: Option<Vec<String>>,
+    pub new_curio_multiaddrs: Option<Vec<String>>,
+    pub detected_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+pub struct ProviderIdentityRepository {
+    pool: PgPool,
+}
+
+impl ProviderIdentityRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Leases providers with a peer ID that was never refreshed or was refreshed before
+    /// `fetched_before` to `owner`
+    pub async fn claim_stale(
+        &self,
+        fetched_before: DateTime<Utc>,
+        limit: i64,
+        owner: &str,
+        lease_ttl_secs: f64,
+    ) -> Result<Vec<ProviderIdentity>> {
+        Ok(sqlx::query_as!(
+            ProviderIdentity,
+            r#"WITH due AS (
+                    SELECT
+                        sp.provider_id
+                    FROM
+                        storage_providers sp
+                    WHERE
+                        sp.peer_id IS NOT NULL
+                        AND (sp.peer_id_fetched_at IS NULL OR sp.peer_id_fetched_at < $1)
+                        AND NOT EXISTS (
+                            SELECT
+                                1
+                            FROM
+                                scheduler_leases leases
+                            WHERE
+                                leases.task = 'peer_id_refresh'
+                                AND leases.resource_id = sp.provider_id
+                                AND leases.expires_at > NOW()
+                        )
+                    ORDER BY
+                        sp.peer_id_fetched_at ASC NULLS FIRST
+                    LIMIT $2
+                    FOR UPDATE OF sp SKIP LOCKED
+                ),
+                leased AS (
+                    INSERT INTO
+                        scheduler_leases (task, resource_id, owner, expires_at)
+                    SELECT
+                        'peer_id_refresh', due.provider_id, $3, NOW() + make_interval(secs => $4)
+                    FROM
+                        due
+                    ON CONFLICT (task, resource_id) DO UPDATE
+                    SET
+                        owner = EXCLUDED.owner,
+                        acquired_at = NOW(),
+                        expires_at = EXCLUDED.expires_at
+                    WHERE
+                        scheduler_leases.expires_at <= NOW()
+                    RETURNING
+                        resource_id
+                )
+                SELECT
+                    provider_id AS "provider_id: ProviderId",
+                    peer_id,
+                    peer_multiaddrs,
+                    peer_curio_multiaddrs,
+                    peer_id_fetched_at
+                FROM
+                    storage_providers
+                    JOIN leased ON leased.resource_id = storage_providers.provider_id
+                ORDER BY
+                    peer_id_fetched_at ASC NULLS FIRST
+            "#,
+            fetched_before,
+            limit,
+            owner,
+            lease_ttl_secs
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Records a refresh that found no change, or the first multiaddrs read for the provider.
+    /// A None set was not read and keeps the recorded one.
+    pub async fn mark_refreshed(
+        &self,
+        provider_id: &ProviderId,
+        multiaddrs: Option<&[String]>,
+        curio_multiaddrs: Option<&[String]>,
+    ) -> Result<()> {
+        sqlx::query!(
+            r#"UPDATE
+                    storage_providers
+               SET
+                    peer_multiaddrs = COALESCE($2, peer_multiaddrs),
+                    peer_curio_multiaddrs = COALESCE($3, peer_curio_multiaddrs),
+                    peer_id_fetched_at = NOW(),
+                    updated_at = NOW()
+               WHERE
+                    provider_id = $1
+            "#,
+            provider_id as &ProviderId,
+            multiaddrs,
+            curio_multiaddrs
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Stores the new identity, records the change and drops the cached endpoints so the
+    /// endpoint scheduler picks the provider up first
+    pub async fn record_change(
+        &self,
+        previous: &ProviderIdentity,
+        peer_id: &str,
+        multiaddrs: &[String],
+        curio_multiaddrs: &[String],
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"INSERT INTO
+                    provider_identity_history (
+                        provider_id,
+                        old_peer_id,
+                        new_peer_id,
+                        old_multiaddrs,
+                        new_multiaddrs,
+                        old_curio_multiaddrs,
+                        new_curio_multiaddrs
+                    )
+               VALUES
+                    ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            &previous.provider_id as &ProviderId,
+            previous.peer_id,
+            peer_id,
+            previous.peer_multiaddrs.as_deref(),
+            multiaddrs,
+            previous.peer_curio_multiaddrs.as_deref(),
+            curio_multiaddrs
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"UPDATE
+                    storage_providers
+               SET
+                    peer_id = $2,
+                    peer_multiaddrs = $3,
+                    peer_curio_multiaddrs = $4,
+                    peer_id_fetched_at = NOW(),
+                    cached_http_endpoints = NULL,
+                    endpoints_fetched_at = NULL,
+                    updated_at = NOW()
+               WHERE
+                    provider_id = $1
+            "#,
+            &previous.provider_id as &ProviderId,
+            peer_id,
+            multiaddrs,
+            curio_multiaddrs
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Most recent identity changes first
+    pub async fn get_history(
+        &self,
+        provider_id: &ProviderId,
+        limit: i64,
+    ) -> Result<Vec<ProviderIdentityChange>> {
+        Ok(sqlx::query_as!(
+            ProviderIdentityChange,
+            r#"SELECT
+                    id,
+                    provider_id AS "provider_id: ProviderId",
+                    old_peer_id,
+                    new_peer_id,
+                    old_multiaddrs,
+                    new_multiaddrs,
+                    old_curio_multiaddrs,
+                    new_curio_multiaddrs,
+                    detected_at
+               FROM
+                    provider_identity_history
+               WHERE
+                    provider_id = $1
+               ORDER BY
+                    detected_at DESC
+               LIMIT $2
+            "#,
+            provider_id as &ProviderId,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+}
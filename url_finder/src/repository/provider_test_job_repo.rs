@@ -0,0 +1,243 @@
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::types::{ProviderId, ProviderTestStatus};
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, sqlx::FromRow)]
+pub struct ProviderTestJob {
+    pub id: Uuid,
+    pub provider_id: ProviderId,
+    pub status: ProviderTestStatus,
+    /// Provider-level URL result written by the run, set once done
+    pub url_result_id: Option<Uuid>,
+    pub error: Option<String>,
+    pub requested_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone)]
+pub struct ProviderTestJobRepository {
+    pool: PgPool,
+}
+
+impl ProviderTestJobRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Queues a test for the provider, or returns its already queued or running job
+    pub async fn enqueue(&self, provider_id: &ProviderId) -> Result<ProviderTestJob> {
+        let inserted = sqlx::query_as!(
+            ProviderTestJob,
+            r#"INSERT INTO
+                    provider_test_jobs (provider_id)
+               VALUES ($1)
+               ON CONFLICT (provider_id) WHERE status IN ('queued', 'running') DO NOTHING
+               RETURNING
+                    id,
+                    provider_id AS "provider_id: ProviderId",
+                    status AS "status: ProviderTestStatus",
+                    url_result_id,
+                    error,
+                    requested_at,
+                    started_at,
+                    completed_at
+            "#,
+            provider_id as &ProviderId
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some(job) = inserted {
+            return Ok(job);
+        }
+
+        Ok(sqlx::query_as!(
+            ProviderTestJob,
+            r#"SELECT
+                    id,
+                    provider_id AS "provider_id: ProviderId",
+                    status AS "status: ProviderTestStatus",
+                    url_result_id,
+                    error,
+                    requested_at,
+                    started_at,
+                    completed_at
+               FROM
+                    provider_test_jobs
+               WHERE
+                    provider_id = $1
+                    AND status IN ('queued', 'running')
+            "#,
+            provider_id as &ProviderId
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    pub async fn get(&self, id: Uuid) -> Result<Option<ProviderTestJob>> {
+        Ok(sqlx::query_as!(
+            ProviderTestJob,
+            r#"SELECT
+                    id,
+                    provider_id AS "provider_id: ProviderId",
+                    status AS "status: ProviderTestStatus",
+                    url_result_id,
+                    error,
+                    requested_at,
+                    started_at,
+                    completed_at
+               FROM
+                    provider_test_jobs
+               WHERE
+                    id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// Marks the oldest queued jobs running and leases them to `owner`, skipping providers whose
+    /// URL discovery is leased. A running job is claimed again once its lease lapses, i.e. the
+    /// instance running it stopped heartbeating.
+    pub async fn claim_queued(
+        &self,
+        limit: i64,
+        owner: &str,
+        lease_ttl_secs: f64,
+    ) -> Result<Vec<ProviderTestJob>> {
+        Ok(sqlx::query_as!(
+            ProviderTestJob,
+            r#"WITH due AS (
+                    SELECT
+                        jobs.id,
+                        jobs.provider_id
+                    FROM
+                        provider_test_jobs jobs
+                    WHERE
+                        jobs.status IN ('queued', 'running')
+                        AND NOT EXISTS (
+                            SELECT
+                                1
+                            FROM
+                                scheduler_leases leases
+                            WHERE
+                                leases.task IN ('url_discovery', 'provider_test')
+                                AND leases.resource_id = jobs.provider_id
+                                AND leases.expires_at > NOW()
+                        )
+                    ORDER BY
+                        jobs.requested_at ASC
+                    LIMIT $1
+                    FOR UPDATE OF jobs SKIP LOCKED
+                ),
+                leased AS (
+                    INSERT INTO
+                        scheduler_leases (task, resource_id, owner, expires_at)
+                    SELECT
+                        'provider_test', due.provider_id, $2, NOW() + make_interval(secs => $3)
+                    FROM
+                        due
+                    ON CONFLICT (task, resource_id) DO UPDATE
+                    SET
+                        owner = EXCLUDED.owner,
+                        acquired_at = NOW(),
+                        expires_at = EXCLUDED.expires_at
+                    WHERE
+                        scheduler_leases.expires_at <= NOW()
+                    RETURNING
+                        resource_id
+                )
+                UPDATE
+                    provider_test_jobs jobs
+                SET
+                    status = 'running',
+                    started_at = NOW(),
+                    claimed_by = $2
+                FROM
+                    due
+                    JOIN leased ON leased.resource_id = due.provider_id
+                WHERE
+                    jobs.id = due.id
+                RETURNING
+                    jobs.id,
+                    jobs.provider_id AS "provider_id: ProviderId",
+                    jobs.status AS "status: ProviderTestStatus",
+                    jobs.url_result_id,
+                    jobs.error,
+                    jobs.requested_at,
+                    jobs.started_at,
+                    jobs.completed_at
+            "#,
+            limit,
+            owner,
+            lease_ttl_secs
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Marks the job done, linking the provider-level result the run wrote, if any
+    pub async fn complete(&self, id: Uuid, url_result_id: Option<Uuid>) -> Result<()> {
+        sqlx::query!(
+            r#"UPDATE
+                    provider_test_jobs
+               SET
+                    status = 'done',
+                    completed_at = NOW(),
+                    url_result_id = $2
+               WHERE
+                    id = $1
+            "#,
+            id,
+            url_result_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Puts a claimed job back in the queue, e.g. when shutdown interrupted its run
+    pub async fn requeue(&self, id: Uuid) -> Result<()> {
+        sqlx::query!(
+            r#"UPDATE
+                    provider_test_jobs
+               SET
+                    status = 'queued',
+                    started_at = NULL,
+                    claimed_by = NULL
+               WHERE
+                    id = $1
+            "#,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn fail(&self, id: Uuid, error: &str) -> Result<()> {
+        sqlx::query!(
+            r#"UPDATE
+                    provider_test_jobs
+               SET
+                    status = 'failed',
+                    completed_at = NOW(),
+                    error = $2
+               WHERE
+                    id = $1
+            "#,
+            id,
+            error
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
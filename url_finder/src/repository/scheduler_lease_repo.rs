@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use color_eyre::Result;
+use sqlx::PgPool;
+
+/// Scheduler work guarded by leases, stored in `scheduler_leases.task`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LeaseTask {
+    UrlDiscovery,
+    EndpointRefresh,
+    BmsJob,
+    ProviderDiscovery,
+    PeerIdRefresh,
+    ProviderTest,
+}
+
+impl LeaseTask {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LeaseTask::UrlDiscovery => "url_discovery",
+            LeaseTask::EndpointRefresh => "endpoint_refresh",
+            LeaseTask::BmsJob => "bms_job",
+            LeaseTask::ProviderDiscovery => "provider_discovery",
+            LeaseTask::PeerIdRefresh => "peer_id_refresh",
+            LeaseTask::ProviderTest => "provider_test",
+        }
+    }
+}
+
+type InFlightLeases = Arc<Mutex<HashMap<(LeaseTask, String), usize>>>;
+
+/// Keeps a lease in the heartbeat while alive. Dropping it, also when the task holding it
+/// panics or is aborted, stops the renewals so the lease lapses and the work is reclaimed.
+pub struct LeaseGuard {
+    in_flight: InFlightLeases,
+    key: (LeaseTask, String),
+}
+
+impl Drop for LeaseGuard {
+    fn drop(&mut self) {
+        let mut in_flight = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(count) = in_flight.get_mut(&self.key) {
+            *count -= 1;
+            if *count == 0 {
+                in_flight.remove(&self.key);
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SchedulerLeaseRepository {
+    pool: PgPool,
+    in_flight: InFlightLeases,
+}
+
+impl SchedulerLeaseRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            in_flight: Arc::default(),
+        }
+    }
+
+    /// Marks a lease this instance took as in flight until the returned guard is dropped
+    pub fn track(&self, task: LeaseTask, resource_id: &str) -> LeaseGuard {
+        let key = (task, resource_id.to_string());
+        *self
+            .in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(key.clone())
+            .or_default() += 1;
+
+        LeaseGuard {
+            in_flight: self.in_flight.clone(),
+            key,
+        }
+    }
+
+    /// Takes the lease unless another owner holds it unexpired; renews it for the current owner
+    pub async fn acquire(
+        &self,
+        task: LeaseTask,
+        resource_id: &str,
+        owner: &str,
+        ttl_secs: f64,
+    ) -> Result<bool> {
+        let acquired = sqlx::query_scalar!(
+            r#"INSERT INTO
+                    scheduler_leases (task, resource_id, owner, expires_at)
+               VALUES ($1, $2, $3, NOW() + make_interval(secs => $4))
+               ON CONFLICT (task, resource_id) DO UPDATE
+               SET
+                    owner = EXCLUDED.owner,
+                    acquired_at = CASE
+                        WHEN scheduler_leases.owner = EXCLUDED.owner THEN scheduler_leases.acquired_at
+                        ELSE NOW()
+                    END,
+                    expires_at = EXCLUDED.expires_at
+               WHERE
+                    scheduler_leases.expires_at <= NOW()
+                    OR scheduler_leases.owner = EXCLUDED.owner
+               RETURNING
+                    resource_id
+            "#,
+            task.as_str(),
+            resource_id,
+            owner,
+            ttl_secs
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(acquired.is_some())
+    }
+
+    /// Extends the in-flight leases held by `owner`, returning how many it still holds.
+    /// Leases nobody tracks anymore are left to expire.
+    pub async fn heartbeat(&self, owner: &str, ttl_secs: f64) -> Result<u64> {
+        let (tasks, resource_ids): (Vec<String>, Vec<String>) = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .keys()
+            .map(|(task, resource_id)| (task.as_str().to_string(), resource_id.clone()))
+            .unzip();
+        if tasks.is_empty() {
+            return Ok(0);
+        }
+
+        let result = sqlx::query!(
+            r#"UPDATE
+                    scheduler_leases
+               SET
+                    expires_at = NOW() + make_interval(secs => $2)
+               FROM
+                    UNNEST($3::text[], $4::text[]) AS in_flight(task, resource_id)
+               WHERE
+                    scheduler_leases.owner = $1
+                    AND scheduler_leases.task = in_flight.task
+                    AND scheduler_leases.resource_id = in_flight.resource_id
+            "#,
+            owner,
+            ttl_secs,
+            &tasks as &[String],
+            &resource_ids as &[String]
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn release(&self, task: LeaseTask, resource_id: &str, owner: &str) -> Result<()> {
+        sqlx::query!(
+            r#"DELETE FROM
+                    scheduler_leases
+               WHERE
+                    task = $1
+                    AND resource_id = $2
+                    AND owner = $3
+            "#,
+            task.as_str(),
+            resource_id,
+            owner
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Frees everything `owner` holds so other instances can pick it up without waiting for expiry
+    pub async fn release_all(&self, owner: &str) -> Result<u64> {
+        let result = sqlx::query!(
+            r#"DELETE FROM
+                    scheduler_leases
+               WHERE
+                    owner = $1
+            "#,
+            owner
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,160 @@
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, sqlx::FromRow)]
+pub struct SchedulerRun {
+    pub id: Uuid,
+    pub scheduler: String,
+    pub instance_id: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub ok_count: i32,
+    pub failed_count: i32,
+    pub skipped_count: i32,
+    /// Why the whole iteration failed
+    pub error: Option<String>,
+    /// Scheduler-specific figures
+    pub details: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewSchedulerRun {
+    pub scheduler: &'static str,
+    pub instance_id: String,
+    pub started_at: DateTime<Utc>,
+    pub ok_count: usize,
+    pub failed_count: usize,
+    pub skipped_count: usize,
+    pub error: Option<String>,
+    pub details: Option<serde_json::Value>,
+}
+
+impl NewSchedulerRun {
+    /// Empty run of `scheduler` started at `started_at`, counts filled in by the caller
+    pub fn new(scheduler: &'static str, instance_id: &str, started_at: DateTime<Utc>) -> Self {
+        Self {
+            scheduler,
+            instance_id: instance_id.to_string(),
+            started_at,
+            ok_count: 0,
+            failed_count: 0,
+            skipped_count: 0,
+            error: None,
+            details: None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SchedulerRunRepository {
+    pool: PgPool,
+}
+
+impl SchedulerRunRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn insert(&self, run: &NewSchedulerRun) -> Result<()> {
+        sqlx::query!(
+            r#"INSERT INTO
+                    scheduler_runs (
+                        scheduler,
+                        instance_id,
+                        started_at,
+                        ok_count,
+                        failed_count,
+                        skipped_count,
+                        error,
+                        details
+                    )
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            run.scheduler,
+            run.instance_id,
+            run.started_at,
+            i32::try_from(run.ok_count)?,
+            i32::try_from(run.failed_count)?,
+            i32::try_from(run.skipped_count)?,
+            run.error,
+            run.details
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Newest runs first, optionally of one scheduler
+    pub async fn list(
+        &self,
+        scheduler: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<SchedulerRun>> {
+        Ok(sqlx::query_as!(
+            SchedulerRun,
+            r#"SELECT
+                    id,
+                    scheduler,
+                    instance_id,
+                    started_at,
+                    finished_at,
+                    ok_count,
+                    failed_count,
+                    skipped_count,
+                    error,
+                    details
+               FROM
+                    scheduler_runs
+               WHERE
+                    $1::text IS NULL
+                    OR scheduler = $1
+               ORDER BY
+                    started_at DESC
+               LIMIT $2
+               OFFSET $3
+            "#,
+            scheduler,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Deletes runs started before `started_before`, returning how many were removed
+    pub async fn delete_started_before(&self, started_before: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query!(
+            r#"DELETE FROM
+                    scheduler_runs
+               WHERE
+                    started_at < $1
+            "#,
+            started_before
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn count(&self, scheduler: Option<&str>) -> Result<i64> {
+        Ok(sqlx::query_scalar!(
+            r#"SELECT
+                    COUNT(*) AS "count!"
+               FROM
+                    scheduler_runs
+               WHERE
+                    $1::text IS NULL
+                    OR scheduler = $1
+            "#,
+            scheduler
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+}
@@ -15,6 +15,8 @@ pub struct StorageProvider {
     pub next_url_discovery_at: DateTime<Utc>,
     pub url_discovery_status: Option<String>,
     pub url_discovery_pending_since: Option<DateTime<Utc>>,
+    /// Why `next_url_discovery_at` was chosen, see `CadenceReason`
+    pub url_discovery_reason: Option<String>,
     pub last_working_url: Option<String>,
     pub next_bms_test_at: DateTime<Utc>,
     pub bms_test_status: Option<String>,
@@ -25,10 +27,27 @@ pub struct StorageProvider {
     pub url_metadata: Option<serde_json::Value>,
     pub cached_http_endpoints: Option<Vec<String>>,
     pub endpoints_fetched_at: Option<DateTime<Utc>>,
+    pub endpoint_protocols: Option<serde_json::Value>,
+    pub protocols_probed_at: Option<DateTime<Utc>>,
+    pub tls_certificates: Option<serde_json::Value>,
+    pub tls_expires_at: Option<DateTime<Utc>>,
+    pub tls_inspected_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// Provider-level outcome of a URL discovery run and when the next one is due
+#[derive(Debug, Clone)]
+pub struct UrlDiscoveryUpdate<'a> {
+    pub last_working_url: Option<String>,
+    pub is_consistent: Option<bool>,
+    pub is_reliable: Option<bool>,
+    pub url_metadata: Option<serde_json::Value>,
+    pub next_interval_hours: i32,
+    /// See `CadenceReason`
+    pub reason: &'a str,
+}
+
 #[derive(Clone)]
 pub struct StorageProviderRepository {
     pool: PgPool,
@@ -77,6 +96,7 @@ impl StorageProviderRepository {
                     next_url_discovery_at,
                     url_discovery_status,
                     url_discovery_pending_since,
+                    url_discovery_reason,
                     last_working_url,
                     next_bms_test_at,
                     bms_test_status,
@@ -87,6 +107,11 @@ impl StorageProviderRepository {
                     url_metadata,
                     cached_http_endpoints,
                     endpoints_fetched_at,
+                    endpoint_protocols,
+                    protocols_probed_at,
+                    tls_certificates,
+                    tls_expires_at,
+                    tls_inspected_at,
                     created_at,
                     updated_at
                FROM
@@ -100,16 +125,67 @@ impl StorageProviderRepository {
         .await?)
     }
 
-    pub async fn get_due_for_url_discovery(&self, limit: i64) -> Result<Vec<StorageProvider>> {
+    /// Leases providers due for URL discovery to `owner`. Providers left pending by an instance
+    /// whose lease expired are claimed again right away.
+    pub async fn claim_due_for_url_discovery(
+        &self,
+        limit: i64,
+        owner: &str,
+        lease_ttl_secs: f64,
+    ) -> Result<Vec<StorageProvider>> {
         Ok(sqlx::query_as!(
             StorageProvider,
-            r#"SELECT
+            r#"WITH due AS (
+                    SELECT
+                        sp.provider_id
+                    FROM
+                        storage_providers sp
+                    WHERE
+                        sp.cached_http_endpoints IS NOT NULL
+                        AND (
+                            sp.next_url_discovery_at <= NOW()
+                            OR sp.url_discovery_status = 'pending'
+                        )
+                        AND NOT EXISTS (
+                            SELECT
+                                1
+                            FROM
+                                scheduler_leases leases
+                            WHERE
+                                leases.task = 'url_discovery'
+                                AND leases.resource_id = sp.provider_id
+                                AND leases.expires_at > NOW()
+                        )
+                    ORDER BY
+                        sp.next_url_discovery_at ASC
+                    LIMIT $1
+                    FOR UPDATE OF sp SKIP LOCKED
+                ),
+                leased AS (
+                    INSERT INTO
+                        scheduler_leases (task, resource_id, owner, expires_at)
+                    SELECT
+                        'url_discovery', due.provider_id, $2, NOW() + make_interval(secs => $3)
+                    FROM
+                        due
+                    ON CONFLICT (task, resource_id) DO UPDATE
+                    SET
+                        owner = EXCLUDED.owner,
+                        acquired_at = NOW(),
+                        expires_at = EXCLUDED.expires_at
+                    WHERE
+                        scheduler_leases.expires_at <= NOW()
+                    RETURNING
+                        resource_id
+                )
+                SELECT
                     id,
                     provider_id AS "provider_id: ProviderId",
                     peer_id,
                     next_url_discovery_at,
                     url_discovery_status,
                     url_discovery_pending_since,
+                    url_discovery_reason,
                     last_working_url,
                     next_bms_test_at,
                     bms_test_status,
@@ -120,31 +196,22 @@ impl StorageProviderRepository {
                     url_metadata,
                     cached_http_endpoints,
                     endpoints_fetched_at,
+                    endpoint_protocols,
+                    protocols_probed_at,
+                    tls_certificates,
+                    tls_expires_at,
+                    tls_inspected_at,
                     created_at,
                     updated_at
-               FROM
+                FROM
                     storage_providers
-               WHERE
-                    cached_http_endpoints IS NOT NULL
-                    AND (
-                        (
-                            next_url_discovery_at <= NOW()
-                            AND url_discovery_status IS DISTINCT FROM 'pending'
-                        )
-                        OR
-                        (
-                            url_discovery_status = 'pending'
-                            AND (
-                                url_discovery_pending_since IS NULL
-                                OR url_discovery_pending_since < NOW() - INTERVAL '60 minutes'
-                            )
-                        )
-                    )
-               ORDER BY
+                    JOIN leased ON leased.resource_id = storage_providers.provider_id
+                ORDER BY
                     next_url_discovery_at ASC
-               LIMIT $1
             "#,
-            limit
+            limit,
+            owner,
+            lease_ttl_secs
         )
         .fetch_all(&self.pool)
         .await?)
@@ -187,16 +254,14 @@ impl StorageProviderRepository {
     pub async fn update_after_url_discovery(
         &self,
         provider_id: &ProviderId,
-        last_working_url: Option<String>,
-        is_consistent: Option<bool>,
-        is_reliable: Option<bool>,
-        url_metadata: Option<serde_json::Value>,
+        update: UrlDiscoveryUpdate<'_>,
     ) -> Result<()> {
         sqlx::query!(
             r#"UPDATE
                     storage_providers
                SET
-                    next_url_discovery_at = DATE_TRUNC('day', NOW()) + INTERVAL '1 day',
+                    next_url_discovery_at = NOW() + make_interval(hours => $6),
+                    url_discovery_reason = $7,
                     url_discovery_status = NULL,
                     url_discovery_pending_since = NULL,
                     last_working_url = $2,
@@ -208,10 +273,12 @@ impl StorageProviderRepository {
                     provider_id = $1
             "#,
             provider_id as &ProviderId,
-            last_working_url,
-            is_consistent,
-            is_reliable,
-            url_metadata
+            update.last_working_url,
+            update.is_consistent,
+            update.is_reliable,
+            update.url_metadata,
+            update.next_interval_hours,
+            update.reason
         )
         .execute(&self.pool)
         .await?;
@@ -254,16 +321,64 @@ impl StorageProviderRepository {
         Ok(())
     }
 
-    pub async fn get_due_for_bms_test(&self, limit: i64) -> Result<Vec<StorageProvider>> {
+    /// Leases providers due for a BMS test to `owner`
+    pub async fn claim_due_for_bms_test(
+        &self,
+        limit: i64,
+        owner: &str,
+        lease_ttl_secs: f64,
+    ) -> Result<Vec<StorageProvider>> {
         Ok(sqlx::query_as!(
             StorageProvider,
-            r#"SELECT
+            r#"WITH due AS (
+                    SELECT
+                        sp.provider_id
+                    FROM
+                        storage_providers sp
+                    WHERE
+                        sp.last_working_url IS NOT NULL
+                        AND sp.is_consistent = true
+                        AND sp.next_bms_test_at <= NOW()
+                        AND NOT EXISTS (
+                            SELECT
+                                1
+                            FROM
+                                scheduler_leases leases
+                            WHERE
+                                leases.task = 'bms_job'
+                                AND leases.resource_id = sp.provider_id
+                                AND leases.expires_at > NOW()
+                        )
+                    ORDER BY
+                        sp.next_bms_test_at ASC
+                    LIMIT $1
+                    FOR UPDATE OF sp SKIP LOCKED
+                ),
+                leased AS (
+                    INSERT INTO
+                        scheduler_leases (task, resource_id, owner, expires_at)
+                    SELECT
+                        'bms_job', due.provider_id, $2, NOW() + make_interval(secs => $3)
+                    FROM
+                        due
+                    ON CONFLICT (task, resource_id) DO UPDATE
+                    SET
+                        owner = EXCLUDED.owner,
+                        acquired_at = NOW(),
+                        expires_at = EXCLUDED.expires_at
+                    WHERE
+                        scheduler_leases.expires_at <= NOW()
+                    RETURNING
+                        resource_id
+                )
+                SELECT
                     id,
                     provider_id AS "provider_id: ProviderId",
                     peer_id,
                     next_url_discovery_at,
                     url_discovery_status,
                     url_discovery_pending_since,
+                    url_discovery_reason,
                     last_working_url,
                     next_bms_test_at,
                     bms_test_status,
@@ -274,19 +389,22 @@ impl StorageProviderRepository {
                     url_metadata,
                     cached_http_endpoints,
                     endpoints_fetched_at,
+                    endpoint_protocols,
+                    protocols_probed_at,
+                    tls_certificates,
+                    tls_expires_at,
+                    tls_inspected_at,
                     created_at,
                     updated_at
-               FROM
+                FROM
                     storage_providers
-               WHERE
-                    last_working_url IS NOT NULL
-                    AND is_consistent = true
-                    AND next_bms_test_at <= NOW()
-               ORDER BY
+                    JOIN leased ON leased.resource_id = storage_providers.provider_id
+                ORDER BY
                     next_bms_test_at ASC
-               LIMIT $1
             "#,
-            limit
+            limit,
+            owner,
+            lease_ttl_secs
         )
         .fetch_all(&self.pool)
         .await?)
@@ -334,6 +452,7 @@ impl StorageProviderRepository {
                     next_url_discovery_at,
                     url_discovery_status,
                     url_discovery_pending_since,
+                    url_discovery_reason,
                     last_working_url,
                     next_bms_test_at,
                     bms_test_status,
@@ -344,6 +463,11 @@ impl StorageProviderRepository {
                     url_metadata,
                     cached_http_endpoints,
                     endpoints_fetched_at,
+                    endpoint_protocols,
+                    protocols_probed_at,
+                    tls_certificates,
+                    tls_expires_at,
+                    tls_inspected_at,
                     created_at,
                     updated_at
             "#,
@@ -373,6 +497,7 @@ impl StorageProviderRepository {
                     next_url_discovery_at,
                     url_discovery_status,
                     url_discovery_pending_since,
+                    url_discovery_reason,
                     last_working_url,
                     next_bms_test_at,
                     bms_test_status,
@@ -383,6 +508,11 @@ impl StorageProviderRepository {
                     url_metadata,
                     cached_http_endpoints,
                     endpoints_fetched_at,
+                    endpoint_protocols,
+                    protocols_probed_at,
+                    tls_certificates,
+                    tls_expires_at,
+                    tls_inspected_at,
                     created_at,
                     updated_at
             "#,
@@ -413,6 +543,7 @@ impl StorageProviderRepository {
                     next_url_discovery_at,
                     url_discovery_status,
                     url_discovery_pending_since,
+                    url_discovery_reason,
                     last_working_url,
                     next_bms_test_at,
                     bms_test_status,
@@ -423,6 +554,11 @@ impl StorageProviderRepository {
                     url_metadata,
                     cached_http_endpoints,
                     endpoints_fetched_at,
+                    endpoint_protocols,
+                    protocols_probed_at,
+                    tls_certificates,
+                    tls_expires_at,
+                    tls_inspected_at,
                     created_at,
                     updated_at
             "#,
@@ -432,19 +568,65 @@ impl StorageProviderRepository {
         .await?)
     }
 
-    pub async fn get_providers_needing_endpoints(
+    /// Leases providers whose endpoints were not fetched today to `owner`
+    pub async fn claim_providers_needing_endpoints(
         &self,
         limit: i64,
+        owner: &str,
+        lease_ttl_secs: f64,
     ) -> Result<Vec<StorageProvider>> {
         Ok(sqlx::query_as!(
             StorageProvider,
-            r#"SELECT
+            r#"WITH due AS (
+                    SELECT
+                        sp.provider_id
+                    FROM
+                        storage_providers sp
+                    WHERE
+                        (
+                            sp.endpoints_fetched_at IS NULL
+                            OR sp.endpoints_fetched_at < DATE_TRUNC('day', NOW())
+                        )
+                        AND NOT EXISTS (
+                            SELECT
+                                1
+                            FROM
+                                scheduler_leases leases
+                            WHERE
+                                leases.task = 'endpoint_refresh'
+                                AND leases.resource_id = sp.provider_id
+                                AND leases.expires_at > NOW()
+                        )
+                    ORDER BY
+                        sp.endpoints_fetched_at ASC NULLS FIRST
+                    LIMIT $1
+                    FOR UPDATE OF sp SKIP LOCKED
+                ),
+                leased AS (
+                    INSERT INTO
+                        scheduler_leases (task, resource_id, owner, expires_at)
+                    SELECT
+                        'endpoint_refresh', due.provider_id, $2, NOW() + make_interval(secs => $3)
+                    FROM
+                        due
+                    ON CONFLICT (task, resource_id) DO UPDATE
+                    SET
+                        owner = EXCLUDED.owner,
+                        acquired_at = NOW(),
+                        expires_at = EXCLUDED.expires_at
+                    WHERE
+                        scheduler_leases.expires_at <= NOW()
+                    RETURNING
+                        resource_id
+                )
+                SELECT
                     id,
                     provider_id AS "provider_id: ProviderId",
                     peer_id,
                     next_url_discovery_at,
                     url_discovery_status,
                     url_discovery_pending_since,
+                    url_discovery_reason,
                     last_working_url,
                     next_bms_test_at,
                     bms_test_status,
@@ -455,23 +637,28 @@ impl StorageProviderRepository {
                     url_metadata,
                     cached_http_endpoints,
                     endpoints_fetched_at,
+                    endpoint_protocols,
+                    protocols_probed_at,
+                    tls_certificates,
+                    tls_expires_at,
+                    tls_inspected_at,
                     created_at,
                     updated_at
-               FROM
+                FROM
                     storage_providers
-               WHERE
-                    endpoints_fetched_at IS NULL
-                    OR endpoints_fetched_at < DATE_TRUNC('day', NOW())
-               ORDER BY
+                    JOIN leased ON leased.resource_id = storage_providers.provider_id
+                ORDER BY
                     endpoints_fetched_at ASC NULLS FIRST
-               LIMIT $1
             "#,
-            limit
+            limit,
+            owner,
+            lease_ttl_secs
         )
         .fetch_all(&self.pool)
         .await?)
     }
 
+    /// URL discovery is pulled forward only when the endpoint set changed
     pub async fn update_cached_endpoints(
         &self,
         provider_id: &ProviderId,
@@ -485,7 +672,18 @@ impl StorageProviderRepository {
                     peer_id = $2,
                     cached_http_endpoints = $3,
                     endpoints_fetched_at = NOW(),
-                    next_url_discovery_at = NOW(),
+                    next_url_discovery_at = CASE
+                        WHEN cached_http_endpoints IS NULL
+                            OR NOT (cached_http_endpoints @> $3 AND cached_http_endpoints <@ $3)
+                        THEN NOW()
+                        ELSE next_url_discovery_at
+                    END,
+                    url_discovery_reason = CASE
+                        WHEN cached_http_endpoints IS NULL
+                            OR NOT (cached_http_endpoints @> $3 AND cached_http_endpoints <@ $3)
+                        THEN 'endpoints_changed'
+                        ELSE url_discovery_reason
+                    END,
                     updated_at = NOW()
                WHERE
                     provider_id = $1
@@ -499,6 +697,55 @@ impl StorageProviderRepository {
         Ok(())
     }
 
+    pub async fn update_endpoint_protocols(
+        &self,
+        provider_id: &ProviderId,
+        endpoint_protocols: serde_json::Value,
+    ) -> Result<()> {
+        sqlx::query!(
+            r#"UPDATE
+                    storage_providers
+               SET
+                    endpoint_protocols = $2,
+                    protocols_probed_at = NOW(),
+                    updated_at = NOW()
+               WHERE
+                    provider_id = $1
+            "#,
+            provider_id as &ProviderId,
+            endpoint_protocols
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn update_tls_inspection(
+        &self,
+        provider_id: &ProviderId,
+        tls_certificates: serde_json::Value,
+        tls_expires_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        sqlx::query!(
+            r#"UPDATE
+                    storage_providers
+               SET
+                    tls_certificates = $2,
+                    tls_expires_at = $3,
+                    tls_inspected_at = NOW(),
+                    updated_at = NOW()
+               WHERE
+                    provider_id = $1
+            "#,
+            provider_id as &ProviderId,
+            tls_certificates,
+            tls_expires_at
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
     pub async fn mark_endpoint_fetch_failed(&self, provider_id: &ProviderId) -> Result<()> {
         sqlx::query!(
             r#"UPDATE
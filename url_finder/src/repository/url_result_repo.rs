@@ -15,6 +15,8 @@ pub struct ProviderFilters {
     pub has_working_url: Option<bool>,
     /// Filter by is_consistent in storage_providers
     pub is_consistent: Option<bool>,
+    /// Filter by tls_expires_at within this many days (includes already expired)
+    pub tls_expiring_within_days: Option<i32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, sqlx::FromRow)]
@@ -74,6 +76,13 @@ pub struct HistoryRow {
     pub large_files_percent: Option<f64>,
 }
 
+/// Outcome of one provider-level discovery run
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct DiscoveryRun {
+    pub result_code: ResultCode,
+    pub retrievability_percent: Option<f64>,
+}
+
 #[derive(Clone)]
 pub struct UrlResultRepository {
     pool: PgPool,
@@ -123,6 +132,36 @@ impl UrlResultRepository {
         Ok(result)
     }
 
+    pub async fn get_by_id(&self, id: Uuid) -> Result<Option<UrlResult>> {
+        Ok(sqlx::query_as!(
+            UrlResult,
+            r#"SELECT
+                    id,
+                    provider_id AS "provider_id: ProviderId",
+                    client_id AS "client_id: ClientId",
+                    result_type AS "result_type: DiscoveryType",
+                    working_url,
+                    retrievability_percent::float8 AS "retrievability_percent",
+                    result_code AS "result_code: ResultCode",
+                    error_code AS "error_code: ErrorCode",
+                    tested_at,
+                    is_consistent,
+                    is_reliable,
+                    url_metadata,
+                    sector_utilization_percent::float8 AS "sector_utilization_percent",
+                    car_files_percent::float8 AS "car_files_percent",
+                    large_files_percent::float8 AS "large_files_percent"
+               FROM
+                    url_results
+               WHERE
+                    id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
     pub async fn get_latest_for_provider_client(
         &self,
         provider_id: &ProviderId,
@@ -236,6 +275,7 @@ impl UrlResultRepository {
                     ur.result_type = 'Provider'
                     AND ($3::bool IS NULL OR (sp.last_working_url IS NOT NULL) = $3)
                     AND ($4::bool IS NULL OR sp.is_consistent = $4)
+                    AND ($5::int IS NULL OR sp.tls_expires_at < NOW() + make_interval(days => $5))
                ORDER BY
                     ur.provider_id,
                     ur.tested_at DESC
@@ -245,7 +285,8 @@ impl UrlResultRepository {
             limit,
             offset,
             filters.has_working_url,
-            filters.is_consistent
+            filters.is_consistent,
+            filters.tls_expiring_within_days
         )
         .fetch_all(&self.pool)
         .await?;
@@ -265,9 +306,11 @@ impl UrlResultRepository {
                     ur.result_type = 'Provider'
                     AND ($1::bool IS NULL OR (sp.last_working_url IS NOT NULL) = $1)
                     AND ($2::bool IS NULL OR sp.is_consistent = $2)
+                    AND ($3::int IS NULL OR sp.tls_expires_at < NOW() + make_interval(days => $3))
             "#,
             filters.has_working_url,
-            filters.is_consistent
+            filters.is_consistent,
+            filters.tls_expiring_within_days
         )
         .fetch_one(&self.pool)
         .await?;
@@ -403,6 +446,55 @@ impl UrlResultRepository {
         Ok(result.rows_affected().try_into()?)
     }
 
+    /// Adds the CommP verification, which finishes after the result itself is stored
+    pub async fn set_commp_verification(
+        &self,
+        id: Uuid,
+        commp_verification: &serde_json::Value,
+    ) -> Result<()> {
+        sqlx::query!(
+            r#"UPDATE
+                    url_results
+               SET
+                    url_metadata = jsonb_set(COALESCE(url_metadata, '{}'::jsonb), '{commp_verification}', $2)
+               WHERE
+                    id = $1
+            "#,
+            id,
+            commp_verification
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Latest provider-level runs, newest first
+    pub async fn get_recent_provider_runs(
+        &self,
+        provider_id: &ProviderId,
+        limit: i64,
+    ) -> Result<Vec<DiscoveryRun>> {
+        Ok(sqlx::query_as!(
+            DiscoveryRun,
+            r#"SELECT
+                    result_code AS "result_code: ResultCode",
+                    retrievability_percent::float8 AS "retrievability_percent"
+               FROM
+                    url_results
+               WHERE
+                    provider_id = $1
+                    AND result_type = 'Provider'
+               ORDER BY
+                    tested_at DESC
+               LIMIT $2
+            "#,
+            provider_id.as_str(),
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
     pub async fn get_history_for_provider(
         &self,
         provider_id: &ProviderId,
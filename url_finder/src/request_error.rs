@@ -0,0 +1,259 @@
+//! Classification of reqwest failures into `UrlTestError`.
+//!
+//! reqwest flattens most failures into a few predicates, so the cause is found by walking
+//! the `source()` chain down to the resolver, hyper, rustls and `std::io` errors.
+
+use std::error::Error;
+use std::io;
+
+use hickory_resolver::ResolveError;
+use hickory_resolver::proto::{ProtoErrorKind, op::ResponseCode};
+
+use crate::tls_inspection::tls_failure_from_error;
+use crate::types::UrlTestError;
+
+/// The error and all of its sources, including errors wrapped by `io::Error`
+pub fn error_chain<'a>(error: &'a (dyn Error + 'static)) -> Vec<&'a (dyn Error + 'static)> {
+    let mut chain = Vec::new();
+    let mut current = Some(error);
+    while let Some(err) = current {
+        chain.push(err);
+        // io::Error::source() skips the wrapped error, so look inside explicitly
+        if let Some(inner) = err.downcast_ref::<io::Error>().and_then(|io| io.get_ref()) {
+            chain.push(inner);
+        }
+        current = err.source();
+    }
+    chain
+}
+
+/// Classifies a reqwest error by the most specific cause found in its source chain
+pub fn classify_request_error(e: &reqwest::Error) -> UrlTestError {
+    if e.is_redirect() {
+        return UrlTestError::Redirect;
+    }
+    if let Some(failure) = tls_failure_from_error(e) {
+        return failure.into();
+    }
+
+    let chain = error_chain(e);
+
+    if let Some(resolve) = chain
+        .iter()
+        .find_map(|err| err.downcast_ref::<ResolveError>())
+    {
+        return classify_resolve_error(resolve);
+    }
+
+    // reqwest's own deadlines carry no io error, so check them before the io kinds
+    if e.is_timeout() {
+        return if e.is_connect() {
+            UrlTestError::ConnectTimeout
+        } else if e.is_body() || e.is_decode() {
+            UrlTestError::ReadTimeout
+        } else {
+            UrlTestError::Timeout
+        };
+    }
+
+    for err in &chain {
+        if let Some(io) = err.downcast_ref::<io::Error>() {
+            match io.kind() {
+                io::ErrorKind::ConnectionRefused => return UrlTestError::ConnectionRefused,
+                io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe => return UrlTestError::ConnectionReset,
+                io::ErrorKind::TimedOut if e.is_connect() => {
+                    return UrlTestError::ConnectTimeout;
+                }
+                io::ErrorKind::TimedOut => return UrlTestError::ReadTimeout,
+                io::ErrorKind::UnexpectedEof if e.is_body() => {
+                    return UrlTestError::BodyTruncated;
+                }
+                _ => {}
+            }
+        }
+        if let Some(hyper_error) = err.downcast_ref::<hyper::Error>() {
+            if hyper_error.is_incomplete_message() {
+                // Closed mid-body is truncation, closed before any response is a reset
+                return if e.is_body() || e.is_decode() {
+                    UrlTestError::BodyTruncated
+                } else {
+                    UrlTestError::ConnectionReset
+                };
+            }
+            if hyper_error.is_parse() || hyper_error.is_parse_status() {
+                return UrlTestError::HttpProtocolError;
+            }
+        }
+    }
+
+    if e.is_connect() {
+        UrlTestError::ConnectionFailed
+    } else if e.is_body() || e.is_decode() {
+        UrlTestError::BodyTruncated
+    } else {
+        UrlTestError::Other(e.to_string())
+    }
+}
+
+fn classify_resolve_error(e: &ResolveError) -> UrlTestError {
+    match e.proto().map(|proto| proto.kind()) {
+        Some(ProtoErrorKind::NoRecordsFound {
+            response_code: ResponseCode::NXDomain,
+            ..
+        }) => UrlTestError::DnsNxDomain,
+        Some(ProtoErrorKind::NoRecordsFound {
+            response_code: ResponseCode::ServFail | ResponseCode::Refused,
+            ..
+        }) => UrlTestError::DnsServFail,
+        _ => UrlTestError::DnsFailure,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_resolver::proto::{ProtoError, op::Query};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use wiremock::{Mock, MockServer, ResponseTemplate, matchers::method};
+
+    fn no_records(response_code: ResponseCode) -> ResolveError {
+        ProtoError::from(ProtoErrorKind::NoRecordsFound {
+            query: Box::new(Query::default()),
+            soa: None,
+            ns: None,
+            negative_ttl: None,
+            response_code,
+            trusted: true,
+            authorities: None,
+        })
+        .into()
+    }
+
+    /// Serves one connection: reads the request, writes `response`, then holds or closes it
+    async fn raw_server(response: &'static [u8], hold: Duration) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf).await;
+            let _ = socket.write_all(response).await;
+            tokio::time::sleep(hold).await;
+        });
+        format!("http://{addr}/piece/x")
+    }
+
+    async fn fetch_body(client: &reqwest::Client, url: &str) -> reqwest::Error {
+        match client.get(url).send().await {
+            Ok(resp) => resp.bytes().await.unwrap_err(),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn test_classify_resolve_error() {
+        assert_eq!(
+            classify_resolve_error(&no_records(ResponseCode::NXDomain)),
+            UrlTestError::DnsNxDomain
+        );
+        assert_eq!(
+            classify_resolve_error(&no_records(ResponseCode::ServFail)),
+            UrlTestError::DnsServFail
+        );
+        assert_eq!(
+            classify_resolve_error(&no_records(ResponseCode::NoError)),
+            UrlTestError::DnsFailure
+        );
+    }
+
+    #[test]
+    fn test_error_chain_includes_io_wrapped_error() {
+        let io = io::Error::other(no_records(ResponseCode::NXDomain));
+
+        let chain = error_chain(&io);
+
+        assert!(chain.iter().any(|e| e.is::<ResolveError>()));
+    }
+
+    #[tokio::test]
+    async fn test_classify_connection_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let e = reqwest::get(format!("http://127.0.0.1:{port}/"))
+            .await
+            .unwrap_err();
+
+        assert_eq!(classify_request_error(&e), UrlTestError::ConnectionRefused);
+    }
+
+    #[tokio::test]
+    async fn test_classify_request_timeout() {
+        let url = raw_server(b"", Duration::from_secs(5)).await;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+
+        let e = client.get(&url).send().await.unwrap_err();
+
+        assert_eq!(classify_request_error(&e), UrlTestError::Timeout);
+    }
+
+    #[tokio::test]
+    async fn test_classify_read_timeout() {
+        let url = raw_server(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\npartial",
+            Duration::from_secs(5),
+        )
+        .await;
+        let client = reqwest::Client::builder()
+            .read_timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+
+        let e = fetch_body(&client, &url).await;
+
+        assert_eq!(classify_request_error(&e), UrlTestError::ReadTimeout);
+    }
+
+    #[tokio::test]
+    async fn test_classify_body_truncated() {
+        let url = raw_server(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\npartial",
+            Duration::ZERO,
+        )
+        .await;
+
+        let e = fetch_body(&reqwest::Client::new(), &url).await;
+
+        assert_eq!(classify_request_error(&e), UrlTestError::BodyTruncated);
+    }
+
+    #[tokio::test]
+    async fn test_classify_http_protocol_error() {
+        let url = raw_server(b"NOT HTTP AT ALL\r\n\r\n", Duration::ZERO).await;
+
+        let e = reqwest::get(&url).await.unwrap_err();
+
+        assert_eq!(classify_request_error(&e), UrlTestError::HttpProtocolError);
+    }
+
+    #[tokio::test]
+    async fn test_classify_redirect_loop() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(302).insert_header("Location", "/again"))
+            .mount(&mock_server)
+            .await;
+
+        let e = reqwest::get(mock_server.uri()).await.unwrap_err();
+
+        assert_eq!(classify_request_error(&e), UrlTestError::Redirect);
+    }
+}
@@ -85,6 +85,11 @@ pub fn create_routes() -> Router<Arc<AppState>> {
             "/providers/{id}/reset",
             post(providers::handle_reset_provider),
         )
+        .route(
+            "/providers/{id}/tests",
+            post(providers::handle_create_provider_test),
+        )
+        .route("/tests/{job_id}", get(providers::handle_get_provider_test))
         .route(
             "/providers/{id}/clients/{client_id}",
             get(providers::handle_get_provider_client),
@@ -116,6 +121,16 @@ pub fn create_routes() -> Router<Arc<AppState>> {
                 .error_handler(too_many_requests_error_handler),
         );
 
+    let admin_api_routes = Router::new()
+        .route(
+            "/admin/scheduler-runs",
+            get(admin::handle_list_scheduler_runs),
+        )
+        .layer(
+            GovernorLayer::new(governor_config.clone())
+                .error_handler(too_many_requests_error_handler),
+        );
+
     let healthcheck_route = Router::new()
         .route("/healthcheck", get(handle_healthcheck))
         .layer(
@@ -128,5 +143,6 @@ pub fn create_routes() -> Router<Arc<AppState>> {
         .merge(legacy_api_routes)
         .merge(providers_api_routes)
         .merge(deals_api_routes)
+        .merge(admin_api_routes)
         .merge(healthcheck_route)
 }
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use crate::config::RELIABILITY_TIMEOUT_THRESHOLD;
 use crate::types::{InconsistencyType, ProviderAnalysis, UrlTestError, UrlTestResult};
 
@@ -8,17 +10,40 @@ pub fn analyze_results(results: &[UrlTestResult]) -> ProviderAnalysis {
 
     let total = results.len();
     let success_count = results.iter().filter(|r| r.success).count();
-    let valid_car_count = results.iter().filter(|r| r.is_valid_car).count();
+    // A valid header with garbage after it is not a real CAR
+    let corrupt_car_count = results.iter().filter(|r| r.is_corrupt_car()).count();
+    let valid_car_count = results
+        .iter()
+        .filter(|r| r.is_valid_car && !r.is_corrupt_car())
+        .count();
+    let car_blocks_verified = results
+        .iter()
+        .filter_map(|r| r.car_blocks.as_ref())
+        .map(|b| b.blocks_verified)
+        .sum();
+    let carv2_index_checked_count = results
+        .iter()
+        .filter(|r| r.carv2_index_valid.is_some())
+        .count();
+    let carv2_index_valid_count = results
+        .iter()
+        .filter(|r| r.carv2_index_valid == Some(true))
+        .count();
     let timeout_count = results
         .iter()
-        .filter(|r| matches!(r.error, Some(UrlTestError::Timeout)))
+        .filter(|r| r.error.as_ref().is_some_and(UrlTestError::is_timeout))
         .count();
+    let mut failure_causes = BTreeMap::new();
+    for error in results.iter().filter_map(|r| r.error.as_ref()) {
+        *failure_causes.entry(error.cause()).or_insert(0) += 1;
+    }
 
     let mut inconsistent_count = 0;
     let mut warm_up = 0;
     let mut flaky = 0;
     let mut small_responses = 0;
     let mut size_mismatch = 0;
+    let mut random_range = 0;
 
     for r in results.iter().filter(|r| !r.consistent) {
         inconsistent_count += 1;
@@ -27,10 +52,20 @@ pub fn analyze_results(results: &[UrlTestResult]) -> ProviderAnalysis {
             Some(InconsistencyType::Flaky) => flaky += 1,
             Some(InconsistencyType::SmallResponses) => small_responses += 1,
             Some(InconsistencyType::SizeMismatch) => size_mismatch += 1,
+            Some(InconsistencyType::RandomRange) => random_range += 1,
             None => {}
         }
     }
 
+    let (range_requested, range_passed) = results
+        .iter()
+        .filter_map(|r| r.random_range.as_ref())
+        .fold((0, 0), |(requested, passed), c| {
+            (requested + c.offsets.len(), passed + c.passed_count)
+        });
+    let random_range_success_percent =
+        (range_requested > 0).then(|| (range_passed as f64 / range_requested as f64) * 100.0);
+
     let total_requests = total * 2;
     let timeout_rate = timeout_count as f64 / total_requests as f64;
 
@@ -49,22 +84,30 @@ pub fn analyze_results(results: &[UrlTestResult]) -> ProviderAnalysis {
         sample_count: total,
         success_count,
         timeout_count,
+        failure_causes,
         inconsistent_count,
         inconsistent_warm_up: warm_up,
         inconsistent_flaky: flaky,
         inconsistent_small_responses: small_responses,
         inconsistent_size_mismatch: size_mismatch,
+        inconsistent_random_range: random_range,
+        random_range_success_percent,
         http_responded_count,
         failed_count,
         valid_car_count,
         small_car_count: 0,
+        corrupt_car_count,
+        car_blocks_verified,
+        carv2_index_checked_count,
+        carv2_index_valid_count,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::InconsistencyType;
+    use crate::car_header::CarBlockScan;
+    use crate::types::{InconsistencyType, RandomRangeCheck, Transport};
 
     fn make_result(
         success: bool,
@@ -74,6 +117,7 @@ mod tests {
     ) -> UrlTestResult {
         UrlTestResult {
             url: "http://test".to_string(),
+            transport: Transport::Http,
             success,
             consistent,
             inconsistency_type: if consistent {
@@ -86,12 +130,17 @@ mod tests {
             error,
             is_valid_car,
             root_cid: None,
+            car_blocks: None,
+            carv2_index_valid: None,
+            timing: Default::default(),
+            random_range: None,
         }
     }
 
     fn make_inconsistent(inconsistency_type: InconsistencyType) -> UrlTestResult {
         UrlTestResult {
             url: "http://test".to_string(),
+            transport: Transport::Http,
             success: true,
             consistent: false,
             inconsistency_type: Some(inconsistency_type),
@@ -100,6 +149,10 @@ mod tests {
             error: None,
             is_valid_car: false,
             root_cid: None,
+            car_blocks: None,
+            carv2_index_valid: None,
+            timing: Default::default(),
+            random_range: None,
         }
     }
 
@@ -219,6 +272,59 @@ mod tests {
         assert!(analysis.is_consistent);
     }
 
+    #[test]
+    fn test_random_range_success_percent() {
+        let check = |passed_count| {
+            Some(RandomRangeCheck {
+                seed: 7,
+                offsets: vec![4096, 8192],
+                passed_count,
+            })
+        };
+        let mut passing = make_result(true, true, None, false);
+        passing.random_range = check(2);
+        let mut failing = make_inconsistent(InconsistencyType::RandomRange);
+        failing.random_range = check(1);
+        let unsampled = make_result(false, true, Some(UrlTestError::Timeout), false);
+
+        let analysis = analyze_results(&[passing, failing, unsampled]);
+
+        assert_eq!(analysis.inconsistent_random_range, 1);
+        assert_eq!(analysis.random_range_success_percent, Some(75.0));
+    }
+
+    #[test]
+    fn test_random_range_success_percent_none_without_samples() {
+        let results = vec![make_result(true, true, None, false)];
+
+        let analysis = analyze_results(&results);
+
+        assert_eq!(analysis.random_range_success_percent, None);
+    }
+
+    #[test]
+    fn test_corrupt_car_not_counted_as_valid() {
+        let mut corrupt = make_result(true, true, None, true);
+        corrupt.car_blocks = Some(CarBlockScan {
+            blocks_verified: 1,
+            first_bad_block_offset: Some(512),
+            hash_algorithms: vec!["sha2-256".to_string()],
+        });
+        let mut verified = make_result(true, true, None, true);
+        verified.car_blocks = Some(CarBlockScan {
+            blocks_verified: 2,
+            first_bad_block_offset: None,
+            hash_algorithms: vec!["sha2-256".to_string()],
+        });
+
+        let analysis = analyze_results(&[corrupt, verified]);
+
+        assert_eq!(analysis.valid_car_count, 1);
+        assert_eq!(analysis.corrupt_car_count, 1);
+        assert_eq!(analysis.car_blocks_verified, 3);
+        assert_eq!(analysis.car_files_percent, 50.0);
+    }
+
     #[test]
     fn test_car_retrievability_counted() {
         let results = vec![
@@ -245,6 +351,7 @@ mod tests {
             make_result(true, true, None, false),
             UrlTestResult {
                 url: "http://test".to_string(),
+                transport: Transport::Http,
                 success: false,
                 consistent: false,
                 inconsistency_type: Some(InconsistencyType::SmallResponses),
@@ -253,6 +360,10 @@ mod tests {
                 error: None,
                 is_valid_car: false,
                 root_cid: None,
+                car_blocks: None,
+                carv2_index_valid: None,
+                timing: Default::default(),
+                random_range: None,
             },
         ];
 
@@ -286,4 +397,24 @@ mod tests {
         assert_eq!(analysis.http_responded_count, 1);
         assert_eq!(analysis.failed_count, 2);
     }
+
+    #[test]
+    fn test_failure_causes_breakdown() {
+        let results = vec![
+            make_result(true, true, None, false),
+            make_result(false, true, Some(UrlTestError::DnsNxDomain), false),
+            make_result(false, true, Some(UrlTestError::DnsNxDomain), false),
+            make_result(false, true, Some(UrlTestError::ConnectTimeout), false),
+            make_result(false, true, Some(UrlTestError::HttpError(503)), false),
+        ];
+
+        let analysis = analyze_results(&results);
+
+        assert_eq!(analysis.failure_causes.get("dns_nxdomain"), Some(&2));
+        assert_eq!(analysis.failure_causes.get("connect_timeout"), Some(&1));
+        assert_eq!(analysis.failure_causes.get("http_5xx"), Some(&1));
+        assert_eq!(analysis.failure_causes.len(), 3);
+        // Connect timeouts still count towards reliability
+        assert_eq!(analysis.timeout_count, 1);
+    }
 }
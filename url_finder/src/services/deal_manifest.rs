@@ -315,7 +315,7 @@ mod tests {
         let raw = br#"[{"pieces":[]}]"#;
         let expected = "0f736d80eccc8276fe81992e0d2b64a1203d45b902fa3c9ea1956c19f1c0b876";
 
-        assert!(manifest_hash_matches(&expected, raw));
+        assert!(manifest_hash_matches(expected, raw));
         assert!(manifest_hash_matches(&format!("0x{expected}"), raw));
         assert!(!manifest_hash_matches("00", raw));
     }
@@ -1,17 +1,33 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::Utc;
 use color_eyre::Result;
+use futures::{StreamExt, stream};
 use sqlx::types::BigDecimal;
+use tracing::{debug, warn};
 
 use crate::{
-    repository::DealRepository,
+    car_header::normalize_cid,
+    config::Config,
+    pix_filspark,
+    repository::{
+        DealLabelRepository, DealRepository, NewDealLabel, NewPixSample, PixSampleRepository,
+    },
     types::{ClientAddress, ClientId, ProviderAddress, ProviderId},
 };
 
+const LABEL_FETCH_CONCURRENCY: usize = 5;
+const PIX_FETCH_CONCURRENCY: usize = 5;
+/// Uncached pieces sampled from pix.filspark per call; the rest follow on later runs
+const MAX_PIX_LOOKUPS: usize = 20;
+
 /// Context for testing a piece URL with deal metadata
 #[derive(Debug, Clone)]
 pub struct PieceTestContext {
     pub piece_cid: String,
     pub deal_id: i32,
     pub piece_size: Option<i64>,
+    pub endpoint: String,
     pub url: String,
 }
 
@@ -51,6 +67,166 @@ pub async fn get_piece_contexts_by_provider(
     Ok(contexts)
 }
 
+/// Resolve deal Label payload CIDs (normalized to CIDv1 base32) keyed by deal_id.
+/// Reads through the `deal_labels` cache; missing labels are fetched from chain and cached.
+/// Deals whose label could not be fetched are left out and retried on the next run.
+pub async fn get_payload_cids(
+    config: &Config,
+    label_repo: &DealLabelRepository,
+    piece_contexts: &[(String, i32, Option<i64>)],
+) -> Result<HashMap<i32, String>> {
+    let deal_ids: Vec<i32> = piece_contexts
+        .iter()
+        .map(|(_, deal_id, _)| *deal_id)
+        .filter(|deal_id| *deal_id > 0)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+
+    let cached = label_repo.get_by_deal_ids(&deal_ids).await?;
+    let cached_ids: HashSet<i32> = cached.iter().map(|l| l.deal_id).collect();
+
+    let mut payload_cids: HashMap<i32, String> = cached
+        .into_iter()
+        .filter_map(|l| Some((l.deal_id, l.payload_cid?)))
+        .collect();
+
+    let missing: Vec<(i32, String)> = piece_contexts
+        .iter()
+        .filter(|(_, deal_id, _)| *deal_id > 0 && !cached_ids.contains(deal_id))
+        .map(|(piece_cid, deal_id, _)| (*deal_id, piece_cid.clone()))
+        .collect::<HashMap<_, _>>()
+        .into_iter()
+        .collect();
+
+    if missing.is_empty() {
+        return Ok(payload_cids);
+    }
+
+    let fetched: Vec<NewDealLabel> = stream::iter(missing)
+        .map(|(deal_id, piece_cid)| async move {
+            match config.chain.get_deal_label(deal_id).await {
+                Ok(label_raw) => Some(NewDealLabel {
+                    deal_id,
+                    piece_cid,
+                    payload_cid: label_raw.as_deref().and_then(normalize_cid),
+                    label_raw,
+                }),
+                Err(e) => {
+                    warn!("Failed to fetch label for deal {}: {:?}", deal_id, e);
+                    None
+                }
+            }
+        })
+        .buffer_unordered(LABEL_FETCH_CONCURRENCY)
+        .filter_map(|label| async move { label })
+        .collect()
+        .await;
+
+    debug!("Fetched {} deal labels from chain", fetched.len());
+    label_repo.insert_batch(&fetched).await?;
+
+    payload_cids.extend(
+        fetched
+            .into_iter()
+            .filter_map(|l| Some((l.deal_id, l.payload_cid?))),
+    );
+
+    Ok(payload_cids)
+}
+
+/// Resolve pix.filspark payload CID samples keyed by piece CID.
+/// Reads through the `pix_payload_samples` cache, fetching at most MAX_PIX_LOOKUPS expired or
+/// missing pieces. Pieces without a sample are cached too; failed fetches are retried next run.
+pub async fn get_pix_samples(
+    config: &Config,
+    pix_repo: &PixSampleRepository,
+    provider_id: &ProviderId,
+    peer_id: &str,
+    piece_cids: &[String],
+) -> Result<HashMap<String, String>> {
+    let piece_cids: Vec<String> = piece_cids
+        .iter()
+        .cloned()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+
+    let fetched_after = Utc::now() - chrono::Duration::hours(config.pix_sample_ttl_hours);
+    let cached = pix_repo
+        .get_fresh(provider_id, &piece_cids, fetched_after)
+        .await?;
+    let cached_pieces: HashSet<String> = cached.iter().map(|s| s.piece_cid.clone()).collect();
+
+    let mut samples: HashMap<String, String> = cached
+        .into_iter()
+        .filter_map(|s| Some((s.piece_cid, s.payload_cid?)))
+        .collect();
+
+    let missing: Vec<String> = piece_cids
+        .into_iter()
+        .filter(|piece_cid| !cached_pieces.contains(piece_cid))
+        .take(MAX_PIX_LOOKUPS)
+        .collect();
+    if missing.is_empty() {
+        return Ok(samples);
+    }
+
+    let fetched: Vec<NewPixSample> = stream::iter(missing)
+        .map(|piece_cid| async move {
+            match pix_filspark::get_samples(&config.pix_filspark_url, peer_id, &piece_cid).await {
+                Ok(cids) => Some(NewPixSample {
+                    piece_cid,
+                    payload_cid: cids.into_iter().next(),
+                }),
+                Err(e) => {
+                    warn!("Failed to fetch pix sample for {}: {:?}", piece_cid, e);
+                    None
+                }
+            }
+        })
+        .buffer_unordered(PIX_FETCH_CONCURRENCY)
+        .filter_map(|sample| async move { sample })
+        .collect()
+        .await;
+
+    debug!(
+        "Fetched {} pix samples for {} from pix.filspark",
+        fetched.len(),
+        provider_id
+    );
+    pix_repo.upsert_batch(provider_id, &fetched).await?;
+
+    samples.extend(
+        fetched
+            .into_iter()
+            .filter_map(|s| Some((s.piece_cid, s.payload_cid?))),
+    );
+
+    Ok(samples)
+}
+
+/// CIDs to look up in IPNI for a few random deals: cached Label payload CIDs first, since
+/// indexers key on payload block multihashes, then the piece CIDs. Does not read chain state.
+pub async fn get_ipni_lookup_cids(
+    deal_repo: &DealRepository,
+    label_repo: &DealLabelRepository,
+    provider_id: &ProviderId,
+    limit: i64,
+) -> Result<Vec<String>> {
+    let deals = deal_repo
+        .get_random_deals_by_provider(provider_id, limit, 0)
+        .await?;
+    let deal_ids: Vec<i32> = deals.iter().map(|deal| deal.deal_id).collect();
+    let labels = label_repo.get_by_deal_ids(&deal_ids).await?;
+
+    Ok(labels
+        .into_iter()
+        .filter_map(|l| l.payload_cid)
+        .chain(deals.into_iter().filter_map(|deal| deal.piece_cid))
+        .collect())
+}
+
 fn bigdecimal_to_i64(val: &BigDecimal) -> Option<i64> {
     use std::str::FromStr;
     i64::from_str(&val.to_string()).ok()
@@ -151,6 +327,7 @@ pub fn build_piece_test_contexts(
                     piece_cid: piece_cid.clone(),
                     deal_id: *deal_id,
                     piece_size: *piece_size,
+                    endpoint: endpoint.to_string(),
                     url: format!("{endpoint}/piece/{piece_cid}"),
                 })
         })
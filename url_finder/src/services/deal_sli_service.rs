@@ -1,6 +1,7 @@
-use std::{collections::BTreeMap, str::FromStr, sync::Arc};
+use std::{collections::BTreeMap, str::FromStr, sync::Arc, time::Duration};
 
 use sqlx::types::BigDecimal;
+use tracing::{error, warn};
 use uuid::Uuid;
 
 use crate::{
@@ -10,16 +11,17 @@ use crate::{
         DealTargetUpsertRequest, DealVersion, MeasurementState,
     },
     config::Config,
-    http_client::build_client,
+    http_client::{build_client, build_streaming_client},
     repository::{
-        DealSliBmsJob, DealSliLatestRun, DealSliManifestSnapshot, DealSliPiece, DealSliRepository,
-        DealSliRequirementValues, DealSliRunPieceSnapshot, DealSliRunTarget,
-        DealSliTargetWithPieces, NewCompletedDealSliRun, NewDealSliManifestSnapshot,
-        NewDealSliPiece, NewDealSliPieceResult, NewDealSliTarget, StorageProviderRepository,
+        CommpSource, CommpVerificationRepository, DealSliBmsJob, DealSliLatestRun,
+        DealSliManifestSnapshot, DealSliPiece, DealSliRepository, DealSliRequirementValues,
+        DealSliRunPieceSnapshot, DealSliRunTarget, DealSliTargetWithPieces, NewCompletedDealSliRun,
+        NewDealSliManifestSnapshot, NewDealSliPiece, NewDealSliPieceResult, NewDealSliTarget,
+        StorageProviderRepository,
     },
     services::deal_manifest::{FetchedManifestSnapshot, fetch_manifest_snapshot},
     types::{ErrorCode, ProviderAddress, ProviderId, ResultCode},
-    url_tester::{ManifestUrlTestResult, test_manifest_urls_double_tap},
+    url_tester::{ManifestUrlTestResult, test_manifest_urls_double_tap, verify_piece_commp},
 };
 
 const MAX_MANUAL_RUN_URL_TESTS: usize = 2_048;
@@ -43,6 +45,7 @@ impl From<color_eyre::Report> for DealSliServiceError {
 pub struct DealSliService {
     repo: Arc<DealSliRepository>,
     storage_provider_repo: Arc<StorageProviderRepository>,
+    commp_repo: Arc<CommpVerificationRepository>,
     config: Arc<Config>,
 }
 
@@ -50,11 +53,13 @@ impl DealSliService {
     pub fn new(
         repo: Arc<DealSliRepository>,
         storage_provider_repo: Arc<StorageProviderRepository>,
+        commp_repo: Arc<CommpVerificationRepository>,
         config: Arc<Config>,
     ) -> Self {
         Self {
             repo,
             storage_provider_repo,
+            commp_repo,
             config,
         }
     }
@@ -150,7 +155,7 @@ impl DealSliService {
 
         let latest = match cached_endpoints {
             Some(endpoints) => {
-                self.run_cached_endpoint_measurement(deal_id, &run_target, endpoints)
+                self.run_cached_endpoint_measurement(deal_id, &provider_id, &run_target, endpoints)
                     .await?
             }
             None => {
@@ -168,6 +173,7 @@ impl DealSliService {
     async fn run_cached_endpoint_measurement(
         &self,
         deal_id: &str,
+        provider_id: &ProviderId,
         run_target: &DealSliRunTarget,
         endpoints: Vec<String>,
     ) -> std::result::Result<DealSliLatestRun, DealSliServiceError> {
@@ -216,6 +222,9 @@ impl DealSliService {
             .collect::<Vec<_>>();
         let url_results = test_manifest_urls_double_tap(&client, tests).await;
         let aggregate = aggregate_manifest_results(&test_contexts, &url_results);
+        let commp_results = self
+            .verify_commp_samples(provider_id, &test_contexts, &url_results)
+            .await;
 
         let run = build_manifest_measurement_run(
             deal_id,
@@ -223,6 +232,7 @@ impl DealSliService {
             &sampled_pieces,
             &test_contexts,
             &url_results,
+            &commp_results,
             &aggregate,
         )?;
 
@@ -231,12 +241,109 @@ impl DealSliService {
             .await
             .map_err(map_run_insert_error)
     }
+
+    /// Streams size-matched pieces within the provider's weekly budget and recomputes their CommP.
+    /// Returns one entry per URL result; None where verification was not attempted or inconclusive.
+    async fn verify_commp_samples(
+        &self,
+        provider_id: &ProviderId,
+        contexts: &[DealSliPieceTestContext],
+        url_results: &[ManifestUrlTestResult],
+    ) -> Vec<Option<bool>> {
+        let mut commp_results = vec![None; url_results.len()];
+        if !self.config.commp_verification_enabled {
+            return commp_results;
+        }
+
+        let mut seen_pieces = std::collections::HashSet::new();
+        let candidates = contexts
+            .iter()
+            .zip(url_results)
+            .enumerate()
+            .filter(|(_, (_, result))| result.size_matched)
+            .filter(|(_, (context, _))| seen_pieces.insert(context.piece_index))
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            return commp_results;
+        }
+
+        let piece_cids = candidates
+            .iter()
+            .map(|(_, (context, _))| context.piece_cid.clone())
+            .collect::<Vec<_>>();
+        let reservations = match self
+            .commp_repo
+            .reserve(
+                provider_id,
+                &piece_cids,
+                self.config.commp_verification_pieces_per_week,
+                CommpSource::DealSli,
+            )
+            .await
+        {
+            Ok(reservations) => reservations,
+            Err(error) => {
+                warn!("Failed to reserve CommP verifications for {provider_id}: {error:?}");
+                return commp_results;
+            }
+        };
+        if reservations.is_empty() {
+            return commp_results;
+        }
+
+        let client = match build_streaming_client(&self.config) {
+            Ok(client) => client,
+            Err(error) => {
+                error!("Failed to build streaming HTTP client: {error:?}");
+                return commp_results;
+            }
+        };
+
+        let timeout = Duration::from_secs(self.config.commp_verification_timeout_secs as u64);
+        for reservation in reservations {
+            let Some((index, (context, result))) = candidates
+                .iter()
+                .find(|(_, (context, _))| context.piece_cid == reservation.piece_cid)
+            else {
+                continue;
+            };
+            let padded_piece_size = context
+                .piece_size_bytes
+                .and_then(|value| u64::try_from(value).ok());
+            let verification = verify_piece_commp(
+                &client,
+                &result.url,
+                &context.piece_cid,
+                padded_piece_size,
+                timeout,
+            )
+            .await;
+            if let Err(error) = self
+                .commp_repo
+                .record_outcome(
+                    reservation.id,
+                    verification.commp_verified,
+                    verification.bytes_read,
+                )
+                .await
+            {
+                warn!(
+                    "Failed to record CommP verification of {}: {error:?}",
+                    context.piece_cid
+                );
+            }
+            commp_results[*index] = verification.commp_verified;
+        }
+
+        commp_results
+    }
 }
 
 #[derive(Debug)]
 struct DealSliPieceTestContext {
     piece_index: i32,
     piece_cid: String,
+    piece_size_bytes: Option<i64>,
     manifest_snapshot_id: Option<Uuid>,
     file_size_bytes: Option<BigDecimal>,
     expected_file_size_bytes: Option<i64>,
@@ -254,6 +361,7 @@ fn build_piece_test_contexts(
             pieces.iter().map(move |piece| DealSliPieceTestContext {
                 piece_index: piece.piece_index,
                 piece_cid: piece.piece_cid.clone(),
+                piece_size_bytes: piece.piece_size_bytes.as_ref().and_then(bigdecimal_to_i64),
                 manifest_snapshot_id: piece.manifest_snapshot_id,
                 file_size_bytes: piece.file_size_bytes.clone(),
                 expected_file_size_bytes: piece
@@ -312,6 +420,7 @@ fn build_manifest_measurement_run(
     sampled_pieces: &[DealSliPiece],
     test_contexts: &[DealSliPieceTestContext],
     url_results: &[ManifestUrlTestResult],
+    commp_results: &[Option<bool>],
     aggregate: &ManifestResultAggregate,
 ) -> std::result::Result<NewCompletedDealSliRun, DealSliServiceError> {
     let manifest_snapshot_id = run_target
@@ -331,7 +440,10 @@ fn build_manifest_measurement_run(
     let piece_results = test_contexts
         .iter()
         .zip(url_results.iter())
-        .map(|(context, result)| map_manifest_piece_result(deal_id, context, result))
+        .zip(commp_results.iter())
+        .map(|((context, result), commp_verified)| {
+            map_manifest_piece_result(deal_id, context, result, *commp_verified)
+        })
         .collect::<Vec<_>>();
     let working_url = url_results
         .iter()
@@ -393,6 +505,7 @@ fn map_manifest_piece_result(
     deal_id: &str,
     context: &DealSliPieceTestContext,
     result: &ManifestUrlTestResult,
+    commp_verified: Option<bool>,
 ) -> NewDealSliPieceResult {
     NewDealSliPieceResult {
         deal_id: deal_id.to_string(),
@@ -407,6 +520,7 @@ fn map_manifest_piece_result(
         size_matched: Some(result.size_matched),
         manifest_response_time_ms: result.response_time_ms,
         is_valid_car: false,
+        commp_verified,
         result_code: if result.size_matched {
             ResultCode::Success
         } else {
@@ -0,0 +1,195 @@
+//! Per-provider URL discovery cadence.
+//!
+//! Providers with the same outcome over the last `url_discovery_stable_runs` runs back off,
+//! doubling the interval for every further steady run up to the configured maximum. Providers
+//! whose last run differed from the one before, whose endpoints were recently added, or that
+//! are referenced by Deal SLI targets are retested after the minimum interval instead.
+
+use chrono::Duration;
+
+use crate::config::Config;
+use crate::repository::DiscoveryRun;
+
+/// Retrievability moves within this many points still count as a steady outcome
+const STABLE_RETRIEVABILITY_TOLERANCE: f64 = 10.0;
+/// Backoff steps beyond the stability threshold, enough to reach any sane maximum
+pub const MAX_BACKOFF_STEPS: i64 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CadenceReason {
+    Default,
+    Stable,
+    StateChanged,
+    EndpointsChanged,
+    DealSliTarget,
+}
+
+impl CadenceReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CadenceReason::Default => "default",
+            CadenceReason::Stable => "stable",
+            CadenceReason::StateChanged => "state_changed",
+            CadenceReason::EndpointsChanged => "endpoints_changed",
+            CadenceReason::DealSliTarget => "deal_sli_target",
+        }
+    }
+}
+
+fn same_outcome(a: &DiscoveryRun, b: &DiscoveryRun) -> bool {
+    if a.result_code != b.result_code {
+        return false;
+    }
+    match (a.retrievability_percent, b.retrievability_percent) {
+        (Some(a), Some(b)) => (a - b).abs() <= STABLE_RETRIEVABILITY_TOLERANCE,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cadence {
+    pub interval: Duration,
+    pub reason: CadenceReason,
+}
+
+/// Picks the delay until the next discovery. `recent_runs` are newest first and include the
+/// run that just finished.
+pub fn next_cadence(
+    config: &Config,
+    recent_runs: &[DiscoveryRun],
+    endpoints_changed: bool,
+    has_deal_sli_target: bool,
+) -> Cadence {
+    let pulled_forward = |reason| Cadence {
+        interval: Duration::hours(config.url_discovery_min_interval_hours),
+        reason,
+    };
+
+    if let [latest, previous, ..] = recent_runs
+        && !same_outcome(latest, previous)
+    {
+        return pulled_forward(CadenceReason::StateChanged);
+    }
+    if endpoints_changed {
+        return pulled_forward(CadenceReason::EndpointsChanged);
+    }
+    if has_deal_sli_target {
+        return pulled_forward(CadenceReason::DealSliTarget);
+    }
+
+    let steady_runs = match recent_runs.first() {
+        Some(latest) => recent_runs
+            .iter()
+            .take_while(|run| same_outcome(run, latest))
+            .count() as i64,
+        None => 0,
+    };
+    if steady_runs < config.url_discovery_stable_runs {
+        return Cadence {
+            interval: Duration::hours(config.url_discovery_interval_hours),
+            reason: CadenceReason::Default,
+        };
+    }
+
+    let steps = (steady_runs - config.url_discovery_stable_runs + 1).min(MAX_BACKOFF_STEPS);
+    let hours = (config.url_discovery_interval_hours << steps)
+        .min(config.url_discovery_max_interval_hours)
+        .max(config.url_discovery_interval_hours);
+    Cadence {
+        interval: Duration::hours(hours),
+        reason: CadenceReason::Stable,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ResultCode;
+
+    fn config() -> Config {
+        Config::new_for_test(String::new(), String::new())
+    }
+
+    fn run(result_code: ResultCode, retrievability_percent: f64) -> DiscoveryRun {
+        DiscoveryRun {
+            result_code,
+            retrievability_percent: Some(retrievability_percent),
+        }
+    }
+
+    fn steady(count: usize) -> Vec<DiscoveryRun> {
+        vec![run(ResultCode::Success, 95.0); count]
+    }
+
+    #[test]
+    fn first_runs_use_default_interval() {
+        let cadence = next_cadence(&config(), &steady(1), false, false);
+
+        assert_eq!(cadence.reason, CadenceReason::Default);
+        assert_eq!(cadence.interval, Duration::hours(24));
+    }
+
+    #[test]
+    fn stable_runs_back_off_up_to_maximum() {
+        let config = config();
+
+        let cadence = next_cadence(&config, &steady(3), false, false);
+        assert_eq!(cadence.reason, CadenceReason::Stable);
+        assert_eq!(cadence.interval, Duration::hours(48));
+
+        assert_eq!(
+            next_cadence(&config, &steady(4), false, false).interval,
+            Duration::hours(96)
+        );
+        assert_eq!(
+            next_cadence(&config, &steady(10), false, false).interval,
+            Duration::hours(168)
+        );
+    }
+
+    #[test]
+    fn small_retrievability_moves_stay_stable() {
+        let runs = vec![
+            run(ResultCode::Success, 90.0),
+            run(ResultCode::Success, 95.0),
+            run(ResultCode::Success, 88.0),
+        ];
+
+        assert_eq!(
+            next_cadence(&config(), &runs, false, false).reason,
+            CadenceReason::Stable
+        );
+    }
+
+    #[test]
+    fn changed_outcome_is_pulled_forward() {
+        let mut runs = steady(5);
+        runs.insert(0, run(ResultCode::FailedToGetWorkingUrl, 0.0));
+
+        let cadence = next_cadence(&config(), &runs, false, false);
+        assert_eq!(cadence.reason, CadenceReason::StateChanged);
+        assert_eq!(cadence.interval, Duration::hours(6));
+
+        let mut runs = steady(5);
+        runs.insert(0, run(ResultCode::Success, 40.0));
+        assert_eq!(
+            next_cadence(&config(), &runs, false, false).reason,
+            CadenceReason::StateChanged
+        );
+    }
+
+    #[test]
+    fn endpoint_changes_and_sli_targets_are_pulled_forward() {
+        let config = config();
+
+        assert_eq!(
+            next_cadence(&config, &steady(5), true, true).reason,
+            CadenceReason::EndpointsChanged
+        );
+        assert_eq!(
+            next_cadence(&config, &steady(5), false, true).reason,
+            CadenceReason::DealSliTarget
+        );
+    }
+}
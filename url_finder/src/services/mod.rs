@@ -2,5 +2,6 @@ pub mod consistency_analyzer;
 pub mod deal_manifest;
 pub mod deal_service;
 pub mod deal_sli_service;
+pub mod discovery_cadence;
 pub mod provider_service;
 pub mod url_discovery_service;
@@ -5,10 +5,13 @@ use chrono::{DateTime, Utc};
 use color_eyre::Result;
 use sqlx::types::BigDecimal;
 
+use crate::protocol_probe::EndpointProtocols;
 use crate::repository::{
-    BmsBandwidthResult, BmsBandwidthResultRepository, ProviderFilters, StorageProviderRepository,
-    UrlResult, UrlResultRepository,
+    BmsBandwidthResult, BmsBandwidthResultRepository, ProviderEndpoint, ProviderEndpointRepository,
+    ProviderFilters, ProviderIndexerResult, StorageProviderRepository, UrlResult,
+    UrlResultRepository,
 };
+use crate::tls_inspection::TlsInspection;
 use crate::types::{ClientId, ErrorCode, ProviderId, ResultCode};
 
 // --- Domain Types ---
@@ -31,6 +34,17 @@ pub struct ProviderData {
     pub performance: PerformanceData,
 }
 
+impl ProviderData {
+    /// Payload CID retrievable from the working URL, recorded by URL discovery
+    pub fn sample_payload_cid(&self) -> Option<String> {
+        self.url_metadata
+            .as_ref()?
+            .get("sample_payload_cid")?
+            .as_str()
+            .map(String::from)
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct PerformanceData {
     pub bandwidth: Option<BandwidthResult>,
@@ -42,6 +56,7 @@ pub struct SchedulingData {
     pub url_discovery_next_at: Option<DateTime<Utc>>,
     pub url_discovery_status: Option<String>,
     pub url_discovery_pending_since: Option<DateTime<Utc>>,
+    pub url_discovery_reason: Option<String>,
     pub bms_test_next_at: Option<DateTime<Utc>>,
     pub bms_test_status: Option<String>,
 }
@@ -103,6 +118,7 @@ pub struct ProviderService {
     url_repo: Arc<UrlResultRepository>,
     bms_repo: Arc<BmsBandwidthResultRepository>,
     sp_repo: Arc<StorageProviderRepository>,
+    endpoint_repo: Arc<ProviderEndpointRepository>,
 }
 
 impl ProviderService {
@@ -110,11 +126,13 @@ impl ProviderService {
         url_repo: Arc<UrlResultRepository>,
         bms_repo: Arc<BmsBandwidthResultRepository>,
         sp_repo: Arc<StorageProviderRepository>,
+        endpoint_repo: Arc<ProviderEndpointRepository>,
     ) -> Self {
         Self {
             url_repo,
             bms_repo,
             sp_repo,
+            endpoint_repo,
         }
     }
 
@@ -221,11 +239,50 @@ impl ProviderService {
             url_discovery_next_at: Some(sp.next_url_discovery_at),
             url_discovery_status: sp.url_discovery_status,
             url_discovery_pending_since: sp.url_discovery_pending_since,
+            url_discovery_reason: sp.url_discovery_reason,
             bms_test_next_at: Some(sp.next_bms_test_at),
             bms_test_status: sp.bms_test_status,
         }))
     }
 
+    /// Last protocol probe of the provider's endpoints, None if never probed
+    pub async fn get_endpoint_protocols(
+        &self,
+        provider_id: &ProviderId,
+    ) -> Result<Option<Vec<EndpointProtocols>>> {
+        let sp = self.sp_repo.get_by_provider_id(provider_id).await?;
+        Ok(sp
+            .and_then(|sp| sp.endpoint_protocols)
+            .and_then(|v| serde_json::from_value(v).ok()))
+    }
+
+    /// Last TLS inspection of the provider's https endpoints, None if never inspected
+    pub async fn get_tls_inspections(
+        &self,
+        provider_id: &ProviderId,
+    ) -> Result<Option<Vec<TlsInspection>>> {
+        let sp = self.sp_repo.get_by_provider_id(provider_id).await?;
+        Ok(sp
+            .and_then(|sp| sp.tls_certificates)
+            .and_then(|v| serde_json::from_value(v).ok()))
+    }
+
+    /// Tracked endpoints of the provider with their retrieval health
+    pub async fn get_endpoint_health(
+        &self,
+        provider_id: &ProviderId,
+    ) -> Result<Vec<ProviderEndpoint>> {
+        self.endpoint_repo.get_by_provider(provider_id).await
+    }
+
+    /// Latest provider record lookup on each configured IPNI indexer
+    pub async fn get_indexer_results(
+        &self,
+        provider_id: &ProviderId,
+    ) -> Result<Vec<ProviderIndexerResult>> {
+        self.endpoint_repo.get_indexer_results(provider_id).await
+    }
+
     // --- Private helpers ---
 
     fn enrich(
@@ -1,18 +1,55 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
 use chrono::{DateTime, Utc};
+use futures::{StreamExt, future::join_all, stream};
 
 use crate::{
+    car_header::normalize_cid,
     config::{Config, MIN_VALID_CONTENT_LENGTH},
-    http_client::build_client,
-    repository::DealRepository,
-    services::{consistency_analyzer::analyze_results, deal_service},
+    connection_timing::{ConnectionTiming, measure_connection},
+    endpoint_preflight::{EndpointPreflight, preflight_endpoint},
+    http_client::{build_client, build_streaming_client, proxy_configured},
+    repository::{
+        CommpSource, CommpVerificationRepository, DealLabelRepository, DealRepository,
+        DealSliRepository, EndpointTestOutcome, PixSampleRepository, ProviderEndpoint,
+        ProviderEndpointRepository, SchedulerLeaseRepository, StorageProviderRepository,
+        UrlResultRepository,
+    },
+    services::{
+        consistency_analyzer::analyze_results,
+        deal_service::{self, PieceTestContext},
+    },
     types::{
         ClientAddress, ClientId, DiscoveryType, ErrorCode, ProviderAddress, ProviderId, ResultCode,
+        UrlTestResult,
+    },
+    url_tester::{
+        CommpVerificationResult, GatewayTestResult, measure_throughput, test_gateway_retrieval,
+        test_url_double_tap, verify_piece_commp,
     },
-    url_tester::test_url_double_tap,
 };
-use tracing::{debug, error, trace};
+use tokio::sync::Semaphore;
+use tracing::{debug, error, trace, warn};
 use uuid::Uuid;
 
+/// Repositories used by URL discovery and the schedulers driving it, shared across tasks
+#[derive(Clone)]
+pub struct DiscoveryDeps {
+    pub sp_repo: Arc<StorageProviderRepository>,
+    pub url_repo: Arc<UrlResultRepository>,
+    pub deal_repo: Arc<DealRepository>,
+    pub deal_label_repo: Arc<DealLabelRepository>,
+    pub pix_sample_repo: Arc<PixSampleRepository>,
+    pub endpoint_repo: Arc<ProviderEndpointRepository>,
+    pub deal_sli_repo: Arc<DealSliRepository>,
+    pub lease_repo: Arc<SchedulerLeaseRepository>,
+    pub commp_repo: Arc<CommpVerificationRepository>,
+    /// Bounds CommP verifications, which stream full pieces after their discovery has finished
+    pub commp_permits: Arc<Semaphore>,
+}
+
 #[derive(Debug, Clone)]
 pub struct UrlDiscoveryResult {
     pub id: Uuid,
@@ -30,6 +67,17 @@ pub struct UrlDiscoveryResult {
     pub sector_utilization_percent: Option<f64>,
     pub car_files_percent: Option<f64>,
     pub large_files_percent: Option<f64>,
+    pub endpoint_outcomes: Vec<EndpointTestOutcome>,
+    /// Pieces to stream for CommP verification once the result is stored
+    pub commp_candidates: Vec<CommpCandidate>,
+}
+
+/// Piece that passed the double-tap test, streamed in full to recompute its CommP
+#[derive(Debug, Clone)]
+pub struct CommpCandidate {
+    pub piece_cid: String,
+    pub url: String,
+    pub padded_piece_size: Option<u64>,
 }
 
 impl UrlDiscoveryResult {
@@ -50,6 +98,8 @@ impl UrlDiscoveryResult {
             sector_utilization_percent: None,
             car_files_percent: None,
             large_files_percent: None,
+            endpoint_outcomes: vec![],
+            commp_candidates: vec![],
         }
     }
 
@@ -70,17 +120,20 @@ impl UrlDiscoveryResult {
             sector_utilization_percent: None,
             car_files_percent: None,
             large_files_percent: None,
+            endpoint_outcomes: vec![],
+            commp_candidates: vec![],
         }
     }
 }
 
 pub async fn discover_url(
     config: &Config,
+    deps: &DiscoveryDeps,
     provider_address: &ProviderAddress,
+    peer_id: Option<&str>,
     client_address: Option<ClientAddress>,
-    deal_repo: &DealRepository,
     endpoints: Vec<String>,
-    tested_at: Option<DateTime<Utc>>,
+    endpoint_health: &[ProviderEndpoint],
 ) -> UrlDiscoveryResult {
     let provider_id: ProviderId = provider_address.clone().into();
     let client_id: Option<ClientId> = client_address.clone().map(|c| c.into());
@@ -94,18 +147,18 @@ pub async fn discover_url(
         Some(c) => UrlDiscoveryResult::new_provider_client(provider_id.clone(), c.clone()),
         None => UrlDiscoveryResult::new_provider_only(provider_id.clone()),
     };
-    if let Some(ts) = tested_at {
-        result.tested_at = ts;
-    }
 
     if endpoints.is_empty() {
         result.result_code = ResultCode::MissingHttpAddrFromCidContact;
         return result;
     }
 
+    // One spelling per endpoint for preflight, test contexts and the recorded outcomes
+    let endpoints = rank_endpoints(normalize_endpoints(endpoints), endpoint_health, Utc::now());
+
     // Get piece contexts (piece_cid + deal_id)
     let piece_contexts = match deal_service::get_piece_contexts_by_provider(
-        deal_repo,
+        &deps.deal_repo,
         &provider_id,
         client_id.as_ref(),
     )
@@ -128,8 +181,54 @@ pub async fn discover_url(
         return result;
     }
 
+    let payload_cids = match deal_service::get_payload_cids(
+        config,
+        &deps.deal_label_repo,
+        &piece_contexts,
+    )
+    .await
+    {
+        Ok(cids) => cids,
+        Err(e) => {
+            warn!(
+                "Failed to resolve deal labels for {} {:?}: {:?}",
+                provider_id, client_id, e
+            );
+            HashMap::new()
+        }
+    };
+
+    // Deals without a Label payload CID can still be gateway tested with a pix.filspark sample
+    let sample_cids = match peer_id.filter(|_| config.pix_filspark_enabled) {
+        Some(peer_id) => {
+            let unlabelled: Vec<String> = piece_contexts
+                .iter()
+                .filter(|(_, deal_id, _)| !payload_cids.contains_key(deal_id))
+                .map(|(piece_cid, _, _)| piece_cid.clone())
+                .collect();
+            let pix_samples = match deal_service::get_pix_samples(
+                config,
+                &deps.pix_sample_repo,
+                &provider_id,
+                peer_id,
+                &unlabelled,
+            )
+            .await
+            {
+                Ok(samples) => samples,
+                Err(e) => {
+                    warn!("Failed to get pix samples for {}: {:?}", provider_id, e);
+                    HashMap::new()
+                }
+            };
+            merge_sample_cids(&payload_cids, &pix_samples, &piece_contexts)
+        }
+        None => payload_cids.clone(),
+    };
+
     // Build test contexts with deal_id preserved
-    let test_contexts = deal_service::build_piece_test_contexts(endpoints.clone(), piece_contexts);
+    let mut test_contexts =
+        deal_service::build_piece_test_contexts(endpoints.clone(), piece_contexts);
     debug!(
         "Built {} test contexts from endpoints: {:?}",
         test_contexts.len(),
@@ -146,14 +245,82 @@ pub async fn discover_url(
         }
     };
 
+    // Skip piece testing on endpoints that cannot be reached at all
+    let preflights = if config.endpoint_preflight_enabled {
+        let proxied = proxy_configured(config);
+        join_all(
+            endpoints
+                .iter()
+                .map(|endpoint| preflight_endpoint(&client, endpoint, proxied)),
+        )
+        .await
+    } else {
+        vec![]
+    };
+    let unreachable: Vec<&EndpointPreflight> =
+        preflights.iter().filter(|p| !p.reachable()).collect();
+    if !unreachable.is_empty() {
+        debug!(
+            "{} of {} endpoints of {} failed preflight: {:?}",
+            unreachable.len(),
+            endpoints.len(),
+            provider_id,
+            unreachable
+        );
+        test_contexts.retain(|ctx| !unreachable.iter().any(|p| p.endpoint == ctx.endpoint));
+    }
+    let unreachable_outcomes: Vec<EndpointTestOutcome> = unreachable
+        .iter()
+        .map(|p| EndpointTestOutcome {
+            endpoint: p.endpoint.clone(),
+            success: false,
+            avg_latency_ms: None,
+        })
+        .collect();
+
+    if test_contexts.is_empty() && !unreachable.is_empty() {
+        result.result_code = ResultCode::FailedToGetWorkingUrl;
+        result.url_metadata = Some(serde_json::json!({
+            "preflight": preflight_metadata(&preflights),
+        }));
+        result.endpoint_outcomes = unreachable_outcomes;
+        return result;
+    }
+
+    // Cold-connection phases are shared by all pieces of an endpoint, and the raw probe
+    // would bypass the proxy
+    let connections: HashMap<String, ConnectionTiming> = if proxy_configured(config) {
+        HashMap::new()
+    } else {
+        let endpoints: HashSet<&String> = test_contexts.iter().map(|ctx| &ctx.endpoint).collect();
+        join_all(
+            endpoints.into_iter().map(|endpoint| async move {
+                (endpoint.clone(), measure_connection(endpoint).await)
+            }),
+        )
+        .await
+        .into_iter()
+        .collect()
+    };
+
     // Double-tap test all URLs, collecting results with context
     let mut test_results = Vec::with_capacity(test_contexts.len());
     for ctx in &test_contexts {
-        let url_result = test_url_double_tap(&client, &ctx.url).await;
+        let mut url_result =
+            test_url_double_tap(&client, &ctx.url, config.random_range_samples).await;
+        if let Some(connection) = connections.get(&ctx.endpoint) {
+            url_result.timing.dns_ms = connection.dns_ms;
+            url_result.timing.connect_ms = connection.connect_ms;
+            url_result.timing.tls_ms = connection.tls_ms;
+        }
         test_results.push((ctx.clone(), url_result));
     }
     debug!("Double-tap tested {} URLs", test_results.len());
 
+    if config.throughput_sample_bytes > 0 {
+        sample_throughput(config, &mut test_results).await;
+    }
+
     // Extract just UrlTestResults for analysis
     let url_results: Vec<_> = test_results.iter().map(|(_, r)| r.clone()).collect();
     let analysis = analyze_results(&url_results);
@@ -166,9 +333,26 @@ pub async fn discover_url(
         .max_by_key(|(_, r)| r.content_length);
 
     let working_url = working_url_result.map(|(_, r)| r.url.clone());
+    let sample_payload_cid = working_url_result.and_then(|(ctx, r)| {
+        sample_cids
+            .get(&ctx.deal_id)
+            .cloned()
+            .or_else(|| r.root_cid.as_deref().and_then(normalize_cid))
+    });
 
     // CAR diagnostics
-    let valid_car_count = test_results.iter().filter(|(_, r)| r.is_valid_car).count();
+    let valid_car_count = analysis.valid_car_count;
+    let mut hash_algorithms: Vec<&str> = Vec::new();
+    for blocks in test_results
+        .iter()
+        .filter_map(|(_, r)| r.car_blocks.as_ref())
+    {
+        for algorithm in &blocks.hash_algorithms {
+            if !hash_algorithms.contains(&algorithm.as_str()) {
+                hash_algorithms.push(algorithm);
+            }
+        }
+    }
     let small_car_count = test_results
         .iter()
         .filter(|(_, r)| r.is_valid_car && r.content_length.unwrap_or(0) < MIN_VALID_CONTENT_LENGTH)
@@ -200,22 +384,33 @@ pub async fn discover_url(
     let http_responded_count = analysis.http_responded_count;
     let failed_count = analysis.failed_count;
 
-    let url_metadata = serde_json::json!({
+    let mut url_metadata = serde_json::json!({
         "counts": {
             "sample_count": analysis.sample_count,
             "http_responded_count": http_responded_count,
             "success_count": analysis.success_count,
             "valid_car_count": valid_car_count,
             "small_car_count": small_car_count,
+            "corrupt_car_count": analysis.corrupt_car_count,
+            "carv2_index_checked_count": analysis.carv2_index_checked_count,
+            "carv2_index_valid_count": analysis.carv2_index_valid_count,
             "timeout_count": analysis.timeout_count,
             "failed_count": failed_count,
         },
+        "failure_breakdown": analysis.failure_causes,
         "inconsistency_breakdown": {
             "total": analysis.inconsistent_count,
             "warm_up": analysis.inconsistent_warm_up,
             "flaky": analysis.inconsistent_flaky,
             "small_responses": analysis.inconsistent_small_responses,
             "size_mismatch": analysis.inconsistent_size_mismatch,
+            "random_range": analysis.inconsistent_random_range,
+        },
+        "random_range": random_range_metadata(&url_results, analysis.random_range_success_percent),
+        "timing": timing_metadata(&url_results),
+        "car_blocks": {
+            "blocks_verified": analysis.car_blocks_verified,
+            "hash_algorithms": hash_algorithms,
         },
         "sector_utilization": {
             "sample_count": utilization_samples.len(),
@@ -224,6 +419,32 @@ pub async fn discover_url(
         },
     });
 
+    if let Some(sample_payload_cid) = sample_payload_cid {
+        url_metadata["sample_payload_cid"] = sample_payload_cid.into();
+    }
+
+    if !preflights.is_empty() {
+        url_metadata["preflight"] = preflight_metadata(&preflights);
+    }
+
+    if let Some(root_cid_verification) =
+        root_cid_verification_metadata(&test_results, &payload_cids)
+    {
+        url_metadata["root_cid_verification"] = root_cid_verification;
+    }
+
+    let gateway_results = test_gateway_samples(&client, &test_results, &sample_cids).await;
+    if !gateway_results.is_empty() {
+        url_metadata["gateway"] = gateway_metadata(&gateway_results);
+    }
+
+    // Only the provider-level discovery streams pieces; client discoveries reuse the same endpoints
+    if client_id.is_none() && config.commp_verification_enabled {
+        result.commp_candidates = commp_candidates(&test_results);
+    }
+
+    result.endpoint_outcomes = endpoint_outcomes(&test_results);
+    result.endpoint_outcomes.extend(unreachable_outcomes);
     result.working_url = working_url.clone();
     result.retrievability_percent = Some(analysis.retrievability_percent);
     result.is_consistent = Some(analysis.is_consistent);
@@ -241,3 +462,736 @@ pub async fn discover_url(
 
     result
 }
+
+/// Endpoints failing for this long without a success are skipped
+const DEAD_ENDPOINT_DAYS: i64 = 7;
+const DEAD_ENDPOINT_MIN_FAILURES: i32 = 3;
+/// Dead endpoints are still retried once per interval so a recovery is noticed
+const DEAD_ENDPOINT_RETRY_HOURS: i64 = 24;
+
+/// Failing for at least DEAD_ENDPOINT_DAYS since the last success (or first sighting)
+pub fn is_dead_endpoint(health: &ProviderEndpoint, now: DateTime<Utc>) -> bool {
+    let last_alive = health.last_success.unwrap_or(health.first_seen);
+    health.consecutive_failures >= DEAD_ENDPOINT_MIN_FAILURES
+        && now - last_alive >= chrono::Duration::days(DEAD_ENDPOINT_DAYS)
+}
+
+fn should_skip_endpoint(health: &ProviderEndpoint, now: DateTime<Utc>) -> bool {
+    let retry_due = health
+        .last_tested_at
+        .is_none_or(|t| now - t >= chrono::Duration::hours(DEAD_ENDPOINT_RETRY_HOURS));
+    is_dead_endpoint(health, now) && !retry_due
+}
+
+/// Strips trailing slashes, matching the `provider_endpoints` keys, and drops the duplicates
+/// this leaves
+fn normalize_endpoints(endpoints: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(endpoints.len());
+    for endpoint in endpoints {
+        let endpoint = endpoint.trim_end_matches('/');
+        if !normalized.iter().any(|e| e == endpoint) {
+            normalized.push(endpoint.to_string());
+        }
+    }
+    normalized
+}
+
+/// Orders endpoints by health: working ones by latency, then untested, then failing by streak.
+/// Long-dead endpoints are dropped unless that would leave nothing to test.
+pub fn rank_endpoints(
+    endpoints: Vec<String>,
+    health: &[ProviderEndpoint],
+    now: DateTime<Utc>,
+) -> Vec<String> {
+    let health: HashMap<&str, &ProviderEndpoint> =
+        health.iter().map(|h| (h.endpoint.as_str(), h)).collect();
+    let lookup = |endpoint: &str| health.get(endpoint.trim_end_matches('/')).copied();
+
+    let (alive, dead): (Vec<String>, Vec<String>) = endpoints
+        .into_iter()
+        .partition(|e| !lookup(e).is_some_and(|h| should_skip_endpoint(h, now)));
+    if alive.is_empty() {
+        debug!("All {} endpoints are dead, testing them anyway", dead.len());
+        return dead;
+    }
+    if !dead.is_empty() {
+        debug!("Skipping dead endpoints: {:?}", dead);
+    }
+
+    let mut ranked = alive;
+    ranked.sort_by(|a, b| {
+        let key = |e: &str| match lookup(e) {
+            Some(h) if h.last_tested_at.is_some() => (
+                if h.consecutive_failures == 0 { 0 } else { 2 },
+                h.consecutive_failures,
+                h.avg_latency_ms.unwrap_or(f64::MAX),
+            ),
+            _ => (1, 0, f64::MAX),
+        };
+        let (a, b) = (key(a), key(b));
+        a.0.cmp(&b.0).then(a.1.cmp(&b.1)).then(a.2.total_cmp(&b.2))
+    });
+    ranked
+}
+
+/// Per-endpoint summary of the double-tap results, in first-tested order
+fn endpoint_outcomes(
+    test_results: &[(PieceTestContext, UrlTestResult)],
+) -> Vec<EndpointTestOutcome> {
+    let mut endpoints: Vec<&str> = Vec::new();
+    for (ctx, _) in test_results {
+        if !endpoints.contains(&ctx.endpoint.as_str()) {
+            endpoints.push(&ctx.endpoint);
+        }
+    }
+
+    endpoints
+        .into_iter()
+        .map(|endpoint| {
+            let successes: Vec<&UrlTestResult> = test_results
+                .iter()
+                .filter(|(ctx, r)| ctx.endpoint == endpoint && r.success)
+                .map(|(_, r)| r)
+                .collect();
+            EndpointTestOutcome {
+                endpoint: endpoint.to_string(),
+                success: !successes.is_empty(),
+                avg_latency_ms: average(successes.iter().map(|r| r.response_time_ms as f64)),
+            }
+        })
+        .collect()
+}
+
+/// Distinct pieces that passed the double-tap test
+fn commp_candidates(test_results: &[(PieceTestContext, UrlTestResult)]) -> Vec<CommpCandidate> {
+    let mut seen_pieces = HashSet::new();
+    test_results
+        .iter()
+        .filter(|(_, r)| r.success)
+        .filter(|(ctx, _)| seen_pieces.insert(ctx.piece_cid.clone()))
+        .map(|(ctx, r)| CommpCandidate {
+            piece_cid: ctx.piece_cid.clone(),
+            url: r.url.clone(),
+            padded_piece_size: ctx.piece_size.and_then(|s| u64::try_from(s).ok()),
+        })
+        .collect()
+}
+
+/// Streams the candidates the provider's weekly budget still allows, recomputes their CommP
+/// and adds the outcome to the stored provider-level result.
+pub async fn verify_commp_samples(
+    config: &Config,
+    deps: &DiscoveryDeps,
+    provider_id: &ProviderId,
+    url_result_id: Uuid,
+    candidates: &[CommpCandidate],
+) {
+    if candidates.is_empty() {
+        return;
+    }
+
+    let piece_cids: Vec<String> = candidates.iter().map(|c| c.piece_cid.clone()).collect();
+    let reservations = match deps
+        .commp_repo
+        .reserve(
+            provider_id,
+            &piece_cids,
+            config.commp_verification_pieces_per_week,
+            CommpSource::UrlDiscovery,
+        )
+        .await
+    {
+        Ok(reservations) => reservations,
+        Err(e) => {
+            warn!(
+                "Failed to reserve CommP verifications for {}: {:?}",
+                provider_id, e
+            );
+            return;
+        }
+    };
+    if reservations.is_empty() {
+        return;
+    }
+
+    let client = match build_streaming_client(config) {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Failed to build streaming HTTP client: {:?}", e);
+            return;
+        }
+    };
+
+    let timeout = Duration::from_secs(config.commp_verification_timeout_secs as u64);
+    let mut verifications = Vec::with_capacity(reservations.len());
+    for reservation in reservations {
+        let Some(candidate) = candidates
+            .iter()
+            .find(|c| c.piece_cid == reservation.piece_cid)
+        else {
+            continue;
+        };
+        let verification = verify_piece_commp(
+            &client,
+            &candidate.url,
+            &candidate.piece_cid,
+            candidate.padded_piece_size,
+            timeout,
+        )
+        .await;
+        if let Err(e) = deps
+            .commp_repo
+            .record_outcome(
+                reservation.id,
+                verification.commp_verified,
+                verification.bytes_read,
+            )
+            .await
+        {
+            warn!(
+                "Failed to record CommP verification of {}: {:?}",
+                candidate.piece_cid, e
+            );
+        }
+        verifications.push(verification);
+    }
+
+    if let Err(e) = deps
+        .url_repo
+        .set_commp_verification(url_result_id, &commp_verification_metadata(&verifications))
+        .await
+    {
+        warn!(
+            "Failed to store CommP verification of {}: {:?}",
+            provider_id, e
+        );
+    }
+}
+
+fn commp_verification_metadata(verifications: &[CommpVerificationResult]) -> serde_json::Value {
+    let verified_count = verifications
+        .iter()
+        .filter(|v| v.commp_verified == Some(true))
+        .count();
+    let mismatch_count = verifications
+        .iter()
+        .filter(|v| v.commp_verified == Some(false))
+        .count();
+
+    // Any mismatch is proof of bad data; otherwise at least one full match is required
+    let commp_verified = if mismatch_count > 0 {
+        Some(false)
+    } else if verified_count > 0 {
+        Some(true)
+    } else {
+        None
+    };
+
+    serde_json::json!({
+        "sample_count": verifications.len(),
+        "verified_count": verified_count,
+        "mismatch_count": mismatch_count,
+        "failed_count": verifications.len() - verified_count - mismatch_count,
+        "commp_verified": commp_verified,
+        "mismatched_pieces": verifications
+            .iter()
+            .filter(|v| v.commp_verified == Some(false))
+            .map(|v| v.piece_cid.clone())
+            .collect::<Vec<_>>(),
+    })
+}
+
+const MAX_THROUGHPUT_SAMPLES: usize = 3;
+const THROUGHPUT_SAMPLE_MAX_DURATION: Duration = Duration::from_secs(30);
+
+/// Downloads a larger range from the first few successful URLs, one per endpoint
+async fn sample_throughput(
+    config: &Config,
+    test_results: &mut [(PieceTestContext, UrlTestResult)],
+) {
+    let client = match build_streaming_client(config) {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Failed to build streaming HTTP client: {:?}", e);
+            return;
+        }
+    };
+
+    let mut sampled_endpoints = HashSet::new();
+    for (ctx, r) in test_results.iter_mut().filter(|(_, r)| r.success) {
+        if sampled_endpoints.len() >= MAX_THROUGHPUT_SAMPLES {
+            break;
+        }
+        if !sampled_endpoints.insert(ctx.endpoint.clone()) {
+            continue;
+        }
+        r.timing.throughput_mbps = measure_throughput(
+            &client,
+            &r.url,
+            config.throughput_sample_bytes,
+            THROUGHPUT_SAMPLE_MAX_DURATION,
+        )
+        .await;
+    }
+}
+
+/// Endpoints checked and the reason each unreachable one failed
+fn preflight_metadata(preflights: &[EndpointPreflight]) -> serde_json::Value {
+    let unreachable: Vec<&EndpointPreflight> =
+        preflights.iter().filter(|p| !p.reachable()).collect();
+
+    serde_json::json!({
+        "checked_count": preflights.len(),
+        "unreachable_count": unreachable.len(),
+        "unreachable": unreachable,
+    })
+}
+
+fn average<I: Iterator<Item = f64>>(values: I) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+fn timing_metadata(url_results: &[UrlTestResult]) -> serde_json::Value {
+    let avg_ms = |phase: fn(&UrlTestResult) -> Option<u64>| {
+        average(url_results.iter().filter_map(phase).map(|ms| ms as f64))
+    };
+
+    serde_json::json!({
+        "dns_ms_avg": avg_ms(|r| r.timing.dns_ms),
+        "connect_ms_avg": avg_ms(|r| r.timing.connect_ms),
+        "tls_ms_avg": avg_ms(|r| r.timing.tls_ms),
+        "ttfb_ms_avg": avg_ms(|r| r.timing.best_ttfb_ms()),
+        "ttfb_ms_min": url_results.iter().filter_map(|r| r.timing.best_ttfb_ms()).min(),
+        "throughput_sample_count": url_results.iter().filter(|r| r.timing.throughput_mbps.is_some()).count(),
+        "throughput_mbps_avg": average(url_results.iter().filter_map(|r| r.timing.throughput_mbps)),
+    })
+}
+
+/// Deal ID to payload CID, preferring the deal Label over a pix.filspark sample
+fn merge_sample_cids(
+    payload_cids: &HashMap<i32, String>,
+    pix_samples: &HashMap<String, String>,
+    piece_contexts: &[(String, i32, Option<i64>)],
+) -> HashMap<i32, String> {
+    let mut sample_cids = payload_cids.clone();
+    for (piece_cid, deal_id, _) in piece_contexts {
+        if let Some(sample) = pix_samples.get(piece_cid) {
+            sample_cids
+                .entry(*deal_id)
+                .or_insert_with(|| sample.clone());
+        }
+    }
+    sample_cids
+}
+
+/// Gateway blocks fetched per endpoint
+const MAX_GATEWAY_SAMPLES_PER_ENDPOINT: usize = 3;
+const GATEWAY_TEST_CONCURRENCY: usize = 5;
+
+/// Distinct (endpoint, payload CID) pairs to fetch via the gateway, a few per endpoint and
+/// only on endpoints that served at least one piece. The CID comes from the deal Label or
+/// pix.filspark, falling back to the root in the served CAR header.
+fn gateway_samples(
+    test_results: &[(PieceTestContext, UrlTestResult)],
+    sample_cids: &HashMap<i32, String>,
+) -> Vec<(String, String)> {
+    let serving: HashSet<&str> = test_results
+        .iter()
+        .filter(|(_, r)| r.success)
+        .map(|(ctx, _)| ctx.endpoint.as_str())
+        .collect();
+    let mut per_endpoint: HashMap<&str, usize> = HashMap::new();
+    let mut samples: Vec<(String, String)> = Vec::new();
+
+    for (ctx, r) in test_results {
+        if !serving.contains(ctx.endpoint.as_str()) {
+            continue;
+        }
+        let root_cid = sample_cids
+            .get(&ctx.deal_id)
+            .cloned()
+            .or_else(|| r.root_cid.as_deref().and_then(normalize_cid));
+        let Some(root_cid) = root_cid else {
+            continue;
+        };
+        let taken = per_endpoint.entry(ctx.endpoint.as_str()).or_default();
+        if *taken >= MAX_GATEWAY_SAMPLES_PER_ENDPOINT
+            || samples
+                .iter()
+                .any(|(endpoint, cid)| *endpoint == ctx.endpoint && *cid == root_cid)
+        {
+            continue;
+        }
+        *taken += 1;
+        samples.push((ctx.endpoint.clone(), root_cid));
+    }
+
+    samples
+}
+
+/// Fetches sampled payload blocks via the trustless gateway on the endpoint that served them
+async fn test_gateway_samples(
+    client: &reqwest::Client,
+    test_results: &[(PieceTestContext, UrlTestResult)],
+    sample_cids: &HashMap<i32, String>,
+) -> Vec<GatewayTestResult> {
+    let results: Vec<GatewayTestResult> = stream::iter(gateway_samples(test_results, sample_cids))
+        .map(|(endpoint, root_cid)| async move {
+            test_gateway_retrieval(client, &endpoint, &root_cid).await
+        })
+        .buffer_unordered(GATEWAY_TEST_CONCURRENCY)
+        .collect()
+        .await;
+
+    debug!("Gateway tested {} root blocks", results.len());
+    results
+}
+
+fn gateway_metadata(results: &[GatewayTestResult]) -> serde_json::Value {
+    let retrieved_count = results.iter().filter(|r| r.retrieved).count();
+    let verified_count = results.iter().filter(|r| r.verified).count();
+
+    serde_json::json!({
+        "sample_count": results.len(),
+        "retrieved_count": retrieved_count,
+        "verified_count": verified_count,
+        "gateway_retrievability_percent": (verified_count as f64 / results.len() as f64) * 100.0,
+    })
+}
+
+const MAX_ROOT_CID_MISMATCHES: usize = 20;
+const MAX_RANDOM_RANGE_FAILURES: usize = 20;
+
+/// Summarises random range sampling, keeping the seed of each failing URL for reproduction
+fn random_range_metadata(
+    url_results: &[UrlTestResult],
+    success_percent: Option<f64>,
+) -> serde_json::Value {
+    let checks: Vec<_> = url_results
+        .iter()
+        .filter_map(|r| r.random_range.as_ref().map(|c| (&r.url, c)))
+        .collect();
+
+    let failures: Vec<_> = checks
+        .iter()
+        .filter(|(_, c)| !c.all_passed())
+        .take(MAX_RANDOM_RANGE_FAILURES)
+        .map(|(url, c)| {
+            serde_json::json!({
+                "url": url,
+                "seed": c.seed,
+                "offsets": c.offsets,
+                "passed_count": c.passed_count,
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "tested_url_count": checks.len(),
+        "sample_count": checks.iter().map(|(_, c)| c.offsets.len()).sum::<usize>(),
+        "passed_count": checks.iter().map(|(_, c)| c.passed_count).sum::<usize>(),
+        "random_range_success_percent": success_percent,
+        "failures": failures,
+    })
+}
+
+/// Compares CAR root CIDs against deal Label payload CIDs.
+/// Returns None when no sample had both a valid CAR header and a known payload CID.
+fn root_cid_verification_metadata(
+    test_results: &[(PieceTestContext, UrlTestResult)],
+    payload_cids: &HashMap<i32, String>,
+) -> Option<serde_json::Value> {
+    let mut compared_count = 0;
+    let mut matched_count = 0;
+    let mut mismatched_deals = HashSet::new();
+    let mut mismatches = Vec::new();
+
+    for (ctx, r) in test_results {
+        let (Some(root_cid), Some(expected)) = (
+            r.root_cid.as_deref().filter(|_| r.is_valid_car),
+            payload_cids.get(&ctx.deal_id),
+        ) else {
+            continue;
+        };
+
+        compared_count += 1;
+        if normalize_cid(root_cid).as_ref() == Some(expected) {
+            matched_count += 1;
+        } else if mismatches.len() < MAX_ROOT_CID_MISMATCHES && mismatched_deals.insert(ctx.deal_id)
+        {
+            mismatches.push(serde_json::json!({
+                "deal_id": ctx.deal_id,
+                "piece_cid": ctx.piece_cid,
+                "expected_root_cid": expected,
+                "actual_root_cid": root_cid,
+            }));
+        }
+    }
+
+    if compared_count == 0 {
+        return None;
+    }
+
+    Some(serde_json::json!({
+        "compared_count": compared_count,
+        "matched_count": matched_count,
+        "root_cid_match_percent": (matched_count as f64 / compared_count as f64) * 100.0,
+        "mismatches": mismatches,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Transport;
+
+    const PAYLOAD_CID: &str = "bafybeiczsscdsbs7ffqz55asqdf3smv6klcw3gofszvwlyarci47bgf354";
+
+    fn make_sample(deal_id: i32, root_cid: Option<&str>) -> (PieceTestContext, UrlTestResult) {
+        (
+            PieceTestContext {
+                piece_cid: format!("piece-{deal_id}"),
+                deal_id,
+                piece_size: None,
+                endpoint: "http://sp".to_string(),
+                url: format!("http://sp/piece/piece-{deal_id}"),
+            },
+            UrlTestResult {
+                url: format!("http://sp/piece/piece-{deal_id}"),
+                transport: Transport::Http,
+                success: true,
+                consistent: true,
+                inconsistency_type: None,
+                content_length: Some(MIN_VALID_CONTENT_LENGTH),
+                response_time_ms: 100,
+                error: None,
+                is_valid_car: root_cid.is_some(),
+                root_cid: root_cid.map(|c| c.to_string()),
+                car_blocks: None,
+                carv2_index_valid: None,
+                timing: Default::default(),
+                random_range: None,
+            },
+        )
+    }
+
+    #[test]
+    fn test_root_cid_verification_counts_matches_and_mismatches() {
+        let payload_cids =
+            HashMap::from([(1, PAYLOAD_CID.to_string()), (2, PAYLOAD_CID.to_string())]);
+        let results = vec![
+            make_sample(1, Some(PAYLOAD_CID)),
+            make_sample(2, Some("bafkqaaa")),
+            make_sample(2, Some("bafkqaaa")),
+        ];
+
+        let meta = root_cid_verification_metadata(&results, &payload_cids).unwrap();
+
+        assert_eq!(meta["compared_count"], 3);
+        assert_eq!(meta["matched_count"], 1);
+        let mismatches = meta["mismatches"].as_array().unwrap();
+        assert_eq!(mismatches.len(), 1, "mismatches are listed once per deal");
+        assert_eq!(mismatches[0]["deal_id"], 2);
+        assert_eq!(mismatches[0]["actual_root_cid"], "bafkqaaa");
+    }
+
+    #[test]
+    fn test_root_cid_verification_matches_cid_v0_label() {
+        let payload_cids = HashMap::from([(
+            1,
+            normalize_cid("QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn").unwrap(),
+        )]);
+        let results = vec![make_sample(1, Some(PAYLOAD_CID))];
+
+        let meta = root_cid_verification_metadata(&results, &payload_cids).unwrap();
+
+        assert_eq!(meta["root_cid_match_percent"], 100.0);
+    }
+
+    #[test]
+    fn test_root_cid_verification_skips_samples_without_car_or_label() {
+        let payload_cids = HashMap::from([(1, PAYLOAD_CID.to_string())]);
+        let results = vec![make_sample(1, None), make_sample(3, Some(PAYLOAD_CID))];
+
+        assert!(root_cid_verification_metadata(&results, &payload_cids).is_none());
+    }
+
+    #[test]
+    fn test_gateway_samples_are_capped_per_serving_endpoint() {
+        let sample_cids: HashMap<i32, String> = (1..=6)
+            .map(|deal_id| (deal_id, format!("bafy-{deal_id}")))
+            .collect();
+        let mut results: Vec<_> = (1..=5).map(|deal_id| make_sample(deal_id, None)).collect();
+        results.push(make_sample(1, None));
+        let (mut dead_ctx, mut dead_result) = make_sample(6, None);
+        dead_ctx.endpoint = "http://dead".to_string();
+        dead_result.success = false;
+        results.push((dead_ctx, dead_result));
+
+        let samples = gateway_samples(&results, &sample_cids);
+
+        assert_eq!(
+            samples,
+            vec![
+                ("http://sp".to_string(), "bafy-1".to_string()),
+                ("http://sp".to_string(), "bafy-2".to_string()),
+                ("http://sp".to_string(), "bafy-3".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_sample_cids_prefers_label() {
+        let payload_cids = HashMap::from([(1, PAYLOAD_CID.to_string())]);
+        let pix_samples = HashMap::from([
+            ("piece-1".to_string(), "bafy-pix-1".to_string()),
+            ("piece-2".to_string(), "bafy-pix-2".to_string()),
+        ]);
+        let piece_contexts = vec![
+            ("piece-1".to_string(), 1, None),
+            ("piece-2".to_string(), 2, None),
+            ("piece-3".to_string(), 3, None),
+        ];
+
+        let merged = merge_sample_cids(&payload_cids, &pix_samples, &piece_contexts);
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[&1], PAYLOAD_CID);
+        assert_eq!(merged[&2], "bafy-pix-2");
+    }
+
+    fn health(
+        endpoint: &str,
+        consecutive_failures: i32,
+        last_success_days_ago: Option<i64>,
+        avg_latency_ms: Option<f64>,
+    ) -> ProviderEndpoint {
+        let now = Utc::now();
+        ProviderEndpoint {
+            provider_id: ProviderId::new("1000").unwrap(),
+            endpoint: endpoint.to_string(),
+            source: crate::types::EndpointSource::Publisher,
+            first_seen: now - chrono::Duration::days(30),
+            last_seen_in_ipni: Some(now),
+            last_seen_on_chain: None,
+            last_tested_at: Some(now - chrono::Duration::hours(1)),
+            last_success: last_success_days_ago.map(|d| now - chrono::Duration::days(d)),
+            consecutive_failures,
+            avg_latency_ms,
+        }
+    }
+
+    fn endpoints(list: &[&str]) -> Vec<String> {
+        list.iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn test_normalize_endpoints_strips_trailing_slash_once() {
+        assert_eq!(
+            normalize_endpoints(endpoints(&["http://a/", "http://b", "http://a"])),
+            endpoints(&["http://a", "http://b"])
+        );
+    }
+
+    #[test]
+    fn test_rank_endpoints_orders_by_health() {
+        let health = vec![
+            health("http://slow", 0, Some(0), Some(900.0)),
+            health("http://fast", 0, Some(0), Some(50.0)),
+            health("http://failing", 2, Some(1), Some(10.0)),
+        ];
+
+        let ranked = rank_endpoints(
+            endpoints(&[
+                "http://failing",
+                "http://new",
+                "http://slow",
+                "http://fast/",
+            ]),
+            &health,
+            Utc::now(),
+        );
+
+        assert_eq!(
+            ranked,
+            endpoints(&[
+                "http://fast/",
+                "http://slow",
+                "http://new",
+                "http://failing"
+            ])
+        );
+    }
+
+    #[test]
+    fn test_rank_endpoints_skips_long_dead_unless_retry_due() {
+        let now = Utc::now();
+        let mut retry_due = health("http://dead-retry", 10, Some(20), None);
+        retry_due.last_tested_at = Some(now - chrono::Duration::days(2));
+        let health = vec![
+            health("http://ok", 0, Some(0), Some(100.0)),
+            health("http://dead", 10, None, None),
+            retry_due,
+        ];
+
+        let ranked = rank_endpoints(
+            endpoints(&["http://dead", "http://ok", "http://dead-retry"]),
+            &health,
+            now,
+        );
+
+        assert_eq!(ranked, endpoints(&["http://ok", "http://dead-retry"]));
+    }
+
+    #[test]
+    fn test_rank_endpoints_keeps_all_when_every_endpoint_dead() {
+        let health = vec![health("http://dead", 10, Some(30), None)];
+
+        let ranked = rank_endpoints(endpoints(&["http://dead"]), &health, Utc::now());
+
+        assert_eq!(ranked, endpoints(&["http://dead"]));
+    }
+
+    #[test]
+    fn test_endpoint_outcomes_per_endpoint() {
+        let mut failed = make_sample(2, None);
+        failed.0.endpoint = "http://other".to_string();
+        failed.1.success = false;
+        let mut slow = make_sample(3, None);
+        slow.1.response_time_ms = 300;
+
+        let outcomes = endpoint_outcomes(&[make_sample(1, None), failed, slow]);
+
+        assert_eq!(
+            outcomes,
+            vec![
+                EndpointTestOutcome {
+                    endpoint: "http://sp".to_string(),
+                    success: true,
+                    avg_latency_ms: Some(200.0),
+                },
+                EndpointTestOutcome {
+                    endpoint: "http://other".to_string(),
+                    success: false,
+                    avg_latency_ms: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_commp_candidates_are_distinct_successful_pieces() {
+        let mut failed = make_sample(2, None);
+        failed.1.success = false;
+        let mut sized = make_sample(1, None);
+        sized.0.piece_size = Some(2048);
+
+        let candidates = commp_candidates(&[sized, make_sample(1, None), failed]);
+
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].piece_cid, "piece-1");
+        assert_eq!(candidates[0].url, "http://sp/piece/piece-1");
+        assert_eq!(candidates[0].padded_piece_size, Some(2048));
+    }
+}
@@ -0,0 +1,390 @@
+//! TLS certificate inspection for https provider endpoints.
+//!
+//! The handshake is completed with a verifier that records the webpki verdict instead of
+//! aborting, so the chain of an expired, self-signed or mismatched certificate can still
+//! be read and the failure reported precisely.
+
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use tokio::net::{TcpStream, lookup_host};
+use tokio::time::timeout;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::{
+    CertificateError, ClientConfig, DigitallySignedStruct, Error as RustlsError, RootCertStore,
+    SignatureScheme,
+    client::{
+        WebPkiServerVerifier,
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    },
+    crypto::aws_lc_rs,
+    pki_types::{CertificateDer, ServerName, UnixTime},
+};
+use tracing::debug;
+use x509_parser::prelude::{FromDer, GeneralName, X509Certificate};
+
+use crate::request_error::error_chain;
+
+const INSPECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Why a certificate would be rejected by a verifying client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsFailure {
+    Expired,
+    SelfSigned,
+    HostnameMismatch,
+    /// Chain does not lead to a trusted root
+    UntrustedIssuer,
+    /// No certificate could be evaluated, or it was rejected for another reason
+    Handshake,
+}
+
+/// Subject and issuer of one certificate in the presented chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CertificateSummary {
+    pub subject: String,
+    pub issuer: String,
+    pub not_after: DateTime<Utc>,
+}
+
+/// Certificate details of a single https endpoint
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TlsInspection {
+    pub endpoint: String,
+    /// Leaf first, as presented by the server
+    pub chain: Vec<CertificateSummary>,
+    /// Leaf SAN entries cover the endpoint host
+    pub san_matches: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Negative once the leaf has expired
+    pub days_to_expiry: Option<i64>,
+    pub failure: Option<TlsFailure>,
+    pub error: Option<String>,
+}
+
+/// Verifier that records the webpki result and lets the handshake complete
+#[derive(Debug)]
+struct RecordingVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    verdict: Mutex<Option<RustlsError>>,
+}
+
+impl ServerCertVerifier for RecordingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, RustlsError> {
+        if let Err(e) = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        ) {
+            *self.verdict.lock().unwrap() = Some(e);
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, RustlsError> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, RustlsError> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// Connects to an https endpoint and records its certificate chain and verification outcome
+pub async fn inspect_tls(endpoint: &str) -> TlsInspection {
+    let mut result = TlsInspection {
+        endpoint: endpoint.to_string(),
+        ..Default::default()
+    };
+
+    let Some((host, port)) = Url::parse(endpoint)
+        .ok()
+        .filter(|u| u.scheme() == "https")
+        .and_then(|u| {
+            let host = u.host_str()?.trim_matches(['[', ']']).to_string();
+            Some((host, u.port_or_known_default()?))
+        })
+    else {
+        result.error = Some("not an https url".to_string());
+        return result;
+    };
+
+    let provider = Arc::new(aws_lc_rs::default_provider());
+    let roots = RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let verifier =
+        match WebPkiServerVerifier::builder_with_provider(roots.into(), provider.clone()).build() {
+            Ok(inner) => Arc::new(RecordingVerifier {
+                inner,
+                verdict: Mutex::new(None),
+            }),
+            Err(e) => {
+                result.error = Some(format!("tls config: {e}"));
+                return result;
+            }
+        };
+    let config =
+        match ClientConfig::builder_with_provider(provider).with_safe_default_protocol_versions() {
+            Ok(builder) => builder
+                .dangerous()
+                .with_custom_certificate_verifier(verifier.clone())
+                .with_no_client_auth(),
+            Err(e) => {
+                result.error = Some(format!("tls config: {e}"));
+                return result;
+            }
+        };
+
+    let Ok(server_name) = ServerName::try_from(host.clone()) else {
+        result.failure = Some(TlsFailure::Handshake);
+        result.error = Some("tls: unsupported host".to_string());
+        return result;
+    };
+
+    let stream = match timeout(INSPECT_TIMEOUT, async {
+        let addr = lookup_host((host.as_str(), port))
+            .await?
+            .next()
+            .ok_or_else(|| std::io::Error::other("no addresses"))?;
+        TcpStream::connect(addr).await
+    })
+    .await
+    {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => {
+            result.error = Some(format!("connect: {e}"));
+            return result;
+        }
+        Err(_) => {
+            result.error = Some("connect: timeout".to_string());
+            return result;
+        }
+    };
+
+    let tls = match timeout(
+        INSPECT_TIMEOUT,
+        TlsConnector::from(Arc::new(config)).connect(server_name, stream),
+    )
+    .await
+    {
+        Ok(Ok(tls)) => tls,
+        Ok(Err(e)) => {
+            debug!("TLS handshake with {host} failed: {e}");
+            result.failure = Some(TlsFailure::Handshake);
+            result.error = Some(format!("tls: {e}"));
+            return result;
+        }
+        Err(_) => {
+            result.failure = Some(TlsFailure::Handshake);
+            result.error = Some("tls: timeout".to_string());
+            return result;
+        }
+    };
+
+    let (_, connection) = tls.get_ref();
+    let certs = connection.peer_certificates().unwrap_or_default();
+    let mut sans = Vec::new();
+    for (i, der) in certs.iter().enumerate() {
+        let Ok((_, cert)) = X509Certificate::from_der(der) else {
+            result.error = Some("unparseable certificate".to_string());
+            break;
+        };
+        if i == 0 {
+            sans = subject_alt_names(&cert);
+        }
+        result.chain.push(CertificateSummary {
+            subject: cert.subject().to_string(),
+            issuer: cert.issuer().to_string(),
+            not_after: DateTime::from_timestamp(cert.validity().not_after.timestamp(), 0)
+                .unwrap_or_default(),
+        });
+    }
+
+    result.san_matches = sans.iter().any(|san| san_matches_host(san, &host));
+    result.expires_at = result.chain.first().map(|leaf| leaf.not_after);
+    result.days_to_expiry = result
+        .expires_at
+        .map(|expires_at| (expires_at - Utc::now()).num_days());
+    result.failure = verifier
+        .verdict
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|e| classify_verdict(e, &result.chain));
+
+    result
+}
+
+/// DNS names and IP addresses from the SubjectAltName extension
+fn subject_alt_names(cert: &X509Certificate<'_>) -> Vec<String> {
+    let Ok(Some(san)) = cert.subject_alternative_name() else {
+        return vec![];
+    };
+    san.value
+        .general_names
+        .iter()
+        .filter_map(|name| match name {
+            GeneralName::DNSName(dns) => Some(dns.to_ascii_lowercase()),
+            GeneralName::IPAddress(bytes) => match bytes.len() {
+                4 => Some(IpAddr::from(<[u8; 4]>::try_from(*bytes).ok()?).to_string()),
+                16 => Some(IpAddr::from(<[u8; 16]>::try_from(*bytes).ok()?).to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// RFC 6125 matching: exact, or a single leftmost `*` label that covers exactly one label
+fn san_matches_host(san: &str, host: &str) -> bool {
+    let host = host.to_ascii_lowercase();
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return san.parse::<IpAddr>().is_ok_and(|san_ip| san_ip == ip);
+    }
+    match san.strip_prefix("*.") {
+        Some(suffix) => host
+            .split_once('.')
+            .is_some_and(|(label, rest)| !label.is_empty() && rest == suffix),
+        None => san == host,
+    }
+}
+
+fn classify_verdict(error: &RustlsError, chain: &[CertificateSummary]) -> TlsFailure {
+    match tls_failure_from_rustls(error) {
+        TlsFailure::UntrustedIssuer if chain.len() == 1 && chain[0].subject == chain[0].issuer => {
+            TlsFailure::SelfSigned
+        }
+        failure => failure,
+    }
+}
+
+fn tls_failure_from_rustls(error: &RustlsError) -> TlsFailure {
+    match error {
+        RustlsError::InvalidCertificate(cert_error) => match cert_error {
+            CertificateError::Expired | CertificateError::ExpiredContext { .. } => {
+                TlsFailure::Expired
+            }
+            CertificateError::NotValidForName | CertificateError::NotValidForNameContext { .. } => {
+                TlsFailure::HostnameMismatch
+            }
+            CertificateError::UnknownIssuer => TlsFailure::UntrustedIssuer,
+            _ => TlsFailure::Handshake,
+        },
+        _ => TlsFailure::Handshake,
+    }
+}
+
+/// Finds a rustls error in an error's source chain, e.g. one returned by reqwest.
+/// The chain has no certificates, so a self-signed leaf shows up as `UntrustedIssuer`.
+pub fn tls_failure_from_error(error: &(dyn std::error::Error + 'static)) -> Option<TlsFailure> {
+    error_chain(error)
+        .into_iter()
+        .find_map(|err| err.downcast_ref::<RustlsError>())
+        .map(tls_failure_from_rustls)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(subject: &str, issuer: &str) -> CertificateSummary {
+        CertificateSummary {
+            subject: subject.to_string(),
+            issuer: issuer.to_string(),
+            not_after: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_san_matches_host_exact_and_wildcard() {
+        assert!(san_matches_host("sp.example.com", "SP.example.com"));
+        assert!(san_matches_host("*.example.com", "sp.example.com"));
+        assert!(!san_matches_host("*.example.com", "a.sp.example.com"));
+        assert!(!san_matches_host("*.example.com", "example.com"));
+        assert!(!san_matches_host("other.example.com", "sp.example.com"));
+    }
+
+    #[test]
+    fn test_san_matches_host_ip() {
+        assert!(san_matches_host("10.0.0.1", "10.0.0.1"));
+        assert!(san_matches_host("::1", "::1"));
+        assert!(!san_matches_host("10.0.0.1", "10.0.0.2"));
+    }
+
+    #[test]
+    fn test_classify_verdict() {
+        let expired = RustlsError::InvalidCertificate(CertificateError::Expired);
+        let mismatch = RustlsError::InvalidCertificate(CertificateError::NotValidForName);
+        let unknown = RustlsError::InvalidCertificate(CertificateError::UnknownIssuer);
+
+        let self_signed = [summary("CN=sp", "CN=sp")];
+        let private_ca = [summary("CN=sp", "CN=ca"), summary("CN=ca", "CN=ca")];
+
+        assert_eq!(
+            classify_verdict(&expired, &self_signed),
+            TlsFailure::Expired
+        );
+        assert_eq!(
+            classify_verdict(&mismatch, &private_ca),
+            TlsFailure::HostnameMismatch
+        );
+        assert_eq!(
+            classify_verdict(&unknown, &self_signed),
+            TlsFailure::SelfSigned
+        );
+        assert_eq!(
+            classify_verdict(&unknown, &private_ca),
+            TlsFailure::UntrustedIssuer
+        );
+        assert_eq!(
+            classify_verdict(&RustlsError::HandshakeNotComplete, &[]),
+            TlsFailure::Handshake
+        );
+    }
+
+    #[test]
+    fn test_tls_failure_from_error_walks_io_wrapper() {
+        let io = std::io::Error::other(RustlsError::InvalidCertificate(CertificateError::Expired));
+
+        assert_eq!(tls_failure_from_error(&io), Some(TlsFailure::Expired));
+        assert_eq!(
+            tls_failure_from_error(&std::io::Error::other("reset")),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_inspect_tls_rejects_plain_http() {
+        let result = inspect_tls("http://127.0.0.1:1/").await;
+
+        assert_eq!(result.error.as_deref(), Some("not an https url"));
+        assert!(result.failure.is_none());
+    }
+}
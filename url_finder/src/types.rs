@@ -4,10 +4,14 @@ use serde::{Deserialize, Serialize};
 use sqlx::error::BoxDynError;
 use sqlx::postgres::{PgArgumentBuffer, PgHasArrayType, PgTypeInfo, PgValueRef};
 use sqlx::{Decode, Encode, Postgres, Type};
+use std::collections::BTreeMap;
 use std::fmt;
 use std::str::FromStr;
 use utoipa::ToSchema;
 
+use crate::car_header::CarBlockScan;
+use crate::tls_inspection::TlsFailure;
+
 #[derive(Deserialize)]
 pub(super) struct DbConnectParams {
     password: String,
@@ -51,6 +55,15 @@ impl ProviderAddress {
     pub fn as_str(&self) -> &str {
         &self.0
     }
+
+    /// Actor ID, as passed to the Curio contract
+    pub fn miner_id(&self) -> Result<u64> {
+        self.0
+            .strip_prefix("f0")
+            .ok_or_else(|| eyre!("Address does not start with 'f0': {}", self.0))?
+            .parse::<u64>()
+            .map_err(|e| eyre!("Failed to parse miner ID from '{}': {}", self.0, e))
+    }
 }
 
 impl fmt::Display for ProviderAddress {
@@ -284,6 +297,133 @@ impl PgHasArrayType for DiscoveryType {
     }
 }
 
+/// Where a provider HTTP endpoint was discovered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum EndpointSource {
+    /// cid.contact Publisher addresses
+    Publisher,
+    /// cid.contact ExtendedProviders addresses
+    ExtendedProviders,
+    /// Multiaddrs registered in the Curio contract
+    CurioContract,
+    Manual,
+    /// HTTP addresses IPNI returns for a sampled piece or payload CID
+    IpniAdvertisement,
+    /// Multiaddrs the miner set on chain, read from StateMinerInfo
+    MinerInfo,
+}
+
+impl fmt::Display for EndpointSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Publisher => write!(f, "Publisher"),
+            Self::ExtendedProviders => write!(f, "ExtendedProviders"),
+            Self::CurioContract => write!(f, "CurioContract"),
+            Self::Manual => write!(f, "Manual"),
+            Self::IpniAdvertisement => write!(f, "IpniAdvertisement"),
+            Self::MinerInfo => write!(f, "MinerInfo"),
+        }
+    }
+}
+
+impl FromStr for EndpointSource {
+    type Err = color_eyre::eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Publisher" => Ok(Self::Publisher),
+            "ExtendedProviders" => Ok(Self::ExtendedProviders),
+            "CurioContract" => Ok(Self::CurioContract),
+            "Manual" => Ok(Self::Manual),
+            "IpniAdvertisement" => Ok(Self::IpniAdvertisement),
+            "MinerInfo" => Ok(Self::MinerInfo),
+            _ => Err(color_eyre::eyre::eyre!("Invalid endpoint source: {}", s)),
+        }
+    }
+}
+
+impl Type<Postgres> for EndpointSource {
+    fn type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("endpoint_source")
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for EndpointSource {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        let s = <&str as Decode<Postgres>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, Postgres> for EndpointSource {
+    fn encode_by_ref(
+        &self,
+        buf: &mut PgArgumentBuffer,
+    ) -> Result<sqlx::encode::IsNull, BoxDynError> {
+        <&str as Encode<Postgres>>::encode_by_ref(&self.to_string().as_str(), buf)
+    }
+}
+
+/// State of an on-demand provider test requested through the API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderTestStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+impl fmt::Display for ProviderTestStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Queued => write!(f, "queued"),
+            Self::Running => write!(f, "running"),
+            Self::Done => write!(f, "done"),
+            Self::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+impl FromStr for ProviderTestStatus {
+    type Err = color_eyre::eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "queued" => Ok(Self::Queued),
+            "running" => Ok(Self::Running),
+            "done" => Ok(Self::Done),
+            "failed" => Ok(Self::Failed),
+            _ => Err(color_eyre::eyre::eyre!(
+                "Invalid provider test status: {}",
+                s
+            )),
+        }
+    }
+}
+
+impl Type<Postgres> for ProviderTestStatus {
+    fn type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("provider_test_status")
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for ProviderTestStatus {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        let s = <&str as Decode<Postgres>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, Postgres> for ProviderTestStatus {
+    fn encode_by_ref(
+        &self,
+        buf: &mut PgArgumentBuffer,
+    ) -> Result<sqlx::encode::IsNull, BoxDynError> {
+        <&str as Encode<Postgres>>::encode_by_ref(&self.to_string().as_str(), buf)
+    }
+}
+
 /// Result codes for URL discovery operations
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone, PartialEq)]
 pub enum ResultCode {
@@ -457,27 +597,94 @@ impl PgHasArrayType for ErrorCode {
 /// Error types for URL testing operations
 #[derive(Debug, Clone, PartialEq)]
 pub enum UrlTestError {
+    /// Overall request deadline hit before the response headers arrived
     Timeout,
+    /// No connection to the provider in time: the TCP/TLS connect, or opening the libp2p stream
+    ConnectTimeout,
+    /// No data for too long while reading the response body
+    ReadTimeout,
     ConnectionRefused,
     ConnectionReset,
+    /// Connect failed for another reason, e.g. network unreachable
+    ConnectionFailed,
+    DnsNxDomain,
+    /// Resolver answered SERVFAIL or REFUSED
+    DnsServFail,
+    /// Other resolution failures: no records, resolver timeout, bad config
     DnsFailure,
-    TlsError,
+    TlsExpired,
+    TlsSelfSigned,
+    TlsHostnameMismatch,
+    TlsUntrustedIssuer,
+    TlsHandshakeFailure,
     HttpError(u16),
+    /// Malformed HTTP response
+    HttpProtocolError,
+    /// Redirect loop or too many redirects
+    Redirect,
+    /// Connection closed before the declared body was fully received
+    BodyTruncated,
     EmptyBody,
+    /// Peer does not speak HTTP over libp2p streams
+    StreamProtocolUnsupported,
     Other(String),
 }
 
+impl UrlTestError {
+    pub fn is_timeout(&self) -> bool {
+        matches!(
+            self,
+            Self::Timeout | Self::ConnectTimeout | Self::ReadTimeout
+        )
+    }
+
+    /// Stable cause name for failure breakdowns; HTTP statuses grouped by class
+    pub fn cause(&self) -> &'static str {
+        match self {
+            Self::Timeout => "timeout",
+            Self::ConnectTimeout => "connect_timeout",
+            Self::ReadTimeout => "read_timeout",
+            Self::ConnectionRefused => "connection_refused",
+            Self::ConnectionReset => "connection_reset",
+            Self::ConnectionFailed => "connection_failed",
+            Self::DnsNxDomain => "dns_nxdomain",
+            Self::DnsServFail => "dns_servfail",
+            Self::DnsFailure => "dns_failure",
+            Self::TlsExpired => "tls_expired",
+            Self::TlsSelfSigned => "tls_self_signed",
+            Self::TlsHostnameMismatch => "tls_hostname_mismatch",
+            Self::TlsUntrustedIssuer => "tls_untrusted_issuer",
+            Self::TlsHandshakeFailure => "tls_handshake_failure",
+            Self::HttpError(code) if *code >= 500 => "http_5xx",
+            Self::HttpError(_) => "http_4xx",
+            Self::HttpProtocolError => "http_protocol_error",
+            Self::Redirect => "redirect",
+            Self::BodyTruncated => "body_truncated",
+            Self::EmptyBody => "empty_body",
+            Self::StreamProtocolUnsupported => "stream_protocol_unsupported",
+            Self::Other(_) => "other",
+        }
+    }
+}
+
 impl std::fmt::Display for UrlTestError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Timeout => write!(f, "timeout"),
-            Self::ConnectionRefused => write!(f, "connection_refused"),
-            Self::ConnectionReset => write!(f, "connection_reset"),
-            Self::DnsFailure => write!(f, "dns_failure"),
-            Self::TlsError => write!(f, "tls_error"),
             Self::HttpError(code) => write!(f, "http_{code}"),
-            Self::EmptyBody => write!(f, "empty_body"),
             Self::Other(msg) => write!(f, "other: {msg}"),
+            _ => f.write_str(self.cause()),
+        }
+    }
+}
+
+impl From<TlsFailure> for UrlTestError {
+    fn from(failure: TlsFailure) -> Self {
+        match failure {
+            TlsFailure::Expired => Self::TlsExpired,
+            TlsFailure::SelfSigned => Self::TlsSelfSigned,
+            TlsFailure::HostnameMismatch => Self::TlsHostnameMismatch,
+            TlsFailure::UntrustedIssuer => Self::TlsUntrustedIssuer,
+            TlsFailure::Handshake => Self::TlsHandshakeFailure,
         }
     }
 }
@@ -494,12 +701,60 @@ pub enum InconsistencyType {
     SmallResponses,
     /// Both valid but different Content-Length
     SizeMismatch,
+    /// Both valid but a random aligned range was not served as requested
+    RandomRange,
+}
+
+/// Latency and bandwidth measured while testing a URL
+#[derive(Debug, Clone, Default)]
+pub struct UrlTiming {
+    /// Cold-connection phases, measured once per endpoint and left unset behind a proxy
+    pub dns_ms: Option<u64>,
+    pub connect_ms: Option<u64>,
+    pub tls_ms: Option<u64>,
+    /// Time to response headers for each tap, None for a failed tap
+    pub tap_ttfb_ms: Vec<Option<u64>>,
+    /// Only measured for a few URLs per discovery run
+    pub throughput_mbps: Option<f64>,
+}
+
+impl UrlTiming {
+    /// Fastest successful tap; the first tap may include connection setup
+    pub fn best_ttfb_ms(&self) -> Option<u64> {
+        self.tap_ttfb_ms.iter().flatten().min().copied()
+    }
+}
+
+/// Outcome of the random aligned range requests made after a valid double-tap
+#[derive(Debug, Clone)]
+pub struct RandomRangeCheck {
+    /// Seed the offsets were drawn from, so a failing sample can be reproduced
+    pub seed: u64,
+    pub offsets: Vec<u64>,
+    pub passed_count: usize,
+}
+
+impl RandomRangeCheck {
+    pub fn all_passed(&self) -> bool {
+        self.passed_count == self.offsets.len()
+    }
+}
+
+/// Transport a piece range request was made over
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    #[default]
+    Http,
+    /// HTTP/1.1 on a libp2p stream, for providers without a public HTTP endpoint
+    Libp2p,
 }
 
 /// Result of a double-tap URL test
 #[derive(Debug, Clone)]
 pub struct UrlTestResult {
     pub url: String,
+    pub transport: Transport,
     pub success: bool,
     pub consistent: bool,
     pub inconsistency_type: Option<InconsistencyType>,
@@ -509,6 +764,22 @@ pub struct UrlTestResult {
     // CAR header info
     pub is_valid_car: bool,
     pub root_cid: Option<String>,
+    pub car_blocks: Option<CarBlockScan>,
+    /// None unless the sample is a CARv2 with an index
+    pub carv2_index_valid: Option<bool>,
+    pub timing: UrlTiming,
+    pub random_range: Option<RandomRangeCheck>,
+}
+
+impl UrlTestResult {
+    /// Valid CAR header followed by a block that is malformed or fails hash verification
+    pub fn is_corrupt_car(&self) -> bool {
+        self.is_valid_car
+            && self
+                .car_blocks
+                .as_ref()
+                .is_some_and(|b| b.first_bad_block_offset.is_some())
+    }
 }
 
 /// Analysis of URL test results for a provider
@@ -522,15 +793,23 @@ pub struct ProviderAnalysis {
     pub sample_count: usize,
     pub success_count: usize,
     pub timeout_count: usize,
+    /// Failed URLs by `UrlTestError::cause`
+    pub failure_causes: BTreeMap<&'static str, usize>,
     pub inconsistent_count: usize,
     pub inconsistent_warm_up: usize,
     pub inconsistent_flaky: usize,
     pub inconsistent_small_responses: usize,
     pub inconsistent_size_mismatch: usize,
+    pub inconsistent_random_range: usize,
+    pub random_range_success_percent: Option<f64>,
     pub http_responded_count: usize,
     pub failed_count: usize,
     pub valid_car_count: usize,
     pub small_car_count: usize,
+    pub corrupt_car_count: usize,
+    pub car_blocks_verified: usize,
+    pub carv2_index_checked_count: usize,
+    pub carv2_index_valid_count: usize,
 }
 
 impl ProviderAnalysis {
@@ -544,15 +823,22 @@ impl ProviderAnalysis {
             sample_count: 0,
             success_count: 0,
             timeout_count: 0,
+            failure_causes: BTreeMap::new(),
             inconsistent_count: 0,
             inconsistent_warm_up: 0,
             inconsistent_flaky: 0,
             inconsistent_small_responses: 0,
             inconsistent_size_mismatch: 0,
+            inconsistent_random_range: 0,
+            random_range_success_percent: None,
             http_responded_count: 0,
             failed_count: 0,
             valid_car_count: 0,
             small_car_count: 0,
+            corrupt_car_count: 0,
+            car_blocks_verified: 0,
+            carv2_index_checked_count: 0,
+            carv2_index_valid_count: 0,
         }
     }
 }
@@ -580,4 +866,16 @@ mod tests {
         let client_address2: ClientAddress = client_id.into();
         assert_eq!(client_address2.as_str(), "f0789012");
     }
+
+    #[test]
+    fn test_url_test_error_display_follows_cause() {
+        assert_eq!(UrlTestError::ReadTimeout.to_string(), "read_timeout");
+        assert_eq!(UrlTestError::TlsExpired.to_string(), "tls_expired");
+        assert_eq!(UrlTestError::HttpError(503).to_string(), "http_503");
+        assert_eq!(UrlTestError::HttpError(503).cause(), "http_5xx");
+        assert_eq!(
+            UrlTestError::Other("boom".to_string()).to_string(),
+            "other: boom"
+        );
+    }
 }
@@ -5,28 +5,45 @@ use std::sync::{
 use std::time::Duration;
 
 use futures::{StreamExt, stream};
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::IndexedRandom};
 use reqwest::Client;
-use tokio::sync::Semaphore;
+use tokio::sync::{Semaphore, mpsc};
 use tracing::debug;
 
-use crate::car_header::{CarHeaderParseResult, parse_car_header};
+use crate::car_header::{
+    CarHeaderParseResult, CarV2Header, parse_car_header, section_multihash, verify_first_block,
+};
+use crate::car_index::parse_index_prefix;
+use crate::commp::{CommpHasher, parse_piece_cid, unpadded_size};
 use crate::config::{
     Config, DOUBLE_TAP_DELAY_MS, MAX_CONCURRENT_URL_TESTS, MIN_VALID_CONTENT_LENGTH,
     RANGE_REQUEST_BYTES,
 };
 use crate::http_client::build_client;
-use crate::types::{InconsistencyType, UrlTestError, UrlTestResult};
+use crate::request_error::classify_request_error;
+use crate::types::{
+    InconsistencyType, RandomRangeCheck, Transport, UrlTestError, UrlTestResult, UrlTiming,
+};
 
 const FILTER_CONCURRENCY_LIMIT: usize = 5;
 const RETRI_CONCURRENCY_LIMIT: usize = 20;
+/// Leading bytes of the CARv2 index fetched; enough for a few thousand entries of the first bucket
+const CARV2_INDEX_SAMPLE_BYTES: u64 = 64 * 1024;
+const CARV2_INDEX_SPOT_CHECKS: usize = 3;
+/// Section prefix read per spot check: varint length plus the block CID
+const CARV2_SECTION_PREFIX_BYTES: u64 = 128;
+/// A single-block trustless gateway response: CAR header plus one block (max 2MiB by spec)
+const GATEWAY_MAX_RESPONSE_BYTES: usize = 2 * 1024 * 1024 + 4096;
+/// Downloaded chunks buffered ahead of the blocking CommP hasher
+const COMMP_HASH_QUEUE_CHUNKS: usize = 16;
 
 /// Response from a range request, containing the total file size from Content-Range header
 #[derive(Debug)]
-#[allow(dead_code)]
-struct RangeResponse {
-    content_length: Option<u64>,
-    response_time_ms: u64,
-    body_sample: Option<Vec<u8>>,
+pub(crate) struct RangeResponse {
+    pub content_length: Option<u64>,
+    /// Time until response headers arrived (TTFB)
+    pub response_time_ms: u64,
+    pub body_sample: Option<Vec<u8>>,
 }
 
 /// Classification of a single tap result for consistency checking.
@@ -124,7 +141,6 @@ impl TapResult {
         }
     }
 
-    #[allow(dead_code)]
     fn root_cid(&self) -> Option<String> {
         self.car_header()
             .filter(|h| h.is_valid)
@@ -188,8 +204,13 @@ async fn range_request(client: &Client, url: &str) -> Result<RangeResponse, UrlT
     let content_length = extract_total_length(&resp);
     let response_time_ms = start.elapsed().as_millis() as u64;
 
-    // Capture body sample for CAR header parsing (limited to prevent full file downloads)
-    let body_sample = read_limited_body(resp, RANGE_REQUEST_BYTES as usize).await;
+    // Capture body sample for CAR header parsing (limited to prevent full file downloads).
+    // A body cut off mid-stream fails the tap rather than passing without a sample.
+    let (body, body_error) = read_body_prefix(resp, RANGE_REQUEST_BYTES as usize).await;
+    if let Some(e) = body_error {
+        return Err(classify_request_error(&e));
+    }
+    let body_sample = (!body.is_empty()).then_some(body);
 
     Ok(RangeResponse {
         content_length,
@@ -202,56 +223,318 @@ async fn range_request(client: &Client, url: &str) -> Result<RangeResponse, UrlT
 /// Falls back to Content-Length header if Content-Range is not present.
 #[allow(dead_code)]
 fn extract_total_length(resp: &reqwest::Response) -> Option<u64> {
-    // Try Content-Range first: "bytes 0-4095/19327352832"
-    if let Some(range) = resp.headers().get("content-range")
-        && let Ok(s) = range.to_str()
-        && let Some(total) = s.split('/').nth(1)
-        && total != "*"
+    content_range_total(resp.headers()).or_else(|| resp.content_length())
+}
+
+/// Total size from a Content-Range header: "bytes 0-4095/19327352832" -> 19327352832
+pub(crate) fn content_range_total(headers: &reqwest::header::HeaderMap) -> Option<u64> {
+    let range = headers.get("content-range")?.to_str().ok()?;
+    let total = range.split('/').nth(1).filter(|total| *total != "*")?;
+    total.parse().ok()
+}
+
+/// Picks `count` distinct RANGE_REQUEST_BYTES-aligned offsets past the first block.
+/// Only whole blocks are used so every range has the exact requested length.
+fn random_range_offsets(seed: u64, total_length: u64, count: usize) -> Vec<u64> {
+    let blocks = total_length / RANGE_REQUEST_BYTES;
+    if blocks < 2 {
+        return vec![];
+    }
+
+    let count = count.min((blocks - 1) as usize);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut offsets = Vec::with_capacity(count);
+    while offsets.len() < count {
+        let offset = rng.random_range(1..blocks) * RANGE_REQUEST_BYTES;
+        if !offsets.contains(&offset) {
+            offsets.push(offset);
+        }
+    }
+    offsets
+}
+
+/// Requests a single aligned range and checks it is served exactly as asked:
+/// 206, a Content-Range echoing the request, and a body of the requested length.
+async fn check_random_range(client: &Client, url: &str, offset: u64, total_length: u64) -> bool {
+    let end = offset + RANGE_REQUEST_BYTES - 1;
+
+    let resp = match client
+        .get(url)
+        .header("Range", format!("bytes={offset}-{end}"))
+        .send()
+        .await
     {
-        return total.parse().ok();
+        Ok(r) => r,
+        Err(e) => {
+            debug!("Random range {offset}-{end} failed for {url}: {e}");
+            return false;
+        }
+    };
+
+    if resp.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+        debug!(
+            "Random range {offset}-{end} for {url} returned {}",
+            resp.status()
+        );
+        return false;
     }
-    // Fall back to Content-Length
-    resp.content_length()
+
+    let expected_range = format!("bytes {offset}-{end}/{total_length}");
+    let range_matches = resp
+        .headers()
+        .get("content-range")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v == expected_range);
+    if !range_matches {
+        debug!("Random range {offset}-{end} for {url} returned wrong Content-Range");
+        return false;
+    }
+
+    // Read one byte past the range so over-long bodies are caught too
+    let body = read_limited_body(resp, RANGE_REQUEST_BYTES as usize + 1).await;
+    body.is_some_and(|b| b.len() as u64 == RANGE_REQUEST_BYTES)
 }
 
-/// Classifies a reqwest error into a more specific UrlTestError type
-#[allow(dead_code)]
-fn classify_request_error(e: &reqwest::Error) -> UrlTestError {
-    if e.is_timeout() {
-        UrlTestError::Timeout
-    } else if e.is_connect() {
-        if e.to_string().contains("dns") {
-            UrlTestError::DnsFailure
-        } else {
-            UrlTestError::ConnectionRefused
+/// Samples random aligned ranges across the declared total length, so a provider
+/// cannot pass by caching only the first block of every piece.
+async fn random_range_check(
+    client: &Client,
+    url: &str,
+    total_length: u64,
+    count: usize,
+) -> RandomRangeCheck {
+    let seed = rand::rng().random();
+    let offsets = random_range_offsets(seed, total_length, count);
+
+    let mut passed_count = 0;
+    for &offset in &offsets {
+        if check_random_range(client, url, offset, total_length).await {
+            passed_count += 1;
         }
-    } else if e.to_string().contains("reset") {
-        UrlTestError::ConnectionReset
-    } else if e.to_string().contains("tls") || e.to_string().contains("ssl") {
-        UrlTestError::TlsError
-    } else {
-        UrlTestError::Other(e.to_string())
     }
+
+    RandomRangeCheck {
+        seed,
+        offsets,
+        passed_count,
+    }
+}
+
+/// Fetches `start..=end` and requires a 206 response; returns the body.
+async fn fetch_range(client: &Client, url: &str, start: u64, end: u64) -> Option<Vec<u8>> {
+    let resp = client
+        .get(url)
+        .header("Range", format!("bytes={start}-{end}"))
+        .send()
+        .await
+        .ok()?;
+    if resp.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+        debug!("Range {start}-{end} for {url} returned {}", resp.status());
+        return None;
+    }
+    read_limited_body(resp, (end - start + 1) as usize).await
+}
+
+/// Checks a CARv2's header offsets against the served size, then spot-checks a few
+/// indexed blocks: each indexed offset must hold a section whose CID has the indexed digest.
+/// Returns None when the CAR carries no index.
+async fn check_carv2_index(
+    client: &Client,
+    url: &str,
+    header: &CarV2Header,
+    total_length: u64,
+) -> Option<bool> {
+    if header.index_offset == 0 {
+        return None;
+    }
+
+    let data_end = header.data_offset.checked_add(header.data_size);
+    let offsets_fit = data_end.is_some_and(|end| {
+        end <= total_length && header.index_offset >= end && header.index_offset < total_length
+    });
+    if !offsets_fit {
+        debug!("CARv2 offsets {header:?} do not fit in {total_length} bytes at {url}");
+        return Some(false);
+    }
+
+    let index_end = (header.index_offset + CARV2_INDEX_SAMPLE_BYTES).min(total_length) - 1;
+    let Some(index_bytes) = fetch_range(client, url, header.index_offset, index_end).await else {
+        return Some(false);
+    };
+    let entries = match parse_index_prefix(&index_bytes, usize::MAX) {
+        Some(entries) if !entries.is_empty() => entries,
+        _ => {
+            debug!("CARv2 index at {url} could not be parsed");
+            return Some(false);
+        }
+    };
+
+    let samples: Vec<_> = entries
+        .choose_multiple(&mut rand::rng(), CARV2_INDEX_SPOT_CHECKS)
+        .cloned()
+        .collect();
+
+    for entry in samples {
+        if entry.offset >= header.data_size {
+            debug!(
+                "CARv2 index entry offset {} beyond data at {url}",
+                entry.offset
+            );
+            return Some(false);
+        }
+        let start = header.data_offset + entry.offset;
+        let end = (start + CARV2_SECTION_PREFIX_BYTES).min(total_length) - 1;
+        let matches = fetch_range(client, url, start, end)
+            .await
+            .and_then(|bytes| section_multihash(&bytes))
+            .is_some_and(|(hash_code, digest)| {
+                digest == entry.digest && entry.hash_code.is_none_or(|c| c == hash_code as u64)
+            });
+        if !matches {
+            debug!(
+                "CARv2 indexed block at offset {} mismatched at {url}",
+                entry.offset
+            );
+            return Some(false);
+        }
+    }
+
+    Some(true)
+}
+
+/// Downloads up to `sample_bytes` from the start of `url` within `max_duration` and returns Mbps.
+/// Returns None if the request fails, too little data arrived to be meaningful, or
+/// `sample_bytes` is 0.
+pub async fn measure_throughput(
+    client: &Client,
+    url: &str,
+    sample_bytes: u64,
+    max_duration: Duration,
+) -> Option<f64> {
+    if sample_bytes == 0 {
+        return None;
+    }
+
+    let start = std::time::Instant::now();
+    let resp = client
+        .get(url)
+        .header("Range", format!("bytes=0-{}", sample_bytes - 1))
+        .send()
+        .await
+        .ok()?;
+    if !resp.status().is_success() {
+        return None;
+    }
+
+    let mut received: u64 = 0;
+    let mut stream = resp.bytes_stream();
+    while let Ok(Some(chunk)) = tokio::time::timeout_at(
+        tokio::time::Instant::from_std(start + max_duration),
+        stream.next(),
+    )
+    .await
+    {
+        match chunk {
+            Ok(bytes) => received += bytes.len() as u64,
+            Err(_) => break,
+        }
+        if received >= sample_bytes {
+            break;
+        }
+    }
+
+    let elapsed = start.elapsed().as_secs_f64();
+    if received < RANGE_REQUEST_BYTES || elapsed <= 0.0 {
+        return None;
+    }
+    Some(received as f64 * 8.0 / elapsed / 1_000_000.0)
 }
 
 /// Performs a double-tap URL test: two range requests with a delay between them.
+/// When both taps are valid, `random_range_samples` extra aligned ranges are checked.
 ///
 /// STRICT CONSISTENCY RULES
 /// - success: true if either request succeeded (HTTP 2xx with valid Content-Length)
 /// - consistent: true ONLY if both requests return VALID responses (>= 8GB) with identical Content-Length
 /// - Everything else is inconsistent: failures, small responses (error pages), mismatched sizes
-pub async fn test_url_double_tap(client: &Client, url: &str) -> UrlTestResult {
+/// - A failed random range makes an otherwise consistent pair inconsistent
+pub async fn test_url_double_tap(
+    client: &Client,
+    url: &str,
+    random_range_samples: usize,
+) -> UrlTestResult {
     let r1 = range_request(client, url).await;
     tokio::time::sleep(Duration::from_millis(DOUBLE_TAP_DELAY_MS)).await;
     let r2 = range_request(client, url).await;
 
+    let timing = UrlTiming {
+        tap_ttfb_ms: [&r1, &r2]
+            .iter()
+            .map(|r| r.as_ref().ok().map(|r| r.response_time_ms))
+            .collect(),
+        ..Default::default()
+    };
+
+    let tap1 = TapResult::from_range_result(r1);
+    let tap2 = TapResult::from_range_result(r2);
+    let mut result = summarize_taps(url, Transport::Http, &tap1, &tap2, timing);
+
+    result.random_range = match (&tap1, &tap2) {
+        (TapResult::Valid { content_length, .. }, TapResult::Valid { .. })
+            if result.consistent && random_range_samples > 0 =>
+        {
+            Some(random_range_check(client, url, *content_length, random_range_samples).await)
+        }
+        _ => None,
+    };
+
+    if result
+        .random_range
+        .as_ref()
+        .is_some_and(|r| !r.all_passed())
+    {
+        result.consistent = false;
+        result.inconsistency_type = Some(InconsistencyType::RandomRange);
+    }
+
+    // CAR header info: prefer tap2, fall back to tap1
+    let best_car = tap2.car_header().or(tap1.car_header());
+    result.carv2_index_valid = match (best_car.and_then(|h| h.v2_header), result.content_length) {
+        (Some(header), Some(total_length)) if result.success => {
+            check_carv2_index(client, url, &header, total_length).await
+        }
+        _ => None,
+    };
+
+    result
+}
+
+/// Double-tap result from two range responses fetched over a transport other than reqwest.
+/// Random ranges and the CARv2 index are not checked.
+pub(crate) fn double_tap_result(
+    url: &str,
+    transport: Transport,
+    r1: Result<RangeResponse, UrlTestError>,
+    r2: Result<RangeResponse, UrlTestError>,
+    timing: UrlTiming,
+) -> UrlTestResult {
     let tap1 = TapResult::from_range_result(r1);
     let tap2 = TapResult::from_range_result(r2);
+    summarize_taps(url, transport, &tap1, &tap2, timing)
+}
 
+/// Applies the STRICT CONSISTENCY RULES to a pair of taps
+fn summarize_taps(
+    url: &str,
+    transport: Transport,
+    tap1: &TapResult,
+    tap2: &TapResult,
+    timing: UrlTiming,
+) -> UrlTestResult {
     let success = tap1.is_valid() || tap2.is_valid();
-    let consistent = is_consistent_pair(&tap1, &tap2);
+    let consistent = is_consistent_pair(tap1, tap2);
 
-    let best_content_length = match (&tap1, &tap2) {
+    let best_content_length = match (tap1, tap2) {
         (
             TapResult::Valid {
                 content_length: a, ..
@@ -265,7 +548,7 @@ pub async fn test_url_double_tap(client: &Client, url: &str) -> UrlTestResult {
         _ => tap2.content_length().or(tap1.content_length()),
     };
 
-    let best_response_time = match (&tap1, &tap2) {
+    let best_response_time = match (tap1, tap2) {
         (
             _,
             TapResult::Valid {
@@ -287,16 +570,18 @@ pub async fn test_url_double_tap(client: &Client, url: &str) -> UrlTestResult {
     let inconsistency_type = if consistent {
         None
     } else {
-        Some(classify_inconsistency(&tap1, &tap2))
+        Some(classify_inconsistency(tap1, tap2))
     };
 
     // CAR header info: prefer tap2, fall back to tap1
     let best_car = tap2.car_header().or(tap1.car_header());
     let is_valid_car = best_car.map(|h| h.is_valid).unwrap_or(false);
-    let root_cid = best_car.and_then(|h| h.root_cid.clone());
+    let root_cid = tap2.root_cid().or_else(|| tap1.root_cid());
+    let car_blocks = best_car.and_then(|h| h.blocks.clone());
 
     UrlTestResult {
         url: url.to_string(),
+        transport,
         success,
         consistent,
         inconsistency_type,
@@ -305,9 +590,142 @@ pub async fn test_url_double_tap(client: &Client, url: &str) -> UrlTestResult {
         error,
         is_valid_car,
         root_cid,
+        car_blocks,
+        carv2_index_valid: None,
+        timing,
+        random_range: None,
     }
 }
 
+/// Outcome of streaming a full piece and recomputing its piece commitment (CommP)
+#[derive(Debug, Clone)]
+pub struct CommpVerificationResult {
+    pub url: String,
+    pub piece_cid: String,
+    /// Some(true) = CommP matches the piece CID, Some(false) = mismatch, None = not determined
+    pub commp_verified: Option<bool>,
+    pub bytes_read: u64,
+    pub error: Option<UrlTestError>,
+}
+
+/// Downloads the whole piece at `url` and compares its CommP against `piece_cid`.
+///
+/// The padded piece size comes from the CID (v2) or from `padded_piece_size` (deal metadata).
+/// Use a client without a total request timeout (see `build_streaming_client`); `timeout`
+/// bounds the whole download instead. Hashing runs on a blocking thread fed chunk by chunk.
+pub async fn verify_piece_commp(
+    client: &Client,
+    url: &str,
+    piece_cid: &str,
+    padded_piece_size: Option<u64>,
+    timeout: Duration,
+) -> CommpVerificationResult {
+    let mut result = CommpVerificationResult {
+        url: url.to_string(),
+        piece_cid: piece_cid.to_string(),
+        commp_verified: None,
+        bytes_read: 0,
+        error: None,
+    };
+
+    let Some(commitment) = parse_piece_cid(piece_cid) else {
+        result.error = Some(UrlTestError::Other("unsupported piece cid".to_string()));
+        return result;
+    };
+    let Some(padded_size) = commitment.padded_size.or(padded_piece_size) else {
+        result.error = Some(UrlTestError::Other("unknown piece size".to_string()));
+        return result;
+    };
+    let max_bytes = unpadded_size(padded_size);
+    let deadline = tokio::time::Instant::now() + timeout;
+
+    let resp = match tokio::time::timeout_at(deadline, client.get(url).send()).await {
+        Ok(Ok(resp)) => resp,
+        Ok(Err(e)) => {
+            result.error = Some(classify_request_error(&e));
+            return result;
+        }
+        Err(_) => {
+            result.error = Some(UrlTestError::Timeout);
+            return result;
+        }
+    };
+    if !resp.status().is_success() {
+        result.error = Some(UrlTestError::HttpError(resp.status().as_u16()));
+        return result;
+    }
+
+    // On an early return the sender is dropped; the hasher drains what is queued and exits
+    let (chunks, queue) = mpsc::channel(COMMP_HASH_QUEUE_CHUNKS);
+    let hashing = tokio::task::spawn_blocking(move || hash_piece_chunks(queue, padded_size));
+
+    let mut stream = resp.bytes_stream();
+    loop {
+        let chunk = match tokio::time::timeout_at(deadline, stream.next()).await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(_) => {
+                result.error = Some(UrlTestError::Timeout);
+                return result;
+            }
+        };
+        match chunk {
+            Ok(bytes) => {
+                result.bytes_read += bytes.len() as u64;
+                if result.bytes_read > max_bytes {
+                    // More data than the piece can hold - cannot be the committed piece
+                    result.commp_verified = Some(false);
+                    return result;
+                }
+                match tokio::time::timeout_at(deadline, chunks.send(bytes)).await {
+                    Ok(Ok(())) => {}
+                    // Hasher is gone, its join error is reported below
+                    Ok(Err(_)) => break,
+                    Err(_) => {
+                        result.error = Some(UrlTestError::Timeout);
+                        return result;
+                    }
+                }
+            }
+            Err(e) => {
+                result.error = Some(classify_request_error(&e));
+                return result;
+            }
+        }
+    }
+    drop(chunks);
+
+    if result.bytes_read == 0 {
+        result.error = Some(UrlTestError::EmptyBody);
+        return result;
+    }
+
+    match hashing.await {
+        Ok(root) => result.commp_verified = Some(root == Some(commitment.root)),
+        Err(e) => {
+            result.error = Some(UrlTestError::Other(format!("CommP hashing failed: {e}")));
+            return result;
+        }
+    }
+    debug!(
+        "CommP verification for {}: verified={:?} bytes={}",
+        url, result.commp_verified, result.bytes_read
+    );
+    result
+}
+
+/// Feeds queued piece chunks into a CommP hasher until the sender is dropped
+fn hash_piece_chunks<T: AsRef<[u8]>>(
+    mut queue: mpsc::Receiver<T>,
+    padded_piece_size: u64,
+) -> Option<[u8; 32]> {
+    let mut hasher = CommpHasher::new();
+    while let Some(chunk) = queue.blocking_recv() {
+        hasher.update(chunk.as_ref());
+    }
+    hasher.finalize(padded_piece_size)
+}
+
 #[derive(Debug, Clone)]
 pub struct ManifestUrlTestResult {
     pub url: String,
@@ -432,9 +850,83 @@ impl ManifestTap {
     }
 }
 
+/// Outcome of fetching a payload root block via the IPFS trustless gateway
+#[derive(Debug, Clone)]
+pub struct GatewayTestResult {
+    pub url: String,
+    pub root_cid: String,
+    /// HTTP 200 with a non-empty body
+    pub retrieved: bool,
+    /// The returned CAR starts with the requested block and its data hashes to the CID
+    pub verified: bool,
+    pub response_time_ms: u64,
+    pub error: Option<UrlTestError>,
+}
+
+/// Builds the trustless gateway URL for the root block of `root_cid` on `endpoint`
+pub fn gateway_block_url(endpoint: &str, root_cid: &str) -> String {
+    format!(
+        "{}/ipfs/{root_cid}?format=car&dag-scope=block",
+        endpoint.trim_end_matches('/')
+    )
+}
+
+/// Requests `/ipfs/{root_cid}?format=car&dag-scope=block` and verifies the returned block.
+pub async fn test_gateway_retrieval(
+    client: &Client,
+    endpoint: &str,
+    root_cid: &str,
+) -> GatewayTestResult {
+    let url = gateway_block_url(endpoint, root_cid);
+    let mut result = GatewayTestResult {
+        url: url.clone(),
+        root_cid: root_cid.to_string(),
+        retrieved: false,
+        verified: false,
+        response_time_ms: 0,
+        error: None,
+    };
+
+    let start = std::time::Instant::now();
+    let resp = match client
+        .get(&url)
+        .header("Accept", "application/vnd.ipld.car")
+        .send()
+        .await
+    {
+        Ok(r) => r,
+        Err(e) => {
+            result.error = Some(classify_request_error(&e));
+            return result;
+        }
+    };
+    result.response_time_ms = start.elapsed().as_millis() as u64;
+
+    if !resp.status().is_success() {
+        result.error = Some(UrlTestError::HttpError(resp.status().as_u16()));
+        return result;
+    }
+
+    let Some(body) = read_limited_body(resp, GATEWAY_MAX_RESPONSE_BYTES).await else {
+        result.error = Some(UrlTestError::EmptyBody);
+        return result;
+    };
+
+    result.retrieved = true;
+    result.verified = verify_first_block(&body, root_cid);
+    if !result.verified {
+        debug!("Gateway block for {root_cid} failed verification at {url}");
+    }
+    result
+}
+
 /// Tests multiple URLs in parallel using double-tap consistency checks.
 /// Limits concurrency to MAX_CONCURRENT_URL_TESTS to avoid overwhelming targets.
-pub async fn test_urls_double_tap(client: &Client, urls: Vec<String>) -> Vec<UrlTestResult> {
+pub async fn test_urls_double_tap(
+    client: &Client,
+    urls: Vec<String>,
+    random_range_samples: usize,
+) -> Vec<UrlTestResult> {
     let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_URL_TESTS));
 
     let futures: Vec<_> = urls
@@ -445,7 +937,7 @@ pub async fn test_urls_double_tap(client: &Client, urls: Vec<String>) -> Vec<Url
 
             async move {
                 let _permit = permit.acquire().await.unwrap();
-                test_url_double_tap(&client, &url).await
+                test_url_double_tap(&client, &url, random_range_samples).await
             }
         })
         .collect();
@@ -691,6 +1183,20 @@ async fn drain_response_body(resp: reqwest::Response) {
 /// Prevents downloading entire files when servers ignore Range headers.
 /// Returns partial data on network errors (CAR header is in first bytes).
 async fn read_limited_body(resp: reqwest::Response, limit: usize) -> Option<Vec<u8>> {
+    let (buffer, _) = read_body_prefix(resp, limit).await;
+
+    if buffer.is_empty() {
+        None
+    } else {
+        Some(buffer)
+    }
+}
+
+/// Reads up to `limit` bytes, returning what was read and the stream error that stopped it early
+async fn read_body_prefix(
+    resp: reqwest::Response,
+    limit: usize,
+) -> (Vec<u8>, Option<reqwest::Error>) {
     let mut stream = resp.bytes_stream();
     let mut buffer = Vec::with_capacity(limit);
 
@@ -704,15 +1210,11 @@ async fn read_limited_body(resp: reqwest::Response, limit: usize) -> Option<Vec<
                 let take = bytes.len().min(remaining);
                 buffer.extend_from_slice(&bytes[..take]);
             }
-            Err(_) => break,
+            Err(e) => return (buffer, Some(e)),
         }
     }
 
-    if buffer.is_empty() {
-        None
-    } else {
-        Some(buffer)
-    }
+    (buffer, None)
 }
 
 #[cfg(test)]
@@ -876,6 +1378,104 @@ mod tests {
         assert_eq!(response.content_length, Some(19327352832));
     }
 
+    /// Piece CID of a 128-byte (padded) all-zero piece
+    const ZERO_PIECE_CID_128: &str =
+        "baga6ea4seaqdomn3tgwgrh3g532zopskstnbrd2n3sxfqbze7rxt7vqn7veigmy";
+    const COMMP_TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[tokio::test]
+    async fn test_verify_piece_commp_matches_piece_cid() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw(vec![0u8; 127], "application/piece"),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let client = Client::new();
+        let result = verify_piece_commp(
+            &client,
+            &mock_server.uri(),
+            ZERO_PIECE_CID_128,
+            Some(128),
+            COMMP_TEST_TIMEOUT,
+        )
+        .await;
+
+        assert_eq!(result.commp_verified, Some(true));
+        assert_eq!(result.bytes_read, 127);
+        assert!(result.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_verify_piece_commp_detects_decoy_data() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw(vec![7u8; 127], "application/piece"),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let client = Client::new();
+        let result = verify_piece_commp(
+            &client,
+            &mock_server.uri(),
+            ZERO_PIECE_CID_128,
+            Some(128),
+            COMMP_TEST_TIMEOUT,
+        )
+        .await;
+
+        assert_eq!(result.commp_verified, Some(false));
+    }
+
+    #[tokio::test]
+    async fn test_verify_piece_commp_requires_piece_size() {
+        let client = Client::new();
+        let result = verify_piece_commp(
+            &client,
+            "http://127.0.0.1:1",
+            ZERO_PIECE_CID_128,
+            None,
+            COMMP_TEST_TIMEOUT,
+        )
+        .await;
+
+        assert_eq!(result.commp_verified, None);
+        assert!(matches!(result.error, Some(UrlTestError::Other(_))));
+    }
+
+    #[tokio::test]
+    async fn test_verify_piece_commp_gives_up_at_deadline() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(vec![0u8; 127], "application/piece")
+                    .set_delay(Duration::from_secs(2)),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let client = Client::new();
+        let result = verify_piece_commp(
+            &client,
+            &mock_server.uri(),
+            ZERO_PIECE_CID_128,
+            Some(128),
+            Duration::from_millis(100),
+        )
+        .await;
+
+        assert_eq!(result.commp_verified, None);
+        assert_eq!(result.error, Some(UrlTestError::Timeout));
+    }
+
     #[tokio::test]
     async fn test_manifest_double_tap_matches_small_expected_size() {
         use wiremock::matchers::header;
@@ -966,7 +1566,7 @@ mod tests {
             .build()
             .unwrap();
 
-        let result = test_url_double_tap(&client, &mock_server.uri()).await;
+        let result = test_url_double_tap(&client, &mock_server.uri(), 0).await;
 
         assert!(result.success);
         assert!(result.consistent);
@@ -1010,7 +1610,7 @@ mod tests {
             .build()
             .unwrap();
 
-        let result = test_url_double_tap(&client, &mock_server.uri()).await;
+        let result = test_url_double_tap(&client, &mock_server.uri(), 0).await;
 
         assert!(result.success); // tap2 was valid - we got real data!
         assert!(!result.consistent);
@@ -1050,7 +1650,7 @@ mod tests {
             .build()
             .unwrap();
 
-        let result = test_url_double_tap(&client, &mock_server.uri()).await;
+        let result = test_url_double_tap(&client, &mock_server.uri(), 0).await;
 
         assert!(result.success);
         assert!(result.consistent);
@@ -1087,7 +1687,7 @@ mod tests {
             .build()
             .unwrap();
 
-        let result = test_url_double_tap(&client, &mock_server.uri()).await;
+        let result = test_url_double_tap(&client, &mock_server.uri(), 0).await;
 
         assert!(result.success);
         assert!(result.consistent);
@@ -1119,7 +1719,7 @@ mod tests {
             .build()
             .unwrap();
 
-        let result = test_url_double_tap(&client, &mock_server.uri()).await;
+        let result = test_url_double_tap(&client, &mock_server.uri(), 0).await;
 
         assert!(!result.success); // Neither tap was valid - NOT successful!
         assert!(!result.consistent);
@@ -1148,7 +1748,7 @@ mod tests {
             .build()
             .unwrap();
 
-        let result = test_url_double_tap(&client, &mock_server.uri()).await;
+        let result = test_url_double_tap(&client, &mock_server.uri(), 0).await;
 
         assert!(!result.success);
         assert!(result.consistent);
@@ -1182,7 +1782,7 @@ mod tests {
             format!("{}/piece/c", mock_server.uri()),
         ];
 
-        let results = test_urls_double_tap(&client, urls).await;
+        let results = test_urls_double_tap(&client, urls, 0).await;
 
         assert_eq!(results.len(), 3);
         assert!(results.iter().all(|r| r.success));
@@ -1222,7 +1822,7 @@ mod tests {
             .build()
             .unwrap();
 
-        let result = test_url_double_tap(&client, &mock_server.uri()).await;
+        let result = test_url_double_tap(&client, &mock_server.uri(), 0).await;
 
         assert!(result.success);
         assert!(!result.consistent);
@@ -1265,7 +1865,7 @@ mod tests {
             .build()
             .unwrap();
 
-        let result = test_url_double_tap(&client, &mock_server.uri()).await;
+        let result = test_url_double_tap(&client, &mock_server.uri(), 0).await;
 
         assert!(result.success);
         assert!(!result.consistent);
@@ -1298,7 +1898,7 @@ mod tests {
             .build()
             .unwrap();
 
-        let result = test_url_double_tap(&client, &mock_server.uri()).await;
+        let result = test_url_double_tap(&client, &mock_server.uri(), 0).await;
 
         assert!(!result.success); // Neither tap was valid
         assert!(!result.consistent);
@@ -1342,7 +1942,7 @@ mod tests {
             .build()
             .unwrap();
 
-        let result = test_url_double_tap(&client, &mock_server.uri()).await;
+        let result = test_url_double_tap(&client, &mock_server.uri(), 0).await;
 
         assert!(!result.consistent);
         assert_eq!(
@@ -1373,7 +1973,7 @@ mod tests {
             .build()
             .unwrap();
 
-        let result = test_url_double_tap(&client, &mock_server.uri()).await;
+        let result = test_url_double_tap(&client, &mock_server.uri(), 0).await;
 
         assert!(result.consistent);
         assert_eq!(result.inconsistency_type, None);
@@ -1485,4 +2085,320 @@ mod tests {
             elapsed
         );
     }
+
+    const RANDOM_RANGE_TOTAL: u64 = 16_000_000_000;
+
+    #[test]
+    fn test_random_range_offsets_are_aligned_and_reproducible() {
+        let offsets = random_range_offsets(42, RANDOM_RANGE_TOTAL, 4);
+
+        assert_eq!(offsets.len(), 4);
+        assert_eq!(offsets, random_range_offsets(42, RANDOM_RANGE_TOTAL, 4));
+        for offset in &offsets {
+            assert_eq!(offset % RANGE_REQUEST_BYTES, 0);
+            assert!(
+                *offset >= RANGE_REQUEST_BYTES,
+                "first block is already tapped"
+            );
+            assert!(offset + RANGE_REQUEST_BYTES <= RANDOM_RANGE_TOTAL);
+        }
+    }
+
+    #[test]
+    fn test_random_range_offsets_skip_single_block_pieces() {
+        assert!(random_range_offsets(42, RANGE_REQUEST_BYTES, 2).is_empty());
+        assert_eq!(
+            random_range_offsets(42, RANGE_REQUEST_BYTES * 3, 5).len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_double_tap_skips_random_ranges_when_inconsistent() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(206)
+                    .insert_header("Content-Range", "bytes 0-252/252")
+                    .set_body_raw(vec![0u8; 252], "application/octet-stream"),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let client = Client::new();
+        let result = test_url_double_tap(&client, &mock_server.uri(), 2).await;
+
+        assert_eq!(
+            result.inconsistency_type,
+            Some(InconsistencyType::SmallResponses)
+        );
+        assert!(result.random_range.is_none());
+    }
+
+    /// CARv1 (header with one root) followed by a single raw block containing `data`
+    fn single_block_car(data: &[u8]) -> (String, Vec<u8>) {
+        use sha2::{Digest, Sha256};
+
+        let mut cid = vec![0x01, 0x55, 0x12, 0x20];
+        cid.extend_from_slice(&Sha256::digest(data));
+
+        let mut root = vec![0x00];
+        root.extend_from_slice(&cid);
+        let header = ciborium::Value::Map(vec![
+            (
+                ciborium::Value::Text("roots".into()),
+                ciborium::Value::Array(vec![ciborium::Value::Tag(
+                    42,
+                    Box::new(ciborium::Value::Bytes(root)),
+                )]),
+            ),
+            (
+                ciborium::Value::Text("version".into()),
+                ciborium::Value::Integer(1.into()),
+            ),
+        ]);
+        let mut header_bytes = Vec::new();
+        ciborium::into_writer(&header, &mut header_bytes).unwrap();
+
+        let mut car = vec![header_bytes.len() as u8];
+        car.extend(header_bytes);
+        car.push((cid.len() + data.len()) as u8);
+        car.extend_from_slice(&cid);
+        car.extend_from_slice(data);
+
+        let root_cid = parse_car_header(&car).root_cid.unwrap();
+        (root_cid, car)
+    }
+
+    #[tokio::test]
+    async fn test_gateway_retrieval_verifies_block() {
+        use wiremock::matchers::{path, query_param};
+
+        let (root_cid, car) = single_block_car(b"gateway block");
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(format!("/ipfs/{root_cid}")))
+            .and(query_param("format", "car"))
+            .and(query_param("dag-scope", "block"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(car, "application/vnd.ipld.car"))
+            .mount(&mock_server)
+            .await;
+
+        let result = test_gateway_retrieval(&Client::new(), &mock_server.uri(), &root_cid).await;
+
+        assert!(result.retrieved);
+        assert!(result.verified);
+        assert!(result.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_gateway_retrieval_rejects_wrong_block() {
+        let (root_cid, _) = single_block_car(b"gateway block");
+        let (_, other_car) = single_block_car(b"some other block");
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw(other_car, "application/vnd.ipld.car"),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let result = test_gateway_retrieval(&Client::new(), &mock_server.uri(), &root_cid).await;
+
+        assert!(result.retrieved);
+        assert!(!result.verified);
+    }
+
+    #[tokio::test]
+    async fn test_gateway_retrieval_not_served() {
+        let (root_cid, _) = single_block_car(b"gateway block");
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+
+        let result = test_gateway_retrieval(&Client::new(), &mock_server.uri(), &root_cid).await;
+
+        assert!(!result.retrieved);
+        assert!(!result.verified);
+        assert!(matches!(result.error, Some(UrlTestError::HttpError(404))));
+    }
+
+    /// Serves byte ranges of an in-memory file
+    struct ServeBytes(Vec<u8>);
+
+    impl wiremock::Respond for ServeBytes {
+        fn respond(&self, request: &wiremock::Request) -> ResponseTemplate {
+            let range = request
+                .headers
+                .get("Range")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("bytes="))
+                .and_then(|v| v.split_once('-'))
+                .and_then(|(a, b)| Some((a.parse::<usize>().ok()?, b.parse::<usize>().ok()?)));
+
+            match range {
+                Some((start, end)) if start < self.0.len() => {
+                    let end = end.min(self.0.len() - 1);
+                    ResponseTemplate::new(206)
+                        .insert_header(
+                            "Content-Range",
+                            format!("bytes {start}-{end}/{}", self.0.len()),
+                        )
+                        .set_body_raw(self.0[start..=end].to_vec(), "application/octet-stream")
+                }
+                _ => ResponseTemplate::new(416),
+            }
+        }
+    }
+
+    /// CARv2 wrapping a single-block CARv1, with an IndexSorted index pointing at the block
+    fn carv2_with_index(index_entry_offset: Option<u64>) -> (CarV2Header, Vec<u8>) {
+        use sha2::{Digest, Sha256};
+
+        let data = b"indexed block";
+        let (_, inner) = single_block_car(data);
+        let block_offset = parse_car_header(&inner).header_size.unwrap() as u64;
+
+        let header = CarV2Header {
+            data_offset: 51,
+            data_size: inner.len() as u64,
+            index_offset: 51 + inner.len() as u64,
+        };
+
+        let mut car = vec![
+            0x0a, 0xa1, 0x67, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x02,
+        ];
+        car.extend([0u8; 16]);
+        car.extend(header.data_offset.to_le_bytes());
+        car.extend(header.data_size.to_le_bytes());
+        car.extend(header.index_offset.to_le_bytes());
+        car.extend(&inner);
+
+        car.extend([0x80, 0x08]); // IndexSorted codec
+        car.extend(1i32.to_le_bytes());
+        car.extend(40u32.to_le_bytes());
+        car.extend(40u64.to_le_bytes());
+        car.extend(Sha256::digest(data));
+        car.extend(index_entry_offset.unwrap_or(block_offset).to_le_bytes());
+
+        (header, car)
+    }
+
+    async fn serve_bytes(bytes: Vec<u8>) -> MockServer {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ServeBytes(bytes))
+            .mount(&mock_server)
+            .await;
+        mock_server
+    }
+
+    #[tokio::test]
+    async fn test_carv2_index_spot_check_passes() {
+        let (header, car) = carv2_with_index(None);
+        assert_eq!(parse_car_header(&car).v2_header, Some(header));
+        let total = car.len() as u64;
+        let mock_server = serve_bytes(car).await;
+
+        let result = check_carv2_index(&Client::new(), &mock_server.uri(), &header, total).await;
+
+        assert_eq!(result, Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_carv2_index_wrong_block_offset_fails() {
+        let (header, car) = carv2_with_index(Some(0));
+        let total = car.len() as u64;
+        let mock_server = serve_bytes(car).await;
+
+        let result = check_carv2_index(&Client::new(), &mock_server.uri(), &header, total).await;
+
+        assert_eq!(result, Some(false));
+    }
+
+    #[tokio::test]
+    async fn test_carv2_data_beyond_content_length_fails() {
+        let (mut header, car) = carv2_with_index(None);
+        header.data_size = 1 << 40;
+        let total = car.len() as u64;
+
+        let result = check_carv2_index(&Client::new(), "http://unused", &header, total).await;
+
+        assert_eq!(result, Some(false));
+    }
+
+    #[tokio::test]
+    async fn test_carv2_without_index_is_not_checked() {
+        let (mut header, car) = carv2_with_index(None);
+        header.index_offset = 0;
+        let total = car.len() as u64;
+
+        let result = check_carv2_index(&Client::new(), "http://unused", &header, total).await;
+
+        assert_eq!(result, None);
+    }
+
+    #[tokio::test]
+    async fn test_measure_throughput_reports_mbps() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(206).set_body_bytes(vec![0u8; 64 * 1024]))
+            .mount(&mock_server)
+            .await;
+
+        let mbps = measure_throughput(
+            &Client::new(),
+            &mock_server.uri(),
+            64 * 1024,
+            Duration::from_secs(5),
+        )
+        .await;
+
+        assert!(mbps.is_some_and(|m| m > 0.0));
+    }
+
+    #[tokio::test]
+    async fn test_measure_throughput_error_status() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+
+        let mbps = measure_throughput(
+            &Client::new(),
+            &mock_server.uri(),
+            64 * 1024,
+            Duration::from_secs(5),
+        )
+        .await;
+
+        assert_eq!(mbps, None);
+    }
+
+    #[tokio::test]
+    async fn test_measure_throughput_zero_sample_is_skipped() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(206).set_body_bytes(vec![0u8; 64 * 1024]))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let mbps = measure_throughput(
+            &Client::new(),
+            &mock_server.uri(),
+            0,
+            Duration::from_secs(5),
+        )
+        .await;
+
+        assert_eq!(mbps, None);
+    }
 }
@@ -202,6 +202,23 @@ pub async fn seed_provider_with_url_status(
     .expect("Failed to insert provider with url status");
 }
 
+pub async fn seed_provider_tls_expiry(app_pool: &PgPool, provider_id: &str, days_from_now: i32) {
+    sqlx::query(
+        r#"UPDATE
+                storage_providers
+           SET
+                tls_expires_at = NOW() + make_interval(days => $2),
+                tls_inspected_at = NOW()
+           WHERE
+                provider_id = $1"#,
+    )
+    .bind(provider_id)
+    .bind(days_from_now)
+    .execute(app_pool)
+    .await
+    .expect("Failed to set provider tls expiry");
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn seed_bms_bandwidth_result(
     app_pool: &PgPool,
@@ -2,7 +2,7 @@
 
 use serde_json::json;
 use wiremock::matchers::{body_partial_json, method, path};
-use wiremock::{Mock, MockServer, ResponseTemplate};
+use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
 
 pub struct MockExternalServices {
     pub lotus: MockServer,
@@ -104,6 +104,13 @@ impl MockExternalServices {
                 )
                 .mount(&self.piece_server)
                 .await;
+
+            // Any other range (random range sampling) is echoed back exactly
+            Mock::given(method("GET"))
+                .and(path(format!("/piece/{piece_cid}")))
+                .respond_with(RangeEcho { total_file_size })
+                .mount(&self.piece_server)
+                .await;
         } else {
             Mock::given(method("GET"))
                 .and(path(format!("/piece/{piece_cid}")))
@@ -113,3 +120,32 @@ impl MockExternalServices {
         }
     }
 }
+
+/// Serves whichever `bytes=start-end` range is requested with a matching Content-Range
+pub struct RangeEcho {
+    pub total_file_size: u64,
+}
+
+impl Respond for RangeEcho {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let range = request
+            .headers
+            .get("Range")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("bytes="))
+            .and_then(|v| v.split_once('-'))
+            .and_then(|(a, b)| Some((a.parse::<u64>().ok()?, b.parse::<u64>().ok()?)));
+
+        match range {
+            Some((start, end)) if start <= end && end < self.total_file_size => {
+                ResponseTemplate::new(206)
+                    .insert_header(
+                        "Content-Range",
+                        format!("bytes {start}-{end}/{}", self.total_file_size),
+                    )
+                    .set_body_raw(vec![0u8; (end - start + 1) as usize], "application/piece")
+            }
+            _ => ResponseTemplate::new(416),
+        }
+    }
+}
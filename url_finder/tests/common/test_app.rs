@@ -11,8 +11,9 @@ use url_finder::{
     AppState,
     config::Config,
     repository::{
-        BmsBandwidthResultRepository, DealRepository, DealSliRepository, StorageProviderRepository,
-        UrlResultRepository,
+        BmsBandwidthResultRepository, CommpVerificationRepository, DealRepository,
+        DealSliRepository, ProviderEndpointRepository, ProviderTestJobRepository,
+        SchedulerRunRepository, StorageProviderRepository, UrlResultRepository,
     },
     services::{deal_sli_service::DealSliService, provider_service::ProviderService},
 };
@@ -41,10 +42,12 @@ pub async fn create_test_app(dbs: &TestDatabases, mocks: &MockExternalServices)
         url_repo.clone(),
         bms_repo.clone(),
         storage_provider_repo.clone(),
+        Arc::new(ProviderEndpointRepository::new(dbs.app_pool.clone())),
     ));
     let deal_sli_service = Arc::new(DealSliService::new(
         deal_sli_repo.clone(),
         storage_provider_repo.clone(),
+        Arc::new(CommpVerificationRepository::new(dbs.app_pool.clone())),
         config.clone(),
     ));
 
@@ -53,7 +56,9 @@ pub async fn create_test_app(dbs: &TestDatabases, mocks: &MockExternalServices)
         deal_sli_repo,
         storage_provider_repo,
         url_repo,
+        provider_test_repo: Arc::new(ProviderTestJobRepository::new(dbs.app_pool.clone())),
         bms_repo,
+        scheduler_run_repo: Arc::new(SchedulerRunRepository::new(dbs.app_pool.clone())),
         deal_sli_service,
         provider_service,
         config,
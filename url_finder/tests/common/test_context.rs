@@ -4,10 +4,15 @@ use axum_test::TestServer;
 use sqlx::{Postgres, migrate::MigrateDatabase};
 use std::env;
 use std::sync::Arc;
+use tokio::sync::Semaphore;
 use tracing::{debug, warn};
 use url_finder::config::Config;
-use url_finder::repository::{DealRepository, UrlResult, UrlResultRepository};
-use url_finder::services::url_discovery_service::discover_url;
+use url_finder::repository::{
+    CommpVerificationRepository, DealLabelRepository, DealRepository, DealSliRepository,
+    PixSampleRepository, ProviderEndpointRepository, SchedulerLeaseRepository,
+    StorageProviderRepository, UrlResult, UrlResultRepository,
+};
+use url_finder::services::url_discovery_service::{DiscoveryDeps, discover_url};
 use url_finder::types::{ClientAddress, ProviderAddress, ProviderId};
 
 use super::container::{ContainerState, get_or_create_container};
@@ -180,6 +185,23 @@ impl TestContext {
         }
     }
 
+    /// Discovery repositories over the app database
+    pub fn discovery_deps(&self) -> DiscoveryDeps {
+        let pool = &self.dbs.app_pool;
+        DiscoveryDeps {
+            sp_repo: Arc::new(StorageProviderRepository::new(pool.clone())),
+            url_repo: Arc::new(UrlResultRepository::new(pool.clone())),
+            deal_repo: Arc::new(DealRepository::new(pool.clone())),
+            deal_label_repo: Arc::new(DealLabelRepository::new(pool.clone())),
+            pix_sample_repo: Arc::new(PixSampleRepository::new(pool.clone())),
+            endpoint_repo: Arc::new(ProviderEndpointRepository::new(pool.clone())),
+            deal_sli_repo: Arc::new(DealSliRepository::new(pool.clone())),
+            lease_repo: Arc::new(SchedulerLeaseRepository::new(pool.clone())),
+            commp_repo: Arc::new(CommpVerificationRepository::new(pool.clone())),
+            commp_permits: Arc::new(Semaphore::new(1)),
+        }
+    }
+
     /// Simulate the background URL Discovery job.
     pub async fn run_discovery_for_provider(
         &self,
@@ -191,22 +213,22 @@ impl TestContext {
         let config =
             Config::new_for_test(format!("{lotus_base}/rpc/v1"), self.mocks.cid_contact_url());
 
-        let deal_repo = DealRepository::new(self.dbs.app_pool.clone());
-        let url_repo = UrlResultRepository::new(self.dbs.app_pool.clone());
+        let deps = self.discovery_deps();
 
         let discovery_result = discover_url(
             &config,
+            &deps,
             &fixture.provider_address,
+            None,
             client_address,
-            &deal_repo,
             fixture.endpoints.clone(),
-            None,
+            &[],
         )
         .await;
 
         let url_result: UrlResult = discovery_result.into();
 
-        url_repo
+        deps.url_repo
             .insert_batch(&[url_result])
             .await
             .expect("Failed to insert discovery result");
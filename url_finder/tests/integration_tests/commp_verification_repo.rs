@@ -0,0 +1,129 @@
+use chrono::Utc;
+use url_finder::repository::{CommpSource, CommpVerificationRepository, UrlResultRepository};
+
+use crate::common::*;
+
+fn pieces(list: &[&str]) -> Vec<String> {
+    list.iter().map(|p| p.to_string()).collect()
+}
+
+#[tokio::test]
+async fn test_weekly_budget_is_shared_across_sources() {
+    let ctx = TestContext::new().await;
+    let repo = CommpVerificationRepository::new(ctx.dbs.app_pool.clone());
+    let provider_id = test_provider_1_id();
+
+    let discovery = repo
+        .reserve(
+            &provider_id,
+            &pieces(&["piece-a", "piece-b"]),
+            3,
+            CommpSource::UrlDiscovery,
+        )
+        .await
+        .unwrap();
+    assert_eq!(discovery.len(), 2);
+
+    // piece-a was already charged this week, only one piece of budget is left
+    let deal_sli = repo
+        .reserve(
+            &provider_id,
+            &pieces(&["piece-a", "piece-c", "piece-d"]),
+            3,
+            CommpSource::DealSli,
+        )
+        .await
+        .unwrap();
+    let reserved: Vec<&str> = deal_sli.iter().map(|r| r.piece_cid.as_str()).collect();
+    assert_eq!(reserved, vec!["piece-c"]);
+
+    let exhausted = repo
+        .reserve(
+            &provider_id,
+            &pieces(&["piece-e"]),
+            3,
+            CommpSource::UrlDiscovery,
+        )
+        .await
+        .unwrap();
+    assert!(exhausted.is_empty());
+}
+
+#[tokio::test]
+async fn test_budget_is_per_provider() {
+    let ctx = TestContext::new().await;
+    let repo = CommpVerificationRepository::new(ctx.dbs.app_pool.clone());
+
+    let first = repo
+        .reserve(
+            &test_provider_1_id(),
+            &pieces(&["piece-a"]),
+            1,
+            CommpSource::UrlDiscovery,
+        )
+        .await
+        .unwrap();
+    repo.record_outcome(first[0].id, Some(true), 127)
+        .await
+        .unwrap();
+
+    let other = repo
+        .reserve(
+            &test_provider_2_id(),
+            &pieces(&["piece-a"]),
+            1,
+            CommpSource::DealSli,
+        )
+        .await
+        .unwrap();
+    assert_eq!(other.len(), 1);
+}
+
+#[tokio::test]
+async fn test_set_commp_verification_keeps_existing_metadata() {
+    let ctx = TestContext::new().await;
+    let repo = UrlResultRepository::new(ctx.dbs.app_pool.clone());
+
+    seed_url_result_with_metadata(
+        &ctx.dbs.app_pool,
+        TEST_PROVIDER_1_DB,
+        None,
+        Some(TEST_WORKING_URL),
+        Some(80.0),
+        None,
+        None,
+        "Success",
+        Utc::now(),
+        Some(true),
+        Some(true),
+        Some(serde_json::json!({ "counts": { "sample_count": 5 } })),
+    )
+    .await;
+    let result_id: uuid::Uuid = sqlx::query_scalar(
+        r#"SELECT
+                id
+           FROM
+                url_results
+           WHERE
+                provider_id = $1
+        "#,
+    )
+    .bind(TEST_PROVIDER_1_DB)
+    .fetch_one(&ctx.dbs.app_pool)
+    .await
+    .unwrap();
+
+    repo.set_commp_verification(result_id, &serde_json::json!({ "commp_verified": true }))
+        .await
+        .unwrap();
+
+    let metadata = repo
+        .get_by_id(result_id)
+        .await
+        .unwrap()
+        .unwrap()
+        .url_metadata
+        .unwrap();
+    assert_eq!(metadata["counts"]["sample_count"], 5);
+    assert_eq!(metadata["commp_verification"]["commp_verified"], true);
+}
@@ -0,0 +1,55 @@
+use url_finder::repository::{DealLabelRepository, NewDealLabel};
+
+use crate::common::*;
+
+const PAYLOAD_CID: &str = "bafybeiczsscdsbs7ffqz55asqdf3smv6klcw3gofszvwlyarci47bgf354";
+
+fn new_label(deal_id: i32, payload_cid: Option<&str>) -> NewDealLabel {
+    NewDealLabel {
+        deal_id,
+        piece_cid: TEST_PIECE_CID.to_string(),
+        label_raw: payload_cid.map(|c| c.to_string()),
+        payload_cid: payload_cid.map(|c| c.to_string()),
+    }
+}
+
+#[tokio::test]
+async fn test_insert_batch_and_get_by_deal_ids() {
+    let ctx = TestContext::new().await;
+    let repo = DealLabelRepository::new(ctx.dbs.app_pool.clone());
+
+    let inserted = repo
+        .insert_batch(&[new_label(1, Some(PAYLOAD_CID)), new_label(2, None)])
+        .await
+        .expect("Failed to insert labels");
+    assert_eq!(inserted, 2);
+
+    let mut labels = repo
+        .get_by_deal_ids(&[1, 2, 3])
+        .await
+        .expect("Failed to fetch labels");
+    labels.sort_by_key(|l| l.deal_id);
+
+    assert_eq!(labels.len(), 2);
+    assert_eq!(labels[0].payload_cid.as_deref(), Some(PAYLOAD_CID));
+    assert!(labels[1].payload_cid.is_none());
+}
+
+#[tokio::test]
+async fn test_insert_batch_keeps_existing_labels() {
+    let ctx = TestContext::new().await;
+    let repo = DealLabelRepository::new(ctx.dbs.app_pool.clone());
+
+    repo.insert_batch(&[new_label(1, Some(PAYLOAD_CID))])
+        .await
+        .expect("First insert should succeed");
+
+    let inserted = repo
+        .insert_batch(&[new_label(1, None)])
+        .await
+        .expect("Conflicting insert should not fail");
+    assert_eq!(inserted, 0);
+
+    let labels = repo.get_by_deal_ids(&[1]).await.unwrap();
+    assert_eq!(labels[0].payload_cid.as_deref(), Some(PAYLOAD_CID));
+}
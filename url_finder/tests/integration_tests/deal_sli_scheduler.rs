@@ -8,7 +8,7 @@ use url_finder::{
     },
     bms_client::BmsClient,
     config::Config,
-    repository::{DealSliRepository, StorageProviderRepository},
+    repository::{CommpVerificationRepository, DealSliRepository, StorageProviderRepository},
     services::deal_sli_service::DealSliService,
 };
 use uuid::Uuid;
@@ -136,6 +136,7 @@ async fn test_scheduler_runs_deal_sli_before_bms_and_links_successful_piece_jobs
     let deal_sli_service = Arc::new(DealSliService::new(
         deal_sli_repo.clone(),
         storage_provider_repo,
+        Arc::new(CommpVerificationRepository::new(ctx.dbs.app_pool.clone())),
         config.clone(),
     ));
     let bms_client = Arc::new(BmsClient::new(config.bms_url.clone()));
@@ -301,6 +302,7 @@ async fn test_deal_sli_bms_result_poller_stores_completed_job_metrics() {
     let deal_sli_service = Arc::new(DealSliService::new(
         deal_sli_repo.clone(),
         storage_provider_repo,
+        Arc::new(CommpVerificationRepository::new(ctx.dbs.app_pool.clone())),
         config.clone(),
     ));
     let bms_client = Arc::new(BmsClient::new(config.bms_url.clone()));
@@ -453,6 +455,7 @@ async fn test_deal_sli_bms_result_poller_stores_failed_or_cancelled_job_without_
         let deal_sli_service = Arc::new(DealSliService::new(
             deal_sli_repo.clone(),
             storage_provider_repo,
+            Arc::new(CommpVerificationRepository::new(ctx.dbs.app_pool.clone())),
             config.clone(),
         ));
         let bms_client = Arc::new(BmsClient::new(config.bms_url.clone()));
@@ -544,6 +547,7 @@ async fn test_deal_sli_bms_result_poller_marks_stale_pending_job_timeout() {
     let deal_sli_service = Arc::new(DealSliService::new(
         deal_sli_repo.clone(),
         storage_provider_repo,
+        Arc::new(CommpVerificationRepository::new(ctx.dbs.app_pool.clone())),
         config.clone(),
     ));
     let bms_client = Arc::new(BmsClient::new(config.bms_url.clone()));
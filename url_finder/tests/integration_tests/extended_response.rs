@@ -9,6 +9,10 @@ use assert_json_diff::assert_json_include;
 use axum::http::StatusCode;
 use chrono::Utc;
 use serde_json::json;
+use url_finder::repository::{
+    NewProviderEndpoint, NewProviderIndexerResult, ProviderEndpointRepository,
+};
+use url_finder::types::{EndpointSource, ResultCode};
 
 use crate::common::*;
 
@@ -157,6 +161,117 @@ async fn test_get_provider_extended_response_has_diagnostics() {
     // (it may be null if no SP record exists - depends on service implementation)
 }
 
+#[tokio::test]
+async fn test_get_provider_extended_response_has_endpoint_status() {
+    let ctx = TestContext::new().await;
+
+    seed_provider(&ctx.dbs.app_pool, TEST_PROVIDER_1_DB).await;
+    seed_url_result_at(
+        &ctx.dbs.app_pool,
+        TEST_PROVIDER_1_DB,
+        None,
+        Some(TEST_WORKING_URL),
+        Some(90.0),
+        "Success",
+        Utc::now(),
+        Some(true),
+        Some(true),
+    )
+    .await;
+    let endpoint_repo = ProviderEndpointRepository::new(ctx.dbs.app_pool.clone());
+    endpoint_repo
+        .upsert_seen_in_ipni(
+            &test_provider_1_id(),
+            &[NewProviderEndpoint {
+                endpoint: TEST_WORKING_URL.to_string(),
+                source: EndpointSource::Publisher,
+            }],
+        )
+        .await
+        .unwrap();
+
+    let response = ctx
+        .app
+        .get(&format!("/providers/{TEST_PROVIDER_1_API}?extended=true"))
+        .await;
+
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let body: serde_json::Value = response.json();
+    assert_json_include!(
+        actual: body["endpoints"],
+        expected: json!([{
+            "endpoint": TEST_WORKING_URL,
+            "source": "Publisher",
+            "status": "untested",
+            "consecutive_failures": 0
+        }])
+    );
+
+    let standard: serde_json::Value = ctx
+        .app
+        .get(&format!("/providers/{TEST_PROVIDER_1_API}"))
+        .await
+        .json();
+    assert!(standard.get("endpoints").is_none());
+}
+
+#[tokio::test]
+async fn test_get_provider_extended_response_reports_indexer_disagreement() {
+    let ctx = TestContext::new().await;
+
+    seed_provider(&ctx.dbs.app_pool, TEST_PROVIDER_1_DB).await;
+    seed_url_result_at(
+        &ctx.dbs.app_pool,
+        TEST_PROVIDER_1_DB,
+        None,
+        Some(TEST_WORKING_URL),
+        Some(90.0),
+        "Success",
+        Utc::now(),
+        Some(true),
+        Some(true),
+    )
+    .await;
+    ProviderEndpointRepository::new(ctx.dbs.app_pool.clone())
+        .replace_indexer_results(
+            &test_provider_1_id(),
+            &[
+                NewProviderIndexerResult {
+                    indexer: "cid.contact".to_string(),
+                    result_code: ResultCode::Success,
+                    error_code: None,
+                    endpoints: vec![TEST_WORKING_URL.to_string()],
+                },
+                NewProviderIndexerResult {
+                    indexer: "private".to_string(),
+                    result_code: ResultCode::MissingHttpAddrFromCidContact,
+                    error_code: None,
+                    endpoints: vec![],
+                },
+            ],
+        )
+        .await
+        .unwrap();
+
+    let response = ctx
+        .app
+        .get(&format!("/providers/{TEST_PROVIDER_1_API}?extended=true"))
+        .await;
+
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let body: serde_json::Value = response.json();
+    assert_json_include!(
+        actual: body["indexers"],
+        expected: json!({
+            "agree": false,
+            "results": [
+                { "indexer": "cid.contact", "result_code": "Success", "endpoints": [TEST_WORKING_URL] },
+                { "indexer": "private", "result_code": "MissingHttpAddrFromCidContact", "endpoints": [] }
+            ]
+        })
+    );
+}
+
 #[tokio::test]
 async fn test_get_provider_extended_response_has_scheduling() {
     let ctx = TestContext::new().await;
@@ -707,6 +822,57 @@ async fn test_get_provider_extended_has_multi_level_retri_in_analysis() {
     assert_eq!(analysis["success_count"], 8);
 }
 
+#[tokio::test]
+async fn test_get_provider_extended_includes_retrieval_verification_metrics() {
+    let ctx = TestContext::new().await;
+
+    seed_provider(&ctx.dbs.app_pool, TEST_PROVIDER_1_DB).await;
+    seed_url_result_with_metadata(
+        &ctx.dbs.app_pool,
+        TEST_PROVIDER_1_DB,
+        None,
+        Some(TEST_WORKING_URL),
+        Some(100.0),
+        Some(100.0),
+        Some(100.0),
+        "Success",
+        Utc::now(),
+        Some(true),
+        Some(true),
+        Some(json!({
+            "counts": {
+                "sample_count": 4,
+                "success_count": 4,
+                "timeout_count": 0
+            },
+            "random_range": {
+                "sample_count": 8,
+                "passed_count": 8,
+                "random_range_success_percent": 100.0
+            },
+            "gateway": {
+                "sample_count": 4,
+                "retrieved_count": 3,
+                "verified_count": 2,
+                "gateway_retrievability_percent": 50.0
+            }
+        })),
+    )
+    .await;
+
+    let response = ctx
+        .app
+        .get(&format!("/providers/{TEST_PROVIDER_1_API}?extended=true"))
+        .await;
+
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let body: serde_json::Value = response.json();
+    let analysis = &body["diagnostics"]["analysis"];
+
+    assert_eq!(analysis["random_range_success_percent"], 100.0);
+    assert_eq!(analysis["gateway_retrievability_percent"], 50.0);
+}
+
 // =============================================================================
 // Error code in diagnostics tests
 // =============================================================================
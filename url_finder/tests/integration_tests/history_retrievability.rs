@@ -189,6 +189,47 @@ async fn test_history_minimal_omits_extended() {
     assert_eq!(body["data"][0]["retrievability_percent"], 85.5);
 }
 
+#[tokio::test]
+async fn test_history_includes_timing_from_url_metadata() {
+    let ctx = TestContext::new().await;
+    let now = Utc::now();
+
+    seed_provider(&ctx.dbs.app_pool, TEST_PROVIDER_1_DB).await;
+    seed_url_result_with_metadata(
+        &ctx.dbs.app_pool,
+        TEST_PROVIDER_1_DB,
+        None,
+        Some(TEST_WORKING_URL),
+        Some(90.0),
+        None,
+        None,
+        "Success",
+        now - Duration::days(1),
+        Some(true),
+        Some(true),
+        Some(json!({
+            "timing": {
+                "ttfb_ms_avg": 120,
+                "throughput_mbps_avg": 48.5
+            }
+        })),
+    )
+    .await;
+
+    let response = ctx
+        .app
+        .get(&format!(
+            "/providers/{TEST_PROVIDER_1_API}/history/retrievability"
+        ))
+        .await;
+
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let body: serde_json::Value = response.json();
+
+    assert_eq!(body["data"][0]["ttfb_ms"], 120.0);
+    assert_eq!(body["data"][0]["throughput_mbps"], 48.5);
+}
+
 #[tokio::test]
 async fn test_history_empty_range_returns_empty_array() {
     let ctx = TestContext::new().await;
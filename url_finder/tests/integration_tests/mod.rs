@@ -1,6 +1,8 @@
 pub mod bms_client;
 pub mod bms_result_repo;
 pub mod clients_providers;
+pub mod commp_verification_repo;
+pub mod deal_label_repo;
 pub mod deal_sli_api;
 pub mod deal_sli_scheduler;
 pub mod deals_auth;
@@ -11,11 +13,19 @@ pub mod find_retri_sp_client;
 pub mod find_url_sp;
 pub mod find_url_sp_client;
 pub mod history_retrievability;
+pub mod pix_sample_repo;
+pub mod provider_endpoint_repo;
+pub mod provider_identity_repo;
+pub mod provider_tests;
 pub mod providers_bulk;
 pub mod providers_client;
 pub mod providers_get;
 pub mod providers_list;
 pub mod providers_reset;
+pub mod random_range;
 pub mod rate_limiting;
+pub mod scheduler_leases;
+pub mod scheduler_runs;
+pub mod url_discovery_cadence;
 pub mod url_discovery_service;
 pub mod url_validation;
@@ -0,0 +1,77 @@
+use chrono::{Duration, Utc};
+use url_finder::repository::{NewPixSample, PixSampleRepository};
+
+use crate::common::*;
+
+const PAYLOAD_CID: &str = "bafybeiczsscdsbs7ffqz55asqdf3smv6klcw3gofszvwlyarci47bgf354";
+
+fn new_sample(piece_cid: &str, payload_cid: Option<&str>) -> NewPixSample {
+    NewPixSample {
+        piece_cid: piece_cid.to_string(),
+        payload_cid: payload_cid.map(|c| c.to_string()),
+    }
+}
+
+#[tokio::test]
+async fn test_upsert_batch_and_get_fresh() {
+    let ctx = TestContext::new().await;
+    let repo = PixSampleRepository::new(ctx.dbs.app_pool.clone());
+    let provider_id = test_provider_1_id();
+
+    let upserted = repo
+        .upsert_batch(
+            &provider_id,
+            &[
+                new_sample(TEST_PIECE_CID, Some(PAYLOAD_CID)),
+                new_sample(TEST_PIECE_CID_2, None),
+            ],
+        )
+        .await
+        .expect("Failed to upsert samples");
+    assert_eq!(upserted, 2);
+
+    let pieces = [TEST_PIECE_CID.to_string(), TEST_PIECE_CID_2.to_string()];
+    let mut samples = repo
+        .get_fresh(&provider_id, &pieces, Utc::now() - Duration::hours(1))
+        .await
+        .expect("Failed to fetch samples");
+    samples.sort_by_key(|s| s.payload_cid.is_none());
+
+    assert_eq!(samples.len(), 2);
+    assert_eq!(samples[0].payload_cid.as_deref(), Some(PAYLOAD_CID));
+    assert!(samples[1].payload_cid.is_none());
+
+    let expired = repo
+        .get_fresh(&provider_id, &pieces, Utc::now() + Duration::hours(1))
+        .await
+        .unwrap();
+    assert!(expired.is_empty());
+}
+
+#[tokio::test]
+async fn test_upsert_batch_replaces_sample() {
+    let ctx = TestContext::new().await;
+    let repo = PixSampleRepository::new(ctx.dbs.app_pool.clone());
+    let provider_id = test_provider_1_id();
+
+    repo.upsert_batch(&provider_id, &[new_sample(TEST_PIECE_CID, None)])
+        .await
+        .expect("First upsert should succeed");
+    repo.upsert_batch(
+        &provider_id,
+        &[new_sample(TEST_PIECE_CID, Some(PAYLOAD_CID))],
+    )
+    .await
+    .expect("Second upsert should succeed");
+
+    let samples = repo
+        .get_fresh(
+            &provider_id,
+            &[TEST_PIECE_CID.to_string()],
+            Utc::now() - Duration::hours(1),
+        )
+        .await
+        .unwrap();
+    assert_eq!(samples.len(), 1);
+    assert_eq!(samples[0].payload_cid.as_deref(), Some(PAYLOAD_CID));
+}
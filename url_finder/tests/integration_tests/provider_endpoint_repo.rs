@@ -0,0 +1,185 @@
+use url_finder::repository::{
+    EndpointTestOutcome, NewProviderEndpoint, NewProviderIndexerResult, ProviderEndpointRepository,
+};
+use url_finder::types::{EndpointSource, ErrorCode, ResultCode};
+
+use crate::common::*;
+
+const ENDPOINT_A: &str = "http://10.0.0.1:8080";
+const ENDPOINT_B: &str = "https://sp.example.com";
+
+fn seen(endpoint: &str, source: EndpointSource) -> NewProviderEndpoint {
+    NewProviderEndpoint {
+        endpoint: endpoint.to_string(),
+        source,
+    }
+}
+
+fn outcome(endpoint: &str, success: bool, avg_latency_ms: Option<f64>) -> EndpointTestOutcome {
+    EndpointTestOutcome {
+        endpoint: endpoint.to_string(),
+        success,
+        avg_latency_ms,
+    }
+}
+
+#[tokio::test]
+async fn test_upsert_seen_in_ipni_keeps_first_seen() {
+    let ctx = TestContext::new().await;
+    let repo = ProviderEndpointRepository::new(ctx.dbs.app_pool.clone());
+    let provider_id = test_provider_1_id();
+
+    repo.upsert_seen_in_ipni(&provider_id, &[seen(ENDPOINT_A, EndpointSource::Publisher)])
+        .await
+        .expect("Failed to insert endpoints");
+    let first = repo.get_by_provider(&provider_id).await.unwrap();
+
+    let upserted = repo
+        .upsert_seen_in_ipni(
+            &provider_id,
+            &[
+                seen(ENDPOINT_A, EndpointSource::ExtendedProviders),
+                seen(ENDPOINT_B, EndpointSource::IpniAdvertisement),
+            ],
+        )
+        .await
+        .expect("Failed to upsert endpoints");
+    assert_eq!(upserted, 2);
+
+    let endpoints = repo.get_by_provider(&provider_id).await.unwrap();
+    assert_eq!(endpoints.len(), 2);
+    assert_eq!(endpoints[0].endpoint, ENDPOINT_A);
+    assert_eq!(endpoints[0].source, EndpointSource::ExtendedProviders);
+    assert_eq!(endpoints[0].first_seen, first[0].first_seen);
+    assert!(endpoints[0].last_seen_in_ipni >= first[0].last_seen_in_ipni);
+    assert_eq!(endpoints[1].source, EndpointSource::IpniAdvertisement);
+    assert!(endpoints[1].last_tested_at.is_none());
+}
+
+#[tokio::test]
+async fn test_upsert_seen_on_chain_keeps_ipni_source() {
+    let ctx = TestContext::new().await;
+    let repo = ProviderEndpointRepository::new(ctx.dbs.app_pool.clone());
+    let provider_id = test_provider_1_id();
+
+    repo.upsert_seen_in_ipni(&provider_id, &[seen(ENDPOINT_A, EndpointSource::Publisher)])
+        .await
+        .expect("Failed to insert endpoints");
+
+    let upserted = repo
+        .upsert_seen_on_chain(
+            &provider_id,
+            &[
+                seen(ENDPOINT_A, EndpointSource::MinerInfo),
+                seen(ENDPOINT_B, EndpointSource::CurioContract),
+            ],
+        )
+        .await
+        .expect("Failed to upsert on-chain endpoints");
+    assert_eq!(upserted, 2);
+
+    let endpoints = repo.get_by_provider(&provider_id).await.unwrap();
+    assert_eq!(endpoints[0].source, EndpointSource::Publisher);
+    assert!(endpoints[0].last_seen_in_ipni.is_some());
+    assert!(endpoints[0].last_seen_on_chain.is_some());
+    assert_eq!(endpoints[1].source, EndpointSource::CurioContract);
+    assert!(endpoints[1].last_seen_in_ipni.is_none());
+    assert!(endpoints[1].last_seen_on_chain.is_some());
+}
+
+#[tokio::test]
+async fn test_record_test_outcomes_tracks_failures_and_latency() {
+    let ctx = TestContext::new().await;
+    let repo = ProviderEndpointRepository::new(ctx.dbs.app_pool.clone());
+    let provider_id = test_provider_1_id();
+    repo.upsert_seen_in_ipni(
+        &provider_id,
+        &[
+            seen(ENDPOINT_A, EndpointSource::Publisher),
+            seen(ENDPOINT_B, EndpointSource::Publisher),
+        ],
+    )
+    .await
+    .unwrap();
+
+    repo.record_test_outcomes(
+        &provider_id,
+        &[
+            outcome(ENDPOINT_A, true, Some(100.0)),
+            outcome(ENDPOINT_B, false, None),
+        ],
+    )
+    .await
+    .expect("Failed to record outcomes");
+    let updated = repo
+        .record_test_outcomes(
+            &provider_id,
+            &[
+                outcome(ENDPOINT_A, true, Some(200.0)),
+                outcome(ENDPOINT_B, false, None),
+                outcome("http://unknown:80", true, Some(1.0)),
+            ],
+        )
+        .await
+        .expect("Failed to record outcomes");
+    assert_eq!(updated, 2, "untracked endpoints are ignored");
+
+    let endpoints = repo.get_by_provider(&provider_id).await.unwrap();
+    let (a, b) = (&endpoints[0], &endpoints[1]);
+    assert_eq!(a.consecutive_failures, 0);
+    assert!(a.last_success.is_some());
+    assert!((a.avg_latency_ms.unwrap() - 130.0).abs() < 1e-9);
+    assert_eq!(b.consecutive_failures, 2);
+    assert!(b.last_success.is_none());
+    assert!(b.last_tested_at.is_some());
+    assert!(b.avg_latency_ms.is_none());
+}
+
+#[tokio::test]
+async fn test_replace_indexer_results_drops_previous_refresh() {
+    let ctx = TestContext::new().await;
+    let repo = ProviderEndpointRepository::new(ctx.dbs.app_pool.clone());
+    let provider_id = test_provider_1_id();
+
+    repo.replace_indexer_results(
+        &provider_id,
+        &[NewProviderIndexerResult {
+            indexer: "retired".to_string(),
+            result_code: ResultCode::Success,
+            error_code: None,
+            endpoints: vec![ENDPOINT_A.to_string()],
+        }],
+    )
+    .await
+    .expect("Failed to store indexer results");
+    repo.replace_indexer_results(
+        &provider_id,
+        &[
+            NewProviderIndexerResult {
+                indexer: "cid.contact".to_string(),
+                result_code: ResultCode::Success,
+                error_code: None,
+                endpoints: vec![ENDPOINT_A.to_string(), ENDPOINT_B.to_string()],
+            },
+            NewProviderIndexerResult {
+                indexer: "private".to_string(),
+                result_code: ResultCode::Error,
+                error_code: Some(ErrorCode::FailedToRetrieveCidContactData),
+                endpoints: vec![],
+            },
+        ],
+    )
+    .await
+    .expect("Failed to replace indexer results");
+
+    let results = repo.get_indexer_results(&provider_id).await.unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].indexer, "cid.contact");
+    assert_eq!(results[0].endpoints, vec![ENDPOINT_A, ENDPOINT_B]);
+    assert_eq!(results[1].indexer, "private");
+    assert_eq!(results[1].result_code, ResultCode::Error);
+    assert_eq!(
+        results[1].error_code,
+        Some(ErrorCode::FailedToRetrieveCidContactData)
+    );
+}
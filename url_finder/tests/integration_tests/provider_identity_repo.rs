@@ -0,0 +1,183 @@
+use chrono::Utc;
+use url_finder::repository::{
+    LeaseTask, ProviderIdentityRepository, SchedulerLeaseRepository, StorageProviderRepository,
+};
+
+use crate::common::*;
+
+const NEW_PEER_ID: &str = "12D3KooWNewPeer";
+const INSTANCE_A: &str = "instance-a";
+const INSTANCE_B: &str = "instance-b";
+const LEASE_TTL_SECS: f64 = 300.0;
+
+#[tokio::test]
+async fn test_claim_stale_returns_unrefreshed_providers_with_peer_id() {
+    let ctx = TestContext::new().await;
+    let repo = ProviderIdentityRepository::new(ctx.dbs.app_pool.clone());
+    let lease_repo = SchedulerLeaseRepository::new(ctx.dbs.app_pool.clone());
+    seed_provider_with_cached_endpoints(
+        &ctx.dbs.app_pool,
+        TEST_PROVIDER_1_DB,
+        &["http://10.0.0.1:8080".to_string()],
+    )
+    .await;
+    seed_provider(&ctx.dbs.app_pool, TEST_PROVIDER_2_DB).await;
+
+    let stale = repo
+        .claim_stale(Utc::now(), 10, INSTANCE_A, LEASE_TTL_SECS)
+        .await
+        .unwrap();
+    assert_eq!(stale.len(), 1);
+    assert_eq!(stale[0].provider_id, test_provider_1_id());
+    assert!(stale[0].peer_multiaddrs.is_none());
+
+    let multiaddrs = vec!["/ip4/10.0.0.1/tcp/24001".to_string()];
+    repo.mark_refreshed(&test_provider_1_id(), Some(&multiaddrs), Some(&[]))
+        .await
+        .unwrap();
+    lease_repo
+        .release(LeaseTask::PeerIdRefresh, TEST_PROVIDER_1_DB, INSTANCE_A)
+        .await
+        .unwrap();
+
+    let fetched_before = Utc::now() - chrono::Duration::hours(1);
+    assert!(
+        repo.claim_stale(fetched_before, 10, INSTANCE_A, LEASE_TTL_SECS)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    let refreshed = repo
+        .claim_stale(Utc::now(), 10, INSTANCE_A, LEASE_TTL_SECS)
+        .await
+        .unwrap();
+    assert_eq!(refreshed[0].peer_multiaddrs, Some(multiaddrs));
+    assert_eq!(refreshed[0].peer_curio_multiaddrs, Some(vec![]));
+}
+
+#[tokio::test]
+async fn test_claim_stale_is_exclusive() {
+    let ctx = TestContext::new().await;
+    let repo = ProviderIdentityRepository::new(ctx.dbs.app_pool.clone());
+    let lease_repo = SchedulerLeaseRepository::new(ctx.dbs.app_pool.clone());
+    seed_provider_with_cached_endpoints(
+        &ctx.dbs.app_pool,
+        TEST_PROVIDER_1_DB,
+        &["http://10.0.0.1:8080".to_string()],
+    )
+    .await;
+
+    let claimed = repo
+        .claim_stale(Utc::now(), 10, INSTANCE_A, LEASE_TTL_SECS)
+        .await
+        .unwrap();
+    assert_eq!(claimed.len(), 1);
+
+    let claimed = repo
+        .claim_stale(Utc::now(), 10, INSTANCE_B, LEASE_TTL_SECS)
+        .await
+        .unwrap();
+    assert!(
+        claimed.is_empty(),
+        "leased provider must not be claimed twice"
+    );
+
+    lease_repo
+        .release(LeaseTask::PeerIdRefresh, TEST_PROVIDER_1_DB, INSTANCE_A)
+        .await
+        .unwrap();
+    let claimed = repo
+        .claim_stale(Utc::now(), 10, INSTANCE_B, LEASE_TTL_SECS)
+        .await
+        .unwrap();
+    assert_eq!(claimed.len(), 1);
+}
+
+#[tokio::test]
+async fn test_record_change_invalidates_cached_endpoints() {
+    let ctx = TestContext::new().await;
+    let repo = ProviderIdentityRepository::new(ctx.dbs.app_pool.clone());
+    let sp_repo = StorageProviderRepository::new(ctx.dbs.app_pool.clone());
+    seed_provider_with_cached_endpoints(
+        &ctx.dbs.app_pool,
+        TEST_PROVIDER_1_DB,
+        &["http://10.0.0.1:8080".to_string()],
+    )
+    .await;
+    let previous = repo
+        .claim_stale(Utc::now(), 10, INSTANCE_A, LEASE_TTL_SECS)
+        .await
+        .unwrap()
+        .remove(0);
+
+    let multiaddrs = vec!["/ip4/10.0.0.2/tcp/24001".to_string()];
+    repo.record_change(&previous, NEW_PEER_ID, &multiaddrs, &[])
+        .await
+        .expect("Failed to record identity change");
+
+    let provider = sp_repo
+        .get_by_provider_id(&test_provider_1_id())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(provider.peer_id.as_deref(), Some(NEW_PEER_ID));
+    assert!(provider.cached_http_endpoints.is_none());
+    assert!(provider.endpoints_fetched_at.is_none());
+
+    let needing_endpoints = sp_repo
+        .claim_providers_needing_endpoints(10, "test-instance", 300.0)
+        .await
+        .unwrap();
+    assert_eq!(needing_endpoints[0].provider_id, test_provider_1_id());
+
+    let history = repo.get_history(&test_provider_1_id(), 10).await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].old_peer_id.as_deref(), Some("test-peer-id"));
+    assert_eq!(history[0].new_peer_id.as_deref(), Some(NEW_PEER_ID));
+    assert!(history[0].old_multiaddrs.is_none());
+    assert_eq!(history[0].new_multiaddrs, Some(multiaddrs));
+}
+
+#[tokio::test]
+async fn test_record_change_of_curio_multiaddrs_only() {
+    let ctx = TestContext::new().await;
+    let repo = ProviderIdentityRepository::new(ctx.dbs.app_pool.clone());
+    let lease_repo = SchedulerLeaseRepository::new(ctx.dbs.app_pool.clone());
+    seed_provider_with_cached_endpoints(
+        &ctx.dbs.app_pool,
+        TEST_PROVIDER_1_DB,
+        &["http://10.0.0.1:8080".to_string()],
+    )
+    .await;
+    let multiaddrs = vec!["/ip4/10.0.0.1/tcp/24001".to_string()];
+    let old_curio = vec!["/dns/sp.example.com/tcp/443/https".to_string()];
+    repo.mark_refreshed(&test_provider_1_id(), Some(&multiaddrs), Some(&old_curio))
+        .await
+        .unwrap();
+    let previous = repo
+        .claim_stale(Utc::now(), 10, INSTANCE_A, LEASE_TTL_SECS)
+        .await
+        .unwrap()
+        .remove(0);
+    lease_repo
+        .release(LeaseTask::PeerIdRefresh, TEST_PROVIDER_1_DB, INSTANCE_A)
+        .await
+        .unwrap();
+
+    let new_curio = vec!["/dns/sp2.example.com/tcp/443/https".to_string()];
+    repo.record_change(&previous, "test-peer-id", &multiaddrs, &new_curio)
+        .await
+        .expect("Failed to record identity change");
+
+    let history = repo.get_history(&test_provider_1_id(), 10).await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].old_multiaddrs, history[0].new_multiaddrs);
+    assert_eq!(history[0].old_curio_multiaddrs, Some(old_curio));
+    assert_eq!(history[0].new_curio_multiaddrs, Some(new_curio.clone()));
+
+    let refreshed = repo
+        .claim_stale(Utc::now(), 10, INSTANCE_A, LEASE_TTL_SECS)
+        .await
+        .unwrap();
+    assert_eq!(refreshed[0].peer_curio_multiaddrs, Some(new_curio));
+}
@@ -0,0 +1,204 @@
+use axum::http::StatusCode;
+use chrono::{Duration, Utc};
+use url_finder::repository::{LeaseTask, ProviderTestJobRepository, SchedulerLeaseRepository};
+use url_finder::types::ProviderTestStatus;
+
+use crate::common::*;
+
+#[tokio::test]
+async fn test_enqueue_returns_open_job() {
+    let ctx = TestContext::new().await;
+    seed_provider(&ctx.dbs.app_pool, TEST_PROVIDER_1_DB).await;
+    let repo = ProviderTestJobRepository::new(ctx.dbs.app_pool.clone());
+
+    let first = repo.enqueue(&test_provider_1_id()).await.unwrap();
+    let second = repo.enqueue(&test_provider_1_id()).await.unwrap();
+
+    assert_eq!(first.id, second.id);
+    assert_eq!(first.status, ProviderTestStatus::Queued);
+}
+
+#[tokio::test]
+async fn test_claim_marks_jobs_running_once() {
+    let ctx = TestContext::new().await;
+    seed_provider(&ctx.dbs.app_pool, TEST_PROVIDER_1_DB).await;
+    let repo = ProviderTestJobRepository::new(ctx.dbs.app_pool.clone());
+
+    let job = repo.enqueue(&test_provider_1_id()).await.unwrap();
+
+    let claimed = repo.claim_queued(5, "test-instance", 300.0).await.unwrap();
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].id, job.id);
+    assert_eq!(claimed[0].status, ProviderTestStatus::Running);
+    assert!(claimed[0].started_at.is_some());
+
+    assert!(
+        repo.claim_queued(5, "test-instance", 300.0)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    repo.fail(job.id, "boom").await.unwrap();
+    let next = repo.enqueue(&test_provider_1_id()).await.unwrap();
+    assert_ne!(next.id, job.id, "finished jobs don't block a new request");
+}
+
+#[tokio::test]
+async fn test_claim_skips_provider_with_url_discovery_lease() {
+    let ctx = TestContext::new().await;
+    seed_provider(&ctx.dbs.app_pool, TEST_PROVIDER_1_DB).await;
+    let repo = ProviderTestJobRepository::new(ctx.dbs.app_pool.clone());
+
+    let lease_repo = SchedulerLeaseRepository::new(ctx.dbs.app_pool.clone());
+    assert!(
+        lease_repo
+            .acquire(
+                LeaseTask::UrlDiscovery,
+                TEST_PROVIDER_1_DB,
+                "other-instance",
+                300.0
+            )
+            .await
+            .unwrap()
+    );
+
+    repo.enqueue(&test_provider_1_id()).await.unwrap();
+
+    assert!(
+        repo.claim_queued(5, "test-instance", 300.0)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn test_running_job_is_reclaimed_once_its_lease_lapses() {
+    let ctx = TestContext::new().await;
+    seed_provider(&ctx.dbs.app_pool, TEST_PROVIDER_1_DB).await;
+    let repo = ProviderTestJobRepository::new(ctx.dbs.app_pool.clone());
+    let lease_repo = SchedulerLeaseRepository::new(ctx.dbs.app_pool.clone());
+
+    let job = repo.enqueue(&test_provider_1_id()).await.unwrap();
+    assert_eq!(
+        repo.claim_queued(5, "crashed-instance", 300.0)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+    assert!(
+        repo.claim_queued(5, "test-instance", 300.0)
+            .await
+            .unwrap()
+            .is_empty(),
+        "a live lease keeps the job with its owner"
+    );
+
+    lease_repo
+        .release(
+            LeaseTask::ProviderTest,
+            TEST_PROVIDER_1_DB,
+            "crashed-instance",
+        )
+        .await
+        .unwrap();
+
+    let reclaimed = repo.claim_queued(5, "test-instance", 300.0).await.unwrap();
+    assert_eq!(reclaimed.len(), 1);
+    assert_eq!(reclaimed[0].id, job.id);
+    assert_eq!(reclaimed[0].status, ProviderTestStatus::Running);
+}
+
+#[tokio::test]
+async fn test_complete_links_the_given_result() {
+    let ctx = TestContext::new().await;
+    seed_provider(&ctx.dbs.app_pool, TEST_PROVIDER_1_DB).await;
+    let repo = ProviderTestJobRepository::new(ctx.dbs.app_pool.clone());
+
+    let job = repo.enqueue(&test_provider_1_id()).await.unwrap();
+    repo.claim_queued(5, "test-instance", 300.0).await.unwrap();
+
+    // The app clock stamps tested_at, so it may trail the DB's started_at
+    seed_url_result_at(
+        &ctx.dbs.app_pool,
+        TEST_PROVIDER_1_DB,
+        None,
+        Some(TEST_WORKING_URL),
+        Some(80.0),
+        "Success",
+        Utc::now() - Duration::minutes(5),
+        Some(true),
+        Some(true),
+    )
+    .await;
+    let result_id: uuid::Uuid = sqlx::query_scalar(
+        r#"SELECT
+                id
+           FROM
+                url_results
+           WHERE
+                provider_id = $1
+        "#,
+    )
+    .bind(TEST_PROVIDER_1_DB)
+    .fetch_one(&ctx.dbs.app_pool)
+    .await
+    .unwrap();
+
+    repo.complete(job.id, Some(result_id)).await.unwrap();
+
+    let response = ctx.app.get(&format!("/tests/{}", job.id)).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["status"], "done");
+    assert_eq!(body["result"]["working_url"], TEST_WORKING_URL);
+}
+
+#[tokio::test]
+async fn test_create_and_get_provider_test() {
+    let ctx = TestContext::new().await;
+    seed_provider(&ctx.dbs.app_pool, TEST_PROVIDER_1_DB).await;
+
+    let response = ctx
+        .app
+        .post(&format!("/providers/{TEST_PROVIDER_1_API}/tests"))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["provider_id"], TEST_PROVIDER_1_API);
+    assert_eq!(body["status"], "queued");
+    let job_id = body["job_id"].as_str().unwrap().to_string();
+
+    let response = ctx.app.get(&format!("/tests/{job_id}")).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["job_id"], job_id);
+    assert_eq!(body["status"], "queued");
+    assert!(body["result"].is_null());
+}
+
+#[tokio::test]
+async fn test_create_provider_test_unknown_provider() {
+    let ctx = TestContext::new().await;
+
+    let response = ctx
+        .app
+        .post(&format!("/providers/{TEST_PROVIDER_1_API}/tests"))
+        .await;
+
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_get_provider_test_invalid_id() {
+    let ctx = TestContext::new().await;
+
+    let response = ctx.app.get("/tests/not-a-uuid").await;
+
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+}
@@ -293,3 +293,47 @@ async fn test_list_providers_filter_combined() {
     assert_eq!(body["total"].as_i64().unwrap(), 1);
     assert_eq!(body["providers"].as_array().unwrap().len(), 1);
 }
+
+#[tokio::test]
+async fn test_list_providers_filter_tls_expiring_within_days() {
+    let ctx = TestContext::new().await;
+
+    for (provider_id, url, expires_in_days) in [
+        (
+            TEST_PROVIDER_1_DB,
+            "https://sp1.example.com/piece/123",
+            Some(5),
+        ),
+        (
+            TEST_PROVIDER_2_DB,
+            "https://sp2.example.com/piece/456",
+            Some(90),
+        ),
+        (TEST_PROVIDER_3_DB, "http://example.com/piece/789", None),
+    ] {
+        seed_provider_with_url_status(&ctx.dbs.app_pool, provider_id, Some(url), Some(true)).await;
+        seed_url_result(
+            &ctx.dbs.app_pool,
+            provider_id,
+            None,
+            Some(url),
+            Some(80.0),
+            "Success",
+        )
+        .await;
+        if let Some(days) = expires_in_days {
+            seed_provider_tls_expiry(&ctx.dbs.app_pool, provider_id, days).await;
+        }
+    }
+
+    let response = ctx.app.get("/providers?tls_expiring_within_days=30").await;
+
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let body: serde_json::Value = response.json();
+
+    assert_eq!(body["total"].as_i64().unwrap(), 1);
+    assert_eq!(
+        body["providers"][0]["provider_id"].as_str().unwrap(),
+        TEST_PROVIDER_1_API
+    );
+}
@@ -0,0 +1,72 @@
+use reqwest::Client;
+use url_finder::config::RANGE_REQUEST_BYTES;
+use url_finder::types::InconsistencyType;
+use url_finder::url_tester::test_url_double_tap;
+use wiremock::{
+    Mock, MockServer, ResponseTemplate,
+    matchers::{header, method},
+};
+
+use crate::common::mock_servers::RangeEcho;
+
+const TOTAL_FILE_SIZE: u64 = 16_000_000_000;
+
+#[tokio::test]
+async fn test_double_tap_random_ranges_served_correctly() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .respond_with(RangeEcho {
+            total_file_size: TOTAL_FILE_SIZE,
+        })
+        .mount(&mock_server)
+        .await;
+
+    let client = Client::new();
+    let result = test_url_double_tap(&client, &mock_server.uri(), 3).await;
+
+    assert!(result.consistent);
+    assert_eq!(result.inconsistency_type, None);
+    let check = result
+        .random_range
+        .expect("random ranges should be sampled");
+    assert_eq!(check.offsets.len(), 3);
+    assert_eq!(check.passed_count, 3);
+    for offset in &check.offsets {
+        assert_eq!(offset % RANGE_REQUEST_BYTES, 0);
+        assert!(offset + RANGE_REQUEST_BYTES <= TOTAL_FILE_SIZE);
+    }
+}
+
+/// Provider that only caches the first block of every piece
+#[tokio::test]
+async fn test_double_tap_random_range_first_block_only() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(header("Range", "bytes=0-4095"))
+        .respond_with(RangeEcho {
+            total_file_size: TOTAL_FILE_SIZE,
+        })
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(206)
+                .insert_header("Content-Range", format!("bytes 0-4095/{TOTAL_FILE_SIZE}"))
+                .set_body_raw(vec![0u8; 4096], "application/octet-stream"),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let client = Client::new();
+    let result = test_url_double_tap(&client, &mock_server.uri(), 2).await;
+
+    assert!(result.success);
+    assert!(!result.consistent);
+    assert_eq!(
+        result.inconsistency_type,
+        Some(InconsistencyType::RandomRange)
+    );
+    assert_eq!(result.random_range.unwrap().passed_count, 0);
+}
@@ -0,0 +1,214 @@
+use url_finder::repository::{LeaseTask, SchedulerLeaseRepository, StorageProviderRepository};
+
+use crate::common::*;
+
+const INSTANCE_A: &str = "instance-a";
+const INSTANCE_B: &str = "instance-b";
+const LEASE_TTL_SECS: f64 = 300.0;
+
+#[tokio::test]
+async fn test_url_discovery_claim_is_exclusive() {
+    let ctx = TestContext::new().await;
+    seed_provider_with_cached_endpoints(
+        &ctx.dbs.app_pool,
+        TEST_PROVIDER_1_DB,
+        &[TEST_WORKING_URL.to_string()],
+    )
+    .await;
+    let sp_repo = StorageProviderRepository::new(ctx.dbs.app_pool.clone());
+    let lease_repo = SchedulerLeaseRepository::new(ctx.dbs.app_pool.clone());
+
+    let claimed = sp_repo
+        .claim_due_for_url_discovery(10, INSTANCE_A, LEASE_TTL_SECS)
+        .await
+        .unwrap();
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].provider_id, test_provider_1_id());
+
+    let claimed = sp_repo
+        .claim_due_for_url_discovery(10, INSTANCE_B, LEASE_TTL_SECS)
+        .await
+        .unwrap();
+    assert!(
+        claimed.is_empty(),
+        "leased provider must not be claimed twice"
+    );
+
+    lease_repo
+        .release(LeaseTask::UrlDiscovery, TEST_PROVIDER_1_DB, INSTANCE_A)
+        .await
+        .unwrap();
+    let claimed = sp_repo
+        .claim_due_for_url_discovery(10, INSTANCE_B, LEASE_TTL_SECS)
+        .await
+        .unwrap();
+    assert_eq!(claimed.len(), 1);
+}
+
+#[tokio::test]
+async fn test_expired_lease_is_reclaimed() {
+    let ctx = TestContext::new().await;
+    seed_provider_with_cached_endpoints(
+        &ctx.dbs.app_pool,
+        TEST_PROVIDER_1_DB,
+        &[TEST_WORKING_URL.to_string()],
+    )
+    .await;
+    let sp_repo = StorageProviderRepository::new(ctx.dbs.app_pool.clone());
+
+    sp_repo
+        .claim_due_for_url_discovery(10, INSTANCE_A, LEASE_TTL_SECS)
+        .await
+        .unwrap();
+    sp_repo
+        .set_url_discovery_pending(&test_provider_1_id())
+        .await
+        .unwrap();
+
+    // Instance A died mid-run and stopped heartbeating
+    sqlx::query(
+        r#"UPDATE scheduler_leases
+           SET expires_at = NOW() - INTERVAL '1 second'
+           WHERE resource_id = $1"#,
+    )
+    .bind(TEST_PROVIDER_1_DB)
+    .execute(&ctx.dbs.app_pool)
+    .await
+    .unwrap();
+
+    let claimed = sp_repo
+        .claim_due_for_url_discovery(10, INSTANCE_B, LEASE_TTL_SECS)
+        .await
+        .unwrap();
+    assert_eq!(claimed.len(), 1);
+
+    let owner: (String,) =
+        sqlx::query_as(r#"SELECT owner FROM scheduler_leases WHERE resource_id = $1"#)
+            .bind(TEST_PROVIDER_1_DB)
+            .fetch_one(&ctx.dbs.app_pool)
+            .await
+            .unwrap();
+    assert_eq!(owner.0, INSTANCE_B);
+}
+
+#[tokio::test]
+async fn test_never_fetched_providers_are_claimed_first_for_endpoints() {
+    let ctx = TestContext::new().await;
+    seed_provider_with_cached_endpoints(
+        &ctx.dbs.app_pool,
+        TEST_PROVIDER_1_DB,
+        &[TEST_WORKING_URL.to_string()],
+    )
+    .await;
+    sqlx::query(
+        r#"UPDATE storage_providers
+           SET endpoints_fetched_at = NOW() - INTERVAL '2 days'
+           WHERE provider_id = $1"#,
+    )
+    .bind(TEST_PROVIDER_1_DB)
+    .execute(&ctx.dbs.app_pool)
+    .await
+    .unwrap();
+    seed_provider(&ctx.dbs.app_pool, TEST_PROVIDER_2_DB).await;
+    let sp_repo = StorageProviderRepository::new(ctx.dbs.app_pool.clone());
+
+    let claimed = sp_repo
+        .claim_providers_needing_endpoints(1, INSTANCE_A, LEASE_TTL_SECS)
+        .await
+        .unwrap();
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].provider_id, test_provider_2_id());
+    assert!(claimed[0].endpoints_fetched_at.is_none());
+
+    let claimed = sp_repo
+        .claim_providers_needing_endpoints(10, INSTANCE_A, LEASE_TTL_SECS)
+        .await
+        .unwrap();
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].provider_id, test_provider_1_id());
+}
+
+#[tokio::test]
+async fn test_singleton_lease_heartbeat_and_release() {
+    let ctx = TestContext::new().await;
+    let lease_repo = SchedulerLeaseRepository::new(ctx.dbs.app_pool.clone());
+    let task = LeaseTask::ProviderDiscovery;
+
+    assert!(
+        lease_repo
+            .acquire(task, task.as_str(), INSTANCE_A, LEASE_TTL_SECS)
+            .await
+            .unwrap()
+    );
+    assert!(
+        !lease_repo
+            .acquire(task, task.as_str(), INSTANCE_B, LEASE_TTL_SECS)
+            .await
+            .unwrap()
+    );
+    assert!(
+        lease_repo
+            .acquire(task, task.as_str(), INSTANCE_A, LEASE_TTL_SECS)
+            .await
+            .unwrap(),
+        "holder renews its own lease"
+    );
+
+    let lease = lease_repo.track(task, task.as_str());
+    assert_eq!(
+        lease_repo
+            .heartbeat(INSTANCE_A, LEASE_TTL_SECS)
+            .await
+            .unwrap(),
+        1
+    );
+    drop(lease);
+    assert_eq!(lease_repo.release_all(INSTANCE_A).await.unwrap(), 1);
+
+    assert!(
+        lease_repo
+            .acquire(task, task.as_str(), INSTANCE_B, LEASE_TTL_SECS)
+            .await
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_heartbeat_skips_leases_no_longer_in_flight() {
+    let ctx = TestContext::new().await;
+    let lease_repo = SchedulerLeaseRepository::new(ctx.dbs.app_pool.clone());
+    let task = LeaseTask::UrlDiscovery;
+
+    for resource_id in [TEST_PROVIDER_1_DB, TEST_PROVIDER_2_DB] {
+        assert!(
+            lease_repo
+                .acquire(task, resource_id, INSTANCE_A, LEASE_TTL_SECS)
+                .await
+                .unwrap()
+        );
+    }
+    let in_flight = lease_repo.track(task, TEST_PROVIDER_1_DB);
+    // The task holding the second provider panicked before releasing it
+    drop(lease_repo.track(task, TEST_PROVIDER_2_DB));
+
+    assert_eq!(
+        lease_repo.heartbeat(INSTANCE_A, 1.0).await.unwrap(),
+        1,
+        "only the in-flight lease is renewed"
+    );
+    let expires_in: Vec<(String, f64)> = sqlx::query_as(
+        r#"SELECT resource_id, EXTRACT(EPOCH FROM expires_at - NOW())::float8
+           FROM scheduler_leases
+           ORDER BY resource_id"#,
+    )
+    .fetch_all(&ctx.dbs.app_pool)
+    .await
+    .unwrap();
+    assert_eq!(expires_in[0].0, TEST_PROVIDER_1_DB);
+    assert!(expires_in[0].1 <= 1.0);
+    assert_eq!(expires_in[1].0, TEST_PROVIDER_2_DB);
+    assert!(expires_in[1].1 > 1.0);
+
+    drop(in_flight);
+    assert_eq!(lease_repo.heartbeat(INSTANCE_A, 1.0).await.unwrap(), 0);
+}
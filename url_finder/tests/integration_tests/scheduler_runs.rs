@@ -0,0 +1,104 @@
+use axum::http::StatusCode;
+use chrono::{Duration, Utc};
+use serde_json::{Value, json};
+use url_finder::repository::{NewSchedulerRun, SchedulerRunRepository};
+
+use crate::common::*;
+
+const INSTANCE: &str = "instance-a";
+
+async fn seed_runs(repo: &SchedulerRunRepository) {
+    let now = Utc::now();
+
+    let mut discovery =
+        NewSchedulerRun::new("url_discovery", INSTANCE, now - Duration::minutes(10));
+    discovery.ok_count = 8;
+    discovery.failed_count = 1;
+    discovery.skipped_count = 1;
+    discovery.details = Some(json!({ "avg_retrievability": 72.5, "consistent": 6 }));
+    repo.insert(&discovery).await.unwrap();
+
+    let mut failed = NewSchedulerRun::new("url_discovery", INSTANCE, now - Duration::minutes(5));
+    failed.error = Some("connection refused".to_string());
+    repo.insert(&failed).await.unwrap();
+
+    let mut bms = NewSchedulerRun::new("bms_job_creator", INSTANCE, now);
+    bms.ok_count = 3;
+    repo.insert(&bms).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_scheduler_runs_are_listed_newest_first() {
+    let ctx = TestContext::new().await;
+    let repo = SchedulerRunRepository::new(ctx.dbs.app_pool.clone());
+    seed_runs(&repo).await;
+
+    let runs = repo.list(None, 10, 0).await.unwrap();
+    let schedulers: Vec<&str> = runs.iter().map(|r| r.scheduler.as_str()).collect();
+    assert_eq!(
+        schedulers,
+        vec!["bms_job_creator", "url_discovery", "url_discovery"]
+    );
+    assert_eq!(runs[1].error.as_deref(), Some("connection refused"));
+    assert_eq!(runs[2].ok_count, 8);
+    assert_eq!(runs[2].failed_count, 1);
+    assert_eq!(runs[2].skipped_count, 1);
+    assert_eq!(runs[2].instance_id, INSTANCE);
+    assert!(runs[2].finished_at >= runs[2].started_at);
+
+    let runs = repo.list(Some("url_discovery"), 1, 1).await.unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].ok_count, 8);
+    assert_eq!(repo.count(Some("url_discovery")).await.unwrap(), 2);
+    assert_eq!(repo.count(None).await.unwrap(), 3);
+}
+
+#[tokio::test]
+async fn test_delete_started_before_prunes_old_runs() {
+    let ctx = TestContext::new().await;
+    let repo = SchedulerRunRepository::new(ctx.dbs.app_pool.clone());
+    seed_runs(&repo).await;
+    let old = NewSchedulerRun::new("url_discovery", INSTANCE, Utc::now() - Duration::days(40));
+    repo.insert(&old).await.unwrap();
+
+    let deleted = repo
+        .delete_started_before(Utc::now() - Duration::days(30))
+        .await
+        .unwrap();
+
+    assert_eq!(deleted, 1);
+    assert_eq!(repo.count(None).await.unwrap(), 3);
+}
+
+#[tokio::test]
+async fn test_list_scheduler_runs_endpoint() {
+    let ctx = TestContext::new().await;
+    seed_runs(&SchedulerRunRepository::new(ctx.dbs.app_pool.clone())).await;
+
+    let response = ctx
+        .app
+        .get("/admin/scheduler-runs?scheduler=url_discovery&limit=1")
+        .authorization_bearer("test-token")
+        .await;
+
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let body: Value = response.json();
+    assert_eq!(body["total"], 2);
+    assert_eq!(body["limit"], 1);
+    assert_eq!(body["offset"], 0);
+    let runs = body["runs"].as_array().unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0]["scheduler"], "url_discovery");
+    assert_eq!(runs[0]["error"], "connection refused");
+}
+
+#[tokio::test]
+async fn test_list_scheduler_runs_without_auth_returns_unauthorized() {
+    let ctx = TestContext::new().await;
+
+    let response = ctx.app.get("/admin/scheduler-runs").await;
+
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+    let body: Value = response.json();
+    assert_eq!(body, json!({ "error": "Unauthorized" }));
+}
@@ -0,0 +1,98 @@
+use chrono::{Duration, Utc};
+use url_finder::repository::{StorageProviderRepository, UrlDiscoveryUpdate};
+
+use crate::common::*;
+
+fn endpoints(list: &[&str]) -> Vec<String> {
+    list.iter().map(|e| e.to_string()).collect()
+}
+
+fn stable_update() -> UrlDiscoveryUpdate<'static> {
+    UrlDiscoveryUpdate {
+        last_working_url: None,
+        is_consistent: None,
+        is_reliable: None,
+        url_metadata: None,
+        next_interval_hours: 48,
+        reason: "stable",
+    }
+}
+
+#[tokio::test]
+async fn test_update_after_url_discovery_records_cadence() {
+    let ctx = TestContext::new().await;
+    let sp_repo = StorageProviderRepository::new(ctx.dbs.app_pool.clone());
+    seed_provider_with_cached_endpoints(
+        &ctx.dbs.app_pool,
+        TEST_PROVIDER_1_DB,
+        &endpoints(&["http://10.0.0.1:8080"]),
+    )
+    .await;
+
+    sp_repo
+        .update_after_url_discovery(&test_provider_1_id(), stable_update())
+        .await
+        .expect("Failed to update provider");
+
+    let provider = sp_repo
+        .get_by_provider_id(&test_provider_1_id())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(provider.url_discovery_reason.as_deref(), Some("stable"));
+    assert!(provider.next_url_discovery_at > Utc::now() + Duration::hours(47));
+    assert!(provider.next_url_discovery_at < Utc::now() + Duration::hours(49));
+}
+
+#[tokio::test]
+async fn test_update_cached_endpoints_pulls_forward_only_on_change() {
+    let ctx = TestContext::new().await;
+    let sp_repo = StorageProviderRepository::new(ctx.dbs.app_pool.clone());
+    let provider_id = test_provider_1_id();
+    seed_provider_with_cached_endpoints(
+        &ctx.dbs.app_pool,
+        TEST_PROVIDER_1_DB,
+        &endpoints(&["http://10.0.0.1:8080", "https://sp.example.com"]),
+    )
+    .await;
+    sp_repo
+        .update_after_url_discovery(&provider_id, stable_update())
+        .await
+        .unwrap();
+
+    // Same set in a different order
+    sp_repo
+        .update_cached_endpoints(
+            &provider_id,
+            "test-peer-id",
+            &endpoints(&["https://sp.example.com", "http://10.0.0.1:8080"]),
+        )
+        .await
+        .unwrap();
+    let provider = sp_repo
+        .get_by_provider_id(&provider_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(provider.url_discovery_reason.as_deref(), Some("stable"));
+    assert!(provider.next_url_discovery_at > Utc::now() + Duration::hours(47));
+
+    sp_repo
+        .update_cached_endpoints(
+            &provider_id,
+            "test-peer-id",
+            &endpoints(&["http://10.0.0.2:8080"]),
+        )
+        .await
+        .unwrap();
+    let provider = sp_repo
+        .get_by_provider_id(&provider_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        provider.url_discovery_reason.as_deref(),
+        Some("endpoints_changed")
+    );
+    assert!(provider.next_url_discovery_at <= Utc::now());
+}
@@ -1,8 +1,7 @@
 use crate::common::*;
 use url_finder::{
     config::Config,
-    repository::DealRepository,
-    services::url_discovery_service::discover_url,
+    services::url_discovery_service::{DiscoveryDeps, discover_url},
     types::{ClientAddress, ProviderAddress, ResultCode},
 };
 
@@ -12,24 +11,18 @@ fn setup_discovery_params(
 ) -> (
     ProviderAddress,
     ClientAddress,
-    DealRepository,
+    DiscoveryDeps,
     Config,
     Vec<String>,
 ) {
     let provider_address = fixture.provider_address.clone();
     let client_address = test_client_address();
-    let deal_repo = DealRepository::new(ctx.dbs.app_pool.clone());
+    let deps = ctx.discovery_deps();
     let lotus_url = ctx.mocks.lotus_url();
     let lotus_base = lotus_url.trim_end_matches('/');
     let config = Config::new_for_test(format!("{lotus_base}/rpc/v1"), ctx.mocks.cid_contact_url());
     let endpoints = fixture.endpoints.clone();
-    (
-        provider_address,
-        client_address,
-        deal_repo,
-        config,
-        endpoints,
-    )
+    (provider_address, client_address, deps, config, endpoints)
 }
 
 #[tokio::test]
@@ -48,16 +41,17 @@ async fn test_url_discovery_success() {
         )
         .await;
 
-    let (provider_address, client_address, deal_repo, config, endpoints) =
+    let (provider_address, client_address, deps, config, endpoints) =
         setup_discovery_params(&ctx, &fixture);
 
     let result = discover_url(
         &config,
+        &deps,
         &provider_address,
+        None,
         Some(client_address),
-        &deal_repo,
         endpoints,
-        None,
+        &[],
     )
     .await;
 
@@ -78,6 +72,18 @@ async fn test_url_discovery_success() {
         Some(100.0),
         "Should have 100% retrievability"
     );
+    assert_eq!(result.is_consistent, Some(true));
+
+    let random_range = &result.url_metadata.as_ref().unwrap()["random_range"];
+    assert_eq!(random_range["random_range_success_percent"], 100.0);
+    assert_eq!(
+        random_range["sample_count"],
+        Config::new_for_test(String::new(), String::new()).random_range_samples
+    );
+
+    // No proxy in tests, so the endpoint's connection phases are measured directly
+    let timing = &result.url_metadata.as_ref().unwrap()["timing"];
+    assert!(timing["connect_ms_avg"].is_number());
 }
 
 #[tokio::test]
@@ -93,16 +99,17 @@ async fn test_url_discovery_partial_retrievability() {
         )
         .await;
 
-    let (provider_address, client_address, deal_repo, config, endpoints) =
+    let (provider_address, client_address, deps, config, endpoints) =
         setup_discovery_params(&ctx, &fixture);
 
     let result = discover_url(
         &config,
+        &deps,
         &provider_address,
+        None,
         Some(client_address),
-        &deal_repo,
         endpoints,
-        None,
+        &[],
     )
     .await;
 
@@ -131,3 +138,44 @@ async fn test_url_discovery_partial_retrievability() {
         "Should have 50% retrievability (1 of 2 pieces)"
     );
 }
+
+#[tokio::test]
+async fn test_url_discovery_skips_unreachable_endpoints() {
+    let ctx = TestContext::new().await;
+
+    let fixture = ctx
+        .setup_provider_with_deals_and_mock_server(
+            TEST_PROVIDER_1_DB,
+            Some(TEST_CLIENT_ID_DB),
+            vec![TEST_PIECE_CID],
+            1.0,
+        )
+        .await;
+
+    let (provider_address, client_address, deps, config, _) =
+        setup_discovery_params(&ctx, &fixture);
+
+    // Nothing listens on port 1
+    let closed_endpoint = "http://127.0.0.1:1".to_string();
+    let result = discover_url(
+        &config,
+        &deps,
+        &provider_address,
+        None,
+        Some(client_address),
+        vec![closed_endpoint.clone()],
+        &[],
+    )
+    .await;
+
+    assert_eq!(result.result_code, ResultCode::FailedToGetWorkingUrl);
+    assert_eq!(result.retrievability_percent, None);
+
+    let preflight = &result.url_metadata.as_ref().unwrap()["preflight"];
+    assert_eq!(preflight["unreachable_count"], 1);
+    assert_eq!(preflight["unreachable"][0]["endpoint"], closed_endpoint);
+    assert_eq!(preflight["unreachable"][0]["failure"], "connect_refused");
+
+    assert_eq!(result.endpoint_outcomes.len(), 1);
+    assert!(!result.endpoint_outcomes[0].success);
+}